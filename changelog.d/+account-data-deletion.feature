@@ -0,0 +1 @@
+Account data can now be deleted through the unstable MSC3391 endpoints, or by setting it to empty content (`{}`), which deletes it rather than storing empty content. Deletions reach clients through `/sync` and the sliding sync `account_data` extension. Admins can inspect and delete a user's account data with `!admin users get-account-data` and `!admin users delete-account-data`.
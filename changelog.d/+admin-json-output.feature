@@ -0,0 +1 @@
+Admin commands accept `--format json` to reply with a JSON object for tooling. Listing and inspection commands such as `users list-users`, `users whois`, `rooms list-rooms` and `media evict-preview` return structured results.
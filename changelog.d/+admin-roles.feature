@@ -0,0 +1 @@
+Admin roles such as moderator, support and media-janitor can be granted with `!admin users grant-role`, limiting their holders to the admin commands configured in `admin_roles`. Inviting, kicking and changing state in the admin room now require the admins' power level, so role holders cannot invite new admins.
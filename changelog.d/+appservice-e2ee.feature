@@ -0,0 +1 @@
+Appservices with `receive_ephemeral` now receive to-device messages, device list changes and one-time key counts (MSC2409/MSC3202), and can masquerade as their users' devices.
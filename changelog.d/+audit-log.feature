@@ -0,0 +1 @@
+Admin commands from every source and suspensions through the admin API are recorded in an append-only audit log, which can be searched with `!admin audit list` and exported as JSON lines with `!admin audit export`.
@@ -0,0 +1 @@
+Sync now only records a room's state for a sync token when it changed since the last recorded token, greatly reducing the growth of the `roomsynctoken_shortstatehash` table. A one-time migration prunes the redundant entries already stored.
@@ -0,0 +1 @@
+The IP addresses, user agents and devices local users connect from are now recorded with when they were first and last seen, kept for `connection_history_retention`. The history is available through the `/_matrix/client/v3/admin/whois/{userId}` endpoint and `!admin users whois`, which also lists the users who connected from an IP address.
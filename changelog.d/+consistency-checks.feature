@@ -0,0 +1 @@
+Added database consistency checks to `!admin check` covering event ID, short event ID, joined member, joined count and relation indexes, each with a `--fix` mode.
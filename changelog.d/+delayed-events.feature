@@ -0,0 +1 @@
+Added support for delayed events (MSC4140), allowing clients to schedule message and state events to be sent later, and to list their pending delayed events. Pending delayed events are persisted and survive restarts, and a delayed state event is cancelled when newer state replaces it.
@@ -0,0 +1 @@
+`/keys/changes`, `/sync` and the sliding sync e2ee extension now list the users who no longer share an encrypted room with the user in `device_lists.left`, including after the user leaves rooms. Device list updates are no longer sent to servers through rooms the user has left.
@@ -0,0 +1 @@
+The media service now records when each file was last downloaded and how often. Remote media not accessed for `media_evict_unaccessed_after` seconds is evicted by a background job, keeping local users' avatars by default. `!admin media evict-preview` lists what would be evicted.
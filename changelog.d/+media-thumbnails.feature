@@ -0,0 +1 @@
+Thumbnails of animated GIF and WebP images stay animated when requested with `animated=true` (MSC2705), photos are thumbnailed as JPEG and other images as WebP for clients accepting it. EXIF, XMP and other metadata is stripped from images uploaded by local users, unless `media_strip_metadata` is disabled.
@@ -0,0 +1 @@
+`/members` now honours the `at` token to list the membership of a room at a point in its history, and appservices can list the members of rooms where one of their users is joined.
@@ -0,0 +1 @@
+Added an in-memory implementation of the MSC4108 rendezvous API for signing in new devices by scanning a QR code. A signed-in device which writes to a rendezvous session with its access token can obtain one login token for the new device without re-entering its password.
@@ -0,0 +1 @@
+Continuwuity can act as a notary key server for other servers with the new `notary_server` option, serving cached or freshly fetched keys at `/_matrix/key/v2/query` countersigned with its own key. `notary_allowed_servers` restricts which servers may query it.
@@ -0,0 +1 @@
+The public room directory is served from a persisted index kept up to date as room state changes, ranks search results by how well rooms match, uses batch tokens that stay valid across restarts, and caches listings fetched from other servers for a few minutes.
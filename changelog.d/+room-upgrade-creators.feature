@@ -0,0 +1 @@
+Room upgrades to room version 12 accept `additional_creators`, keep the creators of the old room privileged, and carry over `m.room.policy` and the canonical alias. Admins can upgrade rooms on behalf of their members with `!admin rooms upgrade`.
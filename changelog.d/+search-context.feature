@@ -0,0 +1 @@
+Search results now include surrounding events and historic sender profiles as requested by `event_context`, support `groupings` by room or sender, and page across all searched rooms with a stable `next_batch` token.
@@ -0,0 +1 @@
+Remote servers can be blocked or ignored at runtime with `!admin federation block-server` and `!admin federation ignore-server`, with optional reasons and expiry times. The rules are stored in the database and apply immediately, alongside the existing config options.
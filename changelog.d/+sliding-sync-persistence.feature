@@ -0,0 +1 @@
+Sliding sync connections are persisted in the database and resumed after a restart, expiring once unused for `sliding_sync_connection_ttl` seconds.
@@ -0,0 +1 @@
+Added `!admin server gc-state` to delete unreferenced state snapshots, with a `--dry-run` mode reporting the space that would be reclaimed.
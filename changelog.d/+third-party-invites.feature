@@ -0,0 +1 @@
+Rooms can now invite users by email address through an identity server. Such invites are stored as `m.room.third_party_invite` events and turned into regular invites once the address is bound, via `/3pid/onbind` and `exchange_third_party_invite`.
@@ -0,0 +1 @@
+Third-party protocol lookups are now proxied to appservices. `/thirdparty/protocols` gathers the metadata of the protocols which appservices declare in their registrations, and `/thirdparty/location` and `/thirdparty/user` lookups are forwarded to the matching appservices, so clients can offer bridge portals. Protocol metadata and lookup results are cached for `appservice_thirdparty_cache_ttl` seconds, and each appservice must answer within `appservice_thirdparty_timeout` seconds.
@@ -0,0 +1 @@
+Read receipts can now be threaded (MSC3771): receipts in different threads no longer replace each other, and private read receipts are stored with their timestamp and thread. Unread notification counts are tracked per thread and returned in `unread_thread_notifications` to clients which request them in their sync filter (MSC3773). The sliding sync receipts extension now also returns receipts for explicitly requested rooms.
@@ -0,0 +1 @@
+Non-members can now read the history and state of world-readable rooms through `/messages`, `/context`, `/event` and `/state`, and search them. World-readable rooms this server is not in yet are peeked over federation through the configured `trusted_servers`, and the state they return is checked against the auth rules.
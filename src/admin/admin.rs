@@ -3,6 +3,7 @@
 
 use crate::{
 	appservice::{self, AppserviceCommand},
+	audit::{self, AuditCommand},
 	check::{self, CheckCommand},
 	context::Context,
 	debug::{self, DebugCommand},
@@ -50,6 +51,10 @@ pub enum AdminCommand {
 	/// Commands for checking integrity
 	Check(CheckCommand),
 
+	#[command(subcommand)]
+	/// Commands for reviewing the audit log of administrative actions
+	Audit(AuditCommand),
+
 	#[command(subcommand)]
 	/// Commands for debugging things
 	Debug(DebugCommand),
@@ -90,5 +95,10 @@ pub(super) async fn process(command: AdminCommand, context: &Context<'_>) -> Res
 			query::process(command, context).await
 		},
 		| Check(command) => check::process(command, context).await,
+		| Audit(command) => {
+			// the audit log may reveal private arguments of other commands
+			context.bail_restricted()?;
+			audit::process(command, context).await
+		},
 	}
 }
@@ -1,8 +1,25 @@
+use std::fmt;
+
 use conduwuit::{Err, Result, checked};
-use futures::{FutureExt, StreamExt, TryFutureExt};
+use futures::StreamExt;
+use serde::Serialize;
 
 use crate::admin_command;
 
+/// The IDs of the registered appservices, as written by `list-registered`.
+#[derive(Serialize)]
+struct AppserviceList {
+	appservices: Vec<String>,
+}
+
+impl fmt::Display for AppserviceList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let len = self.appservices.len();
+		let list = self.appservices.join(", ");
+		write!(f, "Appservices ({len}): {list}")
+	}
+}
+
 #[admin_command]
 pub(super) async fn register(&self) -> Result {
 	let body = &self.body;
@@ -66,15 +83,7 @@ pub(super) async fn show_appservice_config(&self, appservice_identifier: String)
 
 #[admin_command]
 pub(super) async fn list_registered(&self) -> Result {
-	self.services
-		.appservice
-		.iter_ids()
-		.collect()
-		.map(Ok)
-		.and_then(|appservices: Vec<_>| {
-			let len = appservices.len();
-			let list = appservices.join(", ");
-			write!(self, "Appservices ({len}): {list}")
-		})
-		.await
+	let appservices = self.services.appservice.iter_ids().collect().await;
+
+	self.write_data(&AppserviceList { appservices }).await
 }
@@ -0,0 +1,111 @@
+use std::{
+	fmt::{self, Write},
+	time::{Duration, UNIX_EPOCH},
+};
+
+use conduwuit::{Result, utils};
+use futures::StreamExt;
+use serde::Serialize;
+use service::{
+	Services,
+	audit::{Entry, Filter},
+};
+
+use super::AuditFilter;
+use crate::{admin_command, utils::parse_user_id};
+
+/// The most recent recorded actions, as written by `list-audit-log`.
+#[derive(Serialize)]
+struct AuditLog {
+	entries: Vec<Entry>,
+}
+
+impl fmt::Display for AuditLog {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Found {} recorded action(s):", self.entries.len())?;
+		for Entry { ts, actor, source, action, args, outcome } in &self.entries {
+			let time = UNIX_EPOCH
+				.checked_add(Duration::from_millis(*ts))
+				.unwrap_or(UNIX_EPOCH);
+
+			writeln!(
+				f,
+				"- {} {actor} ({source:?}): `{action} {}` {outcome}",
+				utils::time::format(time, "%Y-%m-%d %H:%M:%S"),
+				args.join(" "),
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[admin_command]
+pub(super) async fn list_audit_log(&self, filter: AuditFilter, limit: usize) -> Result {
+	let entries = entries(self.services, filter, limit).await?;
+
+	self.write_data(&AuditLog { entries }).await
+}
+
+/// The most actions `export` writes in a single reply.
+const EXPORT_MAX: usize = 1000;
+
+#[admin_command]
+pub(super) async fn export_audit_log(&self, filter: AuditFilter, limit: usize) -> Result {
+	let limit = limit.min(EXPORT_MAX);
+	let mut entries = entries(self.services, filter, limit.saturating_add(1)).await?;
+	let truncated = entries.len() > limit;
+	entries.truncate(limit);
+	entries.reverse();
+
+	let mut lines = String::from("```json\n");
+	for entry in &entries {
+		writeln!(lines, "{}", serde_json::to_string(entry)?)?;
+	}
+	lines.push_str("```");
+
+	if let Some(oldest) = entries.first().filter(|_| truncated) {
+		let time = UNIX_EPOCH
+			.checked_add(Duration::from_millis(oldest.ts))
+			.unwrap_or(UNIX_EPOCH);
+
+		write!(
+			lines,
+			"\nOnly the {limit} most recent matching actions were exported, back to {}. Pass \
+			 `--until` to export older actions.",
+			utils::time::format(time, "%Y-%m-%d %H:%M:%S"),
+		)?;
+	}
+
+	self.write_str(&lines).await
+}
+
+/// Collects up to `limit` of the most recent entries matching the filter given
+/// on the command line.
+async fn entries(services: &Services, filter: AuditFilter, limit: usize) -> Result<Vec<Entry>> {
+	let actor = filter
+		.actor
+		.as_deref()
+		.map(|actor| parse_user_id(services, actor))
+		.transpose()?;
+
+	let action = filter.action.join(" ");
+	let filter = Filter {
+		actor: actor.as_deref(),
+		action: (!action.is_empty()).then_some(action.as_str()),
+		source: filter.source.map(Into::into),
+		since: filter
+			.since
+			.as_deref()
+			.map(utils::time::parse_duration)
+			.transpose()?,
+		until: filter
+			.until
+			.as_deref()
+			.map(utils::time::parse_duration)
+			.transpose()?,
+		unsuccessful: filter.unsuccessful,
+	};
+
+	Ok(services.audit.entries(&filter).take(limit).collect().await)
+}
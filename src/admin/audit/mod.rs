@@ -0,0 +1,84 @@
+mod commands;
+
+use clap::{Args, Subcommand, ValueEnum};
+use conduwuit::Result;
+use service::audit::Source;
+
+use crate::admin_command_dispatch;
+
+#[admin_command_dispatch]
+#[derive(Debug, Subcommand)]
+pub enum AuditCommand {
+	/// List recorded administrative actions, most recent first
+	#[clap(name = "list")]
+	ListAuditLog {
+		#[command(flatten)]
+		filter: AuditFilter,
+
+		/// Maximum number of actions to list
+		#[arg(short, long, default_value("50"))]
+		limit: usize,
+	},
+
+	/// Export recorded administrative actions as JSON lines, oldest first
+	///
+	/// At most 1000 actions are exported at once, the most recent first; use
+	/// `--until` to page back through older actions.
+	#[clap(name = "export")]
+	ExportAuditLog {
+		#[command(flatten)]
+		filter: AuditFilter,
+
+		/// Maximum number of actions to export, at most 1000
+		#[arg(short, long, default_value("1000"))]
+		limit: usize,
+	},
+}
+
+#[derive(Debug, Args)]
+pub struct AuditFilter {
+	/// Only actions taken by this user
+	#[arg(long)]
+	actor: Option<String>,
+
+	/// Only actions under this command, e.g. `--action users suspend`
+	#[arg(long, num_args(1..))]
+	action: Vec<String>,
+
+	/// Only actions taken from this source
+	#[arg(long)]
+	source: Option<AuditSource>,
+
+	/// Only actions taken within this long ago (e.g. 30m, 7d)
+	#[arg(long)]
+	since: Option<String>,
+
+	/// Only actions taken at least this long ago (e.g. 30m, 7d)
+	#[arg(long)]
+	until: Option<String>,
+
+	/// Only actions which failed or were denied
+	#[arg(long)]
+	unsuccessful: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum AuditSource {
+	AdminRoom,
+	EscapedCommand,
+	Console,
+	Internal,
+	Api,
+}
+
+impl From<AuditSource> for Source {
+	fn from(source: AuditSource) -> Self {
+		match source {
+			| AuditSource::AdminRoom => Self::AdminRoom,
+			| AuditSource::EscapedCommand => Self::EscapedCommand,
+			| AuditSource::Console => Self::Console,
+			| AuditSource::Internal => Self::Internal,
+			| AuditSource::Api => Self::Api,
+		}
+	}
+}
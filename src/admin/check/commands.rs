@@ -1,7 +1,8 @@
 use conduwuit::Result;
 use conduwuit_macros::implement;
-use futures::StreamExt;
+use futures::{Future, StreamExt};
 
+use super::consistency::{self, Report};
 use crate::Context;
 
 #[implement(Context, params = "<'_>")]
@@ -21,3 +22,59 @@ pub(super) async fn check_all_users(&self) -> Result {
 	))
 	.await
 }
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn consistency(&self, fix: bool) -> Result {
+	self.event_ids(fix).await?;
+	self.short_event_ids(fix).await?;
+	self.memberships(fix).await?;
+	self.joined_counts(fix).await?;
+	self.relations(fix).await
+}
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn event_ids(&self, fix: bool) -> Result {
+	self.check_consistency(fix, consistency::event_ids(self.services, fix))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn short_event_ids(&self, fix: bool) -> Result {
+	self.check_consistency(fix, consistency::short_event_ids(self.services, fix))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn memberships(&self, fix: bool) -> Result {
+	self.check_consistency(fix, consistency::memberships(self.services, fix))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn joined_counts(&self, fix: bool) -> Result {
+	self.check_consistency(fix, consistency::joined_counts(self.services, fix))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+pub(super) async fn relations(&self, fix: bool) -> Result {
+	self.check_consistency(fix, consistency::relations(self.services, fix))
+		.await
+}
+
+#[implement(Context, params = "<'_>")]
+async fn check_consistency<F>(&self, fix: bool, check: F) -> Result
+where
+	F: Future<Output = Result<Report>> + Send,
+{
+	if fix {
+		self.bail_restricted()?;
+	}
+
+	let timer = tokio::time::Instant::now();
+	let report = check.await?;
+	let elapsed = timer.elapsed();
+
+	self.write_str(&format!("{report}\nCompleted in {elapsed:?}\n\n"))
+		.await
+}
@@ -0,0 +1,433 @@
+//! Cross-column consistency checks.
+//!
+//! Each check walks one or more maps verifying an invariant that the rest of
+//! the server relies upon but which is not enforced by the database itself.
+//! Inconsistencies are counted into a [`Report`]; when `fix` is set they are
+//! also repaired, preferring the authoritative side of each relationship
+//! (the PDU for its event ID, room state for the membership caches).
+
+use std::{collections::HashSet, fmt};
+
+use conduwuit::{
+	Result,
+	matrix::pdu::{PduCount, PduEvent, RawPduId},
+	utils::{
+		stream::{ReadyExt, TryIgnore},
+		u64_from_u8,
+	},
+};
+use conduwuit_service::Services;
+use futures::{StreamExt, TryStreamExt};
+use ruma::{OwnedRoomId, OwnedUserId, RoomId, UserId, events::StateEventType};
+
+/// Maximum number of inconsistencies described individually in a report.
+const MAX_SAMPLES: usize = 20;
+
+/// Length of a `tofrom_relation` key: the target and relating PDU counts.
+const RELATION_KEY_LEN: usize = size_of::<u64>() * 2;
+
+/// Outcome of one consistency check.
+#[derive(Debug)]
+pub(super) struct Report {
+	check: &'static str,
+	fix: bool,
+	scanned: usize,
+	inconsistent: usize,
+	fixed: usize,
+	samples: Vec<String>,
+}
+
+impl Report {
+	fn new(check: &'static str, fix: bool) -> Self {
+		Self {
+			check,
+			fix,
+			scanned: 0,
+			inconsistent: 0,
+			fixed: 0,
+			samples: Vec::new(),
+		}
+	}
+
+	fn inconsistent(&mut self, describe: impl FnOnce() -> String) {
+		self.inconsistent = self.inconsistent.saturating_add(1);
+		if self.samples.len() < MAX_SAMPLES {
+			self.samples.push(describe());
+		}
+	}
+
+	fn fixed(&mut self) { self.fixed = self.fixed.saturating_add(1); }
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let status = match (self.inconsistent, self.fix) {
+			| (0, _) => "ok",
+			| (_, false) => "inconsistent",
+			| (n, true) if n == self.fixed => "repaired",
+			| (_, true) => "partially repaired",
+		};
+
+		writeln!(f, "### {}: {status}\n", self.check)?;
+		writeln!(f, "```")?;
+		writeln!(f, "Scanned:      {}", self.scanned)?;
+		writeln!(f, "Inconsistent: {}", self.inconsistent)?;
+		if self.fix {
+			writeln!(f, "Fixed:        {}", self.fixed)?;
+		}
+		writeln!(f, "```")?;
+
+		if !self.samples.is_empty() {
+			writeln!(f)?;
+			for sample in &self.samples {
+				writeln!(f, "- {sample}")?;
+			}
+
+			let omitted = self.inconsistent.saturating_sub(self.samples.len());
+			if omitted > 0 {
+				writeln!(f, "- ... and {omitted} more")?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Every `eventid_pduid` row must point at a PDU in `pduid_pdu` carrying the
+/// same event ID, and every PDU must be reachable from its event ID.
+///
+/// Rows pointing at missing or foreign PDUs are deleted; missing rows are
+/// recreated from the PDU. An event ID mapped to a different PDU which does
+/// carry it (a duplicated event) is only reported.
+pub(super) async fn event_ids(services: &Services, fix: bool) -> Result<Report> {
+	let eventid_pduid = services.db.get("eventid_pduid")?;
+	let pduid_pdu = services.db.get("pduid_pdu")?;
+	let mut report = Report::new("eventid_pduid <-> pduid_pdu", fix);
+
+	let mut rows = eventid_pduid.raw_stream();
+	while let Some((event_id, pdu_id)) = rows.try_next().await? {
+		report.scanned = report.scanned.saturating_add(1);
+		let stored = pduid_pdu
+			.get(pdu_id)
+			.await
+			.ok()
+			.and_then(|pdu| serde_json::from_slice::<PduEvent>(&pdu).ok());
+
+		let problem = match stored {
+			| None => "points to a missing PDU",
+			| Some(pdu) if pdu.event_id.as_bytes() != event_id => "points to another event's PDU",
+			| Some(_) => continue,
+		};
+
+		report.inconsistent(|| {
+			format!("eventid_pduid {} {problem}", String::from_utf8_lossy(event_id))
+		});
+
+		if fix {
+			eventid_pduid.remove(event_id);
+			report.fixed();
+		}
+	}
+
+	let mut rows = pduid_pdu.raw_stream();
+	while let Some((pdu_id, pdu)) = rows.try_next().await? {
+		report.scanned = report.scanned.saturating_add(1);
+		let Ok(pdu) = serde_json::from_slice::<PduEvent>(pdu) else {
+			report.inconsistent(|| format!("pduid_pdu {pdu_id:?} does not hold a valid PDU"));
+			continue;
+		};
+
+		match eventid_pduid.get(pdu.event_id.as_bytes()).await {
+			| Ok(mapped) if *mapped == *pdu_id => {},
+			| Ok(_) => {
+				report.inconsistent(|| {
+					format!("{} is stored under more than one PDU ID", pdu.event_id)
+				});
+			},
+			| Err(_) => {
+				report
+					.inconsistent(|| format!("{} is not indexed in eventid_pduid", pdu.event_id));
+				if fix {
+					eventid_pduid.insert(pdu.event_id.as_bytes(), pdu_id);
+					report.fixed();
+				}
+			},
+		}
+	}
+
+	Ok(report)
+}
+
+/// `shorteventid_eventid` and `eventid_shorteventid` must be exact inverses.
+///
+/// A row without its counterpart has the counterpart recreated. Two short IDs
+/// for the same event ID are only reported, as both may be referenced by
+/// compressed state.
+pub(super) async fn short_event_ids(services: &Services, fix: bool) -> Result<Report> {
+	let shorteventid_eventid = services.db.get("shorteventid_eventid")?;
+	let eventid_shorteventid = services.db.get("eventid_shorteventid")?;
+	let mut report = Report::new("shorteventid_eventid <-> eventid_shorteventid", fix);
+
+	let mut rows = shorteventid_eventid.raw_stream();
+	while let Some((shorteventid, event_id)) = rows.try_next().await? {
+		report.scanned = report.scanned.saturating_add(1);
+		let short = u64_from_u8(shorteventid);
+		let event_id_str = String::from_utf8_lossy(event_id);
+		match eventid_shorteventid.get(event_id).await {
+			| Ok(mapped) if *mapped == *shorteventid => {},
+			| Ok(mapped) => report.inconsistent(|| {
+				format!(
+					"{event_id_str} has short ID {short} but maps back to {}",
+					u64_from_u8(&mapped)
+				)
+			}),
+			| Err(_) => {
+				report.inconsistent(|| {
+					format!(
+						"{event_id_str} (short ID {short}) is missing from eventid_shorteventid"
+					)
+				});
+				if fix {
+					eventid_shorteventid.insert(event_id, shorteventid);
+					report.fixed();
+				}
+			},
+		}
+	}
+
+	let mut rows = eventid_shorteventid.raw_stream();
+	while let Some((event_id, shorteventid)) = rows.try_next().await? {
+		report.scanned = report.scanned.saturating_add(1);
+		let short = u64_from_u8(shorteventid);
+		let event_id_str = String::from_utf8_lossy(event_id);
+		match shorteventid_eventid.get(shorteventid).await {
+			| Ok(mapped) if *mapped == *event_id => {},
+			| Ok(mapped) => report.inconsistent(|| {
+				format!(
+					"short ID {short} of {event_id_str} maps back to {}",
+					String::from_utf8_lossy(&mapped)
+				)
+			}),
+			| Err(_) => {
+				report.inconsistent(|| {
+					format!(
+						"short ID {short} of {event_id_str} is missing from shorteventid_eventid"
+					)
+				});
+				if fix {
+					shorteventid_eventid.insert(shorteventid, event_id);
+					report.fixed();
+				}
+			},
+		}
+	}
+
+	Ok(report)
+}
+
+/// `roomuserid_joined` and `userroomid_joined` must both list exactly the
+/// users whose membership is `join` in the current state of each room.
+///
+/// The caches are rewritten to match the room state; joined counts of rooms
+/// that were repaired are recomputed.
+pub(super) async fn memberships(services: &Services, fix: bool) -> Result<Report> {
+	let roomuserid_joined = services.db.get("roomuserid_joined")?;
+	let userroomid_joined = services.db.get("userroomid_joined")?;
+	let state_cache = &services.rooms.state_cache;
+	let mut report = Report::new("roomuserid_joined / userroomid_joined <-> room state", fix);
+
+	let room_ids: Vec<_> = services
+		.rooms
+		.metadata
+		.iter_ids()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for room_id in &room_ids {
+		let Ok(shortstatehash) = services.rooms.state.get_room_shortstatehash(room_id).await
+		else {
+			continue;
+		};
+
+		let mut users: HashSet<OwnedUserId> = services
+			.rooms
+			.state_accessor
+			.state_keys(shortstatehash, &StateEventType::RoomMember)
+			.ready_filter_map(|state_key| UserId::parse(state_key.as_str()).ok())
+			.collect()
+			.await;
+
+		let cached: HashSet<OwnedUserId> = state_cache
+			.room_members(room_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		users.extend(cached.iter().cloned());
+
+		let mut repaired = false;
+		for user_id in &users {
+			report.scanned = report.scanned.saturating_add(1);
+			let joined = services
+				.rooms
+				.state_accessor
+				.user_was_joined(shortstatehash, user_id)
+				.await;
+
+			let in_room_cache = cached.contains(user_id);
+			let in_user_cache = state_cache.is_joined(user_id, room_id).await;
+			if in_room_cache == joined && in_user_cache == joined {
+				continue;
+			}
+
+			report.inconsistent(|| {
+				format!(
+					"{user_id} in {room_id}: joined in state: {joined}, roomuserid_joined: \
+					 {in_room_cache}, userroomid_joined: {in_user_cache}"
+				)
+			});
+
+			if fix {
+				if joined {
+					state_cache.mark_as_joined(user_id, room_id);
+				} else {
+					roomuserid_joined.del((room_id, user_id));
+					userroomid_joined.del((user_id, room_id));
+				}
+
+				repaired = true;
+				report.fixed();
+			}
+		}
+
+		if repaired {
+			state_cache.update_joined_count(room_id).await;
+		}
+	}
+
+	// userroomid_joined rows for users the pass above never visited: neither
+	// joined in state nor present in roomuserid_joined.
+	let stale: Vec<(OwnedUserId, OwnedRoomId)> = userroomid_joined
+		.keys()
+		.ignore_err()
+		.map(|(user_id, room_id): (&UserId, &RoomId)| (user_id.to_owned(), room_id.to_owned()))
+		.collect()
+		.await;
+
+	for (user_id, room_id) in stale {
+		report.scanned = report.scanned.saturating_add(1);
+		if roomuserid_joined.contains(&(&*room_id, &user_id)).await {
+			continue;
+		}
+
+		let joined = match services.rooms.state.get_room_shortstatehash(&room_id).await {
+			| Ok(shortstatehash) =>
+				services
+					.rooms
+					.state_accessor
+					.user_was_joined(shortstatehash, &user_id)
+					.await,
+			| Err(_) => false,
+		};
+
+		if joined {
+			// seen and handled by the per-room pass
+			continue;
+		}
+
+		report.inconsistent(|| {
+			format!("{user_id} in {room_id}: only present in userroomid_joined")
+		});
+
+		if fix {
+			userroomid_joined.del((&user_id, &room_id));
+			report.fixed();
+		}
+	}
+
+	Ok(report)
+}
+
+/// `roomid_joinedcount` must equal the number of joined members cached in
+/// `roomuserid_joined`. Wrong counts are recomputed.
+pub(super) async fn joined_counts(services: &Services, fix: bool) -> Result<Report> {
+	let state_cache = &services.rooms.state_cache;
+	let mut report = Report::new("roomid_joinedcount", fix);
+
+	let room_ids: Vec<_> = services
+		.rooms
+		.metadata
+		.iter_ids()
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for room_id in &room_ids {
+		report.scanned = report.scanned.saturating_add(1);
+		let stored = state_cache.room_joined_count(room_id).await.unwrap_or(0);
+		let actual: u64 = state_cache.room_members(room_id).count().await.try_into()?;
+
+		if stored == actual {
+			continue;
+		}
+
+		report.inconsistent(|| format!("{room_id}: stored {stored}, actual {actual}"));
+		if fix {
+			state_cache.update_joined_count(room_id).await;
+			report.fixed();
+		}
+	}
+
+	Ok(report)
+}
+
+/// Both ends of every `tofrom_relation` row must be PDUs in `pduid_pdu`.
+/// Dangling rows are deleted.
+///
+/// The PDU counts of the whole timeline are held in memory while checking.
+pub(super) async fn relations(services: &Services, fix: bool) -> Result<Report> {
+	let pduid_pdu = services.db.get("pduid_pdu")?;
+	let tofrom_relation = services.db.get("tofrom_relation")?;
+	let mut report = Report::new("tofrom_relation", fix);
+
+	let counts: HashSet<u64> = pduid_pdu
+		.raw_keys()
+		.ignore_err()
+		.ready_filter_map(|pdu_id| match RawPduId::from(pdu_id).pdu_count() {
+			| PduCount::Normal(count) => Some(count),
+			| PduCount::Backfilled(_) => None,
+		})
+		.collect()
+		.await;
+
+	let mut keys = tofrom_relation.raw_keys();
+	while let Some(key) = keys.try_next().await? {
+		report.scanned = report.scanned.saturating_add(1);
+		if key.len() != RELATION_KEY_LEN {
+			report.inconsistent(|| format!("relation {key:?} is malformed"));
+			if fix {
+				tofrom_relation.remove(key);
+				report.fixed();
+			}
+
+			continue;
+		}
+
+		let (to, from) = (u64_from_u8(&key[0..8]), u64_from_u8(&key[8..16]));
+		let missing = match (counts.contains(&to), counts.contains(&from)) {
+			| (true, true) => continue,
+			| (false, true) => "target",
+			| (true, false) => "relating event",
+			| (false, false) => "target and relating event",
+		};
+
+		report.inconsistent(|| format!("relation {from} -> {to}: missing {missing}"));
+		if fix {
+			tofrom_relation.remove(key);
+			report.fixed();
+		}
+	}
+
+	Ok(report)
+}
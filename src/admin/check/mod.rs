@@ -1,4 +1,5 @@
 mod commands;
+mod consistency;
 
 use clap::Subcommand;
 use conduwuit::Result;
@@ -12,4 +13,53 @@ pub enum CheckCommand {
 	/// every user in our database (remote and local). Reports total count, any
 	/// errors if there were any, etc
 	CheckAllUsers,
+
+	/// Runs every consistency check below in turn
+	Consistency {
+		/// Repair the inconsistencies found
+		#[arg(long)]
+		fix: bool,
+	},
+
+	/// Verifies that `eventid_pduid` and `pduid_pdu` agree: every event ID
+	/// points at a stored PDU carrying that event ID, and every PDU is indexed
+	/// by its event ID
+	EventIds {
+		/// Delete dangling rows and index unindexed PDUs
+		#[arg(long)]
+		fix: bool,
+	},
+
+	/// Verifies that `shorteventid_eventid` and `eventid_shorteventid` are
+	/// exact inverses of each other
+	ShortEventIds {
+		/// Recreate missing counterpart rows
+		#[arg(long)]
+		fix: bool,
+	},
+
+	/// Verifies the joined member caches (`roomuserid_joined` and
+	/// `userroomid_joined`) against the current state of every room
+	Memberships {
+		/// Rewrite the caches to match room state
+		#[arg(long)]
+		fix: bool,
+	},
+
+	/// Verifies that `roomid_joinedcount` matches the number of joined members
+	/// of every room
+	JoinedCounts {
+		/// Recompute wrong counts
+		#[arg(long)]
+		fix: bool,
+	},
+
+	/// Looks for `tofrom_relation` rows referring to PDUs which do not exist
+	///
+	/// Holds the PDU counts of the whole timeline in memory while running.
+	Relations {
+		/// Delete dangling rows
+		#[arg(long)]
+		fix: bool,
+	},
 }
@@ -1,5 +1,9 @@
-use std::{fmt, time::SystemTime};
+use std::{
+	fmt::{self, Display},
+	time::SystemTime,
+};
 
+use clap::ValueEnum;
 use conduwuit::{Err, Result};
 use conduwuit_service::Services;
 use futures::{
@@ -7,7 +11,9 @@
 	io::{AsyncWriteExt, BufWriter},
 	lock::Mutex,
 };
-use ruma::{EventId, UserId};
+use ruma::{EventId, RoomId, UserId};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
 use service::admin::InvocationSource;
 
 pub(crate) struct Context<'a> {
@@ -18,6 +24,19 @@ pub(crate) struct Context<'a> {
 	pub(crate) sender: Option<&'a UserId>,
 	pub(crate) output: Mutex<BufWriter<Vec<u8>>>,
 	pub(crate) source: InvocationSource,
+	pub(crate) format: OutputFormat,
+	pub(crate) data: Mutex<Option<JsonValue>>,
+}
+
+/// How the result of a command is written back, chosen with `--format`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum OutputFormat {
+	/// Markdown for reading in the admin room or console.
+	#[default]
+	Markdown,
+
+	/// JSON for parsing by tooling.
+	Json,
 }
 
 impl Context<'_> {
@@ -40,6 +59,22 @@ pub(crate) fn write_str<'a>(
 		})
 	}
 
+	/// Writes the structured result of a command. It is rendered as markdown,
+	/// or kept as JSON for the reply when the command was run with `--format
+	/// json`.
+	pub(crate) async fn write_data<T>(&self, data: &T) -> Result
+	where
+		T: Display + Serialize + Sync,
+	{
+		match self.format {
+			| OutputFormat::Markdown => self.write_str(&data.to_string()).await,
+			| OutputFormat::Json => {
+				*self.data.lock().await = Some(serde_json::to_value(data)?);
+				Ok(())
+			},
+		}
+	}
+
 	/// Get the sender as a string, or service user ID if not available
 	pub(crate) fn sender_or_service_user(&self) -> &UserId {
 		self.sender
@@ -63,4 +98,30 @@ pub(crate) fn bail_restricted(&self) -> Result {
 			Err!("This command can only be used in the admin room.")
 		}
 	}
+
+	/// Returns an Err if the sender of this context holds a delegated admin
+	/// role rather than being a server admin.
+	///
+	/// This guards commands which hand out privileges, so that no role can be
+	/// used to escalate itself.
+	pub(crate) async fn bail_delegated(&self) -> Result {
+		match self.sender {
+			| Some(sender) if self.services.admin.user_role(sender).await.is_some() =>
+				Err!("This command can only be used by server admins."),
+			| _ => Ok(()),
+		}
+	}
+
+	/// Returns an Err if `room_id` is the admin room and the sender of this
+	/// context holds a delegated admin role.
+	///
+	/// Joining users to the admin room would let them run admin commands, so
+	/// role holders may only force-join users to other rooms.
+	pub(crate) async fn bail_delegated_admin_room(&self, room_id: &RoomId) -> Result {
+		if self.services.admin.is_admin_room(room_id).await {
+			self.bail_delegated().await?;
+		}
+
+		Ok(())
+	}
 }
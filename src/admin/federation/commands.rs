@@ -1,10 +1,45 @@
-use std::fmt::Write;
+use std::fmt::{self, Write};
 
-use conduwuit::{Err, Result};
+use conduwuit::{Err, Result, utils};
 use futures::StreamExt;
 use ruma::{OwnedRoomId, OwnedServerName, OwnedUserId};
+use serde::Serialize;
+use service::moderation::{RuleKind, ServerRule};
 
-use crate::{admin_command, get_room_info};
+use super::ServerRuleArgs;
+use crate::{Context, admin_command, get_room_info};
+
+/// The server rules of one kind in effect, as written by
+/// `list-blocked-servers` and `list-ignored-servers`.
+#[derive(Serialize)]
+struct ServerRuleList {
+	#[serde(skip)]
+	kind: RuleKind,
+	rules: Vec<ServerRuleEntry>,
+}
+
+#[derive(Serialize)]
+struct ServerRuleEntry {
+	server_name: OwnedServerName,
+	#[serde(flatten)]
+	rule: ServerRule,
+}
+
+impl fmt::Display for ServerRuleList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Self { kind, rules } = self;
+		if rules.is_empty() {
+			return write!(f, "There are no {kind} rules.");
+		}
+
+		writeln!(f, "{} {kind} rules:\n```", rules.len())?;
+		for ServerRuleEntry { server_name, rule } in rules {
+			writeln!(f, "{server_name} | {rule}")?;
+		}
+
+		write!(f, "```")
+	}
+}
 
 #[admin_command]
 pub(super) async fn disable_room(&self, room_id: OwnedRoomId) -> Result {
@@ -121,3 +156,101 @@ pub(super) async fn remote_user_in_rooms(&self, user_id: OwnedUserId) -> Result
 	self.write_str(&format!("Rooms {user_id} shares with us ({num}):\n```\n{body}\n```",))
 		.await
 }
+
+#[admin_command]
+pub(super) async fn block_server(
+	&self,
+	server_name: OwnedServerName,
+	rule: ServerRuleArgs,
+) -> Result {
+	self.bail_restricted()?;
+	add_server_rule(self, RuleKind::Block, &server_name, rule)?;
+	self.write_str(&format!("Federation with {server_name} is now blocked."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn unblock_server(&self, server_name: OwnedServerName) -> Result {
+	self.bail_restricted()?;
+	remove_server_rule(self, RuleKind::Block, &server_name)?;
+	self.write_str(&format!("Federation with {server_name} is no longer blocked."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn ignore_server(
+	&self,
+	server_name: OwnedServerName,
+	rule: ServerRuleArgs,
+) -> Result {
+	self.bail_restricted()?;
+	add_server_rule(self, RuleKind::Ignore, &server_name, rule)?;
+	self.write_str(&format!("Messages from {server_name} are now ignored."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn unignore_server(&self, server_name: OwnedServerName) -> Result {
+	self.bail_restricted()?;
+	remove_server_rule(self, RuleKind::Ignore, &server_name)?;
+	self.write_str(&format!("Messages from {server_name} are no longer ignored."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn list_blocked_servers(&self) -> Result {
+	list_server_rules(self, RuleKind::Block).await
+}
+
+#[admin_command]
+pub(super) async fn list_ignored_servers(&self) -> Result {
+	list_server_rules(self, RuleKind::Ignore).await
+}
+
+fn add_server_rule(
+	context: &Context<'_>,
+	kind: RuleKind,
+	server_name: &OwnedServerName,
+	rule: ServerRuleArgs,
+) -> Result {
+	let expires_in = rule
+		.expires_in
+		.as_deref()
+		.map(utils::time::parse_duration)
+		.transpose()?;
+
+	let rule = ServerRule::new(context.sender_or_service_user().into(), expires_in, rule.reason);
+	context
+		.services
+		.moderation
+		.add_server_rule(kind, server_name, rule)
+}
+
+fn remove_server_rule(
+	context: &Context<'_>,
+	kind: RuleKind,
+	server_name: &OwnedServerName,
+) -> Result {
+	if context
+		.services
+		.moderation
+		.remove_server_rule(kind, server_name)
+		.is_none()
+	{
+		return Err!("There is no {kind} rule for {server_name}.");
+	}
+
+	Ok(())
+}
+
+async fn list_server_rules(context: &Context<'_>, kind: RuleKind) -> Result {
+	let rules = context
+		.services
+		.moderation
+		.server_rules(kind)
+		.into_iter()
+		.map(|(server_name, rule)| ServerRuleEntry { server_name, rule })
+		.collect();
+
+	context.write_data(&ServerRuleList { kind, rules }).await
+}
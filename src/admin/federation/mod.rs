@@ -1,6 +1,6 @@
 mod commands;
 
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use conduwuit::Result;
 use ruma::{OwnedRoomId, OwnedServerName, OwnedUserId};
 
@@ -39,4 +39,56 @@ pub enum FederationCommand {
 	RemoteUserInRooms {
 		user_id: OwnedUserId,
 	},
+
+	/// Blocks federation with a server until it is unblocked or the block
+	/// expires. Takes effect immediately, in addition to the
+	/// `forbidden_remote_server_names` config option, and overrides
+	/// `allowed_remote_server_names`.
+	///
+	/// Blocking a server name without a port also blocks it on any port.
+	BlockServer {
+		server_name: OwnedServerName,
+
+		#[command(flatten)]
+		rule: ServerRuleArgs,
+	},
+
+	/// Removes a block added with `block-server`. Servers matching the
+	/// `forbidden_remote_server_names` config option remain blocked.
+	UnblockServer {
+		server_name: OwnedServerName,
+	},
+
+	/// Hides messages from a server from local clients until it is unignored
+	/// or the rule expires, in addition to the
+	/// `ignore_messages_from_server_names` config option.
+	IgnoreServer {
+		server_name: OwnedServerName,
+
+		#[command(flatten)]
+		rule: ServerRuleArgs,
+	},
+
+	/// Removes a rule added with `ignore-server`.
+	UnignoreServer {
+		server_name: OwnedServerName,
+	},
+
+	/// Lists the servers blocked with `block-server`
+	ListBlockedServers,
+
+	/// Lists the servers ignored with `ignore-server`
+	ListIgnoredServers,
+}
+
+#[derive(Debug, Args)]
+pub struct ServerRuleArgs {
+	/// Why the rule is being added.
+	#[arg(long)]
+	reason: Option<String>,
+
+	/// How long the rule applies for (e.g. 30m, 12h, 7d). Without this the
+	/// rule applies until it is removed.
+	#[arg(long)]
+	expires_in: Option<String>,
 }
@@ -1,15 +1,57 @@
-use std::time::Duration;
+use std::{
+	fmt,
+	time::{Duration, UNIX_EPOCH},
+};
 
 use conduwuit::{
 	Err, Result, debug, debug_info, debug_warn, error, info, trace,
-	utils::time::{TimeDirection, parse_timepoint_ago},
+	utils::{
+		self,
+		time::{self, TimeDirection, now_millis, parse_timepoint_ago},
+	},
 	warn,
 };
-use conduwuit_service::media::Dim;
+use conduwuit_service::media::{Dim, EvictionCandidate};
 use ruma::{Mxc, OwnedEventId, OwnedMxcUri, OwnedServerName};
+use serde::Serialize;
 
 use crate::{admin_command, utils::parse_local_user_id};
 
+/// The metadata and access statistics of a file, as written by
+/// `get-file-info`.
+#[derive(Serialize)]
+struct FileInfo {
+	mxc: OwnedMxcUri,
+	content_type: Option<String>,
+	content_disposition: Option<String>,
+	last_access: Option<u64>,
+	hits: u64,
+}
+
+impl fmt::Display for FileInfo {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let unknown = "unknown";
+		writeln!(f, "```")?;
+		writeln!(f, "MXC: {}", self.mxc)?;
+		writeln!(f, "Content type: {}", self.content_type.as_deref().unwrap_or(unknown))?;
+		writeln!(
+			f,
+			"Content disposition: {}",
+			self.content_disposition.as_deref().unwrap_or(unknown)
+		)?;
+		match self
+			.last_access
+			.and_then(|ms| UNIX_EPOCH.checked_add(Duration::from_millis(ms)))
+		{
+			| Some(ts) =>
+				writeln!(f, "Last accessed: {}", time::format(ts, "%Y-%m-%d %H:%M:%S"))?,
+			| None => writeln!(f, "Last accessed: never")?,
+		}
+		writeln!(f, "Hits: {}", self.hits)?;
+		write!(f, "```")
+	}
+}
+
 #[admin_command]
 pub(super) async fn delete(
 	&self,
@@ -332,12 +374,83 @@ pub(super) async fn delete_all_from_server(
 		.await
 }
 
+/// The media which would be evicted, as written by `evict-preview`.
+#[derive(Serialize)]
+struct EvictionPreview {
+	#[serde(skip)]
+	unaccessed_for: Duration,
+	total: usize,
+	media: Vec<EvictionCandidate>,
+}
+
+impl fmt::Display for EvictionPreview {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{} media not accessed for {} would be evicted, {} least recently accessed \
+			 shown:\n```",
+			self.total,
+			utils::time::pretty(self.unaccessed_for),
+			self.media.len(),
+		)?;
+		for candidate in &self.media {
+			let ago = Duration::from_millis(now_millis().saturating_sub(candidate.last_access));
+			writeln!(
+				f,
+				"{} | last accessed {} ago | {} hit(s)",
+				candidate.mxc,
+				utils::time::pretty(ago),
+				candidate.hits
+			)?;
+		}
+
+		write!(f, "```")
+	}
+}
+
+#[admin_command]
+pub(super) async fn evict_preview(&self, unaccessed_for: Option<String>, limit: usize) -> Result {
+	let unaccessed_for = match unaccessed_for {
+		| Some(unaccessed_for) => utils::time::parse_duration(&unaccessed_for)?,
+		| None => match self.services.server.config.media_evict_unaccessed_after {
+			| 0 => return Err!("Media eviction is disabled, please specify --unaccessed-for."),
+			| secs => Duration::from_secs(secs),
+		},
+	};
+
+	let mut media = self
+		.services
+		.media
+		.eviction_candidates(unaccessed_for)
+		.await?;
+
+	let total = media.len();
+	media.truncate(limit);
+
+	self.write_data(&EvictionPreview { unaccessed_for, total, media })
+		.await
+}
+
 #[admin_command]
 pub(super) async fn get_file_info(&self, mxc: OwnedMxcUri) -> Result {
-	let mxc: Mxc<'_> = mxc.as_str().try_into()?;
-	let metadata = self.services.media.get_metadata(&mxc).await;
+	let parsed: Mxc<'_> = mxc.as_str().try_into()?;
+	let Some(metadata) = self.services.media.get_metadata(&parsed).await else {
+		return Err!("No metadata found for {mxc}.");
+	};
+
+	let access = self.services.media.get_access(&parsed).await;
+	let info = FileInfo {
+		content_type: metadata.content_type,
+		content_disposition: metadata
+			.content_disposition
+			.as_ref()
+			.map(ToString::to_string),
+		last_access: access.map(|access| access.last_access),
+		hits: access.map_or(0, |access| access.hits),
+		mxc,
+	};
 
-	self.write_str(&format!("```\n{metadata:#?}\n```")).await
+	self.write_data(&info).await
 }
 
 #[admin_command]
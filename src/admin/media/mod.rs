@@ -76,6 +76,23 @@ pub enum MediaCommand {
 		yes_i_want_to_delete_local_media: bool,
 	},
 
+	/// Lists the media which would be evicted for not having been accessed
+	/// for [duration], without deleting anything.
+	///
+	/// Defaults to the `media_evict_unaccessed_after` config option. Media used
+	/// as local users' avatars and local media are skipped according to the
+	/// eviction config.
+	EvictPreview {
+		/// The relative time (e.g. 30s, 5m, 7d) media has not been accessed
+		///   for
+		#[arg(long)]
+		unaccessed_for: Option<String>,
+
+		/// Maximum number of media to list, least recently accessed first
+		#[arg(short, long, default_value("100"))]
+		limit: usize,
+	},
+
 	GetFileInfo {
 		/// The MXC URL to lookup info for.
 		mxc: OwnedMxcUri,
@@ -10,6 +10,7 @@
 pub(crate) mod utils;
 
 pub(crate) mod appservice;
+pub(crate) mod audit;
 pub(crate) mod check;
 pub(crate) mod debug;
 pub(crate) mod federation;
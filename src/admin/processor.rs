@@ -1,6 +1,8 @@
-use std::{fmt::Write, mem::take, panic::AssertUnwindSafe, sync::Arc, time::SystemTime};
+use std::{
+	fmt::Write, iter::successors, mem::take, panic::AssertUnwindSafe, sync::Arc, time::SystemTime,
+};
 
-use clap::{CommandFactory, Parser};
+use clap::{Arg, CommandFactory, FromArgMatches, value_parser};
 use conduwuit::{
 	Error, Result, SyncMutex, debug, error,
 	log::{
@@ -12,7 +14,7 @@
 	utils::string::{collect_stream, common_prefix},
 	warn,
 };
-use futures::{AsyncWriteExt, future::FutureExt, io::BufWriter};
+use futures::{AsyncWriteExt, future::FutureExt, io::BufWriter, lock::Mutex};
 use ruma::{
 	EventId,
 	events::{
@@ -20,17 +22,33 @@
 		room::message::{Relation::Reply, RoomMessageEventContent},
 	},
 };
+use serde_json::{Value as JsonValue, json};
 use service::{
 	Services,
 	admin::{CommandInput, CommandOutput, ProcessorFuture, ProcessorResult},
+	audit::Outcome,
 };
 use tracing::Level;
 use tracing_subscriber::{EnvFilter, filter::LevelFilter};
 
-use crate::{admin, admin::AdminCommand, context::Context};
+use crate::{
+	admin,
+	admin::AdminCommand,
+	context::{Context, OutputFormat},
+};
+
+/// Commands whose arguments or body may hold secrets, which are left out of the
+/// audit log, along with how many leading positional arguments are kept, e.g.
+/// the user concerned.
+const AUDIT_SECRET_COMMANDS: &[(&str, usize)] = &[
+	("users create-user", 1),
+	("users reset-password", 1),
+	("appservices register", 1),
+	("token revoke", 0),
+];
 
 #[must_use]
-pub(super) fn complete(line: &str) -> String { complete_command(AdminCommand::command(), line) }
+pub(super) fn complete(line: &str) -> String { complete_command(command(), line) }
 
 #[must_use]
 pub(super) fn dispatch(services: Arc<Services>, command: CommandInput) -> ProcessorFuture {
@@ -47,11 +65,27 @@ async fn handle_command(services: Arc<Services>, command: CommandInput) -> Proce
 }
 
 async fn process_command(services: Arc<Services>, input: &CommandInput) -> ProcessorResult {
-	let (command, args, body) = match parse(&services, input) {
+	let (command, args, path, body, format) = match parse(&services, input) {
 		| Err(error) => return Err(error),
 		| Ok(parsed) => parsed,
 	};
 
+	if let Some(sender) = input.sender.as_deref() {
+		if !services.admin.user_may_run(sender, &path).await {
+			warn!(
+				%sender,
+				command = ?args,
+				"Denied admin command not permitted by the sender's role"
+			);
+			audit(&services, input, &args, &path, &body, Outcome::Denied);
+			let message = format!("You are not allowed to run `{}`.", path.join(" "));
+			return Err(reply(
+				RoomMessageEventContent::notice_plain(message),
+				input.reply_id.as_deref(),
+			));
+		}
+	}
+
 	let context = Context {
 		services: &services,
 		body: &body,
@@ -60,9 +94,12 @@ async fn process_command(services: Arc<Services>, input: &CommandInput) -> Proce
 		sender: input.sender.as_deref(),
 		output: BufWriter::new(Vec::new()).into(),
 		source: input.source,
+		format,
+		data: Mutex::new(None),
 	};
 
 	let (result, mut logs) = process(&context, command, &args).await;
+	audit(&services, input, &args, &path, &body, Outcome::from(&result));
 
 	let output = &mut context.output.lock().await;
 	output.flush().await.expect("final flush of output stream");
@@ -70,6 +107,11 @@ async fn process_command(services: Arc<Services>, input: &CommandInput) -> Proce
 	let output =
 		String::from_utf8(take(output.get_mut())).expect("invalid utf8 in command output stream");
 
+	if format == OutputFormat::Json {
+		let data = context.data.lock().await.take();
+		return json_reply(result, data, output, logs, context.reply_id);
+	}
+
 	match result {
 		| Ok(()) if logs.is_empty() =>
 			Ok(Some(reply(RoomMessageEventContent::notice_markdown(output), context.reply_id))),
@@ -87,6 +129,101 @@ async fn process_command(services: Arc<Services>, input: &CommandInput) -> Proce
 	}
 }
 
+/// Builds the reply of a command run with `--format json`: an object holding
+/// the structured result of the command, any text it wrote, the logs captured
+/// while it ran, and its error.
+#[allow(clippy::result_large_err)]
+pub(super) fn json_reply(
+	result: Result,
+	data: Option<JsonValue>,
+	output: String,
+	logs: String,
+	reply_id: Option<&EventId>,
+) -> ProcessorResult {
+	let reply_json = json!({
+		"ok": result.is_ok(),
+		"data": data,
+		"output": (!output.is_empty()).then_some(output),
+		"logs": (!logs.is_empty()).then_some(logs),
+		"error": result.as_ref().err().map(ToString::to_string),
+	});
+
+	let body = serde_json::to_string_pretty(&reply_json).expect("JSON value serializes");
+	let content = reply(RoomMessageEventContent::notice_plain(body), reply_id);
+	match result {
+		| Ok(()) => Ok(Some(content)),
+		| Err(_) => Err(content),
+	}
+}
+
+/// The arguments of a command as recorded in the audit log, followed by any
+/// lines of the command body. The secrets of the commands listed in
+/// `AUDIT_SECRET_COMMANDS` are redacted, and their bodies left out.
+pub(super) fn audit_args(
+	action: &str,
+	args: &[String],
+	path: &[String],
+	body: &[&str],
+) -> Vec<String> {
+	// argv holds the program name, then the subcommands and their arguments
+	let args = args.iter().skip(path.len().saturating_add(1));
+	let Some(&(_, kept)) = AUDIT_SECRET_COMMANDS
+		.iter()
+		.find(|(command, _)| *command == action)
+	else {
+		return args
+			.cloned()
+			.chain(
+				body.iter()
+					.filter(|line| !line.trim_start().starts_with("```"))
+					.map(ToString::to_string),
+			)
+			.collect();
+	};
+
+	// keep the flags and the user or registration concerned
+	let mut positional = 0_usize;
+	args.map(|arg| {
+		if arg.starts_with('-') {
+			return match arg.split_once('=') {
+				| Some((flag, _)) => format!("{flag}=[redacted]"),
+				| None => arg.clone(),
+			};
+		}
+
+		positional = positional.saturating_add(1);
+		if positional <= kept {
+			arg.clone()
+		} else {
+			"[redacted]".to_owned()
+		}
+	})
+	.collect()
+}
+
+/// Records a command in the audit log along with its arguments, including
+/// any lines of the command body.
+fn audit(
+	services: &Services,
+	input: &CommandInput,
+	args: &[String],
+	path: &[String],
+	body: &[&str],
+	outcome: Outcome,
+) {
+	let actor = input
+		.sender
+		.as_deref()
+		.unwrap_or_else(|| services.globals.server_user.as_ref());
+
+	let action = path.join(" ");
+	let args = audit_args(&action, args, path, body);
+
+	services
+		.audit
+		.record(actor, input.source.into(), &action, args, outcome);
+}
+
 #[allow(clippy::result_large_err)]
 fn handle_panic(error: &Error, command: &CommandInput) -> ProcessorResult {
 	let link = "Please submit a [bug report](https://forgejo.ellis.link/continuwuation/continuwuity/issues/new). 🥺";
@@ -161,16 +298,16 @@ fn capture_create(context: &Context<'_>) -> (Arc<Capture>, Arc<SyncMutex<String>
 }
 
 /// Parse chat messages from the admin room into an AdminCommand object
-#[allow(clippy::result_large_err)]
+#[allow(clippy::result_large_err, clippy::type_complexity)]
 fn parse<'a>(
 	services: &Arc<Services>,
 	input: &'a CommandInput,
-) -> Result<(AdminCommand, Vec<String>, Vec<&'a str>), CommandOutput> {
+) -> Result<(AdminCommand, Vec<String>, Vec<String>, Vec<&'a str>, OutputFormat), CommandOutput> {
 	let lines = input.command.lines().filter(|line| !line.trim().is_empty());
 	let command_line = lines.clone().next().expect("command missing first line");
 	let body = lines.skip(1).collect();
 	match parse_command(command_line) {
-		| Ok((command, args)) => Ok((command, args, body)),
+		| Ok((command, args, path, format)) => Ok((command, args, path, body, format)),
 		| Err(error) => {
 			let message = error
 				.to_string()
@@ -180,10 +317,39 @@ fn parse<'a>(
 	}
 }
 
-fn parse_command(line: &str) -> Result<(AdminCommand, Vec<String>)> {
+/// Parses a command line, returning the command, its arguments, the
+/// canonical names of its subcommands, e.g. `["rooms", "list-rooms"]` for
+/// `rooms list`, and the requested output format.
+#[allow(clippy::type_complexity)]
+pub(super) fn parse_command(
+	line: &str,
+) -> Result<(AdminCommand, Vec<String>, Vec<String>, OutputFormat)> {
 	let argv = parse_line(line);
-	let command = AdminCommand::try_parse_from(&argv)?;
-	Ok((command, argv))
+	let matches = command().try_get_matches_from(&argv)?;
+	let command = AdminCommand::from_arg_matches(&matches)?;
+	let format = matches
+		.get_one::<OutputFormat>("format")
+		.copied()
+		.unwrap_or_default();
+
+	let path = successors(matches.subcommand(), |(_, matches)| matches.subcommand())
+		.map(|(name, _)| name.to_owned())
+		.collect();
+
+	Ok((command, argv, path, format))
+}
+
+/// The admin command parser, with the `--format` option accepted by every
+/// command.
+fn command() -> clap::Command {
+	AdminCommand::command().arg(
+		Arg::new("format")
+			.long("format")
+			.global(true)
+			.value_name("FORMAT")
+			.value_parser(value_parser!(OutputFormat))
+			.help("Write the result as markdown, or as JSON for tooling"),
+	)
 }
 
 fn complete_command(mut cmd: clap::Command, line: &str) -> String {
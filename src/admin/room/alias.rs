@@ -1,12 +1,49 @@
-use std::fmt::Write;
+use std::fmt;
 
 use clap::Subcommand;
-use conduwuit::{Err, Result};
+use conduwuit::{Err, Result, utils::ReadyExt};
 use futures::StreamExt;
 use ruma::{OwnedRoomAliasId, OwnedRoomId};
+use serde::Serialize;
 
 use crate::Context;
 
+/// Local aliases with the rooms they point to, as written by `list`.
+#[derive(Serialize)]
+struct AliasList {
+	/// The room the aliases were listed for, if any.
+	#[serde(skip)]
+	room_id: Option<OwnedRoomId>,
+	aliases: Vec<Alias>,
+}
+
+#[derive(Serialize)]
+struct Alias {
+	alias: OwnedRoomAliasId,
+	room_id: OwnedRoomId,
+}
+
+impl fmt::Display for AliasList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.room_id {
+			| Some(room_id) => {
+				writeln!(f, "Aliases for {room_id}:")?;
+				for Alias { alias, .. } in &self.aliases {
+					writeln!(f, "- {alias}")?;
+				}
+			},
+			| None => {
+				writeln!(f, "Aliases:")?;
+				for Alias { alias, room_id } in &self.aliases {
+					writeln!(f, "- `{room_id}` -> {alias}")?;
+				}
+			},
+		}
+
+		Ok(())
+	}
+}
+
 #[derive(Debug, Subcommand)]
 pub enum RoomAliasCommand {
 	/// Make an alias point to a room.
@@ -116,44 +153,33 @@ pub(super) async fn process(command: RoomAliasCommand, context: &Context<'_>) ->
 				| RoomAliasCommand::List { .. } => unreachable!(),
 			}
 		},
-		| RoomAliasCommand::List { room_id } =>
-			if let Some(room_id) = room_id {
-				let aliases: Vec<OwnedRoomAliasId> = services
+		| RoomAliasCommand::List { room_id } => {
+			let aliases = if let Some(room_id) = &room_id {
+				services
 					.rooms
 					.alias
-					.local_aliases_for_room(&room_id)
-					.map(Into::into)
+					.local_aliases_for_room(room_id)
+					.map(|alias| Alias {
+						alias: alias.into(),
+						room_id: room_id.clone(),
+					})
 					.collect()
-					.await;
-
-				let plain_list = aliases.iter().fold(String::new(), |mut output, alias| {
-					writeln!(output, "- {alias}")
-						.expect("should be able to write to string buffer");
-					output
-				});
-
-				let plain = format!("Aliases for {room_id}:\n{plain_list}");
-				context.write_str(&plain).await
+					.await
 			} else {
-				let aliases = services
+				let server_name = services.globals.server_name();
+				services
 					.rooms
 					.alias
 					.all_local_aliases()
-					.map(|(room_id, localpart)| (room_id.into(), localpart.into()))
-					.collect::<Vec<(OwnedRoomId, String)>>()
-					.await;
-
-				let server_name = services.globals.server_name();
-				let plain_list = aliases
-					.iter()
-					.fold(String::new(), |mut output, (alias, id)| {
-						writeln!(output, "- `{alias}` -> #{id}:{server_name}")
-							.expect("should be able to write to string buffer");
-						output
-					});
+					.ready_filter_map(|(room_id, localpart)| {
+						let alias = format!("#{localpart}:{server_name}").try_into().ok()?;
+						Some(Alias { alias, room_id: room_id.into() })
+					})
+					.collect()
+					.await
+			};
 
-				let plain = format!("Aliases:\n{plain_list}");
-				context.write_str(&plain).await
-			},
+			context.write_data(&AliasList { room_id, aliases }).await
+		},
 	}
 }
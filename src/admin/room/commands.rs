@@ -1,8 +1,18 @@
-use conduwuit::{Err, Result};
+use std::iter::once;
+
+use api::client::upgrade_room_helper;
+use conduwuit::{Err, Event, Result, RoomVersion, err};
 use futures::StreamExt;
-use ruma::OwnedRoomId;
+use ruma::{
+	OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId, RoomId, RoomVersionId,
+	events::{
+		StateEventType,
+		room::{create::RoomCreateEventContent, power_levels::RoomPowerLevelsEventContent},
+	},
+};
+use service::Services;
 
-use crate::{PAGE_SIZE, admin_command, get_room_info};
+use crate::{PAGE_SIZE, admin_command, get_room_info, utils::RoomList};
 
 #[admin_command]
 pub(super) async fn list_rooms(
@@ -44,20 +54,10 @@ pub(super) async fn list_rooms(
 		return Err!("No more rooms.");
 	}
 
-	let body = rooms
-		.iter()
-		.map(|(id, members, name)| {
-			if no_details {
-				format!("{id}")
-			} else {
-				format!("{id}\tMembers: {members}\tName: {name}")
-			}
-		})
-		.collect::<Vec<_>>()
-		.join("\n");
+	let mut rooms = RoomList::new("Rooms".to_owned(), rooms);
+	rooms.details = !no_details;
 
-	self.write_str(&format!("Rooms ({}):\n```\n{body}\n```", rooms.len(),))
-		.await
+	self.write_data(&rooms).await
 }
 
 #[admin_command]
@@ -66,3 +66,99 @@ pub(super) async fn exists(&self, room_id: OwnedRoomId) -> Result {
 
 	self.write_str(&format!("{result}")).await
 }
+
+#[admin_command]
+pub(super) async fn upgrade_room(
+	&self,
+	room: OwnedRoomOrAliasId,
+	new_version: Option<RoomVersionId>,
+	user: Option<OwnedUserId>,
+	additional_creator: Vec<OwnedUserId>,
+) -> Result {
+	self.bail_restricted()?;
+
+	let room_id = self.services.rooms.alias.resolve(&room).await?;
+	let new_version =
+		new_version.unwrap_or_else(|| self.services.server.config.default_room_version.clone());
+
+	let user_id = match user {
+		| Some(user_id) => {
+			if !self.services.globals.user_is_local(&user_id) {
+				return Err!("{user_id} is not a local user.");
+			}
+
+			if !self
+				.services
+				.rooms
+				.state_cache
+				.is_joined(&user_id, &room_id)
+				.await
+			{
+				return Err!("{user_id} is not joined to {room_id}.");
+			}
+
+			user_id
+		},
+		| None => upgrading_user(self.services, &room_id).await?,
+	};
+
+	let replacement_room =
+		upgrade_room_helper(self.services, &user_id, &room_id, &new_version, additional_creator)
+			.await?;
+
+	self.write_str(&format!(
+		"Upgraded {room_id} to room version {new_version} on behalf of {user_id}. The \
+		 replacement room is {replacement_room}."
+	))
+	.await
+}
+
+/// Picks the local member to upgrade a room on behalf of: a creator of the
+/// room if its version privileges creators and one is joined, otherwise the
+/// member with the highest power level.
+async fn upgrading_user(services: &Services, room_id: &RoomId) -> Result<OwnedUserId> {
+	let members: Vec<OwnedUserId> = services
+		.rooms
+		.state_cache
+		.local_users_in_room(room_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	let create_event = services
+		.rooms
+		.state_accessor
+		.room_state_get(room_id, &StateEventType::RoomCreate, "")
+		.await?;
+
+	let create_content: RoomCreateEventContent =
+		serde_json::from_str(create_event.content().get())?;
+
+	if RoomVersion::new(&create_content.room_version)?.explicitly_privilege_room_creators {
+		let creators: Vec<OwnedUserId> = once(create_event.sender().to_owned())
+			.chain(create_content.additional_creators.unwrap_or_default())
+			.collect();
+
+		if let Some(creator) = members.iter().find(|member| creators.contains(member)) {
+			return Ok(creator.clone());
+		}
+	}
+
+	let power_levels: RoomPowerLevelsEventContent = services
+		.rooms
+		.state_accessor
+		.room_state_get_content(room_id, &StateEventType::RoomPowerLevels, "")
+		.await
+		.unwrap_or_default();
+
+	members
+		.into_iter()
+		.max_by_key(|member| {
+			power_levels
+				.users
+				.get(member)
+				.copied()
+				.unwrap_or(power_levels.users_default)
+		})
+		.ok_or_else(|| err!("None of our users are joined to {room_id}."))
+}
@@ -3,7 +3,7 @@
 use futures::StreamExt;
 use ruma::OwnedRoomId;
 
-use crate::{Context, PAGE_SIZE, get_room_info};
+use crate::{Context, PAGE_SIZE, get_room_info, utils::RoomList};
 
 #[derive(Debug, Subcommand)]
 pub enum RoomDirectoryCommand {
@@ -29,7 +29,7 @@ pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>
 	let services = context.services;
 	match command {
 		| RoomDirectoryCommand::Publish { room_id } => {
-			services.rooms.directory.set_public(&room_id);
+			services.rooms.directory.set_public(&room_id).await;
 			context.write_str("Room published").await
 		},
 		| RoomDirectoryCommand::Unpublish { room_id } => {
@@ -60,14 +60,8 @@ pub(super) async fn process(command: RoomDirectoryCommand, context: &Context<'_>
 				return Err!("No more rooms.");
 			}
 
-			let body = rooms
-				.iter()
-				.map(|(id, members, name)| format!("{id} | Members: {members} | Name: {name}"))
-				.collect::<Vec<_>>()
-				.join("\n");
-
 			context
-				.write_str(&format!("Rooms (page {page}):\n```\n{body}\n```",))
+				.write_data(&RoomList::new(format!("Published rooms, page {page}"), rooms))
 				.await
 		},
 	}
@@ -1,7 +1,10 @@
+use std::fmt;
+
 use clap::Subcommand;
 use conduwuit::{Err, Result, utils::ReadyExt};
 use futures::StreamExt;
-use ruma::OwnedRoomId;
+use ruma::{OwnedRoomId, OwnedUserId};
+use serde::Serialize;
 
 use crate::{admin_command, admin_command_dispatch};
 
@@ -26,6 +29,31 @@ pub enum RoomInfoCommand {
 	},
 }
 
+/// The joined members of a room, as written by `list-joined-members`.
+#[derive(Serialize)]
+struct MemberList {
+	#[serde(skip)]
+	room_name: String,
+	members: Vec<Member>,
+}
+
+#[derive(Serialize)]
+struct Member {
+	user_id: OwnedUserId,
+	displayname: String,
+}
+
+impl fmt::Display for MemberList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} Members in Room \"{}\":\n```", self.members.len(), self.room_name)?;
+		for Member { user_id, displayname } in &self.members {
+			writeln!(f, "{user_id} | {displayname}")?;
+		}
+
+		write!(f, "```")
+	}
+}
+
 #[admin_command]
 async fn list_joined_members(&self, room_id: OwnedRoomId, local_only: bool) -> Result {
 	let room_name = self
@@ -36,7 +64,7 @@ async fn list_joined_members(&self, room_id: OwnedRoomId, local_only: bool) -> R
 		.await
 		.unwrap_or_else(|_| room_id.to_string());
 
-	let member_info: Vec<_> = self
+	let members = self
 		.services
 		.rooms
 		.state_cache
@@ -47,28 +75,21 @@ async fn list_joined_members(&self, room_id: OwnedRoomId, local_only: bool) -> R
 				.unwrap_or(true)
 		})
 		.map(ToOwned::to_owned)
-		.filter_map(|user_id| async move {
-			Some((
-				self.services
+		.then(|user_id| async move {
+			Member {
+				displayname: self
+					.services
 					.users
 					.displayname(&user_id)
 					.await
 					.unwrap_or_else(|_| user_id.to_string()),
 				user_id,
-			))
+			}
 		})
 		.collect()
 		.await;
 
-	let num = member_info.len();
-	let body = member_info
-		.into_iter()
-		.map(|(displayname, mxid)| format!("{mxid} | {displayname}"))
-		.collect::<Vec<_>>()
-		.join("\n");
-
-	self.write_str(&format!("{num} Members in Room \"{room_name}\":\n```\n{body}\n```",))
-		.await
+	self.write_data(&MemberList { room_name, members }).await
 }
 
 #[admin_command]
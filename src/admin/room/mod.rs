@@ -6,7 +6,7 @@
 
 use clap::Subcommand;
 use conduwuit::Result;
-use ruma::OwnedRoomId;
+use ruma::{OwnedRoomId, OwnedRoomOrAliasId, OwnedUserId, RoomVersionId};
 
 use self::{
 	alias::RoomAliasCommand, directory::RoomDirectoryCommand, info::RoomInfoCommand,
@@ -56,4 +56,29 @@ pub enum RoomCommand {
 	Exists {
 		room_id: OwnedRoomId,
 	},
+
+	/// Upgrade a room to a new room version on behalf of one of its local
+	/// members
+	///
+	/// Unless a user is given, the room is upgraded by a local creator of the
+	/// room, for room versions which privilege creators, or else by the local
+	/// member with the highest power level. That user must be permitted to
+	/// upgrade the room, and joins the replacement room.
+	#[clap(name = "upgrade")]
+	UpgradeRoom {
+		room: OwnedRoomOrAliasId,
+
+		/// The room version to upgrade to. Defaults to the
+		/// `default_room_version` config option.
+		new_version: Option<RoomVersionId>,
+
+		/// The local member to upgrade the room on behalf of
+		#[arg(long)]
+		user: Option<OwnedUserId>,
+
+		/// Additional creators of the replacement room, for room versions which
+		/// privilege creators. May be given more than once.
+		#[arg(long)]
+		additional_creator: Vec<OwnedUserId>,
+	},
 }
@@ -8,7 +8,7 @@
 use futures::{FutureExt, StreamExt};
 use ruma::{OwnedRoomId, OwnedRoomOrAliasId, RoomAliasId, RoomId, RoomOrAliasId};
 
-use crate::{admin_command, admin_command_dispatch, get_room_info};
+use crate::{admin_command, admin_command_dispatch, get_room_info, utils::RoomList};
 
 #[admin_command_dispatch]
 #[derive(Debug, Subcommand)]
@@ -451,20 +451,8 @@ async fn list_banned_rooms(&self, no_details: bool) -> Result {
 	rooms.sort_by_key(|r| r.1);
 	rooms.reverse();
 
-	let num = rooms.len();
+	let mut rooms = RoomList::new("Rooms Banned".to_owned(), rooms);
+	rooms.details = !no_details;
 
-	let body = rooms
-		.iter()
-		.map(|(id, members, name)| {
-			if no_details {
-				format!("{id}")
-			} else {
-				format!("{id}\tMembers: {members}\tName: {name}")
-			}
-		})
-		.collect::<Vec<_>>()
-		.join("\n");
-
-	self.write_str(&format!("Rooms Banned ({num}):\n```\n{body}\n```",))
-		.await
+	self.write_data(&rooms).await
 }
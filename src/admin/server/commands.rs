@@ -107,6 +107,20 @@ pub(super) async fn clear_caches(&self) -> Result {
 	self.write_str("Done.").await
 }
 
+#[admin_command]
+pub(super) async fn gc_state(&self, dry_run: bool) -> Result {
+	self.bail_restricted()?;
+
+	let report = self
+		.services
+		.rooms
+		.state_compressor
+		.collect_garbage(dry_run)
+		.await?;
+
+	self.write_str(&format!("{report}")).await
+}
+
 #[admin_command]
 pub(super) async fn list_backups(&self) -> Result {
 	self.services
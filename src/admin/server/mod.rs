@@ -39,6 +39,14 @@ pub enum ServerCommand {
 	/// Clears all of Continuwuity's caches
 	ClearCaches,
 
+	/// Deletes state snapshots no longer referenced by any room, event or
+	/// sync token, re-parenting the snapshots stored on top of them
+	GcState {
+		/// Only report what would be deleted, without changing anything
+		#[arg(long)]
+		dry_run: bool,
+	},
+
 	/// Performs an online backup of the database (only available for RocksDB
 	///   at the moment)
 	BackupDatabase,
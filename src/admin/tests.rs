@@ -24,3 +24,79 @@ fn get_help_inner(input: &str) {
 	assert!(error.contains("Commands:"));
 	assert!(error.contains("Options:"));
 }
+
+#[test]
+fn command_path_canonical() {
+	use crate::processor::parse_command;
+
+	let (_, _, path, _) = parse_command("users list").expect("command parsed");
+	assert_eq!(path, ["users", "list-users"]);
+
+	let (_, _, path, _) =
+		parse_command("rooms moderation list-banned-rooms").expect("command parsed");
+	assert_eq!(path, ["rooms", "moderation", "list-banned-rooms"]);
+}
+
+#[test]
+fn output_format() {
+	use crate::{context::OutputFormat, processor::parse_command};
+
+	let (.., format) = parse_command("users list").expect("command parsed");
+	assert_eq!(format, OutputFormat::Markdown);
+
+	let (.., format) = parse_command("users list --format json").expect("command parsed");
+	assert_eq!(format, OutputFormat::Json);
+
+	let (.., format) = parse_command("--format json rooms list").expect("command parsed");
+	assert_eq!(format, OutputFormat::Json);
+}
+
+#[test]
+fn json_reply_includes_logs() {
+	use crate::processor::json_reply;
+
+	let reply = json_reply(Ok(()), None, String::new(), "captured log line".to_owned(), None)
+		.expect("command succeeded")
+		.expect("command replied");
+
+	let reply: serde_json::Value = serde_json::from_str(reply.body()).expect("reply is JSON");
+	assert_eq!(reply["ok"], true);
+	assert_eq!(reply["logs"], "captured log line");
+	assert!(reply["output"].is_null());
+	assert!(reply["error"].is_null());
+}
+
+/// The arguments recorded in the audit log for `line` and its body.
+fn audited_args(line: &str, body: &[&str]) -> Vec<String> {
+	use crate::processor::{audit_args, parse_command};
+
+	let (_, args, path, _) = parse_command(line).expect("command parsed");
+	audit_args(&path.join(" "), &args, &path, body)
+}
+
+#[test]
+fn audit_redacts_secrets() {
+	assert_eq!(audited_args("users create-user alice hunter2", &[]), ["alice", "[redacted]"]);
+	assert_eq!(audited_args("users reset-password -l alice hunter2", &[]), [
+		"-l",
+		"alice",
+		"[redacted]"
+	]);
+	assert_eq!(audited_args("token revoke secrettoken", &[]), ["[redacted]"]);
+	assert_eq!(
+		audited_args("appservices register", &["```", "as_token: secret", "```"]),
+		Vec::<String>::new()
+	);
+}
+
+#[test]
+fn audit_keeps_other_arguments() {
+	assert_eq!(audited_args("users suspend @alice:example.com", &[]), ["@alice:example.com"]);
+	assert_eq!(
+		audited_args(
+			"users force-join-list-of-local-users !room:example.com --yes-i-want-to-do-this",
+			&["```", "alice", "bob", "```"]
+		),
+		["!room:example.com", "--yes-i-want-to-do-this", "alice", "bob"]
+	);
+}
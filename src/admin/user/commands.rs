@@ -1,6 +1,8 @@
 use std::{
 	collections::{BTreeMap, HashSet},
-	fmt::Write as _,
+	fmt::{self, Write as _},
+	net::IpAddr,
+	time::Duration,
 };
 
 use api::client::{
@@ -10,7 +12,7 @@
 use conduwuit::{
 	Err, Result, debug, debug_warn, error, info, is_equal_to,
 	matrix::{Event, pdu::PduBuilder},
-	utils::{self, ReadyExt},
+	utils::{self, ReadyExt, time::now_millis},
 	warn,
 };
 use futures::{FutureExt, StreamExt};
@@ -22,33 +24,48 @@
 			power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
 			redaction::RoomRedactionEventContent,
 		},
-		tag::{TagEvent, TagEventContent, TagInfo},
+		tag::{TagEvent, TagEventContent, TagInfo, Tags},
 	},
 };
+use serde::Serialize;
+use service::users::Connection;
 
 use crate::{
 	admin_command, get_room_info,
-	utils::{parse_active_local_user_id, parse_local_user_id},
+	utils::{RoomList, parse_active_local_user_id, parse_local_user_id},
 };
 
 const AUTO_GEN_PASSWORD_LENGTH: usize = 25;
 const BULK_JOIN_REASON: &str = "Bulk force joining this room as initiated by the server admin.";
 
+/// The local user accounts, as written by `list-users`.
+#[derive(Serialize)]
+struct UserList {
+	users: Vec<OwnedUserId>,
+}
+
+impl fmt::Display for UserList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Found {} local user account(s):\n```", self.users.len())?;
+		for user_id in &self.users {
+			writeln!(f, "{user_id}")?;
+		}
+
+		write!(f, "```")
+	}
+}
+
 #[admin_command]
 pub(super) async fn list_users(&self) -> Result {
-	let users: Vec<_> = self
+	let users = self
 		.services
 		.users
 		.list_local_users()
-		.map(ToString::to_string)
+		.map(ToOwned::to_owned)
 		.collect()
 		.await;
 
-	let mut plain_msg = format!("Found {} local user account(s):\n```\n", users.len());
-	plain_msg += users.join("\n").as_str();
-	plain_msg += "\n```";
-
-	self.write_str(&plain_msg).await
+	self.write_data(&UserList { users }).await
 }
 
 #[admin_command]
@@ -251,7 +268,6 @@ pub(super) async fn suspend(&self, user_id: String) -> Result {
 	if self.services.users.is_admin(&user_id).await {
 		return Err!("Admin users cannot be suspended.");
 	}
-	// TODO: Record the actual user that sent the suspension where possible
 	self.services
 		.users
 		.suspend_account(&user_id, self.sender_or_service_user())
@@ -449,13 +465,7 @@ pub(super) async fn list_joined_rooms(&self, user_id: String) -> Result {
 	rooms.sort_by_key(|r| r.1);
 	rooms.reverse();
 
-	let body = rooms
-		.iter()
-		.map(|(id, members, name)| format!("{id}\tMembers: {members}\tName: {name}"))
-		.collect::<Vec<_>>()
-		.join("\n");
-
-	self.write_str(&format!("Rooms {user_id} Joined ({}):\n```\n{body}\n```", rooms.len(),))
+	self.write_data(&RoomList::new(format!("Rooms {user_id} Joined"), rooms))
 		.await
 }
 
@@ -492,6 +502,8 @@ pub(super) async fn force_join_list_of_local_users(
 		.resolve_with_servers(&room_id, None)
 		.await?;
 
+	self.bail_delegated_admin_room(&room_id).await?;
+
 	if !self
 		.services
 		.rooms
@@ -607,6 +619,8 @@ pub(super) async fn force_join_all_local_users(
 		.resolve_with_servers(&room_id, None)
 		.await?;
 
+	self.bail_delegated_admin_room(&room_id).await?;
+
 	if !self
 		.services
 		.rooms
@@ -681,6 +695,8 @@ pub(super) async fn force_join_room(
 		.resolve_with_servers(&room_id, None)
 		.await?;
 
+	self.bail_delegated_admin_room(&room_id).await?;
+
 	assert!(
 		self.services.globals.user_is_local(&user_id),
 		"Parsed user_id must be a local user"
@@ -785,6 +801,7 @@ pub(super) async fn force_demote(&self, user_id: String, room_id: OwnedRoomOrAli
 
 #[admin_command]
 pub(super) async fn make_user_admin(&self, user_id: String) -> Result {
+	self.bail_delegated().await?;
 	let user_id = parse_local_user_id(self.services, &user_id)?;
 	assert!(
 		self.services.globals.user_is_local(&user_id),
@@ -801,6 +818,199 @@ pub(super) async fn make_user_admin(&self, user_id: String) -> Result {
 		.await
 }
 
+#[admin_command]
+pub(super) async fn grant_role(&self, user_id: String, role: String) -> Result {
+	self.bail_restricted()?;
+	self.bail_delegated().await?;
+	let user_id = parse_active_local_user_id(self.services, &user_id).await?;
+
+	if user_id == self.services.globals.server_user {
+		return Err!("Not allowed to grant a role to the server service account.");
+	}
+
+	self.services
+		.admin
+		.grant_role(&user_id, &role)
+		.boxed()
+		.await?;
+
+	self.write_str(&format!("{user_id} has been granted the {role} role."))
+		.await
+}
+
+#[admin_command]
+pub(super) async fn revoke_role(&self, user_id: String) -> Result {
+	self.bail_restricted()?;
+	self.bail_delegated().await?;
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+
+	let Some(role) = self.services.admin.user_role(&user_id).await else {
+		return Err!("{user_id} does not hold an admin role.");
+	};
+
+	self.services.admin.revoke_admin(&user_id).boxed().await?;
+
+	self.write_str(&format!("{user_id} no longer holds the {role} role."))
+		.await
+}
+
+/// The admin roles with their commands and holders, as written by
+/// `list-roles`.
+#[derive(Serialize)]
+struct RoleList {
+	roles: Vec<Role>,
+}
+
+#[derive(Serialize)]
+struct Role {
+	role: String,
+	/// None for roles removed from the config, which restrict their holders to
+	/// nothing.
+	commands: Option<Vec<String>>,
+	users: Vec<OwnedUserId>,
+}
+
+impl fmt::Display for RoleList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for Role { role, commands, users } in &self.roles {
+			match commands {
+				| Some(commands) => writeln!(
+					f,
+					"**{role}** ({} user(s)): `{}`",
+					users.len(),
+					commands.join("`, `")
+				)?,
+				| None => writeln!(f, "**{role}** ({} user(s)): not configured", users.len())?,
+			}
+			for user_id in users {
+				writeln!(f, "- {user_id}")?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[admin_command]
+pub(super) async fn list_roles(&self) -> Result {
+	let mut holders: BTreeMap<String, Vec<OwnedUserId>> = BTreeMap::new();
+	self.services
+		.admin
+		.role_holders()
+		.ready_for_each(|(user_id, role)| {
+			holders
+				.entry(role.to_owned())
+				.or_default()
+				.push(user_id.to_owned());
+		})
+		.await;
+
+	let mut roles: Vec<_> = self
+		.services
+		.server
+		.config
+		.admin_roles
+		.iter()
+		.map(|(role, commands)| Role {
+			users: holders.remove(role).unwrap_or_default(),
+			role: role.clone(),
+			commands: Some(commands.clone()),
+		})
+		.collect();
+
+	// Roles removed from the config still restrict their holders to nothing
+	roles.extend(
+		holders
+			.into_iter()
+			.map(|(role, users)| Role { role, commands: None, users }),
+	);
+
+	self.write_data(&RoleList { roles }).await
+}
+
+/// The users who connected from an IP address, as written by `whois`.
+#[derive(Serialize)]
+struct IpUsers {
+	ip: IpAddr,
+	users: Vec<IpUser>,
+}
+
+#[derive(Serialize)]
+struct IpUser {
+	user_id: OwnedUserId,
+	last_seen: u64,
+}
+
+impl fmt::Display for IpUsers {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Users who connected from `{}` ({}):", self.ip, self.users.len())?;
+		for IpUser { user_id, last_seen } in &self.users {
+			writeln!(f, "- {user_id}, last seen {} ago", ago(*last_seen))?;
+		}
+
+		Ok(())
+	}
+}
+
+/// The connection history of a user, as written by `whois`.
+#[derive(Serialize)]
+struct UserConnections {
+	user_id: OwnedUserId,
+	connections: Vec<Connection>,
+}
+
+impl fmt::Display for UserConnections {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Connection history of {} ({}):", self.user_id, self.connections.len())?;
+		for connection in &self.connections {
+			writeln!(
+				f,
+				"- `{}` from `{}` ({}): first seen {} ago, last seen {} ago",
+				connection.device_id,
+				connection.ip,
+				connection
+					.user_agent
+					.as_deref()
+					.unwrap_or("unknown user agent"),
+				ago(connection.first_seen),
+				ago(connection.last_seen),
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// How long ago a timestamp in milliseconds since the unix epoch was.
+fn ago(timestamp: u64) -> String {
+	utils::time::pretty(Duration::from_millis(now_millis().saturating_sub(timestamp)))
+}
+
+#[admin_command]
+pub(super) async fn whois(&self, target: String) -> Result {
+	if let Ok(ip) = target.parse::<IpAddr>() {
+		let users = self
+			.services
+			.users
+			.users_by_ip(ip)
+			.map(|(user_id, last_seen)| IpUser { user_id, last_seen })
+			.collect()
+			.await;
+
+		return self.write_data(&IpUsers { ip, users }).await;
+	}
+
+	let user_id = parse_local_user_id(self.services, &target)?;
+	if !self.services.users.exists(&user_id).await {
+		return Err!("User {user_id} does not exist.");
+	}
+
+	let connections = self.services.users.connections(&user_id).collect().await;
+
+	self.write_data(&UserConnections { user_id, connections })
+		.await
+}
+
 #[admin_command]
 pub(super) async fn put_room_tag(
 	&self,
@@ -877,6 +1087,18 @@ pub(super) async fn delete_room_tag(
 	.await
 }
 
+/// The tags of a room, as written by `get-room-tags`.
+#[derive(Serialize)]
+struct RoomTags {
+	tags: Tags,
+}
+
+impl fmt::Display for RoomTags {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "```\n{:#?}\n```", self.tags)
+	}
+}
+
 #[admin_command]
 pub(super) async fn get_room_tags(&self, user_id: String, room_id: OwnedRoomId) -> Result {
 	let user_id = parse_active_local_user_id(self.services, &user_id).await?;
@@ -890,7 +1112,109 @@ pub(super) async fn get_room_tags(&self, user_id: String, room_id: OwnedRoomId)
 			content: TagEventContent { tags: BTreeMap::new() },
 		});
 
-	self.write_str(&format!("```\n{:#?}\n```", tags_event.content.tags))
+	self.write_data(&RoomTags { tags: tags_event.content.tags })
+		.await
+}
+
+/// The kinds of account data a user has, as written by `get-account-data`.
+#[derive(Serialize)]
+struct AccountDataKinds {
+	user_id: OwnedUserId,
+	room_id: Option<OwnedRoomId>,
+	kinds: Vec<String>,
+}
+
+impl fmt::Display for AccountDataKinds {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Self { user_id, room_id, kinds } = self;
+		match room_id {
+			| Some(room_id) =>
+				writeln!(f, "Account data of {user_id} (room {room_id}, {}):", kinds.len())?,
+			| None => writeln!(f, "Account data of {user_id} (global, {}):", kinds.len())?,
+		}
+		for kind in kinds {
+			writeln!(f, "- `{kind}`")?;
+		}
+
+		Ok(())
+	}
+}
+
+/// The content of one kind of account data, as written by `get-account-data`.
+#[derive(Serialize)]
+struct AccountDataContent {
+	content: serde_json::Value,
+}
+
+impl fmt::Display for AccountDataContent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let content = serde_json::to_string_pretty(&self.content).map_err(|_| fmt::Error)?;
+		write!(f, "```json\n{content}\n```")
+	}
+}
+
+#[admin_command]
+pub(super) async fn get_account_data(
+	&self,
+	user_id: String,
+	kind: Option<String>,
+	room_id: Option<OwnedRoomId>,
+) -> Result {
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+	let where_ = room_id
+		.as_ref()
+		.map_or_else(|| "global".to_owned(), |room_id| format!("room {room_id}"));
+
+	let Some(kind) = kind else {
+		let kinds = self
+			.services
+			.account_data
+			.kinds(room_id.as_deref(), &user_id)
+			.map(ToOwned::to_owned)
+			.collect()
+			.await;
+
+		return self
+			.write_data(&AccountDataKinds { user_id, room_id, kinds })
+			.await;
+	};
+
+	let Ok(event) = self
+		.services
+		.account_data
+		.get_raw(room_id.as_deref(), &user_id, &kind)
+		.await
+	else {
+		return Err!("{user_id} has no {where_} account data of type {kind}.");
+	};
+
+	let mut event: serde_json::Value = serde_json::from_slice(&event)?;
+	let content = event["content"].take();
+
+	self.write_data(&AccountDataContent { content }).await
+}
+
+#[admin_command]
+pub(super) async fn delete_account_data(
+	&self,
+	user_id: String,
+	kind: String,
+	room_id: Option<OwnedRoomId>,
+) -> Result {
+	self.bail_restricted()?;
+
+	let user_id = parse_local_user_id(self.services, &user_id)?;
+	if self
+		.services
+		.account_data
+		.delete(room_id.as_deref(), &user_id, &kind)
+		.await
+		.is_err()
+	{
+		return Err!("{user_id} has no account data of type {kind}.");
+	}
+
+	self.write_str(&format!("Deleted account data of type {kind} for {user_id}."))
 		.await
 }
 
@@ -173,6 +173,36 @@ pub enum UserCommand {
 		user_id: String,
 	},
 
+	/// Grant a delegated admin role to a user.
+	///
+	/// The user is joined to the admin room, but may only run the commands
+	/// allowed for the role by the `admin_roles` config. Granting a role to an
+	/// admin demotes them to it.
+	GrantRole {
+		user_id: String,
+		role: String,
+	},
+
+	/// Revoke a user's delegated admin role, removing them from the admin
+	/// room.
+	RevokeRole {
+		user_id: String,
+	},
+
+	/// List the configured admin roles and the users holding them.
+	ListRoles,
+
+	/// Show the connection history of a local user, or the users who
+	/// connected from an IP address.
+	///
+	/// The history lists each device and IP address a user connected from,
+	/// with the last user agent and when it was first and last seen. It is
+	/// kept for `connection_history_retention` seconds.
+	Whois {
+		/// Username of a local user, or an IP address
+		target: String,
+	},
+
 	/// Puts a room tag for the specified user and room ID.
 	///
 	/// This is primarily useful if you'd like to set your admin room
@@ -199,6 +229,31 @@ pub enum UserCommand {
 		room_id: OwnedRoomId,
 	},
 
+	/// Lists the account data of a user, or shows one event of it.
+	///
+	/// Without `--room-id` the global account data is used.
+	GetAccountData {
+		user_id: String,
+
+		/// The type of account data to show; all types are listed if omitted
+		kind: Option<String>,
+
+		#[arg(long)]
+		room_id: Option<OwnedRoomId>,
+	},
+
+	/// Deletes one event from the account data of a user.
+	///
+	/// Without `--room-id` the global account data is used. The user's
+	/// clients are told of the deletion when they next sync.
+	DeleteAccountData {
+		user_id: String,
+		kind: String,
+
+		#[arg(long)]
+		room_id: Option<OwnedRoomId>,
+	},
+
 	/// Attempts to forcefully redact the specified event ID from the sender
 	///   user
 	///
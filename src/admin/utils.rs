@@ -1,9 +1,56 @@
 #![allow(dead_code)]
 
+use std::fmt;
+
 use conduwuit_core::{Err, Result, err};
 use ruma::{OwnedRoomId, OwnedUserId, RoomId, UserId};
+use serde::Serialize;
 use service::Services;
 
+/// A listing of rooms with their member counts and names, as written by the
+/// room listing commands.
+#[derive(Serialize)]
+pub(crate) struct RoomList {
+	#[serde(skip)]
+	pub(crate) heading: String,
+	#[serde(skip)]
+	pub(crate) details: bool,
+	pub(crate) rooms: Vec<RoomInfo>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RoomInfo {
+	pub(crate) room_id: OwnedRoomId,
+	pub(crate) members: u64,
+	pub(crate) name: String,
+}
+
+impl RoomList {
+	pub(crate) fn new(heading: String, rooms: Vec<(OwnedRoomId, u64, String)>) -> Self {
+		let rooms = rooms
+			.into_iter()
+			.map(|(room_id, members, name)| RoomInfo { room_id, members, name })
+			.collect();
+
+		Self { heading, details: true, rooms }
+	}
+}
+
+impl fmt::Display for RoomList {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} ({}):\n```", self.heading, self.rooms.len())?;
+		for RoomInfo { room_id, members, name } in &self.rooms {
+			if self.details {
+				writeln!(f, "{room_id}\tMembers: {members}\tName: {name}")?;
+			} else {
+				writeln!(f, "{room_id}")?;
+			}
+		}
+
+		write!(f, "```")
+	}
+}
+
 pub(crate) fn escape_html(s: &str) -> String {
 	s.replace('&', "&amp;")
 		.replace('<', "&lt;")
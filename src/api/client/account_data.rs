@@ -4,8 +4,8 @@
 use ruma::{
 	RoomId, UserId,
 	api::client::config::{
-		get_global_account_data, get_room_account_data, set_global_account_data,
-		set_room_account_data,
+		delete_global_account_data, delete_room_account_data, get_global_account_data,
+		get_room_account_data, set_global_account_data, set_room_account_data,
 	},
 	events::{
 		AnyGlobalAccountDataEventContent, AnyRoomAccountDataEventContent,
@@ -21,6 +21,9 @@
 /// # `PUT /_matrix/client/r0/user/{userId}/account_data/{type}`
 ///
 /// Sets some account data for the sender user.
+///
+/// Setting empty content (`{}`) deletes the data instead, as clients without
+/// MSC3391 delete account data; deleting data which is not set is no error.
 pub(crate) async fn set_global_account_data_route(
 	State(services): State<crate::State>,
 	body: Ruma<set_global_account_data::v3::Request>,
@@ -46,6 +49,9 @@ pub(crate) async fn set_global_account_data_route(
 /// # `PUT /_matrix/client/r0/user/{userId}/rooms/{roomId}/account_data/{type}`
 ///
 /// Sets some room account data for the sender user.
+///
+/// Setting empty content (`{}`) deletes the data instead, as clients without
+/// MSC3391 delete account data; deleting data which is not set is no error.
 pub(crate) async fn set_room_account_data_route(
 	State(services): State<crate::State>,
 	body: Ruma<set_room_account_data::v3::Request>,
@@ -68,6 +74,48 @@ pub(crate) async fn set_room_account_data_route(
 	Ok(set_room_account_data::v3::Response {})
 }
 
+/// # `DELETE /_matrix/client/unstable/org.matrix.msc3391/user/{userId}/account_data/{type}`
+///
+/// Deletes some account data of the sender user.
+pub(crate) async fn delete_global_account_data_route(
+	State(services): State<crate::State>,
+	body: Ruma<delete_global_account_data::unstable::Request>,
+) -> Result<delete_global_account_data::unstable::Response> {
+	let sender_user = body.sender_user();
+
+	if sender_user != body.user_id && body.appservice_info.is_none() {
+		return Err!(Request(Forbidden("You cannot delete account data of other users.")));
+	}
+
+	delete_account_data(&services, None, &body.user_id, &body.event_type.to_string()).await?;
+
+	Ok(delete_global_account_data::unstable::Response {})
+}
+
+/// # `DELETE /_matrix/client/unstable/org.matrix.msc3391/user/{userId}/rooms/{roomId}/account_data/{type}`
+///
+/// Deletes some room account data of the sender user.
+pub(crate) async fn delete_room_account_data_route(
+	State(services): State<crate::State>,
+	body: Ruma<delete_room_account_data::unstable::Request>,
+) -> Result<delete_room_account_data::unstable::Response> {
+	let sender_user = body.sender_user();
+
+	if sender_user != body.user_id && body.appservice_info.is_none() {
+		return Err!(Request(Forbidden("You cannot delete account data of other users.")));
+	}
+
+	delete_account_data(
+		&services,
+		Some(&body.room_id),
+		&body.user_id,
+		&body.event_type.to_string(),
+	)
+	.await?;
+
+	Ok(delete_room_account_data::unstable::Response {})
+}
+
 /// # `GET /_matrix/client/r0/user/{userId}/account_data/{type}`
 ///
 /// Gets some account data for the sender user.
@@ -119,22 +167,20 @@ async fn set_account_data(
 	event_type_s: &str,
 	data: &RawJsonValue,
 ) -> Result {
-	if event_type_s == RoomAccountDataEventType::FullyRead.to_cow_str() {
-		return Err!(Request(BadJson(
-			"This endpoint cannot be used for marking a room as fully read (setting \
-			 m.fully_read)"
-		)));
-	}
-
-	if event_type_s == GlobalAccountDataEventType::PushRules.to_cow_str() {
-		return Err!(Request(BadJson(
-			"This endpoint cannot be used for setting/configuring push rules."
-		)));
-	}
+	check_event_type(event_type_s)?;
 
 	let data: serde_json::Value = serde_json::from_str(data.get())
 		.map_err(|e| err!(Request(BadJson(warn!("Invalid JSON provided: {e}")))))?;
 
+	// Setting empty content is how clients without MSC3391 delete account data
+	if data.as_object().is_some_and(serde_json::Map::is_empty) {
+		return services
+			.account_data
+			.delete(room_id, sender_user, event_type_s)
+			.await
+			.or(Ok(()));
+	}
+
 	services
 		.account_data
 		.update(
@@ -149,6 +195,37 @@ async fn set_account_data(
 		.await
 }
 
+async fn delete_account_data(
+	services: &Services,
+	room_id: Option<&RoomId>,
+	sender_user: &UserId,
+	event_type_s: &str,
+) -> Result {
+	check_event_type(event_type_s)?;
+
+	services
+		.account_data
+		.delete(room_id, sender_user, event_type_s)
+		.await
+}
+
+fn check_event_type(event_type_s: &str) -> Result {
+	if event_type_s == RoomAccountDataEventType::FullyRead.to_cow_str() {
+		return Err!(Request(BadJson(
+			"This endpoint cannot be used for marking a room as fully read (setting \
+			 m.fully_read)"
+		)));
+	}
+
+	if event_type_s == GlobalAccountDataEventType::PushRules.to_cow_str() {
+		return Err!(Request(BadJson(
+			"This endpoint cannot be used for setting/configuring push rules."
+		)));
+	}
+
+	Ok(())
+}
+
 #[derive(Deserialize)]
 struct ExtractRoomEventContent {
 	content: Raw<AnyRoomAccountDataEventContent>,
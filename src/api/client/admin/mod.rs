@@ -1,3 +1,4 @@
 mod suspend;
+mod whois;
 
-pub(crate) use self::suspend::*;
+pub(crate) use self::{suspend::*, whois::*};
@@ -1,7 +1,14 @@
 use axum::extract::State;
 use conduwuit::{Err, Result};
+use conduwuit_service::{
+	Services,
+	audit::{Outcome, Source},
+};
 use futures::future::{join, join3};
-use ruma::api::client::admin::{get_suspended, set_suspended};
+use ruma::{
+	UserId,
+	api::client::admin::{get_suspended, set_suspended},
+};
 
 use crate::Ruma;
 
@@ -38,42 +45,63 @@ pub(crate) async fn put_suspended_status(
 	body: Ruma<set_suspended::v1::Request>,
 ) -> Result<set_suspended::v1::Response> {
 	let sender_user = body.sender_user();
+	let result =
+		set_suspended_status(&services, sender_user, &body.user_id, body.suspended).await;
 
+	let action = if body.suspended {
+		"users suspend"
+	} else {
+		"users unsuspend"
+	};
+	services.audit.record(
+		sender_user,
+		Source::Api,
+		action,
+		vec![body.user_id.to_string()],
+		Outcome::from(&result),
+	);
+
+	result.map(|()| set_suspended::v1::Response::new(body.suspended))
+}
+
+async fn set_suspended_status(
+	services: &Services,
+	sender_user: &UserId,
+	user_id: &UserId,
+	suspended: bool,
+) -> Result {
 	let (sender_admin, active, target_admin) = join3(
 		services.users.is_admin(sender_user),
-		services.users.is_active(&body.user_id),
-		services.users.is_admin(&body.user_id),
+		services.users.is_active(user_id),
+		services.users.is_admin(user_id),
 	)
 	.await;
 
 	if !sender_admin {
 		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
 	}
-	if !services.globals.user_is_local(&body.user_id) {
+	if !services.globals.user_is_local(user_id) {
 		return Err!(Request(InvalidParam("Can only set the suspended status of local users")));
 	}
 	if !active {
 		return Err!(Request(NotFound("Unknown user")));
 	}
-	if body.user_id == *sender_user {
+	if user_id == sender_user {
 		return Err!(Request(Forbidden("You cannot suspend yourself")));
 	}
 	if target_admin {
 		return Err!(Request(Forbidden("You cannot suspend another server administrator")));
 	}
-	if services.users.is_suspended(&body.user_id).await? == body.suspended {
+	if services.users.is_suspended(user_id).await? == suspended {
 		// No change
-		return Ok(set_suspended::v1::Response::new(body.suspended));
+		return Ok(());
 	}
 
-	let action = if body.suspended {
-		services
-			.users
-			.suspend_account(&body.user_id, sender_user)
-			.await;
+	let action = if suspended {
+		services.users.suspend_account(user_id, sender_user).await;
 		"suspended"
 	} else {
-		services.users.unsuspend_account(&body.user_id).await;
+		services.users.unsuspend_account(user_id).await;
 		"unsuspended"
 	};
 
@@ -81,9 +109,9 @@ pub(crate) async fn put_suspended_status(
 		// Notify the admin room that an account has been un/suspended
 		services
 			.admin
-			.send_text(&format!("{} has been {} by {}.", body.user_id, action, sender_user))
+			.send_text(&format!("{user_id} has been {action} by {sender_user}."))
 			.await;
 	}
 
-	Ok(set_suspended::v1::Response::new(body.suspended))
+	Ok(())
 }
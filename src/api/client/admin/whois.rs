@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use axum::extract::State;
+use conduwuit::{Err, Result, utils::ReadyExt};
+use ruma::{
+	MilliSecondsSinceUnixEpoch, UInt,
+	api::client::admin::get_user_info::{
+		self,
+		v3::{ConnectionInfo, DeviceInfo, SessionInfo},
+	},
+};
+
+use crate::Ruma;
+
+/// # `GET /_matrix/client/v3/admin/whois/{userId}`
+///
+/// Gets the connection history of a local user: the IP addresses and user
+/// agents each of their devices connected from, and when last.
+///
+/// - Server admins may look up any user, other users only themselves.
+pub(crate) async fn get_whois_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_user_info::v3::Request>,
+) -> Result<get_user_info::v3::Response> {
+	let sender_user = body.sender_user();
+	if sender_user != body.user_id && !services.users.is_admin(sender_user).await {
+		return Err!(Request(Forbidden("Only server administrators can use this endpoint")));
+	}
+
+	if !services.globals.user_is_local(&body.user_id) {
+		return Err!(Request(InvalidParam("Can only look up local users")));
+	}
+
+	if !services.users.exists(&body.user_id).await {
+		return Err!(Request(NotFound("Unknown user")));
+	}
+
+	let mut devices: BTreeMap<String, Vec<ConnectionInfo>> = BTreeMap::new();
+	services
+		.users
+		.connections(&body.user_id)
+		.ready_for_each(|connection| {
+			devices
+				.entry(connection.device_id.to_string())
+				.or_default()
+				.push(ConnectionInfo {
+					ip: Some(connection.ip.to_string()),
+					last_seen: UInt::new(connection.last_seen).map(MilliSecondsSinceUnixEpoch),
+					user_agent: connection.user_agent,
+				});
+		})
+		.await;
+
+	let devices = devices
+		.into_iter()
+		.map(|(device_id, connections)| {
+			(device_id, DeviceInfo {
+				sessions: vec![SessionInfo { connections }],
+			})
+		})
+		.collect();
+
+	Ok(get_user_info::v3::Response {
+		user_id: Some(body.user_id.clone()),
+		devices,
+	})
+}
@@ -1,13 +1,18 @@
 use axum::extract::State;
 use conduwuit::{
-	Err, Event, Result, at, debug_warn, err, ref_at,
+	Err, Event, Result, at, debug_warn, err,
+	matrix::event::Matches,
+	ref_at,
 	utils::{
 		IterStream,
 		future::TryExtExt,
 		stream::{BroadbandExt, ReadyExt, TryIgnore, WidebandExt},
 	},
 };
-use conduwuit_service::rooms::{lazy_loading, lazy_loading::Options, short::ShortStateKey};
+use conduwuit_service::{
+	Services,
+	rooms::{lazy_loading, lazy_loading::Options, short::ShortStateKey},
+};
 use futures::{
 	FutureExt, StreamExt, TryFutureExt, TryStreamExt,
 	future::{OptionFuture, join, join3, try_join3},
@@ -16,7 +21,9 @@
 
 use crate::{
 	Ruma,
-	client::message::{event_filter, ignored_filter, lazy_loading_witness, visibility_filter},
+	client::message::{
+		event_filter, ignored_filter, is_ignored_pdu, lazy_loading_witness, visibility_filter,
+	},
 };
 
 const LIMIT_MAX: usize = 100;
@@ -26,8 +33,10 @@
 ///
 /// Allows loading room history around an event.
 ///
-/// - Only works if the user is joined (TODO: always allow, but only show events
-///   if the user was joined, depending on history_visibility)
+/// - Only events the user is allowed to see according to `history_visibility`
+///   are returned, so non-members can read world-readable rooms
+/// - World-readable rooms this server is not in are peeked over federation, in
+///   which case only events before the requested one are returned
 pub(crate) async fn get_context_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_context::v3::Request>,
@@ -38,10 +47,6 @@ pub(crate) async fn get_context_route(
 	let event_id = &body.event_id;
 	let filter = &body.filter;
 
-	if !services.rooms.metadata.exists(room_id).await {
-		return Err!(Request(Forbidden("Room does not exist to this server")));
-	}
-
 	// Use limit or else 10, with maximum 100
 	let limit: usize = body
 		.limit
@@ -49,6 +54,10 @@ pub(crate) async fn get_context_route(
 		.unwrap_or(LIMIT_DEFAULT)
 		.min(LIMIT_MAX);
 
+	if !services.rooms.metadata.exists(room_id).await {
+		return peek_context(&services, &body, limit).boxed().await;
+	}
+
 	let base_id = services
 		.rooms
 		.timeline
@@ -239,3 +248,61 @@ pub(crate) async fn get_context_route(
 		state,
 	})
 }
+
+/// Loads the events before an event of a world-readable room this server is
+/// not in, via a resident server. The returned `start` token continues in
+/// `/messages`; events after the requested one are not known while peeking.
+async fn peek_context(
+	services: &Services,
+	body: &Ruma<get_context::v3::Request>,
+	limit: usize,
+) -> Result<get_context::v3::Response> {
+	let sender_user = body.sender_user();
+	let event_id = &body.event_id;
+	let peek = services.rooms.peek.peek(&body.room_id, sender_user).await?;
+
+	let mut base_event = services.rooms.peek.event(&peek, event_id).await?;
+	if is_ignored_pdu(services, &base_event, sender_user).await {
+		return Err!(Request(NotFound("Event not found.")));
+	}
+
+	base_event.set_unsigned(Some(sender_user));
+
+	let events_before: Vec<_> = services
+		.rooms
+		.peek
+		.backfill(&peek, vec![event_id.clone()], (limit / 2).saturating_add(1))
+		.await?
+		.into_iter()
+		.filter(|pdu| pdu.event_id != *event_id)
+		.filter(|pdu| body.filter.matches(pdu))
+		.stream()
+		.wide_filter_map(|mut pdu| async move {
+			if is_ignored_pdu(services, &pdu, sender_user).await {
+				return None;
+			}
+
+			pdu.set_unsigned(Some(sender_user));
+			Some(pdu)
+		})
+		.take(limit / 2)
+		.collect()
+		.await;
+
+	Ok(get_context::v3::Response {
+		start: events_before
+			.last()
+			.map_or_else(|| event_id.to_string(), |pdu| pdu.event_id.to_string())
+			.into(),
+
+		end: None,
+
+		event: Some(base_event.into_format()),
+
+		events_before: events_before.into_iter().map(Event::into_format).collect(),
+
+		events_after: Vec::new(),
+
+		state: peek.state.iter().cloned().map(Event::into_format).collect(),
+	})
+}
@@ -0,0 +1,84 @@
+use axum::{
+	Json,
+	extract::State,
+	http::{HeaderMap, header},
+};
+use conduwuit::{Err, Error, Result};
+use futures::StreamExt;
+use ruma::{
+	OwnedUserId,
+	api::client::{
+		delayed_events::update_delayed_event::{self, unstable::UpdateAction as RequestAction},
+		error::ErrorKind,
+	},
+};
+use service::{
+	Services,
+	delayed_events::{DelayedEvent, UpdateAction},
+};
+
+use crate::Ruma;
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc4140/delayed_events`
+///
+/// Lists the user's pending delayed events (MSC4140).
+pub(crate) async fn get_delayed_events_route(
+	State(services): State<crate::State>,
+	headers: HeaderMap,
+) -> Result<Json<serde_json::Value>> {
+	let sender_user = sender_user(&services, &headers).await?;
+	let delayed_events: Vec<DelayedEvent> = services
+		.delayed_events
+		.delayed_events_for_user(&sender_user)
+		.collect()
+		.await;
+
+	Ok(Json(serde_json::json!({ "delayed_events": delayed_events })))
+}
+
+/// # `POST /_matrix/client/unstable/org.matrix.msc4140/delayed_events/{delayId}`
+///
+/// Restarts the timeout of, immediately sends, or cancels one of the user's
+/// pending delayed events (MSC4140).
+pub(crate) async fn update_delayed_event_route(
+	State(services): State<crate::State>,
+	body: Ruma<update_delayed_event::unstable::Request>,
+) -> Result<update_delayed_event::unstable::Response> {
+	let sender_user = body.sender_user();
+	let action = match body.action {
+		| RequestAction::Restart => UpdateAction::Restart,
+		| RequestAction::Send => UpdateAction::Send,
+		| RequestAction::Cancel => UpdateAction::Cancel,
+		| _ => return Err!(Request(InvalidParam("Unknown delayed event action."))),
+	};
+
+	services
+		.delayed_events
+		.update(sender_user, &body.delay_id, action)
+		.await?;
+
+	Ok(update_delayed_event::unstable::Response {})
+}
+
+/// Resolves the user making a request from its `Authorization` header.
+async fn sender_user(services: &Services, headers: &HeaderMap) -> Result<OwnedUserId> {
+	let Some(token) = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+	else {
+		return Err(Error::BadRequest(ErrorKind::MissingToken, "Missing access token."));
+	};
+
+	services
+		.users
+		.find_from_token(token.trim())
+		.await
+		.map(|(user_id, _)| user_id)
+		.map_err(|_| {
+			Error::BadRequest(
+				ErrorKind::UnknownToken { soft_logout: false },
+				"Unknown access token.",
+			)
+		})
+}
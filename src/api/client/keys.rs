@@ -31,7 +31,7 @@
 };
 use serde_json::json;
 
-use super::SESSION_ID_LENGTH;
+use super::{SESSION_ID_LENGTH, device_list_left};
 use crate::Ruma;
 
 /// # `POST /_matrix/client/r0/keys/upload`
@@ -423,9 +423,11 @@ pub(crate) async fn get_key_changes_route(
 		);
 	}
 
+	let left = device_list_left(&services, sender_user, from, Some(to)).await;
+
 	Ok(get_key_changes::v3::Response {
 		changed: device_list_updates.into_iter().collect(),
-		left: Vec::new(), // TODO
+		left: left.into_iter().collect(),
 	})
 }
 
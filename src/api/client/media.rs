@@ -10,6 +10,7 @@
 	Services,
 	media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta, MXC_LENGTH},
 };
+use http::{HeaderMap, header::ACCEPT};
 use reqwest::Url;
 use ruma::{
 	Mxc, UserId,
@@ -64,9 +65,10 @@ pub(crate) async fn create_content_route(
 		media_id: &utils::random_string(MXC_LENGTH),
 	};
 
+	let file = services.media.strip_metadata(content_type, &body.file);
 	if let Err(e) = services
 		.media
-		.create(mxc, Some(user), Some(&content_disposition), content_type, &body.file)
+		.create(mxc, Some(user), Some(&content_disposition), content_type, &file)
 		.await
 	{
 		err!("Failed to save uploaded media: {e}");
@@ -76,7 +78,7 @@ pub(crate) async fn create_content_route(
 	let blurhash = body.generate_blurhash.then(|| {
 		services
 			.media
-			.create_blurhash(&body.file, content_type, filename)
+			.create_blurhash(&file, content_type, filename)
 			.ok()
 			.flatten()
 	});
@@ -99,6 +101,7 @@ pub(crate) async fn create_content_route(
 pub(crate) async fn get_content_thumbnail_route(
 	State(services): State<crate::State>,
 	InsecureClientIp(client): InsecureClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_thumbnail::v1::Request>,
 ) -> Result<get_content_thumbnail::v1::Response> {
 	let user = body.sender_user();
@@ -109,11 +112,13 @@ pub(crate) async fn get_content_thumbnail_route(
 		media_id: &body.media_id,
 	};
 
+	let animated = body.animated.unwrap_or(false);
 	let FileMeta {
 		content,
 		content_type,
 		content_disposition,
-	} = fetch_thumbnail(&services, &mxc, user, body.timeout_ms, &dim).await?;
+	} = fetch_thumbnail(&services, &mxc, user, body.timeout_ms, &dim, animated, accept(&headers))
+		.await?;
 
 	Ok(get_content_thumbnail::v1::Response {
 		file: content.expect("entire file contents"),
@@ -250,12 +255,14 @@ async fn fetch_thumbnail(
 	user: &UserId,
 	timeout_ms: Duration,
 	dim: &Dim,
+	animated: bool,
+	accept: Option<&str>,
 ) -> Result<FileMeta> {
 	let FileMeta {
 		content,
 		content_type,
 		content_disposition,
-	} = fetch_thumbnail_meta(services, mxc, user, timeout_ms, dim).await?;
+	} = fetch_thumbnail_meta(services, mxc, user, timeout_ms, dim, animated, accept).await?;
 
 	let content_disposition = Some(make_content_disposition(
 		content_disposition.as_ref(),
@@ -302,8 +309,14 @@ async fn fetch_thumbnail_meta(
 	user: &UserId,
 	timeout_ms: Duration,
 	dim: &Dim,
+	animated: bool,
+	accept: Option<&str>,
 ) -> Result<FileMeta> {
-	if let Some(filemeta) = services.media.get_thumbnail(mxc, dim).await? {
+	if let Some(filemeta) = services
+		.media
+		.get_thumbnail(mxc, dim, animated, accept)
+		.await?
+	{
 		return Ok(filemeta);
 	}
 
@@ -336,3 +349,8 @@ async fn fetch_file_meta(
 		.fetch_remote_content(mxc, Some(user), None, timeout_ms)
 		.await
 }
+
+/// The client's `Accept` header, used to choose the format of thumbnails.
+pub(crate) fn accept(headers: &HeaderMap) -> Option<&str> {
+	headers.get(ACCEPT).and_then(|accept| accept.to_str().ok())
+}
@@ -7,6 +7,7 @@
 	utils::{content_disposition::make_content_disposition, math::ruma_from_usize},
 };
 use conduwuit_service::media::{CACHE_CONTROL_IMMUTABLE, CORP_CROSS_ORIGIN, Dim, FileMeta};
+use http::HeaderMap;
 use reqwest::Url;
 use ruma::{
 	Mxc,
@@ -16,7 +17,10 @@
 	},
 };
 
-use crate::{Ruma, RumaResponse, client::create_content_route};
+use crate::{
+	Ruma, RumaResponse,
+	client::{accept, create_content_route},
+};
 
 /// # `GET /_matrix/media/v3/config`
 ///
@@ -315,6 +319,7 @@ pub(crate) async fn get_content_as_filename_legacy_legacy_route(
 pub(crate) async fn get_content_thumbnail_legacy_route(
 	State(services): State<crate::State>,
 	InsecureClientIp(client): InsecureClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_thumbnail::v3::Request>,
 ) -> Result<get_content_thumbnail::v3::Response> {
 	let mxc = Mxc {
@@ -323,7 +328,12 @@ pub(crate) async fn get_content_thumbnail_legacy_route(
 	};
 
 	let dim = Dim::from_ruma(body.width, body.height, body.method.clone())?;
-	match services.media.get_thumbnail(&mxc, &dim).await? {
+	let animated = body.animated.unwrap_or(false);
+	match services
+		.media
+		.get_thumbnail(&mxc, &dim, animated, accept(&headers))
+		.await?
+	{
 		| Some(FileMeta {
 			content,
 			content_type,
@@ -387,9 +397,10 @@ pub(crate) async fn get_content_thumbnail_legacy_route(
 pub(crate) async fn get_content_thumbnail_legacy_legacy_route(
 	State(services): State<crate::State>,
 	InsecureClientIp(client): InsecureClientIp,
+	headers: HeaderMap,
 	body: Ruma<get_content_thumbnail::v3::Request>,
 ) -> Result<RumaResponse<get_content_thumbnail::v3::Response>> {
-	get_content_thumbnail_legacy_route(State(services), InsecureClientIp(client), body)
+	get_content_thumbnail_legacy_route(State(services), InsecureClientIp(client), headers, body)
 		.await
 		.map(RumaResponse)
 }
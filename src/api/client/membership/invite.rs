@@ -2,16 +2,23 @@
 use axum_client_ip::InsecureClientIp;
 use conduwuit::{
 	Err, Result, debug_error, err, info,
-	matrix::{event::gen_event_id_canonical_json, pdu::PduBuilder},
+	matrix::{Event, event::gen_event_id_canonical_json, pdu::PduBuilder},
 	warn,
 };
 use futures::FutureExt;
 use ruma::{
 	RoomId, UserId,
-	api::{client::membership::invite_user, federation::membership::create_invite},
+	api::{
+		client::membership::{Invite3pid, invite_user},
+		federation::membership::create_invite,
+	},
 	events::{
+		StateEventType,
 		invite_permission_config::FilterLevel,
-		room::member::{MembershipState, RoomMemberEventContent},
+		room::{
+			member::{MembershipState, RoomMemberEventContent, ThirdPartyInvite},
+			third_party_invite::RoomThirdPartyInviteEventContent,
+		},
 	},
 };
 use service::Services;
@@ -97,18 +104,119 @@ pub(crate) async fn invite_user_route(
 				&body.room_id,
 				body.reason.clone(),
 				false,
+				None,
 			)
 			.boxed()
 			.await?;
 
 			Ok(invite_user::v3::Response {})
 		},
+		| invite_user::v3::InvitationRecipient::ThirdPartyId(invite) => {
+			invite_3pid_helper(&services, sender_user, &body.room_id, invite, false)
+				.boxed()
+				.await?;
+
+			Ok(invite_user::v3::Response {})
+		},
 		| _ => {
 			Err!(Request(NotFound("User not found.")))
 		},
 	}
 }
 
+/// Invites the holder of a third-party identifier. When the identity server
+/// knows the Matrix ID bound to it, that user is invited directly; otherwise a
+/// third-party invite is stored with the identity server and recorded in the
+/// room, to be exchanged for a real invite once the identifier is bound.
+pub(crate) async fn invite_3pid_helper(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	invite: &Invite3pid,
+	is_direct: bool,
+) -> Result {
+	if !services.users.is_admin(sender_user).await && services.config.block_non_admin_invites {
+		info!(
+			"User {sender_user} is not an admin and attempted to send a third-party invite to \
+			 room {room_id}"
+		);
+		return Err!(Request(Forbidden("Invites are not allowed on this server.")));
+	}
+
+	if !services
+		.rooms
+		.state_cache
+		.is_joined(sender_user, room_id)
+		.await
+	{
+		return Err!(Request(Forbidden(
+			"You must be joined in the room you are trying to invite from."
+		)));
+	}
+
+	match services.rooms.third_party_invite.lookup(invite).await? {
+		| Some(recipient_user) =>
+			invite_helper(services, sender_user, &recipient_user, room_id, None, is_direct, None)
+				.boxed()
+				.await,
+		| None =>
+			services
+				.rooms
+				.third_party_invite
+				.invite(sender_user, room_id, invite)
+				.await,
+	}
+}
+
+/// Turns a third-party invite signed by an identity server into an invite of
+/// `recipient_user`, who has since bound the invited identifier. `sender_user`
+/// must be the local user who sent the `m.room.third_party_invite` event named
+/// by the signed token.
+pub(crate) async fn exchange_third_party_invite_helper(
+	services: &Services,
+	sender_user: &UserId,
+	recipient_user: &UserId,
+	room_id: &RoomId,
+	third_party_invite: ThirdPartyInvite,
+) -> Result {
+	let invite_event = services
+		.rooms
+		.state_accessor
+		.room_state_get(
+			room_id,
+			&StateEventType::RoomThirdPartyInvite,
+			&third_party_invite.signed.token,
+		)
+		.await
+		.map_err(|_| {
+			err!(Request(NotFound("No third-party invite with this token in the room.")))
+		})?;
+
+	if invite_event.sender() != sender_user {
+		return Err!(Request(Forbidden("The third-party invite was not sent by this user.")));
+	}
+
+	// The display name is not known to the server exchanging the invite, so it
+	// is always taken from the original third-party invite.
+	let content: RoomThirdPartyInviteEventContent = invite_event.get_content()?;
+	let third_party_invite = ThirdPartyInvite {
+		display_name: content.display_name,
+		..third_party_invite
+	};
+
+	invite_helper(
+		services,
+		sender_user,
+		recipient_user,
+		room_id,
+		None,
+		false,
+		Some(third_party_invite),
+	)
+	.boxed()
+	.await
+}
+
 pub(crate) async fn invite_helper(
 	services: &Services,
 	sender_user: &UserId,
@@ -116,6 +224,7 @@ pub(crate) async fn invite_helper(
 	room_id: &RoomId,
 	reason: Option<String>,
 	is_direct: bool,
+	third_party_invite: Option<ThirdPartyInvite>,
 ) -> Result {
 	if !services.users.is_admin(sender_user).await && services.config.block_non_admin_invites {
 		info!(
@@ -145,6 +254,7 @@ pub(crate) async fn invite_helper(
 				avatar_url: services.users.avatar_url(recipient_user).await.ok(),
 				is_direct: Some(is_direct),
 				reason,
+				third_party_invite,
 				..RoomMemberEventContent::new(MembershipState::Invite)
 			};
 
@@ -235,6 +345,7 @@ pub(crate) async fn invite_helper(
 		blurhash: services.users.blurhash(recipient_user).await.ok(),
 		is_direct: Some(is_direct),
 		reason,
+		third_party_invite,
 		..RoomMemberEventContent::new(MembershipState::Invite)
 	};
 
@@ -1,13 +1,19 @@
 use axum::extract::State;
 use conduwuit::{
-	Err, Event, Result, at,
+	Err, Event, Result, at, err, is_true,
+	matrix::pdu::PduCount,
 	utils::{
 		future::TryExtExt,
 		stream::{BroadbandExt, ReadyExt},
 	},
 };
-use futures::{FutureExt, StreamExt, future::join};
+use conduwuit_service::{Services, appservice::RegistrationInfo};
+use futures::{
+	FutureExt, StreamExt,
+	future::{OptionFuture, join},
+};
 use ruma::{
+	RoomId, UserId,
 	api::client::membership::{
 		get_member_events::{self, v3::MembershipEventFilter},
 		joined_members::{self, v3::RoomMember},
@@ -22,24 +28,56 @@
 
 /// # `POST /_matrix/client/r0/rooms/{roomId}/members`
 ///
-/// Lists all joined users in a room (TODO: at a specific point in time, with a
-/// specific membership).
+/// Lists the membership events of a room, optionally as of the sync or
+/// pagination token given by `at`, and filtered by `membership` and
+/// `not_membership`.
 ///
-/// - Only works if the user is currently joined
+/// - The sender must be able to see the room's state, or have been joined at
+///   `at`
+/// - An appservice just needs one of its users joined
 pub(crate) async fn get_member_events_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_member_events::v3::Request>,
 ) -> Result<get_member_events::v3::Response> {
 	let sender_user = body.sender_user();
+	let room_id = &body.room_id;
 	let membership = body.membership.as_ref();
 	let not_membership = body.not_membership.as_ref();
 
-	if !services
-		.rooms
-		.state_accessor
-		.user_can_see_state_events(sender_user, &body.room_id)
-		.await
-	{
+	let at: Option<PduCount> = body
+		.at
+		.as_deref()
+		.map(str::parse)
+		.transpose()
+		.map_err(|_| err!(Request(InvalidParam("Invalid `at` pagination token."))))?;
+	let shortstatehash = match at {
+		| Some(at) =>
+			services
+				.rooms
+				.state_accessor
+				.room_state_at_count(room_id, at)
+				.await,
+		| None => services.rooms.state.get_room_shortstatehash(room_id).await,
+	}
+	.map_err(|_| err!(Request(NotFound("Room state not found."))))?;
+
+	let was_joined: OptionFuture<_> = at
+		.is_some()
+		.then(|| {
+			services
+				.rooms
+				.state_accessor
+				.user_was_joined(shortstatehash, sender_user)
+		})
+		.into();
+
+	let (can_see, was_joined) = join(
+		can_see_members(&services, sender_user, body.appservice_info.as_ref(), room_id),
+		was_joined,
+	)
+	.await;
+
+	if !can_see && !was_joined.is_some_and(is_true!()) {
 		return Err!(Request(Forbidden("You don't have permission to view this room.")));
 	}
 
@@ -47,8 +85,7 @@ pub(crate) async fn get_member_events_route(
 		chunk: services
 			.rooms
 			.state_accessor
-			.room_state_full(&body.room_id)
-			.ready_filter_map(Result::ok)
+			.state_full(shortstatehash)
 			.ready_filter(|((ty, _), _)| *ty == StateEventType::RoomMember)
 			.map(at!(1))
 			.ready_filter_map(|pdu| membership_filter(pdu, membership, not_membership))
@@ -64,16 +101,18 @@ pub(crate) async fn get_member_events_route(
 /// Lists all members of a room.
 ///
 /// - The sender user must be in the room
-/// - TODO: An appservice just needs a puppet joined
+/// - An appservice just needs one of its users joined
 pub(crate) async fn joined_members_route(
 	State(services): State<crate::State>,
 	body: Ruma<joined_members::v3::Request>,
 ) -> Result<joined_members::v3::Response> {
-	if !services
-		.rooms
-		.state_accessor
-		.user_can_see_state_events(body.sender_user(), &body.room_id)
-		.await
+	if !can_see_members(
+		&services,
+		body.sender_user(),
+		body.appservice_info.as_ref(),
+		&body.room_id,
+	)
+	.await
 	{
 		return Err!(Request(Forbidden("You don't have permission to view this room.")));
 	}
@@ -98,6 +137,36 @@ pub(crate) async fn joined_members_route(
 	})
 }
 
+/// Whether the sender may list the current members of a room: either it can
+/// see the room's state, or it is an appservice with one of its users joined.
+/// Only the appservice's own users are looked for, through the cached
+/// membership check also used to route events to appservices.
+async fn can_see_members(
+	services: &Services,
+	sender_user: &UserId,
+	appservice: Option<&RegistrationInfo>,
+	room_id: &RoomId,
+) -> bool {
+	if services
+		.rooms
+		.state_accessor
+		.user_can_see_state_events(sender_user, room_id)
+		.await
+	{
+		return true;
+	}
+
+	let Some(appservice) = appservice else {
+		return false;
+	};
+
+	services
+		.rooms
+		.state_cache
+		.appservice_in_room(room_id, appservice)
+		.await
+}
+
 fn membership_filter<Pdu: Event>(
 	pdu: Pdu,
 	for_membership: Option<&MembershipEventFilter>,
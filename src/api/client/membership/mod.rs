@@ -26,7 +26,9 @@
 pub(crate) use self::{
 	ban::ban_user_route,
 	forget::forget_room_route,
-	invite::{invite_helper, invite_user_route},
+	invite::{
+		exchange_third_party_invite_helper, invite_3pid_helper, invite_helper, invite_user_route,
+	},
 	join::{join_room_by_id_or_alias_route, join_room_by_id_route},
 	kick::kick_user_route,
 	knock::knock_room_route,
@@ -116,11 +118,7 @@ pub(crate) async fn banned_room_check(
 			return Err!(Request(Forbidden("This room is banned on this homeserver.")));
 		}
 	} else if let Some(server_name) = server_name {
-		if services
-			.config
-			.forbidden_remote_server_names
-			.is_match(server_name.host())
-		{
+		if services.moderation.is_remote_server_forbidden(server_name) {
 			warn!(
 				"User {user_id} who is not an admin tried joining a room which has the server \
 				 name {server_name} that is globally forbidden. Rejecting.",
@@ -1,7 +1,9 @@
+use std::collections::BTreeSet;
+
 use axum::extract::State;
 use axum_client_ip::InsecureClientIp;
 use conduwuit::{
-	Err, Result, at, debug_warn,
+	Result, at, debug_warn, err,
 	matrix::{
 		event::{Event, Matches},
 		pdu::PduCount,
@@ -23,7 +25,7 @@
 };
 use futures::{FutureExt, StreamExt, TryFutureExt, future::OptionFuture, pin_mut};
 use ruma::{
-	DeviceId, RoomId, UserId,
+	DeviceId, OwnedEventId, RoomId, UserId,
 	api::{
 		Direction,
 		client::{filter::RoomEventFilter, message::get_message_events},
@@ -67,8 +69,9 @@
 ///
 /// Allows paginating through room history.
 ///
-/// - Only works if the user is joined (TODO: always allow, but only show events
-///   where the user was joined, depending on `history_visibility`)
+/// - Only events the user is allowed to see according to `history_visibility`
+///   are returned, so non-members can read world-readable rooms
+/// - World-readable rooms this server is not in are peeked over federation
 pub(crate) async fn get_message_events_route(
 	State(services): State<crate::State>,
 	InsecureClientIp(client_ip): InsecureClientIp,
@@ -82,11 +85,16 @@ pub(crate) async fn get_message_events_route(
 
 	services
 		.users
-		.update_device_last_seen(sender_user, sender_device, client_ip)
+		.update_device_last_seen(
+			sender_user,
+			sender_device,
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	if !services.rooms.metadata.exists(room_id).await {
-		return Err!(Request(Forbidden("Room does not exist to this server")));
+		return peek_message_events(&services, &body).boxed().await;
 	}
 
 	let from: PduCount = body
@@ -207,6 +215,95 @@ pub(crate) async fn get_message_events_route(
 	})
 }
 
+/// Paginates backwards through a world-readable room this server is not in,
+/// via a resident server. Pagination tokens are the event IDs to continue
+/// from; the newest events have no token since history is only known going
+/// back from the room's latest events.
+async fn peek_message_events(
+	services: &Services,
+	body: &Ruma<get_message_events::v3::Request>,
+) -> Result<get_message_events::v3::Response> {
+	let sender_user = body.sender_user();
+	let filter = &body.filter;
+	let peek = services.rooms.peek.peek(&body.room_id, sender_user).await?;
+
+	let from: Option<OwnedEventId> = body
+		.from
+		.as_deref()
+		.map(OwnedEventId::try_from)
+		.transpose()
+		.map_err(|_| {
+			err!(Request(InvalidParam("Invalid pagination token for a peeked room.")))
+		})?;
+
+	if matches!(body.dir, Direction::Forward) {
+		// nothing newer than the latest events of the room is known
+		return Ok(get_message_events::v3::Response {
+			start: body.from.clone().unwrap_or_default(),
+			end: None,
+			chunk: Vec::new(),
+			state: Vec::new(),
+		});
+	}
+
+	let limit: usize = body
+		.limit
+		.try_into()
+		.unwrap_or(LIMIT_DEFAULT)
+		.min(LIMIT_MAX);
+
+	let start = from
+		.clone()
+		.map_or_else(|| peek.latest.clone(), |from| vec![from]);
+	let events = services
+		.rooms
+		.peek
+		.backfill(&peek, start, limit.saturating_add(1))
+		.await?;
+
+	// backfill includes the events it starts from, which were already returned
+	let events: Vec<_> = events
+		.into_iter()
+		.filter(|pdu| from.as_ref() != Some(&pdu.event_id))
+		.filter(|pdu| filter.matches(pdu))
+		.stream()
+		.wide_filter_map(|mut pdu| async move {
+			if is_ignored_pdu(services, &pdu, sender_user).await {
+				return None;
+			}
+
+			pdu.set_unsigned(Some(sender_user));
+			Some(pdu)
+		})
+		.take(limit)
+		.collect()
+		.await;
+
+	let senders: BTreeSet<_> = events.iter().map(Event::sender).collect();
+	let state = senders
+		.into_iter()
+		.filter_map(|sender| {
+			peek.state
+				.iter()
+				.find(|pdu| *pdu.kind() == RoomMember && pdu.state_key() == Some(sender.as_str()))
+		})
+		.cloned()
+		.map(Event::into_format)
+		.collect();
+
+	let end = (events.len() >= limit)
+		.then(|| events.last())
+		.flatten()
+		.map(|pdu| pdu.event_id.to_string());
+
+	Ok(get_message_events::v3::Response {
+		start: body.from.clone().unwrap_or_default(),
+		end,
+		chunk: events.into_iter().map(Event::into_format).collect(),
+		state,
+	})
+}
+
 pub(crate) async fn lazy_loading_witness<'a, I>(
 	services: &Services,
 	lazy_loading_context: &lazy_loading::Context<'_>,
@@ -6,6 +6,7 @@
 pub(super) mod backup;
 pub(super) mod capabilities;
 pub(super) mod context;
+pub(super) mod delayed_events;
 pub(super) mod device;
 pub(super) mod directory;
 pub(super) mod filter;
@@ -21,6 +22,7 @@
 pub(super) mod read_marker;
 pub(super) mod redact;
 pub(super) mod relations;
+pub(super) mod rendezvous;
 pub(super) mod report;
 pub(super) mod room;
 pub(super) mod search;
@@ -49,6 +51,7 @@
 pub(super) use backup::*;
 pub(super) use capabilities::*;
 pub(super) use context::*;
+pub(super) use delayed_events::*;
 pub(super) use device::*;
 pub(super) use directory::*;
 pub(super) use filter::*;
@@ -66,7 +69,9 @@
 pub(super) use read_marker::*;
 pub(super) use redact::*;
 pub(super) use relations::*;
+pub(super) use rendezvous::*;
 pub(super) use report::*;
+pub use room::upgrade_room_helper;
 pub(super) use room::*;
 pub(super) use search::*;
 pub(super) use send::*;
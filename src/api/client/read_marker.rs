@@ -47,7 +47,8 @@ pub(crate) async fn set_read_marker_route(
 		services
 			.rooms
 			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+			.reset_notification_counts(sender_user, &body.room_id)
+			.await;
 	}
 
 	// ping presence
@@ -105,10 +106,12 @@ pub(crate) async fn set_read_marker_route(
 			)));
 		};
 
-		services
-			.rooms
-			.read_receipt
-			.private_read_set(&body.room_id, sender_user, count);
+		services.rooms.read_receipt.private_read_set(
+			&body.room_id,
+			sender_user,
+			count,
+			&ReceiptThread::Unthreaded,
+		);
 	}
 
 	Ok(set_read_marker::v3::Response {})
@@ -123,9 +126,20 @@ pub(crate) async fn create_receipt_route(
 	body: Ruma<create_receipt::v3::Request>,
 ) -> Result<create_receipt::v3::Response> {
 	let sender_user = body.sender_user();
+	if matches!(body.receipt_type, create_receipt::v3::ReceiptType::FullyRead)
+		&& body.thread != ReceiptThread::Unthreaded
+	{
+		return Err!(Request(InvalidParam("The fully read marker cannot be threaded.")));
+	}
+
 	services
 		.users
-		.update_device_last_seen(sender_user, body.sender_device.as_deref(), client_ip)
+		.update_device_last_seen(
+			sender_user,
+			body.sender_device.as_deref(),
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	if matches!(
@@ -135,7 +149,8 @@ pub(crate) async fn create_receipt_route(
 		services
 			.rooms
 			.user
-			.reset_notification_counts(sender_user, &body.room_id);
+			.reset_receipt_notification_counts(sender_user, &body.room_id, &body.thread)
+			.await;
 	}
 
 	// ping presence
@@ -172,7 +187,7 @@ pub(crate) async fn create_receipt_route(
 						sender_user.to_owned(),
 						ruma::events::receipt::Receipt {
 							ts: Some(MilliSecondsSinceUnixEpoch::now()),
-							thread: ReceiptThread::Unthreaded,
+							thread: body.thread.clone(),
 						},
 					)]),
 				)]),
@@ -205,10 +220,12 @@ pub(crate) async fn create_receipt_route(
 				)));
 			};
 
-			services
-				.rooms
-				.read_receipt
-				.private_read_set(&body.room_id, sender_user, count);
+			services.rooms.read_receipt.private_read_set(
+				&body.room_id,
+				sender_user,
+				count,
+				&body.thread,
+			);
 		},
 		| _ => {
 			return Err!(Request(InvalidParam(warn!(
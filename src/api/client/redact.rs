@@ -20,7 +20,12 @@ pub(crate) async fn redact_event_route(
 	let sender_user = body.sender_user();
 	services
 		.users
-		.update_device_last_seen(sender_user, body.sender_device.as_deref(), client_ip)
+		.update_device_last_seen(
+			sender_user,
+			body.sender_device.as_deref(),
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 	let body = &body.body;
 	if services.users.is_suspended(sender_user).await? {
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use axum::{
+	Json,
+	body::Bytes,
+	extract::{Path, State},
+	http::{HeaderMap, HeaderValue, StatusCode, header},
+	response::{IntoResponse, Response},
+};
+use axum_client_ip::InsecureClientIp;
+use axum_extra::{
+	TypedHeader,
+	headers::{Expires, LastModified},
+};
+use conduwuit::{Err, Error, Result};
+use ruma::{OwnedDeviceId, OwnedUserId, api::client::error::ErrorKind};
+use service::{Services, rendezvous::Payload};
+
+/// How long a conditional GET waits for the session to change before
+/// answering 304 Not Modified.
+const LONG_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// # `POST /_matrix/client/unstable/org.matrix.msc4108/rendezvous`
+///
+/// Creates a rendezvous session holding the request body, through which a new
+/// device and an existing one can exchange messages in order to sign the new
+/// device in (MSC4108). The session is identified by the returned URL.
+pub(crate) async fn create_rendezvous_session_route(
+	State(services): State<crate::State>,
+	InsecureClientIp(client): InsecureClientIp,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Response> {
+	let (id, payload) = services
+		.rendezvous
+		.create(client, content_type(&headers), body)?;
+
+	let base_url =
+		services.config.well_known.client.as_ref().map_or_else(
+			|| format!("https://{}", services.config.server_name),
+			ToString::to_string,
+		);
+
+	let url = format!(
+		"{}/_matrix/client/unstable/org.matrix.msc4108/rendezvous/{id}",
+		base_url.trim_end_matches('/')
+	);
+
+	Ok((
+		StatusCode::CREATED,
+		payload_headers(&payload),
+		Json(serde_json::json!({ "url": url })),
+	)
+		.into_response())
+}
+
+/// # `PUT /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Replaces the contents of a rendezvous session. The `If-Match` header must
+/// carry the session's current ETag. A signed-in device writing with its
+/// access token approves the login of the new device.
+pub(crate) async fn update_rendezvous_session_route(
+	State(services): State<crate::State>,
+	Path(id): Path<String>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Response> {
+	let Some(if_match) = etag_header(&headers, header::IF_MATCH) else {
+		return Err!(Request(MissingParam("Missing If-Match header.")));
+	};
+
+	let writer = writer(&services, &headers).await?;
+	let Some(payload) =
+		services
+			.rendezvous
+			.update(&id, if_match, writer, content_type(&headers), body)?
+	else {
+		return Ok((
+			StatusCode::PRECONDITION_FAILED,
+			Json(serde_json::json!({
+				"errcode": "M_CONCURRENT_WRITE",
+				"error": "The rendezvous session was modified concurrently.",
+			})),
+		)
+			.into_response());
+	};
+
+	Ok((StatusCode::ACCEPTED, payload_headers(&payload)).into_response())
+}
+
+/// # `GET /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Returns the contents of a rendezvous session. When `If-None-Match` carries
+/// the current ETag, the request is held open until the session is written or
+/// the long-poll timeout elapses, in which case 304 Not Modified is returned.
+pub(crate) async fn get_rendezvous_session_route(
+	State(services): State<crate::State>,
+	Path(id): Path<String>,
+	headers: HeaderMap,
+) -> Result<Response> {
+	let if_none_match = etag_header(&headers, header::IF_NONE_MATCH);
+	let payload = services
+		.rendezvous
+		.get(&id, if_none_match, LONG_POLL_TIMEOUT)
+		.await?;
+
+	if if_none_match.is_some_and(|etag| etag == payload.etag) {
+		return Ok((StatusCode::NOT_MODIFIED, payload_headers(&payload)).into_response());
+	}
+
+	let content_type = HeaderValue::from_str(&payload.content_type)
+		.unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream"));
+
+	Ok((
+		StatusCode::OK,
+		payload_headers(&payload),
+		[(header::CONTENT_TYPE, content_type)],
+		payload.data,
+	)
+		.into_response())
+}
+
+/// # `DELETE /_matrix/client/unstable/org.matrix.msc4108/rendezvous/{sessionId}`
+///
+/// Deletes a rendezvous session.
+pub(crate) async fn delete_rendezvous_session_route(
+	State(services): State<crate::State>,
+	Path(id): Path<String>,
+) -> Result<Response> {
+	services.rendezvous.delete(&id)?;
+
+	Ok((StatusCode::NO_CONTENT, [(header::CACHE_CONTROL, "no-store")]).into_response())
+}
+
+/// Resolves the device writing to a session from its access token, if it
+/// sent one.
+async fn writer(
+	services: &Services,
+	headers: &HeaderMap,
+) -> Result<Option<(OwnedUserId, OwnedDeviceId)>> {
+	let Some(token) = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+	else {
+		return Ok(None);
+	};
+
+	services
+		.users
+		.find_from_token(token.trim())
+		.await
+		.map(Some)
+		.map_err(|_| {
+			Error::BadRequest(
+				ErrorKind::UnknownToken { soft_logout: false },
+				"Unknown access token.",
+			)
+		})
+}
+
+fn payload_headers(
+	payload: &Payload,
+) -> (
+	TypedHeader<Expires>,
+	TypedHeader<LastModified>,
+	[(header::HeaderName, String); 2],
+) {
+	(
+		TypedHeader(Expires::from(payload.expires)),
+		TypedHeader(LastModified::from(payload.last_modified)),
+		[
+			(header::ETAG, format!("\"{}\"", payload.etag)),
+			(header::CACHE_CONTROL, "no-store".to_owned()),
+		],
+	)
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+	headers
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.unwrap_or("application/octet-stream")
+		.to_owned()
+}
+
+/// Reads an entity tag from a conditional request header, without its quotes.
+fn etag_header(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+	let value = headers.get(name)?.to_str().ok()?.trim();
+	let value = value.strip_prefix("W/").unwrap_or(value);
+
+	Some(value.trim_matches('"'))
+}
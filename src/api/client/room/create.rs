@@ -31,7 +31,10 @@
 };
 use serde_json::{json, value::to_raw_value};
 
-use crate::{Ruma, client::invite_helper};
+use crate::{
+	Ruma,
+	client::{invite_3pid_helper, invite_helper},
+};
 
 /// # `POST /_matrix/client/v3/createRoom`
 ///
@@ -493,15 +496,32 @@ pub(crate) async fn create_room_route(
 			.await?;
 	}
 
-	// 8. Events implied by invite (and TODO: invite_3pid)
+	// 8. Events implied by invite and invite_3pid
 	drop(state_lock);
 	for recipient_user in &invitees {
+		if let Err(e) = invite_helper(
+			&services,
+			sender_user,
+			recipient_user,
+			&room_id,
+			None,
+			body.is_direct,
+			None,
+		)
+		.boxed()
+		.await
+		{
+			warn!(?e, "Failed to send invite");
+		}
+	}
+
+	for invite in &body.invite_3pid {
 		if let Err(e) =
-			invite_helper(&services, sender_user, recipient_user, &room_id, None, body.is_direct)
+			invite_3pid_helper(&services, sender_user, &room_id, invite, body.is_direct)
 				.boxed()
 				.await
 		{
-			warn!(?e, "Failed to send invite");
+			warn!(?e, "Failed to send third-party invite");
 		}
 	}
 
@@ -514,7 +534,7 @@ pub(crate) async fn create_room_route(
 	}
 
 	if body.visibility == room::Visibility::Public {
-		services.rooms.directory.set_public(&room_id);
+		services.rooms.directory.set_public(&room_id).await;
 
 		if services.server.config.admin_room_notices {
 			services
@@ -8,6 +8,8 @@
 /// # `GET /_matrix/client/r0/rooms/{roomId}/event/{eventId}`
 ///
 /// Gets a single event.
+///
+/// - World-readable rooms this server is not in are peeked over federation
 pub(crate) async fn get_room_event_route(
 	State(ref services): State<crate::State>,
 	ref body: Ruma<get_room_event::v3::Request>,
@@ -15,6 +17,22 @@ pub(crate) async fn get_room_event_route(
 	let event_id = &body.event_id;
 	let room_id = &body.room_id;
 
+	if !services.rooms.metadata.exists(room_id).await {
+		let peek = services
+			.rooms
+			.peek
+			.peek(room_id, body.sender_user())
+			.await?;
+		let mut event = services.rooms.peek.event(&peek, event_id).await?;
+		if is_ignored_pdu(services, &event, body.sender_user()).await {
+			return Err!(Request(Forbidden("You don't have permission to view this event.")));
+		}
+
+		event.set_unsigned(body.sender_user.as_deref());
+
+		return Ok(get_room_event::v3::Response { event: event.into_format() });
+	}
+
 	let event = services
 		.rooms
 		.timeline
@@ -5,6 +5,7 @@
 mod summary;
 mod upgrade;
 
+pub use self::upgrade::upgrade_room_helper;
 pub(crate) use self::{
 	aliases::get_room_aliases_route,
 	create::create_room_route,
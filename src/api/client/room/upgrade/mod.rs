@@ -1,17 +1,22 @@
-use std::cmp::max;
+mod tests;
+
+use std::{cmp::max, iter::once};
 
 use axum::extract::State;
 use conduwuit::{
 	Err, Error, Event, Result, RoomVersion, debug, err, info,
 	matrix::{StateKey, pdu::PduBuilder},
 };
+use conduwuit_service::Services;
 use futures::{FutureExt, StreamExt};
 use ruma::{
-	CanonicalJsonObject, RoomId, RoomVersionId,
+	CanonicalJsonObject, CanonicalJsonValue, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId,
+	UserId,
 	api::client::{error::ErrorKind, room::upgrade_room},
 	events::{
 		StateEventType, TimelineEventType,
 		room::{
+			create::RoomCreateEventContent,
 			member::{MembershipState, RoomMemberEventContent},
 			power_levels::RoomPowerLevelsEventContent,
 			tombstone::RoomTombstoneEventContent,
@@ -25,8 +30,9 @@
 use crate::router::Ruma;
 
 /// Recommended transferable state events list from the spec
-const TRANSFERABLE_STATE_EVENTS: &[StateEventType; 11] = &[
+const TRANSFERABLE_STATE_EVENTS: &[StateEventType; 12] = &[
 	StateEventType::RoomAvatar,
+	StateEventType::RoomCanonicalAlias,
 	StateEventType::RoomEncryption,
 	StateEventType::RoomGuestAccess,
 	StateEventType::RoomHistoryVisibility,
@@ -37,9 +43,13 @@
 	StateEventType::RoomTopic,
 	// Not explicitly recommended in spec, but very useful.
 	StateEventType::SpaceChild,
-	StateEventType::SpaceParent, // TODO: m.room.policy?
+	StateEventType::SpaceParent,
 ];
 
+/// State events transferred in addition to [`TRANSFERABLE_STATE_EVENTS`] which
+/// have no variant of their own.
+const TRANSFERABLE_CUSTOM_STATE_EVENTS: &[&str; 1] = &["m.room.policy"];
+
 /// # `POST /_matrix/client/r0/rooms/{roomId}/upgrade`
 ///
 /// Upgrades the room.
@@ -54,29 +64,71 @@ pub(crate) async fn upgrade_room_route(
 	State(services): State<crate::State>,
 	body: Ruma<upgrade_room::v3::Request>,
 ) -> Result<upgrade_room::v3::Response> {
-	// TODO[v12]: Handle additional creators
-	let sender_user = body.sender_user.as_ref().expect("user is authenticated");
+	let sender_user = body.sender_user();
+
+	if services.users.is_suspended(sender_user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	let additional_creators = additional_creators(body.json_body.as_ref())?;
+	let replacement_room = upgrade_room_helper(
+		&services,
+		sender_user,
+		&body.room_id,
+		&body.new_version,
+		additional_creators,
+	)
+	.await?;
+
+	Ok(upgrade_room::v3::Response { replacement_room })
+}
+
+/// Reads `additional_creators` from the request body. They are only used when
+/// upgrading to a room version which explicitly privileges room creators.
+fn additional_creators(json_body: Option<&CanonicalJsonValue>) -> Result<Vec<OwnedUserId>> {
+	let Some(CanonicalJsonValue::Object(json_body)) = json_body else {
+		return Ok(Vec::new());
+	};
+
+	json_body
+		.get("additional_creators")
+		.map(|creators| serde_json::from_value(creators.clone().into()))
+		.transpose()
+		.map_err(|e| err!(Request(BadJson("Invalid additional_creators: {e}"))))
+		.map(Option::unwrap_or_default)
+}
 
-	if !services.server.supported_room_version(&body.new_version) {
+/// Upgrades a room to `new_version` on behalf of `sender_user`, who must be a
+/// local member permitted to send a tombstone into it, and returns the
+/// replacement room.
+///
+/// When the new room version explicitly privileges room creators, the sender
+/// and `additional_creators` become its creators, and are left out of its
+/// power levels. Creators of the old room who are not creators of the new one
+/// keep the highest power level instead of losing their privileges.
+pub async fn upgrade_room_helper(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	new_version: &RoomVersionId,
+	additional_creators: Vec<OwnedUserId>,
+) -> Result<OwnedRoomId> {
+	if !services.server.supported_room_version(new_version) {
 		return Err(Error::BadRequest(
 			ErrorKind::UnsupportedRoomVersion,
 			"This server does not support that room version.",
 		));
 	}
 
-	if services.users.is_suspended(sender_user).await? {
-		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
-	}
-
 	// Make sure this isn't the admin room
 	// Admin room upgrades are hacky and should be done manually instead.
-	if services.admin.is_admin_room(&body.room_id).await {
+	if services.admin.is_admin_room(room_id).await {
 		return Err!(Request(Forbidden("Upgrading the admin room this way is not allowed.")));
 	}
 
 	// First, check if the user has permission to upgrade the room (send tombstone
 	// event)
-	let old_room_state_lock = services.rooms.state.mutex.lock(&body.room_id).await;
+	let old_room_state_lock = services.rooms.state.mutex.lock(room_id).await;
 
 	// Check tombstone permission by attempting to create (but not send) the event
 	// Note that this does internally call the policy server with a fake room ID,
@@ -90,7 +142,7 @@ pub(crate) async fn upgrade_room_route(
 				replacement_room: RoomId::new(services.globals.server_name()),
 			}),
 			sender_user,
-			Some(&body.room_id),
+			Some(room_id),
 			&old_room_state_lock,
 		)
 		.await;
@@ -102,7 +154,21 @@ pub(crate) async fn upgrade_room_route(
 	drop(old_room_state_lock);
 
 	// Create a replacement room
-	let room_features = RoomVersion::new(&body.new_version)?;
+	let room_features = RoomVersion::new(new_version)?;
+
+	// Creators of the replacement room, if its version explicitly privileges them
+	let mut additional_creators = additional_creators;
+	additional_creators.retain(|creator| creator != sender_user);
+	additional_creators.sort_unstable();
+	additional_creators.dedup();
+	let new_creators: Vec<&UserId> = if room_features.explicitly_privilege_room_creators {
+		once(sender_user)
+			.chain(additional_creators.iter().map(AsRef::as_ref))
+			.collect()
+	} else {
+		Vec::new()
+	};
+
 	let replacement_room_owned = if !room_features.room_ids_as_hashes {
 		Some(RoomId::new(services.globals.server_name()))
 	} else {
@@ -122,7 +188,7 @@ pub(crate) async fn upgrade_room_route(
 
 	// For pre-v12 rooms, send tombstone before creating replacement room
 	let tombstone_event_id = if !room_features.room_ids_as_hashes {
-		let state_lock = services.rooms.state.mutex.lock(&body.room_id).await;
+		let state_lock = services.rooms.state.mutex.lock(room_id).await;
 		// Send a m.room.tombstone event to the old room to indicate that it is not
 		// intended to be used any further
 		let tombstone_event_id = services
@@ -134,7 +200,7 @@ pub(crate) async fn upgrade_room_route(
 					replacement_room: replacement_room.unwrap().to_owned(),
 				}),
 				sender_user,
-				Some(&body.room_id),
+				Some(room_id),
 				&state_lock,
 			)
 			.await?;
@@ -147,16 +213,32 @@ pub(crate) async fn upgrade_room_route(
 	let state_lock = services.rooms.state.mutex.lock(replacement_room_tmp).await;
 
 	// Get the old room creation event
-	let mut create_event_content: CanonicalJsonObject = services
+	let old_create_event = services
 		.rooms
 		.state_accessor
-		.room_state_get_content(&body.room_id, &StateEventType::RoomCreate, "")
+		.room_state_get(room_id, &StateEventType::RoomCreate, "")
 		.await
 		.map_err(|_| err!(Database("Found room without m.room.create event.")))?;
 
+	let mut create_event_content: CanonicalJsonObject =
+		serde_json::from_str(old_create_event.content().get())?;
+
+	// Creators of the old room, if its version explicitly privileged them
+	let old_create_content: RoomCreateEventContent =
+		serde_json::from_str(old_create_event.content().get())?;
+	let old_creators: Vec<OwnedUserId> = if RoomVersion::new(&old_create_content.room_version)?
+		.explicitly_privilege_room_creators
+	{
+		once(old_create_event.sender().to_owned())
+			.chain(old_create_content.additional_creators.unwrap_or_default())
+			.collect()
+	} else {
+		Vec::new()
+	};
+
 	// Use the m.room.tombstone event as the predecessor
 	let predecessor = Some(ruma::events::room::create::PreviousRoom::new(
-		body.room_id.clone(),
+		room_id.to_owned(),
 		tombstone_event_id,
 	));
 
@@ -164,7 +246,7 @@ pub(crate) async fn upgrade_room_route(
 	// room_version
 	{
 		use RoomVersionId::*;
-		match body.new_version {
+		match new_version {
 			| V1 | V2 | V3 | V4 | V5 | V6 | V7 | V8 | V9 | V10 => {
 				create_event_content.insert(
 					"creator".into(),
@@ -178,13 +260,23 @@ pub(crate) async fn upgrade_room_route(
 				// "creator" key no longer exists in V11 rooms
 				create_event_content.remove("creator");
 			},
-			// TODO(hydra): additional_creators
 		}
 	}
 
+	// Only the creators requested for this upgrade are creators of the new room
+	create_event_content.remove("additional_creators");
+	if !additional_creators.is_empty() && room_features.explicitly_privilege_room_creators {
+		create_event_content.insert(
+			"additional_creators".into(),
+			json!(&additional_creators).try_into().map_err(|_| {
+				Error::BadRequest(ErrorKind::BadJson, "Error forming creation event")
+			})?,
+		);
+	}
+
 	create_event_content.insert(
 		"room_version".into(),
-		json!(&body.new_version)
+		json!(&new_version)
 			.try_into()
 			.map_err(|_| Error::BadRequest(ErrorKind::BadJson, "Error forming creation event"))?,
 	);
@@ -265,17 +357,26 @@ pub(crate) async fn upgrade_room_route(
 		.await?;
 
 	// Replicate transferable state events to the new room
-	for event_type in TRANSFERABLE_STATE_EVENTS {
+	let custom_state_events = TRANSFERABLE_CUSTOM_STATE_EVENTS
+		.iter()
+		.map(|&event_type| StateEventType::from(event_type));
+	let transferable_state_events: Vec<StateEventType> = TRANSFERABLE_STATE_EVENTS
+		.iter()
+		.cloned()
+		.chain(custom_state_events)
+		.collect();
+
+	for event_type in &transferable_state_events {
 		let state_keys = services
 			.rooms
 			.state_accessor
-			.room_state_keys(&body.room_id, event_type)
+			.room_state_keys(room_id, event_type)
 			.await?;
 		for state_key in state_keys {
 			let mut event_content = match services
 				.rooms
 				.state_accessor
-				.room_state_get(&body.room_id, event_type, &state_key)
+				.room_state_get(room_id, event_type, &state_key)
 				.await
 			{
 				| Ok(v) => v.content().to_owned(),
@@ -285,18 +386,18 @@ pub(crate) async fn upgrade_room_route(
 				// If the event content is empty, we skip it
 				continue;
 			}
-			// If this is a power levels event, and the new room version has creators,
-			// we need to make sure they dont appear in the users block of power levels.
+			// Creators may be privileged implicitly in either room, so power levels
+			// have to be adjusted rather than copied.
 			if *event_type == StateEventType::RoomPowerLevels {
-				// TODO(v12): additional creators
-				let creators = vec![sender_user];
-				let mut power_levels_event_content: RoomPowerLevelsEventContent =
+				let power_levels_event_content: RoomPowerLevelsEventContent =
 					serde_json::from_str(event_content.get()).map_err(|_| {
 						err!(Request(BadJson("Power levels event content is not valid")))
 					})?;
-				for creator in creators {
-					power_levels_event_content.users.remove(creator);
-				}
+				let power_levels_event_content = transfer_power_levels(
+					power_levels_event_content,
+					&old_creators,
+					&new_creators,
+				);
 				event_content = to_raw_value(&power_levels_event_content)
 					.expect("event is valid, we just deserialized and modified it");
 			}
@@ -321,11 +422,7 @@ pub(crate) async fn upgrade_room_route(
 	}
 
 	// Moves any local aliases to the new room
-	let mut local_aliases = services
-		.rooms
-		.alias
-		.local_aliases_for_room(&body.room_id)
-		.boxed();
+	let mut local_aliases = services.rooms.alias.local_aliases_for_room(room_id).boxed();
 
 	while let Some(alias) = local_aliases.next().await {
 		services
@@ -344,7 +441,7 @@ pub(crate) async fn upgrade_room_route(
 	let power_levels_event_content: RoomPowerLevelsEventContent = services
 		.rooms
 		.state_accessor
-		.room_state_get_content(&body.room_id, &StateEventType::RoomPowerLevels, "")
+		.room_state_get_content(room_id, &StateEventType::RoomPowerLevels, "")
 		.await
 		.map_err(|_| err!(Database("Found room without m.room.power_levels event.")))?;
 
@@ -371,7 +468,7 @@ pub(crate) async fn upgrade_room_route(
 				..power_levels_event_content
 			}),
 			sender_user,
-			Some(&body.room_id),
+			Some(room_id),
 			&state_lock,
 		)
 		.boxed()
@@ -381,7 +478,7 @@ pub(crate) async fn upgrade_room_route(
 
 	// For v12 rooms, send tombstone AFTER creating replacement room
 	if room_features.room_ids_as_hashes {
-		let old_room_state_lock = services.rooms.state.mutex.lock(&body.room_id).await;
+		let old_room_state_lock = services.rooms.state.mutex.lock(room_id).await;
 		// For v12 rooms, no event reference in predecessor due to cyclic dependency -
 		// could best effort one maybe?
 		services
@@ -393,7 +490,7 @@ pub(crate) async fn upgrade_room_route(
 					replacement_room: replacement_room.unwrap().to_owned(),
 				}),
 				sender_user,
-				Some(&body.room_id),
+				Some(room_id),
 				&old_room_state_lock,
 			)
 			.await?;
@@ -405,7 +502,7 @@ pub(crate) async fn upgrade_room_route(
 	let parents = services
 		.rooms
 		.state_accessor
-		.room_state_keys(&body.room_id, &StateEventType::SpaceParent)
+		.room_state_keys(room_id, &StateEventType::SpaceParent)
 		.await?;
 
 	for raw_space_id in parents {
@@ -416,7 +513,7 @@ pub(crate) async fn upgrade_room_route(
 			.room_state_get_content::<SpaceChildEventContent>(
 				space_id,
 				&StateEventType::SpaceChild,
-				body.room_id.as_str(),
+				room_id.as_str(),
 			)
 			.await
 		else {
@@ -425,12 +522,12 @@ pub(crate) async fn upgrade_room_route(
 		};
 		debug!(
 			"Updating space {space_id} child event for room {} to {}",
-			&body.room_id,
+			room_id,
 			replacement_room.unwrap()
 		);
 		// First, drop the space's child event
 		let state_lock = services.rooms.state.mutex.lock(space_id).await;
-		debug!("Removing space child event for room {} in space {space_id}", &body.room_id);
+		debug!("Removing space child event for room {} in space {space_id}", room_id);
 		services
 			.rooms
 			.timeline
@@ -439,7 +536,7 @@ pub(crate) async fn upgrade_room_route(
 					event_type: StateEventType::SpaceChild.into(),
 					content: to_raw_value(&RedactedSpaceChildEventContent {})
 						.expect("event is valid, we just created it"),
-					state_key: Some(body.room_id.clone().as_str().into()),
+					state_key: Some(room_id.to_owned().as_str().into()),
 					..Default::default()
 				},
 				sender_user,
@@ -478,14 +575,48 @@ pub(crate) async fn upgrade_room_route(
 			.ok();
 		debug!(
 			"Finished updating space {space_id} child event for room {} to {}",
-			&body.room_id,
+			room_id,
 			replacement_room.unwrap()
 		);
 		drop(state_lock);
 	}
 
 	// Return the replacement room id
-	Ok(upgrade_room::v3::Response {
-		replacement_room: replacement_room.unwrap().to_owned(),
-	})
+	Ok(replacement_room.unwrap().to_owned())
+}
+
+/// Adapts the power levels of the old room for the replacement room.
+///
+/// Creators of the old room have no power level of their own when its version
+/// privileges them implicitly, so they are given the highest power level in
+/// the room. Creators of the new room are privileged implicitly instead, and
+/// must not appear in its power levels at all.
+fn transfer_power_levels(
+	mut content: RoomPowerLevelsEventContent,
+	old_creators: &[OwnedUserId],
+	new_creators: &[&UserId],
+) -> RoomPowerLevelsEventContent {
+	let highest = content.users.values().copied().fold(int!(100), max);
+
+	for creator in old_creators {
+		content.users.entry(creator.clone()).or_insert(highest);
+	}
+
+	if new_creators.is_empty() {
+		return content;
+	}
+
+	for creator in new_creators {
+		content.users.remove(*creator);
+	}
+
+	// Like newly created rooms, only creators may upgrade the room again by
+	// default
+	if old_creators.is_empty() {
+		content
+			.events
+			.insert(TimelineEventType::RoomTombstone, int!(150));
+	}
+
+	content
 }
@@ -0,0 +1,130 @@
+#![cfg(test)]
+
+use std::collections::BTreeMap;
+
+use ruma::{
+	CanonicalJsonValue, Int, UserId,
+	events::{TimelineEventType, room::power_levels::RoomPowerLevelsEventContent},
+	int, owned_user_id, user_id,
+};
+use serde_json::json;
+
+use super::{additional_creators, transfer_power_levels};
+
+fn power_levels(users: &[(&UserId, Int)]) -> RoomPowerLevelsEventContent {
+	RoomPowerLevelsEventContent {
+		users: users
+			.iter()
+			.map(|(user_id, level)| ((*user_id).to_owned(), *level))
+			.collect(),
+		..Default::default()
+	}
+}
+
+fn body(body: serde_json::Value) -> CanonicalJsonValue {
+	serde_json::from_value(body).expect("request body is canonical JSON")
+}
+
+#[test]
+fn additional_creators_from_body() {
+	assert!(additional_creators(None).expect("no body").is_empty());
+
+	let without = body(json!({ "new_version": "12" }));
+	assert!(
+		additional_creators(Some(&without))
+			.expect("no creators")
+			.is_empty()
+	);
+
+	let with = body(json!({
+		"new_version": "12",
+		"additional_creators": ["@bob:example.com", "@carol:example.com"],
+	}));
+	assert_eq!(additional_creators(Some(&with)).expect("valid creators"), [
+		owned_user_id!("@bob:example.com"),
+		owned_user_id!("@carol:example.com"),
+	]);
+}
+
+#[test]
+fn invalid_additional_creators_are_rejected() {
+	for creators in [json!("@bob:example.com"), json!(["not a user id"]), json!([1])] {
+		let invalid = body(json!({ "new_version": "12", "additional_creators": creators }));
+		assert!(additional_creators(Some(&invalid)).is_err());
+	}
+}
+
+#[test]
+fn old_creators_are_promoted_to_highest_power() {
+	let (alice, bob, carol) = (
+		user_id!("@alice:example.com"),
+		user_id!("@bob:example.com"),
+		user_id!("@carol:example.com"),
+	);
+	let content = power_levels(&[(bob, int!(50)), (carol, int!(120))]);
+
+	// upgrading a room privileging creators to one which does not
+	let content = transfer_power_levels(content, &[alice.to_owned()], &[]);
+
+	assert_eq!(
+		content.users,
+		BTreeMap::from([
+			(alice.to_owned(), int!(120)),
+			(bob.to_owned(), int!(50)),
+			(carol.to_owned(), int!(120)),
+		])
+	);
+	assert!(
+		!content
+			.events
+			.contains_key(&TimelineEventType::RoomTombstone)
+	);
+}
+
+#[test]
+fn old_creators_have_at_least_default_admin_power() {
+	let (alice, bob) = (user_id!("@alice:example.com"), user_id!("@bob:example.com"));
+	let content = power_levels(&[(bob, int!(50))]);
+
+	let content = transfer_power_levels(content, &[alice.to_owned()], &[]);
+
+	assert_eq!(content.users.get(alice), Some(&int!(100)));
+}
+
+#[test]
+fn new_creators_are_removed_from_power_levels() {
+	let (alice, bob, carol) = (
+		user_id!("@alice:example.com"),
+		user_id!("@bob:example.com"),
+		user_id!("@carol:example.com"),
+	);
+	let content = power_levels(&[(alice, int!(100)), (bob, int!(50)), (carol, int!(50))]);
+
+	// upgrading a room which does not privilege creators to one which does
+	let content = transfer_power_levels(content, &[], &[alice, bob]);
+
+	assert_eq!(content.users, BTreeMap::from([(carol.to_owned(), int!(50))]));
+	assert_eq!(content.events.get(&TimelineEventType::RoomTombstone), Some(&int!(150)));
+}
+
+#[test]
+fn creators_carried_over_stay_out_of_power_levels() {
+	let (alice, bob, carol) = (
+		user_id!("@alice:example.com"),
+		user_id!("@bob:example.com"),
+		user_id!("@carol:example.com"),
+	);
+	let content = power_levels(&[(bob, int!(50))]);
+
+	// alice created the old room, and bob is made a creator of the new one, both
+	// of which privilege creators; carol created the old room only
+	let old_creators = [alice.to_owned(), carol.to_owned()];
+	let content = transfer_power_levels(content, &old_creators, &[alice, bob]);
+
+	assert_eq!(content.users, BTreeMap::from([(carol.to_owned(), int!(100))]));
+	assert!(
+		!content
+			.events
+			.contains_key(&TimelineEventType::RoomTombstone)
+	);
+}
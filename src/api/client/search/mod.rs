@@ -0,0 +1,458 @@
+mod tests;
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	iter::once,
+};
+
+use axum::extract::State;
+use conduwuit::{
+	Err, Result, at, is_true,
+	matrix::{Event, PduCount, PduEvent},
+	ref_at,
+	result::FlatOk,
+	utils::{
+		IterStream,
+		stream::{ReadyExt, TryIgnore, WidebandExt},
+	},
+};
+use conduwuit_service::{
+	Services,
+	rooms::{search::RoomQuery, timeline::PdusIterItem},
+};
+use futures::{
+	FutureExt, StreamExt, TryFutureExt, TryStreamExt,
+	future::{OptionFuture, join},
+};
+use ruma::{
+	CanonicalJsonValue, EventId, OwnedRoomId, OwnedUserId, RoomId, UInt, UserId,
+	api::client::search::search_events::{
+		self,
+		v3::{
+			Criteria, EventContext, EventContextResult, GroupingKey, Groupings,
+			OwnedRoomIdOrUserId, ResultCategories, ResultGroup, ResultRoomEvents, SearchResult,
+			UserProfile,
+		},
+	},
+	events::{AnyStateEvent, StateEventType, room::member::RoomMemberEventContent},
+	serde::Raw,
+};
+use search_events::v3::{Request, Response};
+
+use crate::{
+	Ruma,
+	client::message::{ignored_filter, visibility_filter},
+};
+
+type RoomStates = BTreeMap<OwnedRoomId, RoomState>;
+type RoomState = Vec<Raw<AnyStateEvent>>;
+type ResultGroups = BTreeMap<GroupingKey, BTreeMap<OwnedRoomIdOrUserId, ResultGroup>>;
+
+const LIMIT_DEFAULT: usize = 10;
+const LIMIT_MAX: usize = 100;
+const CONTEXT_LIMIT_MAX: usize = 50;
+
+/// # `POST /_matrix/client/r0/search`
+///
+/// Searches rooms for messages.
+///
+/// - Rooms named in the filter must be joined or world-readable, and only
+///   events the user is allowed to see according to `history_visibility` are
+///   returned
+/// - Results of all searched rooms are returned newest first; `next_batch`
+///   resumes after the last result returned
+pub(crate) async fn search_events_route(
+	State(services): State<crate::State>,
+	body: Ruma<Request>,
+) -> Result<Response> {
+	let sender_user = body.sender_user();
+	let next_batch = body.next_batch.as_deref();
+	let with_context = event_context_requested(body.json_body.as_ref());
+	let room_events_result: OptionFuture<_> = body
+		.search_categories
+		.room_events
+		.as_ref()
+		.map(|criteria| {
+			let event_context = with_context.then_some(&criteria.event_context);
+			category_room_events(&services, sender_user, next_batch, criteria, event_context)
+		})
+		.into();
+
+	Ok(Response {
+		search_categories: ResultCategories {
+			room_events: Box::pin(room_events_result)
+				.await
+				.unwrap_or_else(|| Ok(ResultRoomEvents::default()))?,
+		},
+	})
+}
+
+#[allow(clippy::map_unwrap_or)]
+async fn category_room_events(
+	services: &Services,
+	sender_user: &UserId,
+	next_batch: Option<&str>,
+	criteria: &Criteria,
+	event_context: Option<&EventContext>,
+) -> Result<ResultRoomEvents> {
+	let filter = &criteria.filter;
+
+	let limit: usize = filter
+		.limit
+		.map(TryInto::try_into)
+		.flat_ok()
+		.unwrap_or(LIMIT_DEFAULT)
+		.min(LIMIT_MAX);
+
+	let before: Option<PduCount> = next_batch.map(str::parse).transpose()?;
+
+	let rooms = filter
+		.rooms
+		.clone()
+		.map(IntoIterator::into_iter)
+		.map(IterStream::stream)
+		.map(StreamExt::boxed)
+		.unwrap_or_else(|| {
+			services
+				.rooms
+				.state_cache
+				.rooms_joined(sender_user)
+				.map(ToOwned::to_owned)
+				.boxed()
+		});
+
+	let results: Vec<_> = rooms
+		.filter_map(|room_id| async move {
+			check_room_visible(services, sender_user, &room_id, criteria)
+				.await
+				.is_ok()
+				.then_some(room_id)
+		})
+		.filter_map(|room_id| async move {
+			// One more than the limit is requested so that we know whether
+			// another batch follows this one.
+			let query = RoomQuery {
+				room_id: &room_id,
+				user_id: Some(sender_user),
+				criteria,
+				before,
+				limit: limit.saturating_add(1),
+			};
+
+			let (count, results) = services
+				.rooms
+				.search
+				.search_pdus(&query, sender_user)
+				.await
+				.ok()?;
+
+			results
+				.collect::<Vec<_>>()
+				.map(|results| (room_id.clone(), count, results))
+				.map(Some)
+				.await
+		})
+		.collect()
+		.await;
+
+	let total: UInt = results
+		.iter()
+		.fold(0, |a: usize, (_, count, _)| a.saturating_add(*count))
+		.try_into()?;
+
+	let state: RoomStates = results
+		.iter()
+		.stream()
+		.ready_filter(|_| criteria.include_state.is_some_and(is_true!()))
+		.filter_map(|(room_id, ..)| async move {
+			procure_room_state(services, room_id)
+				.map_ok(|state| (room_id.clone(), state))
+				.await
+				.ok()
+		})
+		.collect()
+		.await;
+
+	let (results, next_batch) = paginate(results.into_iter().flat_map(at!(2)).collect(), limit);
+	let groups = group_results(&criteria.groupings, &results);
+
+	let results: Vec<SearchResult> = results
+		.into_iter()
+		.stream()
+		.wide_then(|item| search_result(services, sender_user, event_context, item))
+		.collect()
+		.await;
+
+	let highlights = criteria
+		.search_term
+		.split_terminator(|c: char| !c.is_alphanumeric())
+		.map(str::to_lowercase)
+		.collect();
+
+	Ok(ResultRoomEvents {
+		count: Some(total),
+		next_batch,
+		results,
+		state,
+		highlights,
+		groups,
+	})
+}
+
+/// Interleaves the results of all rooms newest first and keeps the first
+/// `limit`; when more remain, the position of the last one kept is where the
+/// next batch resumes.
+fn paginate(mut results: Vec<PdusIterItem>, limit: usize) -> (Vec<PdusIterItem>, Option<String>) {
+	results.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+
+	let more = results.len() > limit;
+	results.truncate(limit);
+
+	let next_batch = results
+		.last()
+		.filter(|_| more)
+		.map(at!(0))
+		.as_ref()
+		.map(ToString::to_string);
+
+	(results, next_batch)
+}
+
+/// Whether the client asked for the context of results. Ruma fills in a
+/// default `event_context` when the request omits it, so this is told from
+/// the request body.
+fn event_context_requested(json_body: Option<&CanonicalJsonValue>) -> bool {
+	let mut value = json_body;
+	for key in ["search_categories", "room_events", "event_context"] {
+		value = match value {
+			| Some(CanonicalJsonValue::Object(object)) => object.get(key),
+			| _ => None,
+		};
+	}
+
+	value.is_some()
+}
+
+async fn search_result(
+	services: &Services,
+	sender_user: &UserId,
+	options: Option<&EventContext>,
+	(count, pdu): PdusIterItem,
+) -> SearchResult {
+	let context: OptionFuture<_> = options
+		.map(|options| event_context(services, sender_user, options, count, &pdu))
+		.into();
+
+	let context = context.await.unwrap_or_default();
+
+	SearchResult {
+		rank: None,
+		result: Some(pdu.into_format()),
+		context,
+	}
+}
+
+/// Fetches the events surrounding a search result, and when requested the
+/// profiles of their senders as of the result.
+async fn event_context(
+	services: &Services,
+	sender_user: &UserId,
+	options: &EventContext,
+	count: PduCount,
+	pdu: &PduEvent,
+) -> EventContextResult {
+	let room_id = pdu.room_id_or_hash();
+	let before_limit: usize = options
+		.before_limit
+		.try_into()
+		.unwrap_or(CONTEXT_LIMIT_MAX)
+		.min(CONTEXT_LIMIT_MAX);
+
+	let after_limit: usize = options
+		.after_limit
+		.try_into()
+		.unwrap_or(CONTEXT_LIMIT_MAX)
+		.min(CONTEXT_LIMIT_MAX);
+
+	let events_before = services
+		.rooms
+		.timeline
+		.pdus_rev(&room_id, Some(count))
+		.ignore_err()
+		.wide_filter_map(|item| ignored_filter(services, item, sender_user))
+		.wide_filter_map(|item| visibility_filter(services, item, sender_user))
+		.take(before_limit)
+		.collect();
+
+	let events_after = services
+		.rooms
+		.timeline
+		.pdus(&room_id, Some(count))
+		.ignore_err()
+		.wide_filter_map(|item| ignored_filter(services, item, sender_user))
+		.wide_filter_map(|item| visibility_filter(services, item, sender_user))
+		.take(after_limit)
+		.collect();
+
+	let (events_before, events_after): (Vec<_>, Vec<_>) = join(events_before, events_after).await;
+
+	let profile_info: OptionFuture<_> = options
+		.include_profile
+		.then(|| {
+			let senders: BTreeSet<_> = once(pdu)
+				.chain(events_before.iter().map(ref_at!(1)))
+				.chain(events_after.iter().map(ref_at!(1)))
+				.map(Event::sender)
+				.collect();
+
+			profile_info(services, pdu.event_id(), senders)
+		})
+		.into();
+
+	EventContextResult {
+		profile_info: profile_info.await.unwrap_or_default(),
+		start: events_before
+			.last()
+			.map(at!(0))
+			.or(Some(count))
+			.as_ref()
+			.map(ToString::to_string),
+		end: events_after
+			.last()
+			.map(at!(0))
+			.or(Some(count))
+			.as_ref()
+			.map(ToString::to_string),
+		events_before: events_before
+			.into_iter()
+			.map(at!(1))
+			.map(|mut pdu| {
+				pdu.set_unsigned(Some(sender_user));
+				pdu.into_format()
+			})
+			.collect(),
+		events_after: events_after
+			.into_iter()
+			.map(at!(1))
+			.map(|mut pdu| {
+				pdu.set_unsigned(Some(sender_user));
+				pdu.into_format()
+			})
+			.collect(),
+	}
+}
+
+/// Historic profiles of `senders`, taken from the room state at `event_id`.
+async fn profile_info(
+	services: &Services,
+	event_id: &EventId,
+	senders: BTreeSet<&UserId>,
+) -> BTreeMap<OwnedUserId, UserProfile> {
+	let Ok(shortstatehash) = services
+		.rooms
+		.state_accessor
+		.pdu_shortstatehash(event_id)
+		.await
+	else {
+		return BTreeMap::new();
+	};
+
+	senders
+		.into_iter()
+		.stream()
+		.wide_filter_map(|user_id| async move {
+			let content: RoomMemberEventContent = services
+				.rooms
+				.state_accessor
+				.state_get_content(shortstatehash, &StateEventType::RoomMember, user_id.as_str())
+				.await
+				.ok()?;
+
+			let mut profile = UserProfile::new();
+			profile.displayname = content.displayname;
+			profile.avatar_url = content.avatar_url;
+
+			Some((user_id.to_owned(), profile))
+		})
+		.collect()
+		.await
+}
+
+/// Groups the results of this batch by each requested key, in order of first
+/// appearance. Unknown grouping keys are ignored.
+///
+/// Groups carry no `next_batch`: batches are cut across all searched rooms,
+/// so there is no token resuming a single group.
+fn group_results(groupings: &Groupings, results: &[PdusIterItem]) -> ResultGroups {
+	groupings
+		.group_by
+		.iter()
+		.filter_map(|grouping| grouping.key.clone())
+		.filter(|key| matches!(key, GroupingKey::RoomId | GroupingKey::Sender))
+		.map(|key| {
+			let mut groups: BTreeMap<OwnedRoomIdOrUserId, ResultGroup> = BTreeMap::new();
+			for (_, pdu) in results {
+				let group_id = match key {
+					| GroupingKey::RoomId => OwnedRoomIdOrUserId::RoomId(pdu.room_id_or_hash()),
+					| _ => OwnedRoomIdOrUserId::UserId(pdu.sender().to_owned()),
+				};
+
+				let order = groups.len();
+				groups
+					.entry(group_id)
+					.or_insert_with(|| {
+						let mut group = ResultGroup::new();
+						group.order = order.try_into().ok();
+						group
+					})
+					.results
+					.push(pdu.event_id().to_owned());
+			}
+
+			(key, groups)
+		})
+		.collect()
+}
+
+async fn procure_room_state(services: &Services, room_id: &RoomId) -> Result<RoomState> {
+	let state = services
+		.rooms
+		.state_accessor
+		.room_state_full_pdus(room_id)
+		.map_ok(Event::into_format)
+		.try_collect()
+		.await?;
+
+	Ok(state)
+}
+
+async fn check_room_visible(
+	services: &Services,
+	user_id: &UserId,
+	room_id: &RoomId,
+	search: &Criteria,
+) -> Result {
+	let check_visible = search.filter.rooms.is_some();
+	let check_state = check_visible && search.include_state.is_some_and(is_true!());
+
+	let room_visible = !check_visible
+		|| services.rooms.state_cache.is_joined(user_id, room_id).await
+		|| services
+			.rooms
+			.state_accessor
+			.is_world_readable(room_id)
+			.await;
+
+	let state_visible = !check_state
+		|| services
+			.rooms
+			.state_accessor
+			.user_can_see_state_events(user_id, room_id)
+			.await;
+
+	if !room_visible || !state_visible {
+		return Err!(Request(Forbidden("You don't have permission to view {room_id:?}")));
+	}
+
+	Ok(())
+}
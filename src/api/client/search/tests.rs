@@ -0,0 +1,116 @@
+#![cfg(test)]
+
+use conduwuit::{PduCount, PduEvent, pdu::EventHash};
+use conduwuit_service::rooms::timeline::PdusIterItem;
+use ruma::{
+	CanonicalJsonValue, EventId, OwnedEventId, RoomId, UInt, UserId,
+	api::client::search::search_events::v3::{GroupingKey, Groupings, OwnedRoomIdOrUserId},
+	events::TimelineEventType,
+	room_id, user_id,
+};
+use serde_json::{json, value::to_raw_value};
+
+use super::{event_context_requested, group_results, paginate};
+
+fn result(count: u64, room_id: &RoomId, sender: &UserId) -> PdusIterItem {
+	let pdu = PduEvent {
+		event_id: EventId::parse(format!("${count}:example.com")).expect("valid event id"),
+		room_id: Some(room_id.to_owned()),
+		sender: sender.to_owned(),
+		origin_server_ts: UInt::from(1_u32),
+		kind: TimelineEventType::RoomMessage,
+		content: to_raw_value(&json!({"msgtype": "m.text", "body": "hello"})).expect("content"),
+		state_key: None,
+		prev_events: vec![],
+		depth: UInt::from(1_u32),
+		auth_events: vec![],
+		redacts: None,
+		unsigned: None,
+		hashes: EventHash { sha256: String::new() },
+		signatures: None,
+		origin: None,
+	};
+
+	(PduCount::Normal(count), pdu)
+}
+
+fn event_ids(results: &[PdusIterItem]) -> Vec<OwnedEventId> {
+	results
+		.iter()
+		.map(|(_, pdu)| pdu.event_id.clone())
+		.collect()
+}
+
+fn request(body: serde_json::Value) -> CanonicalJsonValue {
+	serde_json::from_value(body).expect("request body is canonical JSON")
+}
+
+#[test]
+fn event_context_only_when_requested() {
+	let without = request(json!({
+		"search_categories": { "room_events": { "search_term": "hello" } }
+	}));
+	let with = request(json!({
+		"search_categories": {
+			"room_events": { "search_term": "hello", "event_context": { "before_limit": 1 } }
+		}
+	}));
+
+	assert!(!event_context_requested(None));
+	assert!(!event_context_requested(Some(&without)));
+	assert!(event_context_requested(Some(&with)));
+}
+
+#[test]
+fn paginate_interleaves_rooms_newest_first() {
+	let (a, b) = (room_id!("!a:example.com"), room_id!("!b:example.com"));
+	let alice = user_id!("@alice:example.com");
+	let results = vec![result(5, a, alice), result(2, a, alice), result(4, b, alice)];
+
+	let (page, next_batch) = paginate(results, 2);
+
+	assert_eq!(event_ids(&page), event_ids(&[result(5, a, alice), result(4, b, alice)]));
+	assert_eq!(next_batch.as_deref(), Some("4"));
+}
+
+#[test]
+fn paginate_last_batch_has_no_token() {
+	let a = room_id!("!a:example.com");
+	let alice = user_id!("@alice:example.com");
+	let results = vec![result(3, a, alice), result(1, a, alice)];
+
+	let (page, next_batch) = paginate(results, 2);
+
+	assert_eq!(page.len(), 2);
+	assert_eq!(next_batch, None);
+}
+
+#[test]
+fn groups_in_order_of_first_appearance() {
+	let (a, b) = (room_id!("!a:example.com"), room_id!("!b:example.com"));
+	let (alice, bob) = (user_id!("@alice:example.com"), user_id!("@bob:example.com"));
+	let results = vec![result(6, b, alice), result(5, a, bob), result(4, b, bob)];
+	let groupings: Groupings = serde_json::from_value(json!({
+		"group_by": [{ "key": "room_id" }, { "key": "sender" }]
+	}))
+	.expect("valid groupings");
+
+	let groups = group_results(&groupings, &results);
+
+	let by_room = &groups[&GroupingKey::RoomId];
+	let room_b = &by_room[&OwnedRoomIdOrUserId::RoomId(b.to_owned())];
+	let room_a = &by_room[&OwnedRoomIdOrUserId::RoomId(a.to_owned())];
+	assert_eq!(room_b.order, Some(UInt::from(0_u32)));
+	assert_eq!(room_a.order, Some(UInt::from(1_u32)));
+	assert_eq!(room_b.results, event_ids(&[result(6, b, alice), result(4, b, bob)]));
+
+	let by_sender = &groups[&GroupingKey::Sender];
+	let from_bob = &by_sender[&OwnedRoomIdOrUserId::UserId(bob.to_owned())];
+	assert_eq!(from_bob.results, event_ids(&[result(5, a, bob), result(4, b, bob)]));
+	assert!(
+		by_room
+			.values()
+			.chain(by_sender.values())
+			.all(|group| group.next_batch.is_none())
+	);
+}
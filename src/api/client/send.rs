@@ -1,12 +1,86 @@
 use std::collections::BTreeMap;
 
-use axum::extract::State;
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+};
 use axum_client_ip::InsecureClientIp;
 use conduwuit::{Err, Result, err, matrix::pdu::PduBuilder, utils};
-use ruma::{api::client::message::send_message_event, events::MessageLikeEventType};
+use ruma::{
+	api::client::{delayed_events::delayed_message_event, message::send_message_event},
+	events::MessageLikeEventType,
+};
 use serde_json::from_str;
 
-use crate::Ruma;
+use crate::{Ruma, RumaResponse};
+
+/// # `PUT /_matrix/client/v3/rooms/{roomId}/send/{eventType}/{txnId}`
+///
+/// Send a message event into the room, or schedule it to be sent later when
+/// the MSC4140 `org.matrix.msc4140.delay` query parameter is given.
+pub(crate) async fn send_message_event_or_delayed_route(
+	State(services): State<crate::State>,
+	InsecureClientIp(client_ip): InsecureClientIp,
+	body: Ruma<send_message_event::v3::Request>,
+) -> Result<Response> {
+	let Some(delay) = body.delay else {
+		return send_message_event_route(State(services), InsecureClientIp(client_ip), body)
+			.await
+			.map(RumaResponse)
+			.map(IntoResponse::into_response);
+	};
+
+	let sender_user = body.sender_user();
+	if services.users.is_suspended(sender_user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	if MessageLikeEventType::RoomEncrypted == body.event_type && !services.config.allow_encryption
+	{
+		return Err!(Request(Forbidden("Encryption has been disabled")));
+	}
+
+	// Retries of a delayed send return the delay id scheduled the first time
+	let sender_device = body.sender_device.as_deref();
+	let state_lock = services.rooms.state.mutex.lock(&body.room_id).await;
+	if let Ok(response) = services
+		.transaction_ids
+		.existing_txnid(sender_user, sender_device, &body.txn_id)
+		.await
+	{
+		if response.is_empty() || response.starts_with(b"$") {
+			return Err!(Request(InvalidParam(
+				"Tried to use txn id already used for an incompatible endpoint."
+			)));
+		}
+
+		let delay_id = utils::string_from_bytes(&response)
+			.map_err(|e| err!(Database("Invalid delay_id in txnid data: {e:?}")))?;
+
+		return Ok(
+			RumaResponse(delayed_message_event::unstable::Response { delay_id }).into_response()
+		);
+	}
+
+	let content = from_str(body.body.body.json().get())
+		.map_err(|e| err!(Request(BadJson("Invalid JSON body: {e}"))))?;
+
+	let delay_id = services
+		.delayed_events
+		.schedule(sender_user, &body.room_id, body.event_type.to_string(), None, content, delay)
+		.await?;
+
+	services.transaction_ids.add_txnid(
+		sender_user,
+		sender_device,
+		&body.txn_id,
+		delay_id.as_bytes(),
+	);
+
+	drop(state_lock);
+
+	Ok(RumaResponse(delayed_message_event::unstable::Response { delay_id }).into_response())
+}
 
 /// # `PUT /_matrix/client/v3/rooms/{roomId}/send/{eventType}/{txnId}`
 ///
@@ -31,7 +105,12 @@ pub(crate) async fn send_message_event_route(
 
 	services
 		.users
-		.update_device_last_seen(sender_user, body.sender_device.as_deref(), client_ip)
+		.update_device_last_seen(
+			sender_user,
+			body.sender_device.as_deref(),
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	// Forbid m.room.encrypted if encryption is disabled
@@ -54,9 +133,10 @@ pub(crate) async fn send_message_event_route(
 		.existing_txnid(sender_user, sender_device, &body.txn_id)
 		.await
 	{
-		// The client might have sent a txnid of the /sendToDevice endpoint
-		// This txnid has no response associated with it
-		if response.is_empty() {
+		// The client might have sent a txnid of the /sendToDevice endpoint, which
+		// has no response associated with it, or of a delayed send, whose response
+		// is a delay id rather than an event id
+		if response.is_empty() || !response.starts_with(b"$") {
 			return Err!(Request(InvalidParam(
 				"Tried to use txn id already used for an incompatible endpoint."
 			)));
@@ -3,7 +3,7 @@
 use axum::extract::State;
 use axum_client_ip::InsecureClientIp;
 use conduwuit::{
-	Err, Error, Result, debug, err, info,
+	Err, Error, Result, debug, debug_info, err, info,
 	utils::{self, ReadyExt, hash},
 	warn,
 };
@@ -370,40 +370,51 @@ pub(crate) async fn login_token_route(
 	// TODO: How do we make only UIA sessions that have not been used before valid?
 	let (sender_user, sender_device) = body.sender();
 
-	let mut uiaainfo = uiaa::UiaaInfo {
-		flows: vec![uiaa::AuthFlow { stages: vec![uiaa::AuthType::Password] }],
-		completed: Vec::new(),
-		params: Box::default(),
-		session: None,
-		auth_error: None,
-	};
-
-	match &body.auth {
-		| Some(auth) => {
-			let (worked, uiaainfo) = services
-				.uiaa
-				.try_auth(sender_user, sender_device, auth, &uiaainfo)
-				.await?;
-
-			if !worked {
-				return Err(Error::Uiaa(uiaainfo));
-			}
-
-			// Success!
-		},
-		| _ => match body.json_body.as_ref() {
-			| Some(json) => {
-				uiaainfo.session = Some(utils::random_string(SESSION_ID_LENGTH));
-				services
+	// A cross-signed device which approved a QR code login through a rendezvous
+	// session already proved itself to the new device; it is issued one token
+	// without UIA
+	if services
+		.rendezvous
+		.take_login_grant(sender_user, sender_device)
+		.await
+	{
+		debug_info!(%sender_user, "Issuing login token for rendezvous login");
+	} else {
+		let mut uiaainfo = uiaa::UiaaInfo {
+			flows: vec![uiaa::AuthFlow { stages: vec![uiaa::AuthType::Password] }],
+			completed: Vec::new(),
+			params: Box::default(),
+			session: None,
+			auth_error: None,
+		};
+
+		match &body.auth {
+			| Some(auth) => {
+				let (worked, uiaainfo) = services
 					.uiaa
-					.create(sender_user, sender_device, &uiaainfo, json);
+					.try_auth(sender_user, sender_device, auth, &uiaainfo)
+					.await?;
 
-				return Err(Error::Uiaa(uiaainfo));
+				if !worked {
+					return Err(Error::Uiaa(uiaainfo));
+				}
+
+				// Success!
 			},
-			| _ => {
-				return Err!(Request(NotJson("No JSON body was sent when required.")));
+			| _ => match body.json_body.as_ref() {
+				| Some(json) => {
+					uiaainfo.session = Some(utils::random_string(SESSION_ID_LENGTH));
+					services
+						.uiaa
+						.create(sender_user, sender_device, &uiaainfo, json);
+
+					return Err(Error::Uiaa(uiaainfo));
+				},
+				| _ => {
+					return Err!(Request(NotJson("No JSON body was sent when required.")));
+				},
 			},
-		},
+		}
 	}
 
 	let login_token = utils::random_string(TOKEN_LENGTH);
@@ -1,4 +1,7 @@
-use axum::extract::State;
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+};
 use axum_client_ip::InsecureClientIp;
 use conduwuit::{
 	Err, Result, err,
@@ -9,7 +12,10 @@
 use futures::{FutureExt, TryStreamExt};
 use ruma::{
 	MilliSecondsSinceUnixEpoch, OwnedEventId, RoomId, UserId,
-	api::client::state::{get_state_events, get_state_events_for_key, send_state_event},
+	api::client::{
+		delayed_events::delayed_state_event,
+		state::{get_state_events, get_state_events_for_key, send_state_event},
+	},
 	events::{
 		AnyStateEventContent, StateEventType,
 		room::{
@@ -37,7 +43,12 @@ pub(crate) async fn send_state_event_for_key_route(
 	let sender_user = body.sender_user();
 	services
 		.users
-		.update_device_last_seen(sender_user, body.sender_device.as_deref(), ip)
+		.update_device_last_seen(
+			sender_user,
+			body.sender_device.as_deref(),
+			ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	if services.users.is_suspended(sender_user).await? {
@@ -62,6 +73,52 @@ pub(crate) async fn send_state_event_for_key_route(
 	})
 }
 
+/// # `PUT /_matrix/client/*/rooms/{roomId}/state/{eventType}/{stateKey}`
+///
+/// Sends a state event into the room, or schedules it to be sent later when
+/// the MSC4140 `org.matrix.msc4140.delay` query parameter is given.
+pub(crate) async fn send_state_event_for_key_or_delayed_route(
+	State(services): State<crate::State>,
+	InsecureClientIp(ip): InsecureClientIp,
+	body: Ruma<send_state_event::v3::Request>,
+) -> Result<Response> {
+	let Some(delay) = body.delay else {
+		return send_state_event_for_key_route(State(services), InsecureClientIp(ip), body)
+			.boxed()
+			.await
+			.map(RumaResponse)
+			.map(IntoResponse::into_response);
+	};
+
+	let sender_user = body.sender_user();
+	if services.users.is_suspended(sender_user).await? {
+		return Err!(Request(UserSuspended("You cannot perform this action while suspended.")));
+	}
+
+	allowed_to_send_state_event(
+		&services,
+		&body.room_id,
+		&body.event_type,
+		&body.state_key,
+		&body.body.body,
+	)
+	.await?;
+
+	let delay_id = services
+		.delayed_events
+		.schedule(
+			sender_user,
+			&body.room_id,
+			body.event_type.to_string(),
+			Some(body.state_key.clone()),
+			serde_json::from_str(body.body.body.json().get())?,
+			delay,
+		)
+		.await?;
+
+	Ok(RumaResponse(delayed_state_event::unstable::Response { delay_id }).into_response())
+}
+
 /// # `PUT /_matrix/client/*/rooms/{roomId}/state/{eventType}`
 ///
 /// Sends a state event into the room.
@@ -69,11 +126,10 @@ pub(crate) async fn send_state_event_for_empty_key_route(
 	State(services): State<crate::State>,
 	InsecureClientIp(ip): InsecureClientIp,
 	body: Ruma<send_state_event::v3::Request>,
-) -> Result<RumaResponse<send_state_event::v3::Response>> {
-	send_state_event_for_key_route(State(services), InsecureClientIp(ip), body)
+) -> Result<Response> {
+	send_state_event_for_key_or_delayed_route(State(services), InsecureClientIp(ip), body)
 		.boxed()
 		.await
-		.map(RumaResponse)
 }
 
 /// # `GET /_matrix/client/v3/rooms/{roomid}/state`
@@ -82,12 +138,21 @@ pub(crate) async fn send_state_event_for_empty_key_route(
 ///
 /// - If not joined: Only works if current room history visibility is world
 ///   readable
+/// - World-readable rooms this server is not in are peeked over federation
 pub(crate) async fn get_state_events_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_state_events::v3::Request>,
 ) -> Result<get_state_events::v3::Response> {
 	let sender_user = body.sender_user();
 
+	if !services.rooms.metadata.exists(&body.room_id).await {
+		let peek = services.rooms.peek.peek(&body.room_id, sender_user).await?;
+
+		return Ok(get_state_events::v3::Response {
+			room_state: peek.state.iter().cloned().map(Event::into_format).collect(),
+		});
+	}
+
 	if !services
 		.rooms
 		.state_accessor
@@ -116,35 +181,50 @@ pub(crate) async fn get_state_events_route(
 ///
 /// - If not joined: Only works if current room history visibility is world
 ///   readable
+/// - World-readable rooms this server is not in are peeked over federation
 pub(crate) async fn get_state_events_for_key_route(
 	State(services): State<crate::State>,
 	body: Ruma<get_state_events_for_key::v3::Request>,
 ) -> Result<get_state_events_for_key::v3::Response> {
 	let sender_user = body.sender_user();
+	let not_found = || {
+		err!(Request(NotFound(debug_warn!(
+				room_id = %body.room_id,
+				event_type = %body.event_type,
+				"State event not found in room.",
+		))))
+	};
 
-	if !services
-		.rooms
-		.state_accessor
-		.user_can_see_state_events(sender_user, &body.room_id)
-		.await
-	{
-		return Err!(Request(NotFound(debug_warn!(
-			"You don't have permission to view the room state."
-		))));
-	}
+	let event = if services.rooms.metadata.exists(&body.room_id).await {
+		if !services
+			.rooms
+			.state_accessor
+			.user_can_see_state_events(sender_user, &body.room_id)
+			.await
+		{
+			return Err!(Request(NotFound(debug_warn!(
+				"You don't have permission to view the room state."
+			))));
+		}
 
-	let event = services
-		.rooms
-		.state_accessor
-		.room_state_get(&body.room_id, &body.event_type, &body.state_key)
-		.await
-		.map_err(|_| {
-			err!(Request(NotFound(debug_warn!(
-					room_id = %body.room_id,
-					event_type = %body.event_type,
-					"State event not found in room.",
-			))))
-		})?;
+		services
+			.rooms
+			.state_accessor
+			.room_state_get(&body.room_id, &body.event_type, &body.state_key)
+			.await
+			.map_err(|_| not_found())?
+	} else {
+		let peek = services.rooms.peek.peek(&body.room_id, sender_user).await?;
+
+		peek.state
+			.iter()
+			.find(|pdu| {
+				pdu.kind.to_string() == body.event_type.to_string()
+					&& pdu.state_key.as_deref() == Some(body.state_key.as_str())
+			})
+			.cloned()
+			.ok_or_else(not_found)?
+	};
 
 	let event_format = body
 		.format
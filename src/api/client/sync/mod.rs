@@ -1,20 +1,28 @@
+mod tests;
 mod v3;
 mod v5;
 
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 use conduwuit::{
-	Event, PduCount, Result, debug_warn, err,
+	Event, PduCount, Result, at, debug_warn, err,
 	matrix::pdu::PduEvent,
 	ref_at, trace,
-	utils::stream::{BroadbandExt, ReadyExt, TryIgnore},
+	utils::{
+		IterStream,
+		stream::{BroadbandExt, ReadyExt, TryIgnore},
+	},
 };
 use conduwuit_service::Services;
 use futures::StreamExt;
 use ruma::{
-	OwnedUserId, RoomId, UserId,
-	events::TimelineEventType::{
-		self, Beacon, CallInvite, PollStart, RoomEncrypted, RoomMessage, Sticker,
+	OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+	events::{
+		StateEventType,
+		TimelineEventType::{
+			self, Beacon, CallInvite, PollStart, RoomEncrypted, RoomMessage, Sticker,
+		},
+		room::member::{MembershipState, RoomMemberEventContent},
 	},
 };
 
@@ -163,3 +171,224 @@ async fn share_encrypted_room(
 		})
 		.await
 }
+
+/// Returns the users who stopped sharing any encrypted room with the user
+/// between `since` and `to`, either by leaving the rooms the user is in or
+/// because the user left the rooms they shared. The user's clients can stop
+/// tracking their device lists.
+pub(crate) async fn device_list_left(
+	services: &Services,
+	user_id: &UserId,
+	since: u64,
+	to: Option<u64>,
+) -> HashSet<OwnedUserId> {
+	let mut candidates = left_rooms_members(services, user_id, since, to).await;
+
+	let rooms_joined: Vec<OwnedRoomId> = services
+		.rooms
+		.state_cache
+		.rooms_joined(user_id)
+		.map(ToOwned::to_owned)
+		.collect()
+		.await;
+
+	for room_id in &rooms_joined {
+		candidates.extend(room_members_left(services, room_id, since, to).await);
+	}
+
+	no_longer_sharing(services, user_id, candidates).await
+}
+
+/// Returns the users who stopped sharing any encrypted room with the user
+/// because the user left rooms after `since`.
+async fn device_list_left_rooms(
+	services: &Services,
+	user_id: &UserId,
+	since: u64,
+	to: Option<u64>,
+) -> HashSet<OwnedUserId> {
+	let candidates = left_rooms_members(services, user_id, since, to).await;
+
+	no_longer_sharing(services, user_id, candidates).await
+}
+
+/// Returns the members of the encrypted rooms the user left between `since`
+/// and `to`.
+async fn left_rooms_members(
+	services: &Services,
+	user_id: &UserId,
+	since: u64,
+	to: Option<u64>,
+) -> HashSet<OwnedUserId> {
+	let rooms_left: Vec<OwnedRoomId> = services
+		.rooms
+		.state_cache
+		.rooms_left(user_id)
+		.map(at!(0))
+		.collect()
+		.await;
+
+	let mut members = HashSet::new();
+	for room_id in &rooms_left {
+		let left_in_range = services
+			.rooms
+			.state_cache
+			.get_left_count(room_id, user_id)
+			.await
+			.is_ok_and(|count| in_range(count, since, to));
+
+		if !left_in_range
+			|| !services
+				.rooms
+				.state_accessor
+				.is_encrypted_room(room_id)
+				.await
+		{
+			continue;
+		}
+
+		services
+			.rooms
+			.state_cache
+			.room_members(room_id)
+			.map(ToOwned::to_owned)
+			.ready_for_each(|member| {
+				members.insert(member);
+			})
+			.await;
+	}
+
+	members
+}
+
+/// Whether the count falls in the range (since, to], `to` being unbounded when
+/// absent.
+fn in_range(count: u64, since: u64, to: Option<u64>) -> bool {
+	count > since && to.is_none_or(|to| count <= to)
+}
+
+/// Returns the users who left or were banned from the encrypted room between
+/// `since` and `to`, comparing the room's state as of both tokens. The current
+/// state stands in for `to` when absent or not yet passed.
+async fn room_members_left(
+	services: &Services,
+	room_id: &RoomId,
+	since: u64,
+	to: Option<u64>,
+) -> Vec<OwnedUserId> {
+	if !services
+		.rooms
+		.state_accessor
+		.is_encrypted_room(room_id)
+		.await
+	{
+		return Vec::new();
+	}
+
+	// nothing changed after a `to` which is still the latest count, so the
+	// current state is used for it rather than the state recorded at a token
+	let current_count = services.globals.current_count().unwrap_or_default();
+	let to_shortstatehash = match to {
+		| Some(to) if to < current_count =>
+			services
+				.rooms
+				.user
+				.get_token_shortstatehash(room_id, to)
+				.await,
+		| _ => services.rooms.state.get_room_shortstatehash(room_id).await,
+	};
+
+	let (Ok(since_shortstatehash), Ok(to_shortstatehash)) = (
+		services
+			.rooms
+			.user
+			.get_token_shortstatehash(room_id, since)
+			.await,
+		to_shortstatehash,
+	) else {
+		return Vec::new();
+	};
+
+	if since_shortstatehash == to_shortstatehash {
+		return Vec::new();
+	}
+
+	let Ok(added) = services
+		.rooms
+		.state_accessor
+		.state_added((since_shortstatehash, to_shortstatehash))
+		.await
+	else {
+		return Vec::new();
+	};
+
+	let changes: Vec<_> = added
+		.into_iter()
+		.stream()
+		.filter_map(async |(shortstatekey, shorteventid)| {
+			let (event_type, state_key) = services
+				.rooms
+				.short
+				.get_statekey_from_short(shortstatekey)
+				.await
+				.ok()?;
+
+			if event_type != StateEventType::RoomMember {
+				return None;
+			}
+
+			let event_id: OwnedEventId = services
+				.rooms
+				.short
+				.get_eventid_from_short(shorteventid)
+				.await
+				.ok()?;
+
+			let content: RoomMemberEventContent = services
+				.rooms
+				.timeline
+				.get_pdu(&event_id)
+				.await
+				.ok()?
+				.get_content()
+				.ok()?;
+
+			Some((state_key, content.membership))
+		})
+		.collect()
+		.await;
+
+	members_left(changes)
+}
+
+/// Returns the users whose membership changed to leave or ban, from the
+/// state keys and memberships of the member events which changed.
+fn members_left<K: AsRef<str>>(
+	changes: impl IntoIterator<Item = (K, MembershipState)>,
+) -> Vec<OwnedUserId> {
+	changes
+		.into_iter()
+		.filter(|(_, membership)| {
+			matches!(membership, MembershipState::Leave | MembershipState::Ban)
+		})
+		.filter_map(|(state_key, _)| UserId::parse(state_key.as_ref()).ok())
+		.collect()
+}
+
+/// Keeps the candidates who no longer share any encrypted room with the user.
+async fn no_longer_sharing(
+	services: &Services,
+	user_id: &UserId,
+	candidates: HashSet<OwnedUserId>,
+) -> HashSet<OwnedUserId> {
+	candidates
+		.into_iter()
+		.stream()
+		.ready_filter(|candidate| candidate != user_id)
+		.filter_map(async |candidate| {
+			(!share_encrypted_room(services, user_id, &candidate, None).await)
+				.then_some(candidate)
+		})
+		.collect()
+		.await
+}
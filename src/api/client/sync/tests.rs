@@ -0,0 +1,47 @@
+#![cfg(test)]
+
+use ruma::{events::room::member::MembershipState, owned_user_id};
+
+use super::{in_range, members_left};
+
+#[test]
+fn range_excludes_since_and_includes_to() {
+	assert!(!in_range(10, 10, Some(20)));
+	assert!(in_range(11, 10, Some(20)));
+	assert!(in_range(20, 10, Some(20)));
+	assert!(!in_range(21, 10, Some(20)));
+	assert!(!in_range(5, 10, Some(20)));
+}
+
+#[test]
+fn range_without_to_is_unbounded() {
+	assert!(!in_range(10, 10, None));
+	assert!(in_range(11, 10, None));
+	assert!(in_range(u64::MAX, 10, None));
+}
+
+#[test]
+fn leaves_and_bans_are_members_left() {
+	let changes = [
+		("@alice:example.com", MembershipState::Leave),
+		("@bob:example.com", MembershipState::Ban),
+		("@carol:example.com", MembershipState::Join),
+		("@dave:example.com", MembershipState::Invite),
+		("@erin:example.com", MembershipState::Knock),
+	];
+
+	assert_eq!(members_left(changes), [
+		owned_user_id!("@alice:example.com"),
+		owned_user_id!("@bob:example.com"),
+	]);
+}
+
+#[test]
+fn invalid_state_keys_are_skipped() {
+	let changes = [
+		("not a user id", MembershipState::Leave),
+		("@alice:example.com", MembershipState::Leave),
+	];
+
+	assert_eq!(members_left(changes), [owned_user_id!("@alice:example.com")]);
+}
@@ -20,7 +20,7 @@
 	future::{OptionFuture, join, join3, join4, try_join, try_join3},
 };
 use ruma::{
-	OwnedRoomId, OwnedUserId, RoomId, UserId,
+	OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
 	api::client::sync::sync_events::{
 		UnreadNotificationsCount,
 		v3::{Ephemeral, JoinedRoom, RoomAccountData, RoomSummary, State as RoomState, Timeline},
@@ -31,7 +31,6 @@
 		room::member::{MembershipState, RoomMemberEventContent},
 	},
 	serde::Raw,
-	uint,
 };
 use service::rooms::short::ShortStateHash;
 
@@ -73,6 +72,7 @@ pub(super) async fn load_joined_room(
 			timeline,
 			summary,
 			notification_counts,
+			thread_notification_counts,
 			device_list_updates,
 		},
 	) = try_join3(
@@ -101,7 +101,7 @@ pub(super) async fn load_joined_room(
 			events: state_events.into_iter().map(Event::into_format).collect(),
 		},
 		ephemeral,
-		unread_thread_notifications: BTreeMap::new(),
+		unread_thread_notifications: thread_notification_counts,
 	};
 
 	Ok((joined_room, device_list_updates))
@@ -243,6 +243,7 @@ struct StateAndTimeline {
 	timeline: Timeline,
 	summary: Option<RoomSummary>,
 	notification_counts: Option<UnreadNotificationsCount>,
+	thread_notification_counts: BTreeMap<OwnedEventId, UnreadNotificationsCount>,
 	device_list_updates: DeviceListUpdates,
 }
 
@@ -259,10 +260,19 @@ async fn build_state_and_timeline(
 	)
 	.await?;
 
-	let (state_events, notification_counts, joined_since_last_sync) = try_join3(
-		build_state_events(services, sync_context, room_id, shortstatehashes, &timeline),
+	let joined_since_last_sync =
+		check_joined_since_last_sync(services, shortstatehashes, sync_context).await?;
+
+	let (state_events, (notification_counts, thread_notification_counts)) = try_join(
+		build_state_events(
+			services,
+			sync_context,
+			room_id,
+			shortstatehashes,
+			&timeline,
+			joined_since_last_sync,
+		),
 		build_notification_counts(services, sync_context, room_id, &timeline),
-		check_joined_since_last_sync(services, shortstatehashes, sync_context),
 	)
 	.await?;
 
@@ -325,6 +335,7 @@ async fn build_state_and_timeline(
 		},
 		summary,
 		notification_counts,
+		thread_notification_counts,
 		device_list_updates,
 	})
 }
@@ -381,11 +392,8 @@ async fn fetch_shortstatehashes(
 
 	/*
 	associate the `current_count` with the `current_shortstatehash`, so we can
-	use it on the next sync as the `last_sync_end_shortstatehash`.
-
-	TODO: the table written to by this call grows extremely fast, gaining one new entry for each
-	joined room on _every single sync request_. we need to find a better way to remember the shortstatehash
-	between syncs.
+	use it on the next sync as the `last_sync_end_shortstatehash`. this only
+	writes when the room's state changed since the last recorded token.
 	*/
 	services
 		.rooms
@@ -445,6 +453,7 @@ async fn build_state_events(
 	room_id: &RoomId,
 	shortstatehashes: ShortStateHashes,
 	timeline: &TimelinePdus,
+	joined_since_last_sync: bool,
 ) -> Result<Vec<PduEvent>> {
 	let SyncContext {
 		syncing_user,
@@ -486,13 +495,16 @@ async fn build_state_events(
 		join(timeline_start_shortstatehash, lazily_loaded_members).await;
 
 	// compute the state delta between the previous sync and this sync.
-	match (last_sync_end_count, last_sync_end_shortstatehash) {
-		/*
-		if `last_sync_end_count` is Some (meaning this is an incremental sync), and `last_sync_end_shortstatehash`
-		is Some (meaning the syncing user didn't just join this room for the first time ever), and `full_state` is false,
-		then use `build_state_incremental`.
-		*/
-		| (Some(last_sync_end_count), Some(last_sync_end_shortstatehash)) if !full_state =>
+	match state_sync_kind(
+		last_sync_end_count,
+		last_sync_end_shortstatehash,
+		full_state,
+		joined_since_last_sync,
+	) {
+		| StateSyncKind::Incremental {
+			last_sync_end_count,
+			last_sync_end_shortstatehash,
+		} =>
 			build_state_incremental(
 				services,
 				syncing_user,
@@ -506,12 +518,7 @@ async fn build_state_events(
 			)
 			.boxed()
 			.await,
-		/*
-		otherwise use `build_state_initial`. note that this branch will be taken if the user joined this room since the last sync
-		for the first time ever, because in that case we have no `last_sync_end_shortstatehash` and can't correctly calculate
-		the state using the incremental sync algorithm.
-		*/
-		| _ =>
+		| StateSyncKind::Initial =>
 			build_state_initial(
 				services,
 				syncing_user,
@@ -523,14 +530,58 @@ async fn build_state_events(
 	}
 }
 
-/// Compute the number of unread notifications in this room.
+/// Which algorithm is used to compute the state events to sync.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum StateSyncKind {
+	/// Send the state changes since the end of the last sync.
+	Incremental {
+		last_sync_end_count: u64,
+		last_sync_end_shortstatehash: ShortStateHash,
+	},
+	/// Send the full state as of the start of the timeline.
+	Initial,
+}
+
+/// Use `build_state_incremental` only if this is an incremental sync, the
+/// room's state at the end of the last sync is known, `full_state` is false,
+/// and the syncing user was already joined at the end of the last sync. The
+/// state recorded for the last sync's token may have been written by another
+/// user's sync from before the syncing user joined, in which case the
+/// incremental sync algorithm would miss state.
+pub(super) fn state_sync_kind(
+	last_sync_end_count: Option<u64>,
+	last_sync_end_shortstatehash: Option<ShortStateHash>,
+	full_state: bool,
+	joined_since_last_sync: bool,
+) -> StateSyncKind {
+	match (last_sync_end_count, last_sync_end_shortstatehash) {
+		| (Some(last_sync_end_count), Some(last_sync_end_shortstatehash))
+			if !full_state && !joined_since_last_sync =>
+			StateSyncKind::Incremental {
+				last_sync_end_count,
+				last_sync_end_shortstatehash,
+			},
+		| _ => StateSyncKind::Initial,
+	}
+}
+
+/// Compute the number of unread notifications in this room, and in each of its
+/// threads if the client asked for them separately (MSC3773).
 #[tracing::instrument(level = "debug", skip_all)]
 async fn build_notification_counts(
 	services: &Services,
-	SyncContext { syncing_user, last_sync_end_count, .. }: SyncContext<'_>,
+	SyncContext {
+		syncing_user,
+		last_sync_end_count,
+		filter,
+		..
+	}: SyncContext<'_>,
 	room_id: &RoomId,
 	timeline: &TimelinePdus,
-) -> Result<Option<UnreadNotificationsCount>> {
+) -> Result<(
+	Option<UnreadNotificationsCount>,
+	BTreeMap<OwnedEventId, UnreadNotificationsCount>,
+)> {
 	// determine whether to actually update the notification counts
 	let should_send_notification_counts = async {
 		// if we're going to sync some timeline events, the notification count has
@@ -561,32 +612,67 @@ async fn build_notification_counts(
 		false
 	};
 
-	if should_send_notification_counts.await {
-		let (notification_count, highlight_count) = join(
-			services
-				.rooms
-				.user
-				.notification_count(syncing_user, room_id)
-				.map(TryInto::try_into)
-				.unwrap_or(uint!(0)),
+	if !should_send_notification_counts.await {
+		return Ok((None, BTreeMap::new()));
+	}
+
+	let thread_counts: OptionFuture<_> = filter
+		.room
+		.timeline
+		.unread_thread_notifications
+		.then(|| {
 			services
 				.rooms
 				.user
-				.highlight_count(syncing_user, room_id)
-				.map(TryInto::try_into)
-				.unwrap_or(uint!(0)),
-		)
-		.await;
+				.thread_notification_counts(syncing_user, room_id)
+		})
+		.into();
+
+	let (notification_count, highlight_count, thread_counts) = join3(
+		services
+			.rooms
+			.user
+			.notification_count(syncing_user, room_id),
+		services.rooms.user.highlight_count(syncing_user, room_id),
+		thread_counts,
+	)
+	.await;
+
+	// the room's counts only cover the main timeline when threads are counted
+	// separately
+	let thread_counts = thread_counts.unwrap_or_default();
+	let (thread_notifications, thread_highlights) = thread_counts
+		.values()
+		.fold((0_u64, 0_u64), |(n, h), (thread_n, thread_h)| {
+			(n.saturating_add(*thread_n), h.saturating_add(*thread_h))
+		});
+
+	let notification_count =
+		ruma_from_u64(notification_count.saturating_sub(thread_notifications));
+	let highlight_count = ruma_from_u64(highlight_count.saturating_sub(thread_highlights));
+
+	trace!(
+		%notification_count, %highlight_count, threads = thread_counts.len(),
+		"syncing new notification counts"
+	);
 
-		trace!(%notification_count, %highlight_count, "syncing new notification counts");
+	let thread_notification_counts = thread_counts
+		.into_iter()
+		.map(|(thread_root, (notification_count, highlight_count))| {
+			(thread_root, UnreadNotificationsCount {
+				notification_count: Some(ruma_from_u64(notification_count)),
+				highlight_count: Some(ruma_from_u64(highlight_count)),
+			})
+		})
+		.collect();
 
-		Ok(Some(UnreadNotificationsCount {
+	Ok((
+		Some(UnreadNotificationsCount {
 			notification_count: Some(notification_count),
 			highlight_count: Some(highlight_count),
-		}))
-	} else {
-		Ok(None)
-	}
+		}),
+		thread_notification_counts,
+	))
 }
 
 /// Check if the syncing user joined the room since their last incremental sync.
@@ -613,14 +699,7 @@ async fn check_joined_since_last_sync(
 		| None => None,
 	};
 
-	// TODO: If the requesting user got state-reset out of the room, this
-	// will be `true` when it shouldn't be. this function should never be called
-	// in that situation, but it may be if the membership cache didn't get updated.
-	// the root cause of this needs to be addressed
-	let joined_since_last_sync =
-		membership_during_previous_sync.is_none_or(|content: RoomMemberEventContent| {
-			content.membership != MembershipState::Join
-		});
+	let joined_since_last_sync = joined_since(membership_during_previous_sync);
 
 	if joined_since_last_sync {
 		trace!("user joined since last sync");
@@ -629,6 +708,19 @@ async fn check_joined_since_last_sync(
 	Ok(joined_since_last_sync)
 }
 
+/// Whether the syncing user wasn't joined as of their membership event during
+/// the last sync, if any.
+// TODO: If the requesting user got state-reset out of the room, this
+// will be `true` when it shouldn't be. `check_joined_since_last_sync` should
+// never be called in that situation, but it may be if the membership cache
+// didn't get updated. the root cause of this needs to be addressed
+pub(super) fn joined_since(
+	membership_during_previous_sync: Option<RoomMemberEventContent>,
+) -> bool {
+	membership_during_previous_sync
+		.is_none_or(|content| content.membership != MembershipState::Join)
+}
+
 /// Build the `summary` field of the room object, which includes
 /// the number of joined and invited users and the room's heroes.
 #[tracing::instrument(level = "debug", skip_all)]
@@ -1,6 +1,7 @@
 mod joined;
 mod left;
 mod state;
+mod tests;
 
 use std::{
 	cmp::{self},
@@ -44,7 +45,7 @@
 };
 use service::rooms::lazy_loading::{self, MemberSet, Options as _};
 
-use super::{load_timeline, share_encrypted_room};
+use super::{device_list_left_rooms, load_timeline, share_encrypted_room};
 use crate::{
 	Ruma, RumaResponse,
 	client::{
@@ -197,7 +198,12 @@ pub(crate) async fn sync_events_route(
 	// Increment the "device last active" metadata
 	services
 		.users
-		.update_device_last_seen(sender_user, Some(sender_device), client_ip)
+		.update_device_last_seen(
+			sender_user,
+			Some(sender_device),
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	// Setup watchers, so if there's no response, we can wait for them
@@ -409,6 +415,13 @@ pub(crate) async fn build_sync_events(
 	let (joined_rooms, mut device_list_updates) = joined_rooms;
 	device_list_updates.changed.extend(keys_changed);
 
+	// users who only shared rooms with us which we've since left
+	if let Some(since) = last_sync_end_count {
+		device_list_updates.left.extend(
+			device_list_left_rooms(services, syncing_user, since, Some(current_count)).await,
+		);
+	}
+
 	let response = sync_events::v3::Response {
 		account_data: GlobalAccountData { events: account_data },
 		device_lists: device_list_updates.into(),
@@ -0,0 +1,52 @@
+#![cfg(test)]
+
+use ruma::events::room::member::{MembershipState, RoomMemberEventContent};
+
+use super::joined::{StateSyncKind, joined_since, state_sync_kind};
+
+#[test]
+fn incremental_sync_of_joined_room() {
+	let membership = RoomMemberEventContent::new(MembershipState::Join);
+	let joined_since_last_sync = joined_since(Some(membership));
+
+	assert!(!joined_since_last_sync);
+	assert_eq!(
+		state_sync_kind(Some(10), Some(5), false, joined_since_last_sync),
+		StateSyncKind::Incremental {
+			last_sync_end_count: 10,
+			last_sync_end_shortstatehash: 5,
+		}
+	);
+}
+
+#[test]
+fn join_after_other_user_synced() {
+	// another user's sync recorded the room's state at the syncing user's last
+	// token, from before the syncing user joined.
+	let joined_since_last_sync = joined_since(None);
+
+	assert!(joined_since_last_sync);
+	assert_eq!(
+		state_sync_kind(Some(10), Some(5), false, joined_since_last_sync),
+		StateSyncKind::Initial
+	);
+}
+
+#[test]
+fn rejoin_after_other_user_synced() {
+	let membership = RoomMemberEventContent::new(MembershipState::Leave);
+	let joined_since_last_sync = joined_since(Some(membership));
+
+	assert!(joined_since_last_sync);
+	assert_eq!(
+		state_sync_kind(Some(10), Some(5), false, joined_since_last_sync),
+		StateSyncKind::Initial
+	);
+}
+
+#[test]
+fn initial_and_full_state_syncs() {
+	assert_eq!(state_sync_kind(None, None, false, true), StateSyncKind::Initial);
+	assert_eq!(state_sync_kind(Some(10), None, false, false), StateSyncKind::Initial);
+	assert_eq!(state_sync_kind(Some(10), Some(5), true, false), StateSyncKind::Initial);
+}
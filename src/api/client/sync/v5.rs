@@ -30,7 +30,9 @@
 	api::client::sync::sync_events::{self, DeviceLists, UnreadNotificationsCount},
 	directory::RoomTypeFilter,
 	events::{
-		AnyRawAccountDataEvent, AnySyncEphemeralRoomEvent, StateEventType, TimelineEventType,
+		AnyRawAccountDataEvent, AnySyncEphemeralRoomEvent, StateEventType,
+		SyncEphemeralRoomEvent, TimelineEventType,
+		receipt::ReceiptEventContent,
 		room::member::{MembershipState, RoomMemberEventContent},
 		typing::TypingEventContent,
 	},
@@ -38,7 +40,7 @@
 	uint,
 };
 
-use super::share_encrypted_room;
+use super::{device_list_left_rooms, share_encrypted_room};
 use crate::{
 	Ruma,
 	client::{
@@ -71,7 +73,12 @@ pub(crate) async fn sync_events_v5_route(
 
 	services
 		.users
-		.update_device_last_seen(sender_user, Some(sender_device), client_ip)
+		.update_device_last_seen(
+			sender_user,
+			Some(sender_device),
+			client_ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	let mut body = body.body;
@@ -91,7 +98,7 @@ pub(crate) async fn sync_events_v5_route(
 
 	let snake_key = into_snake_key(sender_user, sender_device, conn_id);
 
-	if globalsince != 0 && !services.sync.snake_connection_cached(&snake_key) {
+	if globalsince != 0 && !services.sync.snake_connection_cached(&snake_key).await {
 		return Err!(Request(UnknownPos(
 			"Connection data unknown to server; restarting sync stream."
 		)));
@@ -158,7 +165,7 @@ pub(crate) async fn sync_events_v5_route(
 
 	let to_device = collect_to_device(services, sync_info, next_batch).map(Ok);
 
-	let receipts = collect_receipts(services).map(Ok);
+	let receipts = collect_receipts(services, sync_info).map(Ok);
 
 	let (account_data, e2ee, to_device, receipts) =
 		try_join4(account_data, e2ee, to_device, receipts).await?;
@@ -224,6 +231,8 @@ pub(crate) async fn sync_events_v5_route(
 	let typing = collect_typing_events(services, sender_user, &body, &todo_rooms).await?;
 	response.extensions.typing = typing;
 
+	services.sync.save_snake_connection(&snake_key);
+
 	trace!(
 		rooms = ?response.rooms.len(),
 		account_data = ?response.extensions.account_data.rooms.len(),
@@ -457,48 +466,14 @@ async fn process_rooms<'a, Rooms>(
 			);
 		}
 
-		let last_privateread_update = services
-			.rooms
-			.read_receipt
-			.last_privateread_update(sender_user, room_id)
-			.await;
-
-		let private_read_event: OptionFuture<_> = (last_privateread_update > *roomsince)
-			.then(|| {
-				services
-					.rooms
-					.read_receipt
-					.private_read_get(room_id, sender_user)
-					.ok()
-			})
-			.into();
-
-		let mut receipts: Vec<Raw<AnySyncEphemeralRoomEvent>> = services
-			.rooms
-			.read_receipt
-			.readreceipts_since(room_id, Some(*roomsince))
-			.filter_map(|(read_user, _ts, v)| async move {
-				services
-					.users
-					.user_is_ignored(&read_user, sender_user)
-					.await
-					.or_some(v)
-			})
-			.collect()
-			.await;
-
-		if let Some(private_read_event) = private_read_event.await.flatten() {
-			receipts.push(private_read_event);
-		}
-
-		let receipt_size = receipts.len();
-
-		if receipt_size > 0 {
+		if let Some(receipts) =
+			collect_room_receipts(services, sender_user, room_id, *roomsince).await
+		{
 			response
 				.extensions
 				.receipts
 				.rooms
-				.insert(room_id.clone(), pack_receipts(Box::new(receipts.into_iter())));
+				.insert(room_id.clone(), receipts);
 		}
 
 		if roomsince != &0
@@ -959,6 +934,12 @@ async fn collect_e2ee<'a, Rooms>(
 		}
 	}
 
+	// Users who only shared rooms with us which we've since left
+	if globalsince != 0 {
+		device_list_left
+			.extend(device_list_left_rooms(services, sender_user, globalsince, None).await);
+	}
+
 	Ok(sync_events::v5::response::E2EE {
 		device_unused_fallback_key_types: None,
 
@@ -998,9 +979,80 @@ async fn collect_to_device(
 	})
 }
 
-async fn collect_receipts(_services: &Services) -> sync_events::v5::response::Receipts {
-	sync_events::v5::response::Receipts { rooms: BTreeMap::new() }
-	// TODO: get explicitly requested read receipts
+/// Collects the receipts of the rooms the client explicitly requested them for.
+async fn collect_receipts(
+	services: &Services,
+	(sender_user, _, globalsince, body): SyncInfo<'_>,
+) -> sync_events::v5::response::Receipts {
+	let mut receipts = sync_events::v5::response::Receipts { rooms: BTreeMap::new() };
+
+	if !body.extensions.receipts.enabled.unwrap_or(false) {
+		return receipts;
+	}
+
+	for room_id in body.extensions.receipts.rooms.iter().flatten() {
+		if !services
+			.rooms
+			.state_cache
+			.is_joined(sender_user, room_id)
+			.await
+		{
+			continue;
+		}
+
+		if let Some(room_receipts) =
+			collect_room_receipts(services, sender_user, room_id, globalsince).await
+		{
+			receipts.rooms.insert(room_id.clone(), room_receipts);
+		}
+	}
+
+	receipts
+}
+
+/// Collects the receipts in the room since `since`, with the user's own
+/// private read receipts, leaving out the receipts of ignored users.
+async fn collect_room_receipts(
+	services: &Services,
+	sender_user: &UserId,
+	room_id: &RoomId,
+	since: u64,
+) -> Option<Raw<SyncEphemeralRoomEvent<ReceiptEventContent>>> {
+	let last_privateread_update = services
+		.rooms
+		.read_receipt
+		.last_privateread_update(sender_user, room_id)
+		.await;
+
+	let private_read_event: OptionFuture<_> = (last_privateread_update > since)
+		.then(|| {
+			services
+				.rooms
+				.read_receipt
+				.private_read_get(room_id, sender_user)
+				.ok()
+		})
+		.into();
+
+	let mut receipts: Vec<Raw<AnySyncEphemeralRoomEvent>> = services
+		.rooms
+		.read_receipt
+		.readreceipts_since(room_id, Some(since))
+		.filter_map(|(read_user, _ts, v)| async move {
+			services
+				.users
+				.user_is_ignored(&read_user, sender_user)
+				.await
+				.or_some(v)
+		})
+		.collect()
+		.await;
+
+	if let Some(private_read_event) = private_read_event.await.flatten() {
+		receipts.push(private_read_event);
+	}
+
+	(!receipts.is_empty()).then(|| pack_receipts(receipts.into_iter()))
 }
 
 fn filter_rooms<'a, Rooms>(
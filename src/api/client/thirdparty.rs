@@ -1,18 +1,23 @@
-use std::collections::BTreeMap;
-
-use conduwuit::Result;
-use ruma::api::client::thirdparty::get_protocols;
+use axum::extract::State;
+use conduwuit::{Err, Result};
+use ruma::api::client::thirdparty::{
+	get_location_for_protocol, get_location_for_room_alias, get_protocol, get_protocols,
+	get_user_for_protocol, get_user_for_user_id,
+};
 
 use crate::{Ruma, RumaResponse};
 
 /// # `GET /_matrix/client/r0/thirdparty/protocols`
 ///
-/// TODO: Fetches all metadata about protocols supported by the homeserver.
+/// Fetches all metadata about protocols supported by the homeserver, as
+/// provided by the appservices.
 pub(crate) async fn get_protocols_route(
+	State(services): State<crate::State>,
 	_body: Ruma<get_protocols::v3::Request>,
 ) -> Result<get_protocols::v3::Response> {
-	// TODO
-	Ok(get_protocols::v3::Response { protocols: BTreeMap::new() })
+	let protocols = services.appservice.protocols().await;
+
+	Ok(get_protocols::v3::Response { protocols })
 }
 
 /// # `GET /_matrix/client/unstable/thirdparty/protocols`
@@ -20,7 +25,100 @@ pub(crate) async fn get_protocols_route(
 /// Same as `get_protocols_route`, except for some reason Element Android legacy
 /// calls this
 pub(crate) async fn get_protocols_route_unstable(
+	State(services): State<crate::State>,
 	body: Ruma<get_protocols::v3::Request>,
 ) -> Result<RumaResponse<get_protocols::v3::Response>> {
-	get_protocols_route(body).await.map(RumaResponse)
+	get_protocols_route(State(services), body)
+		.await
+		.map(RumaResponse)
+}
+
+/// # `GET /_matrix/client/r0/thirdparty/protocol/{protocol}`
+///
+/// Fetches the metadata of one protocol supported by the homeserver.
+pub(crate) async fn get_protocol_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_protocol::v3::Request>,
+) -> Result<get_protocol::v3::Response> {
+	let Some(protocol) = services.appservice.protocols().await.remove(&body.protocol) else {
+		return Err!(Request(NotFound("The protocol is unknown.")));
+	};
+
+	Ok(get_protocol::v3::Response::new(protocol))
+}
+
+/// # `GET /_matrix/client/r0/thirdparty/location/{protocol}`
+///
+/// Looks up portal rooms for a protocol by its location fields.
+pub(crate) async fn get_location_for_protocol_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_location_for_protocol::v3::Request>,
+) -> Result<get_location_for_protocol::v3::Response> {
+	let locations = services
+		.appservice
+		.thirdparty_locations(&body.protocol, &body.fields)
+		.await?;
+
+	if locations.is_empty() {
+		return Err!(Request(NotFound("No portal rooms were found.")));
+	}
+
+	Ok(get_location_for_protocol::v3::Response::new(locations))
+}
+
+/// # `GET /_matrix/client/r0/thirdparty/location?alias={alias}`
+///
+/// Looks up the third-party locations bridged to a portal room alias.
+pub(crate) async fn get_location_for_room_alias_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_location_for_room_alias::v3::Request>,
+) -> Result<get_location_for_room_alias::v3::Response> {
+	let locations = services
+		.appservice
+		.thirdparty_locations_by_alias(&body.alias)
+		.await;
+
+	if locations.is_empty() {
+		return Err!(Request(NotFound("The Matrix room alias was not found.")));
+	}
+
+	Ok(get_location_for_room_alias::v3::Response::new(locations))
+}
+
+/// # `GET /_matrix/client/r0/thirdparty/user/{protocol}`
+///
+/// Looks up third-party users for a protocol by their user fields.
+pub(crate) async fn get_user_for_protocol_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_user_for_protocol::v3::Request>,
+) -> Result<get_user_for_protocol::v3::Response> {
+	let users = services
+		.appservice
+		.thirdparty_users(&body.protocol, &body.fields)
+		.await?;
+
+	if users.is_empty() {
+		return Err!(Request(NotFound("The Matrix User ID was not found.")));
+	}
+
+	Ok(get_user_for_protocol::v3::Response::new(users))
+}
+
+/// # `GET /_matrix/client/r0/thirdparty/user?userid={userid}`
+///
+/// Looks up the third-party users bridged to a Matrix user.
+pub(crate) async fn get_user_for_user_id_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_user_for_user_id::v3::Request>,
+) -> Result<get_user_for_user_id::v3::Response> {
+	let users = services
+		.appservice
+		.thirdparty_users_by_user_id(&body.userid)
+		.await;
+
+	if users.is_empty() {
+		return Err!(Request(NotFound("The Matrix User ID was not found.")));
+	}
+
+	Ok(get_user_for_user_id::v3::Response::new(users))
 }
@@ -17,7 +17,12 @@ pub(crate) async fn create_typing_event_route(
 	let sender_user = body.sender_user();
 	services
 		.users
-		.update_device_last_seen(sender_user, body.sender_device.as_deref(), ip)
+		.update_device_last_seen(
+			sender_user,
+			body.sender_device.as_deref(),
+			ip,
+			body.user_agent.as_deref(),
+		)
 		.await;
 
 	if sender_user != body.user_id && body.appservice_info.is_none() {
@@ -20,9 +20,10 @@
 /// Note: Unstable features are used while developing new features. Clients
 /// should avoid using unstable features in their stable releases
 pub(crate) async fn get_supported_versions_route(
+	State(services): State<crate::State>,
 	_body: Ruma<get_supported_versions::Request>,
 ) -> Result<get_supported_versions::Response> {
-	let resp = get_supported_versions::Response {
+	let mut resp = get_supported_versions::Response {
 		versions: vec![
 			"r0.0.1".to_owned(),
 			"r0.1.0".to_owned(),
@@ -59,9 +60,16 @@ pub(crate) async fn get_supported_versions_route(
 			("org.matrix.simplified_msc3575".to_owned(), true), /* Simplified Sliding sync (https://github.com/matrix-org/matrix-spec-proposals/pull/4186) */
 			("uk.timedout.msc4323".to_owned(), true), /* agnostic suspend (https://github.com/matrix-org/matrix-spec-proposals/pull/4323) */
 			("org.matrix.msc4155".to_owned(), true), /* invite filtering (https://github.com/matrix-org/matrix-spec-proposals/pull/4155) */
+			("org.matrix.msc4140".to_owned(), true), /* delayed events (https://github.com/matrix-org/matrix-spec-proposals/pull/4140) */
 		]),
 	};
 
+	if services.rendezvous.enabled() {
+		/* QR code login rendezvous (https://github.com/matrix-org/matrix-spec-proposals/pull/4108) */
+		resp.unstable_features
+			.insert("org.matrix.msc4108".to_owned(), true);
+	}
+
 	Ok(resp)
 }
 
@@ -9,7 +9,7 @@
 use axum::{
 	Router,
 	response::{IntoResponse, Redirect},
-	routing::{any, get, post},
+	routing::{any, get, post, put},
 };
 use conduwuit::{Server, err};
 pub(super) use conduwuit_service::state::State;
@@ -32,6 +32,16 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::get_login_types_route)
 		.ruma_route(&client::login_route)
 		.ruma_route(&client::login_token_route)
+		.route(
+			"/_matrix/client/unstable/org.matrix.msc4108/rendezvous",
+			post(client::create_rendezvous_session_route),
+		)
+		.route(
+			"/_matrix/client/unstable/org.matrix.msc4108/rendezvous/:session_id",
+			get(client::get_rendezvous_session_route)
+				.put(client::update_rendezvous_session_route)
+				.delete(client::delete_rendezvous_session_route),
+		)
 		.ruma_route(&client::whoami_route)
 		.ruma_route(&client::logout_route)
 		.ruma_route(&client::logout_all_route)
@@ -60,6 +70,8 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::set_room_account_data_route)
 		.ruma_route(&client::get_global_account_data_route)
 		.ruma_route(&client::get_room_account_data_route)
+		.ruma_route(&client::delete_global_account_data_route)
+		.ruma_route(&client::delete_room_account_data_route)
 		.ruma_route(&client::set_displayname_route)
 		.ruma_route(&client::get_displayname_route)
 		.ruma_route(&client::set_avatar_url_route)
@@ -113,12 +125,38 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		.ruma_route(&client::search_users_route)
 		.ruma_route(&client::get_member_events_route)
 		.ruma_route(&client::get_protocols_route)
+		.ruma_route(&client::get_protocol_route)
+		.ruma_route(&client::get_location_for_protocol_route)
+		.ruma_route(&client::get_location_for_room_alias_route)
+		.ruma_route(&client::get_user_for_protocol_route)
+		.ruma_route(&client::get_user_for_user_id_route)
 		.route("/_matrix/client/unstable/thirdparty/protocols",
 			get(client::get_protocols_route_unstable))
-		.ruma_route(&client::send_message_event_route)
-		.ruma_route(&client::send_state_event_for_key_route)
+		// The send and state routes dispatch MSC4140 delayed events, which respond with a
+		// different body, so they can't be registered through their Ruma request types
+		.route(
+			"/_matrix/client/r0/rooms/:room_id/send/:event_type/:txn_id",
+			put(client::send_message_event_or_delayed_route),
+		)
+		.route(
+			"/_matrix/client/v3/rooms/:room_id/send/:event_type/:txn_id",
+			put(client::send_message_event_or_delayed_route),
+		)
+		.route(
+			"/_matrix/client/unstable/org.matrix.msc4140/delayed_events",
+			get(client::get_delayed_events_route),
+		)
+		.ruma_route(&client::update_delayed_event_route)
 		.ruma_route(&client::get_state_events_route)
 		.ruma_route(&client::get_state_events_for_key_route)
+		.route(
+			"/_matrix/client/r0/rooms/:room_id/state/:event_type/:state_key",
+			put(client::send_state_event_for_key_or_delayed_route),
+		)
+		.route(
+			"/_matrix/client/v3/rooms/:room_id/state/:event_type/:state_key",
+			put(client::send_state_event_for_key_or_delayed_route),
+		)
 		// Ruma doesn't have support for multiple paths for a single endpoint yet, and these routes
 		// share one Ruma request / response type pair with {get,send}_state_event_for_key_route
 		.route(
@@ -182,6 +220,7 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 		)
 		.ruma_route(&client::get_suspended_status)
 		.ruma_route(&client::put_suspended_status)
+		.ruma_route(&client::get_whois_route)
 		.ruma_route(&client::well_known_support)
 		.ruma_route(&client::well_known_client)
 		.route("/_conduwuit/server_version", get(client::conduwuit_server_version))
@@ -197,6 +236,8 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 				"/_matrix/key/v2/server/:key_id",
 				get(server::get_server_keys_deprecated_route),
 			)
+			.ruma_route(&server::get_remote_server_keys_route)
+			.ruma_route(&server::get_remote_server_keys_batch_route)
 			.ruma_route(&server::get_public_rooms_route)
 			.ruma_route(&server::get_public_rooms_filtered_route)
 			.ruma_route(&server::send_transaction_message_route)
@@ -215,6 +256,8 @@ pub fn build(router: Router<State>, server: &Server) -> Router<State> {
 			.ruma_route(&server::create_join_event_v1_route)
 			.ruma_route(&server::create_join_event_v2_route)
 			.ruma_route(&server::create_invite_route)
+			.ruma_route(&server::third_party_invite_onbind_route)
+			.ruma_route(&server::exchange_third_party_invite_route)
 			.ruma_route(&server::get_devices_route)
 			.ruma_route(&server::get_room_information_route)
 			.ruma_route(&server::get_profile_information_route)
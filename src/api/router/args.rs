@@ -37,6 +37,13 @@ pub(crate) struct Args<T> {
 	/// Parsed JSON content.
 	/// None when body is not a valid string
 	pub(crate) json_body: Option<CanonicalJsonValue>,
+
+	/// MSC4140 delay in milliseconds from the `org.matrix.msc4140.delay` query
+	/// parameter. None when the request is not to be delayed.
+	pub(crate) delay: Option<u64>,
+
+	/// The User-Agent header of the request, if any.
+	pub(crate) user_agent: Option<String>,
 }
 
 impl<T> Args<T>
@@ -116,6 +123,13 @@ async fn from_request(
 			sender_device: auth.sender_device,
 			appservice_info: auth.appservice_info,
 			json_body,
+			delay: request.query.delay,
+			user_agent: request
+				.parts
+				.headers
+				.get(http::header::USER_AGENT)
+				.and_then(|user_agent| user_agent.to_str().ok())
+				.map(ToOwned::to_owned),
 		})
 	}
 }
@@ -23,7 +23,11 @@
 			profile::{get_avatar_url, get_display_name, get_profile, get_profile_key},
 			voip::get_turn_server_info,
 		},
-		federation::{authentication::XMatrix, openid::get_openid_userinfo},
+		federation::{
+			authentication::XMatrix,
+			discovery::{get_remote_server_keys, get_remote_server_keys_batch},
+			openid::get_openid_userinfo,
+		},
 	},
 };
 use service::{
@@ -103,6 +107,12 @@ pub(super) async fn auth(
 					}
 				}
 			},
+			| &get_remote_server_keys::v2::Request::METADATA
+			| &get_remote_server_keys_batch::v2::Request::METADATA => {
+				if !services.server.config.notary_allowed_servers.is_empty() {
+					return auth_notary(services, request, json_body).await;
+				}
+			},
 			| _ => {},
 		}
 	}
@@ -233,10 +243,28 @@ async fn auth_appservice(
 		return Err!(Request(Exclusive("User is not in namespace.")));
 	}
 
+	// MSC3202 device masquerading
+	let sender_device = match request.query.device_id.as_deref() {
+		| None => None,
+		| Some(device_id) => {
+			let device_id: OwnedDeviceId = device_id.into();
+			if services
+				.users
+				.get_device_metadata(&user_id, &device_id)
+				.await
+				.is_err()
+			{
+				return Err!(Request(Forbidden("Device does not exist for this user.")));
+			}
+
+			Some(device_id)
+		},
+	};
+
 	Ok(Auth {
 		origin: None,
 		sender_user: Some(user_id),
-		sender_device: None,
+		sender_device,
 		appservice_info: Some(*info),
 	})
 }
@@ -318,6 +346,30 @@ async fn auth_server(
 	})
 }
 
+/// Key queries are unauthenticated in the spec; when the notary is restricted
+/// to certain servers, they must sign their requests like any other federation
+/// request.
+async fn auth_notary(
+	services: &Services,
+	request: &mut Request,
+	body: Option<&CanonicalJsonValue>,
+) -> Result<Auth> {
+	let auth = auth_server(services, request, body).await?;
+	let allowed = auth.origin.as_ref().is_some_and(|origin| {
+		services
+			.server
+			.config
+			.notary_allowed_servers
+			.contains(origin)
+	});
+
+	if !allowed {
+		return Err!(Request(Forbidden("This server does not serve key queries to you.")));
+	}
+
+	Ok(auth)
+}
+
 fn auth_server_checks(services: &Services, x_matrix: &XMatrix) -> Result<()> {
 	if !services.config.allow_federation {
 		return Err!(Config("allow_federation", "Federation is disabled."));
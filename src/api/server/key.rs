@@ -4,15 +4,30 @@
 };
 
 use axum::{Json, extract::State, response::IntoResponse};
-use conduwuit::{Result, utils::timepoint_from_now};
+use conduwuit::{
+	Err, Result, debug_warn,
+	utils::{IterStream, stream::BroadbandExt, timepoint_from_now},
+};
+use conduwuit_service::Services;
+use futures::StreamExt;
 use ruma::{
-	MilliSecondsSinceUnixEpoch, Signatures,
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, ServerName, Signatures,
 	api::{
 		OutgoingResponse,
-		federation::discovery::{OldVerifyKey, ServerSigningKeys, get_server_keys},
+		federation::discovery::{
+			OldVerifyKey, ServerSigningKeys, get_remote_server_keys,
+			get_remote_server_keys_batch, get_server_keys,
+		},
 	},
 	serde::Raw,
 };
+use serde_json::value::to_raw_value;
+
+use crate::Ruma;
+
+/// Maximum number of servers whose keys are fetched concurrently for a batch
+/// key query.
+const NOTARY_FETCH_CONCURRENCY: usize = 8;
 
 /// # `GET /_matrix/key/v2/server`
 ///
@@ -25,6 +40,90 @@
 pub(crate) async fn get_server_keys_route(
 	State(services): State<crate::State>,
 ) -> Result<impl IntoResponse> {
+	Ok(Json(own_server_keys(&services).await?))
+}
+
+/// # `GET /_matrix/key/v2/query/{serverName}`
+///
+/// Gets the public signing keys of a server, countersigned by this server
+/// acting as a notary.
+///
+/// - Keys of other servers are only served with `notary_server` enabled.
+pub(crate) async fn get_remote_server_keys_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_remote_server_keys::v2::Request>,
+) -> Result<get_remote_server_keys::v2::Response> {
+	let server_keys =
+		query_server_keys(&services, &body.server_name, body.minimum_valid_until_ts)
+			.await
+			.into_iter()
+			.collect();
+
+	Ok(get_remote_server_keys::v2::Response::new(server_keys))
+}
+
+/// # `POST /_matrix/key/v2/query`
+///
+/// Gets the public signing keys of several servers, countersigned by this
+/// server acting as a notary.
+///
+/// - Keys of other servers are only served with `notary_server` enabled.
+pub(crate) async fn get_remote_server_keys_batch_route(
+	State(services): State<crate::State>,
+	body: Ruma<get_remote_server_keys_batch::v2::Request>,
+) -> Result<get_remote_server_keys_batch::v2::Response> {
+	if body.server_keys.len() > services.server.config.notary_batch_size {
+		return Err!(Request(InvalidParam("Too many servers in key query.")));
+	}
+
+	let server_keys = body
+		.server_keys
+		.iter()
+		.stream()
+		.broadn_filter_map(NOTARY_FETCH_CONCURRENCY, |(server_name, criteria)| {
+			let minimum_valid_until_ts = criteria
+				.values()
+				.filter_map(|criteria| criteria.minimum_valid_until_ts)
+				.max()
+				.unwrap_or_else(MilliSecondsSinceUnixEpoch::now);
+
+			query_server_keys(&services, server_name, minimum_valid_until_ts)
+		})
+		.collect()
+		.await;
+
+	Ok(get_remote_server_keys_batch::v2::Response::new(server_keys))
+}
+
+/// Keys of a server for a key query, or None when they cannot be served.
+async fn query_server_keys(
+	services: &Services,
+	server_name: &ServerName,
+	minimum_valid_until_ts: MilliSecondsSinceUnixEpoch,
+) -> Option<Raw<ServerSigningKeys>> {
+	if services.globals.server_is_ours(server_name) {
+		return own_server_keys(services)
+			.await
+			.and_then(|keys| Ok(Raw::from_json(to_raw_value(&keys)?)))
+			.ok();
+	}
+
+	if !services.server.config.notary_server
+		|| services.moderation.is_remote_server_forbidden(server_name)
+	{
+		return None;
+	}
+
+	services
+		.server_keys
+		.notary_keys(server_name, minimum_valid_until_ts)
+		.await
+		.inspect_err(|e| debug_warn!(%server_name, "Failed to serve keys: {e}"))
+		.ok()
+}
+
+/// Our own signing keys, signed.
+async fn own_server_keys(services: &Services) -> Result<CanonicalJsonObject> {
 	let server_name = services.globals.server_name();
 	let active_key_id = services.server_keys.active_key_id();
 	let mut all_keys = services.server_keys.verify_keys_for(server_name).await;
@@ -47,14 +146,14 @@ pub(crate) async fn get_server_keys_route(
 	};
 
 	let server_key = Raw::new(&server_key)?;
-	let mut response = get_server_keys::v2::Response::new(server_key)
+	let mut response: CanonicalJsonObject = get_server_keys::v2::Response::new(server_key)
 		.try_into_http_response::<Vec<u8>>()
 		.map(|mut response| take(response.body_mut()))
 		.and_then(|body| serde_json::from_slice(&body).map_err(Into::into))?;
 
 	services.server_keys.sign_json(&mut response)?;
 
-	Ok(Json(response))
+	Ok(response)
 }
 
 fn valid_until_ts() -> MilliSecondsSinceUnixEpoch {
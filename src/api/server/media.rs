@@ -77,7 +77,10 @@ pub(crate) async fn get_content_thumbnail_route(
 		content,
 		content_type,
 		content_disposition,
-	}) = services.media.get_thumbnail(&mxc, &dim).await?
+	}) = services
+		.media
+		.get_thumbnail(&mxc, &dim, body.animated.unwrap_or(false), None)
+		.await?
 	else {
 		return Err!(Request(NotFound("Media not found.")));
 	};
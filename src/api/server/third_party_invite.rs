@@ -0,0 +1,118 @@
+use axum::extract::State;
+use conduwuit::{Err, Result, debug_warn, err};
+use futures::FutureExt;
+use ruma::{
+	api::federation::third_party::{bind_callback, exchange_invite},
+	events::{StateEventType, room::member::ThirdPartyInvite},
+};
+
+use crate::{Ruma, client::exchange_third_party_invite_helper};
+
+/// # `PUT /_matrix/federation/v1/3pid/onbind`
+///
+/// Called by an identity server once a third-party identifier with pending
+/// invites has been bound to a local user. Each invite is exchanged for a
+/// regular invite with the server of the user who sent it.
+pub(crate) async fn third_party_invite_onbind_route(
+	State(services): State<crate::State>,
+	body: Ruma<bind_callback::v1::Request>,
+) -> Result<bind_callback::v1::Response> {
+	if !services.globals.user_is_local(&body.mxid) {
+		return Err!(Request(InvalidParam("User does not belong to this server.")));
+	}
+
+	for invite in &body.invites {
+		if invite.mxid != body.mxid {
+			debug_warn!(mxid = %invite.mxid, "Ignoring third-party invite for another user");
+			continue;
+		}
+
+		let third_party_invite = ThirdPartyInvite {
+			display_name: invite.address.clone(),
+			signed: invite.signed.clone(),
+		};
+
+		let result = if services.globals.user_is_local(&invite.sender) {
+			exchange_third_party_invite_helper(
+				&services,
+				&invite.sender,
+				&invite.mxid,
+				&invite.room_id,
+				third_party_invite,
+			)
+			.boxed()
+			.await
+		} else {
+			let request = exchange_invite::v1::Request {
+				room_id: invite.room_id.clone(),
+				kind: StateEventType::RoomMember,
+				sender: invite.sender.clone(),
+				state_key: invite.mxid.clone(),
+				content: third_party_invite,
+			};
+
+			services
+				.sending
+				.send_federation_request(invite.sender.server_name(), request)
+				.await
+				.map(|_| ())
+		};
+
+		if let Err(e) = result {
+			debug_warn!(
+				room_id = %invite.room_id,
+				sender = %invite.sender,
+				"Failed to exchange third-party invite: {e}"
+			);
+		}
+	}
+
+	Ok(bind_callback::v1::Response {})
+}
+
+/// # `PUT /_matrix/federation/v1/exchange_third_party_invite/{roomId}`
+///
+/// Exchanges a third-party invite sent by a local user, and signed by the
+/// identity server on behalf of the now-bound invitee, for an invite of the
+/// invitee.
+pub(crate) async fn exchange_third_party_invite_route(
+	State(services): State<crate::State>,
+	body: Ruma<exchange_invite::v1::Request>,
+) -> Result<exchange_invite::v1::Response> {
+	if body.kind != StateEventType::RoomMember {
+		return Err!(Request(InvalidParam("Third-party invites must be m.room.member events.")));
+	}
+
+	if body.state_key.server_name() != body.origin() {
+		return Err!(Request(Forbidden(
+			"Not allowed to exchange invites on behalf of another server."
+		)));
+	}
+
+	if !services.globals.user_is_local(&body.sender) {
+		return Err!(Request(InvalidParam("Invite sender does not belong to this server.")));
+	}
+
+	if !services.rooms.metadata.exists(&body.room_id).await {
+		return Err!(Request(NotFound("Room is unknown to this server.")));
+	}
+
+	services
+		.rooms
+		.event_handler
+		.acl_check(body.origin(), &body.room_id)
+		.await?;
+
+	exchange_third_party_invite_helper(
+		&services,
+		&body.sender,
+		&body.state_key,
+		&body.room_id,
+		body.content.clone(),
+	)
+	.boxed()
+	.await
+	.map_err(|e| err!(Request(Forbidden("Failed to exchange third-party invite: {e}"))))?;
+
+	Ok(exchange_invite::v1::Response {})
+}
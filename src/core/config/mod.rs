@@ -502,6 +502,21 @@ pub struct Config {
 	#[serde(default = "default_appservice_idle_timeout")]
 	pub appservice_idle_timeout: u64,
 
+	/// Timeout for third-party protocol lookups proxied to each appservice
+	/// (seconds). An appservice which does not answer in time is left out of
+	/// the results.
+	///
+	/// default: 5
+	#[serde(default = "default_appservice_thirdparty_timeout")]
+	pub appservice_thirdparty_timeout: u64,
+
+	/// How long the third-party protocol metadata gathered from appservices,
+	/// and the results of location and user lookups, are cached (seconds).
+	///
+	/// default: 300
+	#[serde(default = "default_appservice_thirdparty_cache_ttl")]
+	pub appservice_thirdparty_cache_ttl: u64,
+
 	/// Notification gateway pusher request connection timeout (seconds).
 	///
 	/// default: 15
@@ -805,8 +820,8 @@ pub struct Config {
 	/// Servers listed here will be used to gather public keys of other servers
 	/// (notary trusted key servers).
 	///
-	/// Currently, continuwuity doesn't support inbound batched key requests, so
-	/// this list should only contain other Synapse servers.
+	/// These can be Synapse servers or other continuwuity servers with
+	/// notary_server enabled.
 	///
 	/// example: ["matrix.org", "tchncs.de"]
 	///
@@ -850,6 +865,30 @@ pub struct Config {
 	#[serde(default = "default_trusted_server_batch_size")]
 	pub trusted_server_batch_size: usize,
 
+	/// Whether to act as a notary key server, serving the signing keys of
+	/// other servers at `/_matrix/key/v2/query` countersigned with this
+	/// server's key. Other servers can then list this server in their
+	/// trusted_servers. This server's own keys are always served.
+	#[serde(default)]
+	pub notary_server: bool,
+
+	/// Servers allowed to query this server's keys at `/_matrix/key/v2/query`.
+	/// When set, key queries must be signed by one of these servers like any
+	/// other federation request. When empty, anyone may query.
+	///
+	/// example: ["example.com", "example.org"]
+	///
+	/// default: []
+	#[serde(default)]
+	pub notary_allowed_servers: Vec<OwnedServerName>,
+
+	/// Maximum number of servers whose keys may be requested at once from
+	/// `/_matrix/key/v2/query`.
+	///
+	/// default: 16
+	#[serde(default = "default_notary_batch_size")]
+	pub notary_batch_size: usize,
+
 	/// Max log level for continuwuity. Allows debug, info, warn, or error.
 	///
 	/// See also:
@@ -935,6 +974,56 @@ pub struct Config {
 	#[serde(default = "default_login_token_ttl")]
 	pub login_token_ttl: u64,
 
+	/// Maximum number of concurrent MSC4108 rendezvous sessions, used for
+	/// signing in a new device by scanning a QR code shown by an existing one.
+	/// Sessions are only kept in memory. Requires login_via_existing_session
+	/// since the new device is ultimately handed a login token.
+	///
+	/// Set this to 0 to disable the rendezvous API.
+	///
+	/// default: 100
+	#[serde(default = "default_rendezvous_max_sessions")]
+	pub rendezvous_max_sessions: usize,
+
+	/// Maximum number of concurrent MSC4108 rendezvous sessions created from
+	/// a single IP address, so that unauthenticated clients cannot use up
+	/// rendezvous_max_sessions.
+	///
+	/// default: 3
+	#[serde(default = "default_rendezvous_max_sessions_per_ip")]
+	pub rendezvous_max_sessions_per_ip: usize,
+
+	/// How long an MSC4108 rendezvous session lives after it was created, in
+	/// seconds.
+	///
+	/// default: 120
+	#[serde(default = "default_rendezvous_session_ttl")]
+	pub rendezvous_session_ttl: u64,
+
+	/// Maximum size in bytes of a single MSC4108 rendezvous payload.
+	///
+	/// default: 4096
+	#[serde(default = "default_rendezvous_max_payload_size")]
+	pub rendezvous_max_payload_size: usize,
+
+	/// How long a sliding sync connection is kept after it was last used, in
+	/// seconds. Connections are persisted in the database, so clients can
+	/// resume them after a restart without starting a new initial sync.
+	///
+	/// default: 604800
+	#[serde(default = "default_sliding_sync_connection_ttl")]
+	pub sliding_sync_connection_ttl: u64,
+
+	/// How long the connection history of local users is kept, in seconds.
+	/// The history records the IP addresses, user agents and devices users
+	/// connect from, for the admin whois endpoint and `!admin user whois`.
+	///
+	/// Set this to 0 to disable recording connection history.
+	///
+	/// default: 2419200
+	#[serde(default = "default_connection_history_retention")]
+	pub connection_history_retention: u64,
+
 	/// Static TURN username to provide the client if not using a shared secret
 	/// ("turn_secret"), It is recommended to use a shared secret over static
 	/// credentials.
@@ -1404,6 +1493,24 @@ pub struct Config {
 	#[serde(default = "default_typing_client_timeout_max_s")]
 	pub typing_client_timeout_max_s: u64,
 
+	/// Maximum delay in milliseconds that clients may request for a delayed
+	/// event (MSC4140). Delayed events allow clients to schedule a message or
+	/// state event to be sent later, which Element Call uses to clean up call
+	/// membership when a client disappears.
+	///
+	/// Set this to 0 to disable delayed events.
+	///
+	/// default: 86400000
+	#[serde(default = "default_max_delayed_event_delay")]
+	pub max_delayed_event_delay: u64,
+
+	/// Maximum number of pending delayed events a single user may have
+	/// scheduled at once.
+	///
+	/// default: 100
+	#[serde(default = "default_max_delayed_events_per_user")]
+	pub max_delayed_events_per_user: usize,
+
 	/// Set this to true for continuwuity to compress HTTP response bodies using
 	/// zstd. This option does nothing if continuwuity was not built with
 	/// `zstd_compression` feature. Please be aware that enabling HTTP
@@ -1504,6 +1611,36 @@ pub struct Config {
 	#[serde(default)]
 	pub prune_missing_media: bool,
 
+	/// Strip metadata such as EXIF camera details and GPS location from JPEG,
+	/// PNG and WebP images uploaded by local users. The image data itself is
+	/// not re-encoded; only the orientation is kept so images display upright.
+	///
+	/// Set this to false to store uploads byte-for-byte as sent by clients.
+	#[serde(default = "true_fn")]
+	pub media_strip_metadata: bool,
+
+	/// Evict cached remote media which has not been downloaded by anyone for
+	/// this many seconds. Media is checked hourly; preview what would be
+	/// evicted with `!admin media evict-preview`. Media cached before access
+	/// tracking was introduced counts as last accessed when its file was
+	/// created.
+	///
+	/// Set this to 0 to never evict media.
+	///
+	/// default: 0
+	#[serde(default)]
+	pub media_evict_unaccessed_after: u64,
+
+	/// Evict local media along with remote media under
+	/// `media_evict_unaccessed_after`. Local media cannot be fetched again
+	/// once evicted.
+	#[serde(default)]
+	pub media_evict_local_media: bool,
+
+	/// Never evict media used as the avatar of a local user.
+	#[serde(default = "true_fn")]
+	pub media_evict_keep_local_avatars: bool,
+
 	/// List of forbidden server names via regex patterns that we will block
 	/// incoming AND outgoing federation with, and block client room joins /
 	/// remote user invites.
@@ -1848,6 +1985,26 @@ pub struct Config {
 	#[serde(default = "true_fn")]
 	pub admins_from_room: bool,
 
+	/// Delegated admin roles which can be granted to users with `!admin users
+	/// grant-role`, instead of full admin privileges.
+	///
+	/// Each role maps to the admin commands its holders may run, given as
+	/// command paths. A path allows every command beneath it, e.g. "media"
+	/// allows all media commands and "users suspend" only that command. "*"
+	/// allows all commands. Role holders are joined to the admin room but
+	/// are not otherwise treated as server admins, and can never force-join
+	/// users to the admin room or grant roles.
+	///
+	/// The built-in roles are "moderator" (suspending, locking and logging out
+	/// users, redacting their events, room moderation and deleting media),
+	/// "support" (listing users and rooms, resetting passwords and toggling
+	/// logins) and "media-janitor" (all media commands). Configuring this
+	/// option replaces them.
+	///
+	/// example: { helpdesk = ["users reset-password", "rooms info"] }
+	#[serde(default = "default_admin_roles")]
+	pub admin_roles: BTreeMap<String, Vec<String>>,
+
 	/// Sentry.io crash/panic reporting, performance monitoring/metrics, etc.
 	/// This is NOT enabled by default.
 	#[serde(default)]
@@ -2499,6 +2656,10 @@ fn default_appservice_timeout() -> u64 { 35 }
 
 fn default_appservice_idle_timeout() -> u64 { 300 }
 
+fn default_appservice_thirdparty_timeout() -> u64 { 5 }
+
+fn default_appservice_thirdparty_cache_ttl() -> u64 { 300 }
+
 fn default_pusher_conn_timeout() -> u64 { 15 }
 
 fn default_pusher_timeout() -> u64 { 60 }
@@ -2547,6 +2708,18 @@ fn default_openid_token_ttl() -> u64 { 60 * 60 }
 
 fn default_login_token_ttl() -> u64 { 2 * 60 * 1000 }
 
+fn default_rendezvous_max_sessions() -> usize { 100 }
+
+fn default_rendezvous_max_sessions_per_ip() -> usize { 3 }
+
+fn default_rendezvous_session_ttl() -> u64 { 120 }
+
+fn default_rendezvous_max_payload_size() -> usize { 4096 }
+
+fn default_sliding_sync_connection_ttl() -> u64 { 60 * 60 * 24 * 7 }
+
+fn default_connection_history_retention() -> u64 { 60 * 60 * 24 * 28 }
+
 fn default_turn_ttl() -> u64 { 60 * 60 * 24 }
 
 fn default_presence_idle_timeout_s() -> u64 { 5 * 60 }
@@ -2559,6 +2732,10 @@ fn default_typing_client_timeout_min_s() -> u64 { 15 }
 
 fn default_typing_client_timeout_max_s() -> u64 { 45 }
 
+fn default_max_delayed_event_delay() -> u64 { 24 * 60 * 60 * 1000 }
+
+fn default_max_delayed_events_per_user() -> usize { 100 }
+
 fn default_rocksdb_recovery_mode() -> u8 { 1 }
 
 fn default_rocksdb_log_level() -> String { "error".to_owned() }
@@ -2651,6 +2828,42 @@ fn default_admin_log_capture() -> String {
 
 fn default_admin_room_tag() -> String { "m.server_notice".to_owned() }
 
+fn default_admin_roles() -> BTreeMap<String, Vec<String>> {
+	let role = |name: &str, commands: &[&str]| {
+		(name.to_owned(), commands.iter().map(ToString::to_string).collect())
+	};
+
+	BTreeMap::from([
+		role("moderator", &[
+			"users suspend",
+			"users unsuspend",
+			"users lock",
+			"users unlock",
+			"users logout",
+			"users list-joined-rooms",
+			"users redact-event",
+			"rooms list-rooms",
+			"rooms info",
+			"rooms exists",
+			"rooms moderation",
+			"media delete",
+			"media delete-list",
+			"media get-file-info",
+		]),
+		role("support", &[
+			"users list-users",
+			"users list-joined-rooms",
+			"users reset-password",
+			"users enable-login",
+			"users disable-login",
+			"rooms list-rooms",
+			"rooms info",
+			"rooms exists",
+		]),
+		role("media-janitor", &["media"]),
+	])
+}
+
 #[allow(clippy::as_conversions, clippy::cast_precision_loss)]
 fn parallelism_scaled_f64(val: f64) -> f64 { val * (sys::available_parallelism() as f64) }
 
@@ -2663,6 +2876,8 @@ fn parallelism_scaled(val: usize) -> usize { val.saturating_mul(sys::available_p
 
 fn default_trusted_server_batch_size() -> usize { 256 }
 
+fn default_notary_batch_size() -> usize { 16 }
+
 fn default_db_pool_workers() -> usize {
 	sys::available_parallelism()
 		.saturating_mul(4)
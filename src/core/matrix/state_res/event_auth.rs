@@ -1,11 +1,11 @@
-use std::{borrow::Borrow, collections::BTreeSet};
+use std::{borrow::Borrow, collections::BTreeSet, iter::once};
 
 use futures::{
 	Future,
 	future::{OptionFuture, join, join3},
 };
 use ruma::{
-	Int, OwnedUserId, RoomVersionId, UserId,
+	CanonicalJsonObject, CanonicalJsonValue, Int, OwnedUserId, RoomVersionId, UserId,
 	events::room::{
 		create::RoomCreateEventContent,
 		join_rules::{JoinRule, RoomJoinRulesEventContent},
@@ -15,6 +15,7 @@
 	},
 	int,
 	serde::{Base64, Raw},
+	signatures::{PublicKeyMap, PublicKeySet},
 };
 use serde::{
 	Deserialize,
@@ -30,7 +31,7 @@
 	},
 	room_version::RoomVersion,
 };
-use crate::{debug, error, trace, warn};
+use crate::{debug, error, trace, utils::to_canonical_object, warn};
 
 // FIXME: field extracting could be bundled for `content`
 #[derive(Deserialize)]
@@ -41,6 +42,7 @@ struct GetMembership {
 #[derive(Deserialize, Debug)]
 struct RoomMemberContentFields {
 	membership: Option<Raw<MembershipState>>,
+	third_party_invite: Option<Raw<ThirdPartyInvite>>,
 	join_authorised_via_users_server: Option<Raw<OwnedUserId>>,
 }
 
@@ -401,8 +403,30 @@ pub async fn auth_check<E, F, Fut>(
 
 		let join_rules_event = fetch_state(&StateEventType::RoomJoinRules, "");
 
-		let (join_rules_event, target_user_member_event, user_for_join_auth_event) =
-			join3(join_rules_event, target_user_member_event, user_for_join_auth_event).await;
+		// The m.room.third_party_invite event named by the invite's token, unless
+		// the caller already supplied it.
+		let third_party_invite_event: OptionFuture<_> = current_third_party_invite
+			.is_none()
+			.then_some(content.third_party_invite.as_ref())
+			.flatten()
+			.and_then(|invite| invite.deserialize().ok())
+			.map(|invite| {
+				fetch_state(&StateEventType::RoomThirdPartyInvite, invite.signed.token.as_str())
+			})
+			.into();
+
+		let (
+			(join_rules_event, target_user_member_event, user_for_join_auth_event),
+			third_party_invite_event,
+		) = join(
+			join3(join_rules_event, target_user_member_event, user_for_join_auth_event),
+			third_party_invite_event,
+		)
+		.await;
+
+		let third_party_invite_event = third_party_invite_event.flatten();
+		let current_third_party_invite =
+			current_third_party_invite.or(third_party_invite_event.as_ref());
 
 		let user_for_join_auth_membership = user_for_join_auth_event
 			.and_then(|mem| from_json_str::<GetMembership>(mem?.content().get()).ok())
@@ -1539,48 +1563,213 @@ fn verify_third_party_invite(
 		};
 
 	#[allow(clippy::manual_let_else)]
-	let decoded_invite_token = match Base64::parse(&tp_id.signed.token) {
-		| Ok(tok) => tok,
-		// FIXME: Log a warning?
+	let signed = match to_canonical_object(&tp_id.signed) {
+		| Ok(signed) => signed,
 		| Err(_) => return false,
 	};
 
-	// A list of public keys in the public_keys field
-	for key in tpid_ev.public_keys.unwrap_or_default() {
-		if key.public_key == decoded_invite_token {
-			return true;
-		}
-	}
+	// A list of public keys in the public_keys field, or a single public key in
+	// the public_key field
+	tpid_ev
+		.public_keys
+		.unwrap_or_default()
+		.into_iter()
+		.map(|key| key.public_key)
+		.chain(once(tpid_ev.public_key))
+		.any(|public_key| is_signed_with(&signed, &public_key))
+}
 
-	// A single public key in the public_key field
-	tpid_ev.public_key == decoded_invite_token
+/// Whether any one of the signatures on `signed` was made with `public_key`.
+fn is_signed_with(signed: &CanonicalJsonObject, public_key: &Base64) -> bool {
+	let Some(CanonicalJsonValue::Object(signatures)) = signed.get("signatures") else {
+		return false;
+	};
+
+	signatures.iter().any(|(entity, entity_signatures)| {
+		let CanonicalJsonValue::Object(entity_signatures) = entity_signatures else {
+			return false;
+		};
+
+		entity_signatures.iter().any(|(key_id, signature)| {
+			let signature: CanonicalJsonObject = [(key_id.clone(), signature.clone())].into();
+			let signatures: CanonicalJsonObject =
+				[(entity.clone(), CanonicalJsonValue::Object(signature))].into();
+
+			let mut object = signed.clone();
+			object.insert("signatures".into(), CanonicalJsonValue::Object(signatures));
+
+			let keys: PublicKeySet = [(key_id.clone(), public_key.clone())].into();
+			let keys: PublicKeyMap = [(entity.clone(), keys)].into();
+			ruma::signatures::verify_json(&keys, object).is_ok()
+		})
+	})
 }
 
 #[cfg(test)]
 mod tests {
-	use ruma::events::{
-		StateEventType, TimelineEventType,
-		room::{
-			join_rules::{
-				AllowRule, JoinRule, Restricted, RoomJoinRulesEventContent, RoomMembership,
+	use futures::future::ready;
+	use ruma::{
+		CanonicalJsonObject, UserId,
+		events::{
+			StateEventType, TimelineEventType,
+			room::{
+				join_rules::{
+					AllowRule, JoinRule, Restricted, RoomJoinRulesEventContent, RoomMembership,
+				},
+				member::{MembershipState, RoomMemberEventContent, ThirdPartyInvite},
 			},
-			member::{MembershipState, RoomMemberEventContent},
 		},
+		serde::Base64,
+		signatures::{Ed25519KeyPair, sign_json},
 	};
-	use serde_json::value::to_raw_value as to_raw_json_value;
+	use serde_json::{json, value::to_raw_value as to_raw_json_value};
 
 	use crate::{
 		matrix::{Event, EventTypeExt, Pdu as PduEvent},
 		state_res::{
 			RoomVersion, StateMap,
-			event_auth::valid_membership_change,
+			event_auth::{auth_check, valid_membership_change, verify_third_party_invite},
 			test_utils::{
-				INITIAL_EVENTS, INITIAL_EVENTS_CREATE_ROOM, alice, charlie, ella, event_id,
+				INITIAL_EVENTS, INITIAL_EVENTS_CREATE_ROOM, alice, bob, charlie, ella, event_id,
 				member_content_ban, member_content_join, room_id, to_pdu_event,
 			},
 		},
 	};
 
+	fn identity_server_keypair() -> Ed25519KeyPair {
+		let der = Ed25519KeyPair::generate().unwrap();
+		Ed25519KeyPair::from_der(&der, "0".to_owned()).unwrap()
+	}
+
+	/// The `m.room.third_party_invite` event alice sent for an invite stored
+	/// with the identity server holding `keypair`.
+	fn third_party_invite_event(token: &str, keypair: &Ed25519KeyPair) -> PduEvent {
+		let content = json!({
+			"display_name": "e...@f...",
+			"key_validity_url": "https://id.foo/_matrix/identity/v2/pubkey/isvalid",
+			"public_key": Base64::new(keypair.public_key().to_vec()).encode(),
+		});
+
+		to_pdu_event(
+			"TPI",
+			alice(),
+			TimelineEventType::RoomThirdPartyInvite,
+			Some(token),
+			to_raw_json_value(&content).unwrap(),
+			&["CREATE", "IMA", "IPOWER"],
+			&["IMC"],
+		)
+	}
+
+	/// The invite of `mxid` exchanged for the third-party invite `token`,
+	/// signed by the identity server holding `signer`.
+	fn third_party_invite(
+		mxid: &UserId,
+		token: &str,
+		signer: &Ed25519KeyPair,
+	) -> ThirdPartyInvite {
+		let mut signed: CanonicalJsonObject =
+			serde_json::from_value(json!({ "mxid": mxid, "token": token })).unwrap();
+
+		sign_json("id.foo", signer, &mut signed).unwrap();
+		serde_json::from_value(json!({ "display_name": "e...@f...", "signed": signed })).unwrap()
+	}
+
+	fn third_party_invite_member(invite: &ThirdPartyInvite) -> PduEvent {
+		let content = json!({ "membership": "invite", "third_party_invite": invite });
+
+		to_pdu_event(
+			"HELLO",
+			alice(),
+			TimelineEventType::RoomMember,
+			Some(invite.signed.mxid.as_str()),
+			to_raw_json_value(&content).unwrap(),
+			&["CREATE", "IMA", "IPOWER", "TPI"],
+			&["TPI"],
+		)
+	}
+
+	/// Runs the auth rules on `invite` against the initial room state plus
+	/// `third_party_invite_event`.
+	async fn third_party_invite_allowed(
+		invite: &PduEvent,
+		third_party_invite_event: PduEvent,
+	) -> bool {
+		let mut events = INITIAL_EVENTS();
+		events.insert(third_party_invite_event.event_id().to_owned(), third_party_invite_event);
+
+		let auth_events = events
+			.values()
+			.map(|ev| (ev.event_type().with_state_key(ev.state_key().unwrap()), ev.clone()))
+			.collect::<StateMap<_>>();
+
+		let fetch_state = |ty: &StateEventType, key: &str| {
+			ready(auth_events.get(&(ty.clone(), key.into())).cloned())
+		};
+
+		let create_event = events[&event_id("CREATE")].clone();
+		auth_check(&RoomVersion::V6, invite, None, fetch_state, &create_event)
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn third_party_invite_valid() {
+		let keypair = identity_server_keypair();
+		let invite = third_party_invite(ella(), "token", &keypair);
+
+		assert!(
+			third_party_invite_allowed(
+				&third_party_invite_member(&invite),
+				third_party_invite_event("token", &keypair),
+			)
+			.await
+		);
+	}
+
+	#[tokio::test]
+	async fn third_party_invite_bad_signature() {
+		let keypair = identity_server_keypair();
+		let invite = third_party_invite(ella(), "token", &identity_server_keypair());
+
+		assert!(
+			!third_party_invite_allowed(
+				&third_party_invite_member(&invite),
+				third_party_invite_event("token", &keypair),
+			)
+			.await
+		);
+	}
+
+	#[tokio::test]
+	async fn third_party_invite_mismatched_token() {
+		let keypair = identity_server_keypair();
+		let invite = third_party_invite(ella(), "other", &keypair);
+
+		assert!(
+			!third_party_invite_allowed(
+				&third_party_invite_member(&invite),
+				third_party_invite_event("token", &keypair),
+			)
+			.await
+		);
+
+		// even when the caller supplies the m.room.third_party_invite event
+		let event = third_party_invite_event("token", &keypair);
+		assert!(!verify_third_party_invite(Some(ella()), alice(), &invite, Some(&event)));
+	}
+
+	#[test]
+	fn third_party_invite_mismatched_target_or_sender() {
+		let keypair = identity_server_keypair();
+		let invite = third_party_invite(ella(), "token", &keypair);
+		let event = third_party_invite_event("token", &keypair);
+
+		assert!(verify_third_party_invite(Some(ella()), alice(), &invite, Some(&event)));
+		assert!(!verify_third_party_invite(Some(charlie()), alice(), &invite, Some(&event)));
+		assert!(!verify_third_party_invite(Some(ella()), bob(), &invite, Some(&event)));
+	}
+
 	#[test]
 	fn test_ban_pass() {
 		let _ = tracing::subscriber::set_default(
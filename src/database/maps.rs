@@ -33,6 +33,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "aliasid_alias",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "appserviceid_devicechange",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "auditid_entry",
+		..descriptor::SEQUENTIAL_SMALL
+	},
 	Descriptor {
 		name: "backupid_algorithm",
 		..descriptor::RANDOM_SMALL
@@ -88,6 +96,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "id_appserviceregistrations",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "ipuserid_lastseen",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "keychangeid_userid",
 		..descriptor::RANDOM
@@ -100,6 +112,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "lazyloadedids",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "mediaid_access",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "mediaid_file",
 		..descriptor::RANDOM_SMALL
@@ -121,6 +137,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		index_size: 512,
 		..descriptor::SEQUENTIAL
 	},
+	Descriptor {
+		name: "publicroomid_chunk",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "publicroomids",
 		..descriptor::RANDOM_SMALL
@@ -175,6 +195,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomserverids",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomstatekey_userdelayid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomsynctoken_shortstatehash",
 		file_shape: 3,
@@ -188,6 +212,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomuserdataid_accountdata",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomuserdataid_deletedat",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomuserid_invitecount",
 		val_size_hint: Some(8),
@@ -215,6 +243,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "roomuserid_privateread",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "roomuserthreadid_privateread",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "roomuserthreadid_readreceiptid",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "roomuseroncejoinedids",
 		..descriptor::RANDOM
@@ -247,6 +283,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "servername_override",
 		..descriptor::RANDOM_SMALL_CACHE
 	},
+	Descriptor {
+		name: "servername_rule",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "servername_signedkeys",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "servernameevent_data",
 		cache_disp: CacheDisp::Unique,
@@ -334,6 +378,14 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "url_previews",
 		..descriptor::RANDOM
 	},
+	Descriptor {
+		name: "userdelayid_delayedevent",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userdeviceconnid_snakesync",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdeviceid_metadata",
 		..descriptor::RANDOM_SMALL
@@ -342,6 +394,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userdeviceid_token",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userdeviceip_connection",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userdevicesessionid_uiaainfo",
 		..descriptor::RANDOM_SMALL
@@ -354,6 +410,10 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userfilterid_filter",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userid_adminrole",
+		..descriptor::RANDOM_SMALL
+	},
 	Descriptor {
 		name: "userid_avatarurl",
 		..descriptor::RANDOM_SMALL
@@ -450,4 +510,12 @@ pub(super) fn open_list(db: &Arc<Engine>, maps: &[Descriptor]) -> Result<Maps> {
 		name: "userroomid_invitesender",
 		..descriptor::RANDOM_SMALL
 	},
+	Descriptor {
+		name: "userroomthreadid_highlightcount",
+		..descriptor::RANDOM_SMALL
+	},
+	Descriptor {
+		name: "userroomthreadid_notificationcount",
+		..descriptor::RANDOM_SMALL
+	},
 ];
@@ -151,18 +151,24 @@ fn cors_layer(_server: &Server) -> CorsLayer {
 		Method::OPTIONS,
 	];
 
-	let headers: [HeaderName; 5] = [
+	let headers: [HeaderName; 7] = [
 		header::ORIGIN,
 		HeaderName::from_lowercase(b"x-requested-with").unwrap(),
 		header::CONTENT_TYPE,
 		header::ACCEPT,
 		header::AUTHORIZATION,
+		header::IF_MATCH,
+		header::IF_NONE_MATCH,
 	];
 
+	// MSC4108 rendezvous sessions are versioned through these
+	let exposed_headers: [HeaderName; 3] = [header::ETAG, header::EXPIRES, header::LAST_MODIFIED];
+
 	CorsLayer::new()
 		.allow_origin(cors::Any)
 		.allow_methods(METHODS)
 		.allow_headers(headers)
+		.expose_headers(exposed_headers)
 		.max_age(Duration::from_secs(86400))
 }
 
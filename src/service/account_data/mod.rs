@@ -1,10 +1,12 @@
-use std::sync::Arc;
+mod tests;
+
+use std::{sync::Arc, time::Duration};
 
 use conduwuit::{
 	Err, Result, err, implement,
-	utils::{ReadyExt, result::LogErr, stream::TryIgnore},
+	utils::{ReadyExt, result::LogErr, stream::TryIgnore, time::now_millis},
 };
-use database::{Deserialized, Handle, Ignore, Json, Map};
+use database::{Deserialized, Handle, Ignore, Interfix, Json, Map};
 use futures::{Stream, StreamExt, TryFutureExt};
 use ruma::{
 	RoomId, UserId,
@@ -15,6 +17,7 @@
 	serde::Raw,
 };
 use serde::Deserialize;
+use serde_json::{json, value::to_raw_value};
 
 use crate::{Dep, globals};
 
@@ -25,9 +28,13 @@ pub struct Service {
 
 struct Data {
 	roomuserdataid_accountdata: Arc<Map>,
+	roomuserdataid_deletedat: Arc<Map>,
 	roomusertype_roomuserdataid: Arc<Map>,
 }
 
+/// How long deletions are kept for syncing clients to learn of them.
+const DELETION_RETENTION: Duration = Duration::from_secs(60 * 60 * 24 * 90);
+
 struct Services {
 	globals: Dep<globals::Service>,
 }
@@ -40,6 +47,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			},
 			db: Data {
 				roomuserdataid_accountdata: args.db["roomuserdataid_accountdata"].clone(),
+				roomuserdataid_deletedat: args.db["roomuserdataid_deletedat"].clone(),
 				roomusertype_roomuserdataid: args.db["roomusertype_roomuserdataid"].clone(),
 			},
 		}))
@@ -78,9 +86,77 @@ pub async fn update(
 		self.db.roomuserdataid_accountdata.remove(&prev);
 	}
 
+	// The new event supersedes any deletion of it
+	self.prune_deletions(room_id, user_id, Some(event_type.to_string().as_str()))
+		.await;
+
 	Ok(())
 }
 
+/// Deletes one event from the account data of the user (MSC3391).
+///
+/// The deletion is recorded in a stream of its own, alongside the changes, so
+/// syncing clients learn of it as an event with empty content. Deletions are
+/// dropped once the event is set again, or after `DELETION_RETENTION`.
+#[implement(Service)]
+pub async fn delete(&self, room_id: Option<&RoomId>, user_id: &UserId, kind: &str) -> Result {
+	let key = (room_id, user_id, kind);
+	let Ok(prev) = self.db.roomusertype_roomuserdataid.qry(&key).await else {
+		return Err!(Request(NotFound("Data not found.")));
+	};
+
+	self.db.roomusertype_roomuserdataid.del(key);
+	self.db.roomuserdataid_accountdata.remove(&prev);
+	self.prune_deletions(room_id, user_id, Some(kind)).await;
+
+	let count = self.services.globals.next_count().unwrap();
+	self.db
+		.roomuserdataid_deletedat
+		.put((room_id, user_id, count, kind), now_millis());
+
+	Ok(())
+}
+
+/// Drops the user's deletions of `kind`, and those older than
+/// `DELETION_RETENTION`.
+#[implement(Service)]
+async fn prune_deletions(&self, room_id: Option<&RoomId>, user_id: &UserId, kind: Option<&str>) {
+	type Key<'a> = (Option<&'a RoomId>, &'a UserId, u64, &'a str);
+
+	let retention = DELETION_RETENTION
+		.as_millis()
+		.try_into()
+		.unwrap_or(u64::MAX);
+	let oldest = now_millis().saturating_sub(retention);
+	let prefix = (room_id, user_id, Interfix);
+	self.db
+		.roomuserdataid_deletedat
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.ready_filter(|((.., deleted), deleted_at): &(Key<'_>, u64)| {
+			is_pruned(kind, oldest, deleted, *deleted_at)
+		})
+		.ready_for_each(|(key, _)| self.db.roomuserdataid_deletedat.del(key))
+		.await;
+}
+
+/// Returns the kinds of account data the user has, globally or in a room.
+#[implement(Service)]
+pub fn kinds<'a>(
+	&'a self,
+	room_id: Option<&'a RoomId>,
+	user_id: &'a UserId,
+) -> impl Stream<Item = &'a str> + Send + 'a {
+	type Key<'a> = (Ignore, Ignore, &'a str);
+
+	let prefix = (room_id, user_id, Interfix);
+	self.db
+		.roomusertype_roomuserdataid
+		.keys_prefix(&prefix)
+		.ignore_err()
+		.map(|(_, _, kind): Key<'_>| kind)
+}
+
 /// Searches the room account data for a specific kind.
 #[implement(Service)]
 pub async fn get_global<T>(&self, user_id: &UserId, kind: GlobalAccountDataEventType) -> Result<T>
@@ -124,6 +200,8 @@ pub async fn get_raw(
 }
 
 /// Returns all changes to the account data that happened after `since`.
+/// Deletions are returned as events with empty content, after the events
+/// which were set.
 #[implement(Service)]
 pub fn changes_since<'a>(
 	&'a self,
@@ -132,19 +210,30 @@ pub fn changes_since<'a>(
 	since: Option<u64>,
 	to: Option<u64>,
 ) -> impl Stream<Item = AnyRawAccountDataEvent> + Send + 'a {
-	type Key<'a> = (Option<&'a RoomId>, &'a UserId, u64, Ignore);
+	type Key<'a> = (Option<&'a RoomId>, &'a UserId, u64, &'a str);
 
 	// Skip the data that's exactly at since, because we sent that last time
 	// ...unless this is an initial sync, in which case send everything
+	// except deletions
+	let initial = since.is_none_or(|since| since == 0);
 	let first_possible = (room_id, user_id, since.map_or(0, |since| since.saturating_add(1)));
+	let in_range = move |&(room_id_, user_id_, count, _): &Key<'_>| {
+		room_id == room_id_ && user_id == user_id_ && to.is_none_or(|to| count <= to)
+	};
+
+	let deletions = self
+		.db
+		.roomuserdataid_deletedat
+		.stream_from(&first_possible)
+		.ignore_err()
+		.ready_take_while(move |(key, _): &(Key<'_>, u64)| !initial && in_range(key))
+		.map(move |((.., kind), _)| deletion(room_id, kind));
 
 	self.db
 		.roomuserdataid_accountdata
 		.stream_from(&first_possible)
 		.ignore_err()
-		.ready_take_while(move |((room_id_, user_id_, count, _), _): &(Key<'_>, _)| {
-			room_id == *room_id_ && user_id == *user_id_ && to.is_none_or(|to| *count <= to)
-		})
+		.ready_take_while(move |(key, _): &(Key<'_>, &[u8])| in_range(key))
 		.map(move |(_, v)| {
 			match room_id {
 				| Some(_) => serde_json::from_slice::<Raw<AnyRoomAccountDataEvent>>(v)
@@ -153,7 +242,25 @@ pub fn changes_since<'a>(
 					.map(AnyRawAccountDataEvent::Global),
 			}
 			.map_err(|e| err!(Database("Database contains invalid account data: {e}")))
-			.log_err()
 		})
+		.chain(deletions)
+		.map(LogErr::log_err)
 		.ignore_err()
 }
+
+/// Whether a deletion of `deleted` at `deleted_at` is dropped when pruning
+/// the deletions of `kind` and those from before `oldest`.
+fn is_pruned(kind: Option<&str>, oldest: u64, deleted: &str, deleted_at: u64) -> bool {
+	kind.is_some_and(|kind| kind == deleted) || deleted_at < oldest
+}
+
+/// The event telling clients that account data of `kind` was deleted: an
+/// event with empty content.
+fn deletion(room_id: Option<&RoomId>, kind: &str) -> Result<AnyRawAccountDataEvent> {
+	let event = to_raw_value(&json!({ "type": kind, "content": {} }))?;
+
+	Ok(match room_id {
+		| Some(_) => AnyRawAccountDataEvent::Room(Raw::from_json(event)),
+		| None => AnyRawAccountDataEvent::Global(Raw::from_json(event)),
+	})
+}
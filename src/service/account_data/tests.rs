@@ -0,0 +1,39 @@
+#![cfg(test)]
+
+use ruma::{events::AnyRawAccountDataEvent, room_id};
+use serde_json::{Value as JsonValue, json, value::RawValue as RawJsonValue};
+
+use super::{deletion, is_pruned};
+
+fn event_json(event: &RawJsonValue) -> JsonValue {
+	serde_json::from_str(event.get()).expect("event is JSON")
+}
+
+#[test]
+fn deletion_has_empty_content() {
+	let AnyRawAccountDataEvent::Global(global) = deletion(None, "m.direct").expect("deletion")
+	else {
+		panic!("global deletion should be global account data");
+	};
+	assert_eq!(event_json(global.json()), json!({ "type": "m.direct", "content": {} }));
+
+	let room_id = room_id!("!room:example.com");
+	let AnyRawAccountDataEvent::Room(room) = deletion(Some(room_id), "m.tag").expect("deletion")
+	else {
+		panic!("room deletion should be room account data");
+	};
+	assert_eq!(event_json(room.json()), json!({ "type": "m.tag", "content": {} }));
+}
+
+#[test]
+fn setting_data_drops_its_deletion() {
+	assert!(is_pruned(Some("m.direct"), 0, "m.direct", 5));
+	assert!(!is_pruned(Some("m.direct"), 0, "m.tag", 5));
+}
+
+#[test]
+fn old_deletions_are_dropped() {
+	assert!(is_pruned(None, 10, "m.direct", 9));
+	assert!(!is_pruned(None, 10, "m.direct", 10));
+	assert!(is_pruned(Some("m.tag"), 10, "m.direct", 9));
+}
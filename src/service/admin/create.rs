@@ -88,15 +88,14 @@ pub async fn create_admin_room(services: &Services) -> Result {
 
 	// 3. Power levels
 	let users = BTreeMap::from_iter([(server_user.into(), 69420.into())]);
+	let mut power_levels = RoomPowerLevelsEventContent { users, ..Default::default() };
+	super::grant::restrict_power_levels(&mut power_levels);
 
 	services
 		.rooms
 		.timeline
 		.build_and_append_pdu(
-			PduBuilder::state(String::new(), &RoomPowerLevelsEventContent {
-				users,
-				..Default::default()
-			}),
+			PduBuilder::state(String::new(), &power_levels),
 			server_user,
 			Some(&room_id),
 			&state_lock,
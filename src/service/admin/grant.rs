@@ -4,9 +4,9 @@
 	Err, Result, debug_info, debug_warn, error, implement, matrix::pdu::PduBuilder, warn,
 };
 use ruma::{
-	RoomId, UserId,
+	Int, RoomId, UserId,
 	events::{
-		RoomAccountDataEventType, StateEventType,
+		RoomAccountDataEventType, StateEventType, TimelineEventType,
 		room::{
 			member::{MembershipState, RoomMemberEventContent},
 			message::RoomMessageEventContent,
@@ -16,14 +16,110 @@
 	},
 };
 
+use crate::rooms::state::RoomMutexGuard;
+
+/// Power level of admins in the admin room.
+const ADMIN_POWER_LEVEL: i32 = 100;
+
+/// Power level of users holding a delegated role in the admin room.
+pub(super) const ROLE_POWER_LEVEL: i32 = 50;
+
+/// Raises the power levels needed to change the admin room's membership or
+/// state to the admins' level. Role holders may then run their commands but
+/// cannot invite anyone, who would be a full admin once joined, nor open the
+/// room up. Returns whether anything was raised.
+pub(super) fn restrict_power_levels(power_levels: &mut RoomPowerLevelsEventContent) -> bool {
+	let admin = Int::from(ADMIN_POWER_LEVEL);
+	let mut changed = false;
+	let mut raise = |level: &mut Int| {
+		if *level < admin {
+			*level = admin;
+			changed = true;
+		}
+	};
+
+	raise(&mut power_levels.invite);
+	raise(&mut power_levels.kick);
+	raise(&mut power_levels.ban);
+	raise(&mut power_levels.redact);
+	raise(&mut power_levels.state_default);
+	power_levels
+		.events
+		.iter_mut()
+		.filter(|(event_type, _)| {
+			!matches!(event_type, TimelineEventType::RoomMessage | TimelineEventType::Reaction)
+		})
+		.for_each(|(_, level)| raise(level));
+
+	changed
+}
+
+/// Restricts the power levels of an existing admin room, see
+/// [`restrict_power_levels`].
+#[implement(super::Service)]
+pub async fn restrict_admin_room_power_levels(&self) -> Result {
+	let Ok(room_id) = self.get_admin_room().await else {
+		return Ok(());
+	};
+
+	let state_lock = self.services.state.mutex.lock(&room_id).await;
+	let mut room_power_levels = self
+		.services
+		.state_accessor
+		.room_state_get_content::<RoomPowerLevelsEventContent>(
+			&room_id,
+			&StateEventType::RoomPowerLevels,
+			"",
+		)
+		.await
+		.unwrap_or_default();
+
+	if !restrict_power_levels(&mut room_power_levels) {
+		return Ok(());
+	}
+
+	let server_user = self.services.globals.server_user.as_ref();
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(String::new(), &room_power_levels),
+			server_user,
+			Some(&room_id),
+			&state_lock,
+		)
+		.await
+		.map(|_| ())
+}
+
 /// Invite the user to the conduwuit admin room.
 ///
 /// This is equivalent to granting server admin privileges.
 #[implement(super::Service)]
 pub async fn make_user_admin(&self, user_id: &UserId) -> Result {
+	// Users holding a delegated role are already in the admin room; dropping
+	// the role is enough to promote them.
+	if self.user_role(user_id).await.is_some() {
+		self.db.userid_adminrole.remove(user_id);
+
+		if let Ok(room_id) = self.get_admin_room().await
+			&& self.services.state_cache.is_joined(user_id, &room_id).await
+		{
+			let state_lock = self.services.state.mutex.lock(&room_id).await;
+			return self
+				.set_admin_power_level(&room_id, user_id, ADMIN_POWER_LEVEL, &state_lock)
+				.await;
+		}
+	}
+
+	self.join_admin_room(user_id, ADMIN_POWER_LEVEL).await
+}
+
+/// Joins the user to the admin room with the given power level.
+#[implement(super::Service)]
+pub(super) async fn join_admin_room(&self, user_id: &UserId, power_level: i32) -> Result {
 	let Ok(room_id) = self.get_admin_room().await else {
 		debug_warn!(
-			"make_user_admin was called without an admin room being available or created"
+			"join_admin_room was called without an admin room being available or created"
 		);
 		return Ok(());
 	};
@@ -42,7 +138,7 @@ pub async fn make_user_admin(&self, user_id: &UserId) -> Result {
 		return Err!(debug_warn!("User is already pending an invitation to the admin room"));
 	}
 
-	// Use the server user to grant the new admin's power level
+	// Use the server user to invite the new member
 	let server_user = self.services.globals.server_user.as_ref();
 
 	// if this is our local user, just forcefully join them in the room. otherwise,
@@ -91,31 +187,7 @@ pub async fn make_user_admin(&self, user_id: &UserId) -> Result {
 			.await?;
 	}
 
-	// Set power levels
-	let mut room_power_levels = self
-		.services
-		.state_accessor
-		.room_state_get_content::<RoomPowerLevelsEventContent>(
-			&room_id,
-			&StateEventType::RoomPowerLevels,
-			"",
-		)
-		.await
-		.unwrap_or_default();
-
-	room_power_levels
-		.users
-		.insert(server_user.into(), 69420.into());
-	room_power_levels.users.insert(user_id.into(), 100.into());
-
-	self.services
-		.timeline
-		.build_and_append_pdu(
-			PduBuilder::state(String::new(), &room_power_levels),
-			server_user,
-			Some(&room_id),
-			&state_lock,
-		)
+	self.set_admin_power_level(&room_id, user_id, power_level, &state_lock)
 		.await?;
 
 	// Set room tag
@@ -146,6 +218,48 @@ pub async fn make_user_admin(&self, user_id: &UserId) -> Result {
 	Ok(())
 }
 
+#[implement(super::Service)]
+pub(super) async fn set_admin_power_level(
+	&self,
+	room_id: &RoomId,
+	user_id: &UserId,
+	power_level: i32,
+	state_lock: &RoomMutexGuard,
+) -> Result {
+	// Use the server user to grant the power level
+	let server_user = self.services.globals.server_user.as_ref();
+
+	let mut room_power_levels = self
+		.services
+		.state_accessor
+		.room_state_get_content::<RoomPowerLevelsEventContent>(
+			room_id,
+			&StateEventType::RoomPowerLevels,
+			"",
+		)
+		.await
+		.unwrap_or_default();
+
+	room_power_levels
+		.users
+		.insert(server_user.into(), 69420.into());
+	room_power_levels
+		.users
+		.insert(user_id.into(), power_level.into());
+	restrict_power_levels(&mut room_power_levels);
+
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(String::new(), &room_power_levels),
+			server_user,
+			Some(room_id),
+			state_lock,
+		)
+		.await
+		.map(|_| ())
+}
+
 #[implement(super::Service)]
 async fn set_room_tag(&self, room_id: &RoomId, user_id: &UserId, tag: &str) -> Result {
 	let mut event = self
@@ -173,11 +287,14 @@ async fn set_room_tag(&self, room_id: &RoomId, user_id: &UserId, tag: &str) -> R
 		.await
 }
 
-/// Demote an admin, removing its rights.
+/// Demote an admin, removing its rights. Any delegated role held by the user is
+/// removed along with their membership of the admin room.
 #[implement(super::Service)]
 pub async fn revoke_admin(&self, user_id: &UserId) -> Result {
 	use MembershipState::{Invite, Join, Knock, Leave};
 
+	self.db.userid_adminrole.remove(user_id);
+
 	if self
 		.services
 		.server
@@ -2,6 +2,8 @@
 mod create;
 mod execute;
 mod grant;
+mod roles;
+mod tests;
 
 use std::{
 	pin::Pin,
@@ -14,6 +16,7 @@
 	Error, Event, Result, Server, debug, err, error, error::default_log, pdu::PduBuilder,
 };
 pub use create::create_admin_room;
+use database::Map;
 use futures::{Future, FutureExt, StreamExt, TryFutureExt};
 use loole::{Receiver, Sender};
 use ruma::{
@@ -34,6 +37,7 @@
 
 pub struct Service {
 	services: Services,
+	db: Data,
 	channel: (Sender<CommandInput>, Receiver<CommandInput>),
 	pub handle: RwLock<Option<Processor>>,
 	pub complete: SyncRwLock<Option<Completer>>,
@@ -54,6 +58,10 @@ struct Services {
 	media: Dep<crate::media::Service>,
 }
 
+struct Data {
+	userid_adminrole: Arc<Map>,
+}
+
 /// Inputs to a command are a multi-line string, invocation source, optional
 /// reply_id, and optional sender.
 #[derive(Debug)]
@@ -125,6 +133,9 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				services: None.into(),
 				media: args.depend::<crate::media::Service>("media"),
 			},
+			db: Data {
+				userid_adminrole: args.db["userid_adminrole"].clone(),
+			},
 			channel: loole::bounded(COMMAND_QUEUE_LIMIT),
 			handle: RwLock::new(None),
 			complete: SyncRwLock::new(None),
@@ -385,7 +396,7 @@ async fn process_command(&self, command: CommandInput) -> ProcessorResult {
 
 	/// Returns the list of admins for this server. First loads
 	/// the admin_list from the configuration, then adds users from
-	/// the admin room if applicable.
+	/// the admin room if applicable, except those holding a delegated role.
 	pub async fn get_admins(&self) -> Vec<OwnedUserId> {
 		let mut generated_admin_list: Vec<OwnedUserId> =
 			self.services.server.config.admins_list.clone();
@@ -396,7 +407,9 @@ pub async fn get_admins(&self) -> Vec<OwnedUserId> {
 				let mut stream = admin_users;
 
 				while let Some(user_id) = stream.next().await {
-					generated_admin_list.push(user_id.to_owned());
+					if self.user_role(user_id).await.is_none() {
+						generated_admin_list.push(user_id.to_owned());
+					}
 				}
 			}
 		}
@@ -404,7 +417,8 @@ pub async fn get_admins(&self) -> Vec<OwnedUserId> {
 		generated_admin_list
 	}
 
-	/// Checks whether a given user is an admin of this server
+	/// Checks whether a given user is an admin of this server. Users holding a
+	/// delegated role are in the admin room but are not admins.
 	pub async fn user_is_admin(&self, user_id: &UserId) -> bool {
 		if self
 			.services
@@ -422,7 +436,7 @@ pub async fn user_is_admin(&self, user_id: &UserId) -> bool {
 					.services
 					.state_cache
 					.is_joined(user_id, &admin_room)
-					.await;
+					.await && self.user_role(user_id).await.is_none();
 			}
 		}
 
@@ -531,8 +545,10 @@ pub async fn is_admin_command<E>(&self, event: &E, body: &str) -> Option<Invocat
 	where
 		E: Event + Send + Sync,
 	{
-		// If the user isn't an admin they definitely can't run admin commands
-		if !self.user_is_admin(event.sender()).await {
+		// If the user is neither an admin nor holds a delegated role they
+		// definitely can't run admin commands
+		let is_admin = self.user_is_admin(event.sender()).await;
+		if !is_admin && self.user_role(event.sender()).await.is_none() {
 			return None;
 		}
 
@@ -567,6 +583,11 @@ pub async fn is_admin_command<E>(&self, event: &E, body: &str) -> Option<Invocat
 				return None;
 			}
 
+			// Delegated roles only apply in the admin room
+			if !is_admin {
+				return None;
+			}
+
 			// Only admin users belonging to this server can use escaped commands
 			if !self.services.globals.user_is_local(event.sender()) {
 				return None;
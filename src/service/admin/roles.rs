@@ -0,0 +1,106 @@
+//! Delegated admin roles
+//!
+//! Admins can grant a user one of the roles configured in `admin_roles`
+//! instead of full admin privileges. Role holders join the admin room like
+//! admins do, but may only run the commands under the subtrees listed for
+//! their role, and are not regarded as server admins anywhere else.
+
+use conduwuit::{Err, Result, debug_info, error, implement, utils::stream::TryIgnore, warn};
+use database::Deserialized;
+use futures::Stream;
+use ruma::UserId;
+
+use super::grant::ROLE_POWER_LEVEL;
+
+/// Returns the delegated role held by the user, if any.
+#[implement(super::Service)]
+pub async fn user_role(&self, user_id: &UserId) -> Option<String> {
+	self.db
+		.userid_adminrole
+		.get(user_id)
+		.await
+		.deserialized()
+		.ok()
+}
+
+/// Returns every user holding a delegated role along with that role.
+#[implement(super::Service)]
+pub fn role_holders(&self) -> impl Stream<Item = (&UserId, &str)> + Send + '_ {
+	self.db.userid_adminrole.stream().ignore_err()
+}
+
+/// Grants a delegated role to the user, joining them to the admin room if
+/// they are not in it yet. An admin granted a role is demoted to it.
+#[implement(super::Service)]
+pub async fn grant_role(&self, user_id: &UserId, role: &str) -> Result {
+	if !self.services.server.config.admin_roles.contains_key(role) {
+		return Err!("There is no admin role named {role:?} in the admin_roles config.");
+	}
+
+	if self
+		.services
+		.server
+		.config
+		.admins_list
+		.contains(&user_id.to_owned())
+	{
+		warn!(
+			"{user_id} is within the admins_list config and will keep full admin privileges \
+			 regardless of their {role} role."
+		);
+	}
+
+	let Ok(room_id) = self.get_admin_room().await else {
+		return Err!(error!("No admin room available or created."));
+	};
+
+	self.db.userid_adminrole.insert(user_id, role);
+	debug_info!("Granted {role} role to {user_id}");
+
+	if self.services.state_cache.is_joined(user_id, &room_id).await {
+		let state_lock = self.services.state.mutex.lock(&room_id).await;
+		return self
+			.set_admin_power_level(&room_id, user_id, ROLE_POWER_LEVEL, &state_lock)
+			.await;
+	}
+
+	self.join_admin_room(user_id, ROLE_POWER_LEVEL).await
+}
+
+/// Checks whether the user may run the admin command named by `path`, the
+/// names of its subcommands from the top level down, e.g. `["media",
+/// "delete"]`.
+///
+/// Admins may run any command. Role holders may run the commands under any of
+/// the subtrees configured for their role.
+#[implement(super::Service)]
+pub async fn user_may_run<S>(&self, user_id: &UserId, path: &[S]) -> bool
+where
+	S: AsRef<str> + Sync,
+{
+	let config = &self.services.server.config;
+	match self.user_role(user_id).await {
+		| None => self.user_is_admin(user_id).await,
+		| Some(_) if config.admins_list.contains(&user_id.to_owned()) => true,
+		| Some(role) => config
+			.admin_roles
+			.get(&role)
+			.is_some_and(|subtrees| subtrees.iter().any(|subtree| covers(subtree, path))),
+	}
+}
+
+/// Whether a configured subtree such as `"users suspend"` covers the command
+/// at `path`. The subtree `"*"` covers every command.
+fn covers<S: AsRef<str>>(subtree: &str, path: &[S]) -> bool {
+	if subtree.trim() == "*" {
+		return true;
+	}
+
+	let names: Vec<_> = subtree.split_whitespace().collect();
+	!names.is_empty()
+		&& names.len() <= path.len()
+		&& names
+			.iter()
+			.zip(path)
+			.all(|(name, command)| *name == command.as_ref())
+}
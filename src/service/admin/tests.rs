@@ -0,0 +1,57 @@
+#![cfg(test)]
+
+use ruma::{
+	events::{
+		StateEventType,
+		room::power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+	},
+	owned_user_id, user_id,
+};
+
+use super::grant::{ROLE_POWER_LEVEL, restrict_power_levels};
+
+fn admin_room_power_levels() -> RoomPowerLevelsEventContent {
+	let mut content = RoomPowerLevelsEventContent::default();
+	content
+		.users
+		.insert(owned_user_id!("@conduit:example.com"), 69420.into());
+	content
+		.users
+		.insert(owned_user_id!("@admin:example.com"), 100.into());
+	content
+		.users
+		.insert(owned_user_id!("@moderator:example.com"), ROLE_POWER_LEVEL.into());
+
+	content
+}
+
+#[test]
+fn role_holder_cannot_invite_admins() {
+	let mut content = admin_room_power_levels();
+	assert!(restrict_power_levels(&mut content));
+
+	let power_levels = RoomPowerLevels::from(content);
+	let moderator = user_id!("@moderator:example.com");
+	assert!(!power_levels.user_can_invite(moderator));
+	assert!(!power_levels.user_can_kick(moderator));
+	assert!(!power_levels.user_can_send_state(moderator, StateEventType::RoomJoinRules));
+	assert!(!power_levels.user_can_send_state(moderator, StateEventType::RoomPowerLevels));
+	assert!(!power_levels.user_can_send_state(moderator, StateEventType::RoomMember));
+
+	let admin = user_id!("@admin:example.com");
+	assert!(power_levels.user_can_invite(admin));
+	assert!(power_levels.user_can_send_state(admin, StateEventType::RoomJoinRules));
+}
+
+#[test]
+fn default_power_levels_let_role_holder_invite() {
+	let power_levels = RoomPowerLevels::from(admin_room_power_levels());
+	assert!(power_levels.user_can_invite(user_id!("@moderator:example.com")));
+}
+
+#[test]
+fn restrict_power_levels_is_idempotent() {
+	let mut content = admin_room_power_levels();
+	assert!(restrict_power_levels(&mut content));
+	assert!(!restrict_power_levels(&mut content));
+}
@@ -1,25 +1,39 @@
 mod namespace_regex;
 mod registration_info;
+mod thirdparty;
 
 use std::{collections::BTreeMap, iter::IntoIterator, sync::Arc};
 
 use async_trait::async_trait;
-use conduwuit::{Err, Result, err, utils::stream::IterStream};
+use conduwuit::{Err, Result, Server, SyncMutex, err, utils::stream::IterStream};
 use database::Map;
 use futures::{Future, FutureExt, Stream, TryStreamExt};
-use ruma::{RoomAliasId, RoomId, UserId, api::appservice::Registration};
-use tokio::sync::{RwLock, RwLockReadGuard};
-
-pub use self::{namespace_regex::NamespaceRegex, registration_info::RegistrationInfo};
+use lru_cache::LruCache;
+use ruma::{
+	RoomAliasId, RoomId, UserId,
+	api::appservice::Registration,
+	thirdparty::{Location, User},
+};
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+
+use self::thirdparty::{LOOKUP_CACHE_MAX, LookupCache, ProtocolCache};
+pub use self::{
+	namespace_regex::NamespaceRegex, registration_info::RegistrationInfo, thirdparty::Protocols,
+};
 use crate::{Dep, globals, sending, users};
 
 pub struct Service {
 	registration_info: RwLock<Registrations>,
+	protocols: RwLock<Option<ProtocolCache>>,
+	protocols_fetch: Mutex<()>,
+	location_lookups: LookupCache<Location>,
+	user_lookups: LookupCache<User>,
 	services: Services,
 	db: Data,
 }
 
 struct Services {
+	server: Arc<Server>,
 	globals: Dep<globals::Service>,
 	sending: Dep<sending::Service>,
 	users: Dep<users::Service>,
@@ -36,7 +50,12 @@ impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			registration_info: RwLock::new(BTreeMap::new()),
+			protocols: RwLock::new(None),
+			protocols_fetch: Mutex::new(()),
+			location_lookups: SyncMutex::new(LruCache::new(LOOKUP_CACHE_MAX)),
+			user_lookups: SyncMutex::new(LruCache::new(LOOKUP_CACHE_MAX)),
 			services: Services {
+				server: args.server.clone(),
 				globals: args.depend::<globals::Service>("globals"),
 				sending: args.depend::<sending::Service>("sending"),
 				users: args.depend::<users::Service>("users"),
@@ -94,6 +113,8 @@ async fn worker(self: Arc<Self>) -> Result {
 		Ok(())
 	}
 
+	async fn clear_cache(&self) { self.clear_thirdparty_cache().await; }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -132,6 +153,8 @@ async fn start_appservice(&self, id: String, registration: Registration) -> Resu
 			.await
 			.insert(id, registration.try_into()?);
 
+		self.clear_thirdparty_cache().await;
+
 		Ok(())
 	}
 
@@ -192,6 +215,8 @@ pub async fn unregister_appservice(&self, appservice_id: &str) -> Result {
 			.remove(appservice_id)
 			.ok_or_else(|| err!("Appservice not found"))?;
 
+		self.clear_thirdparty_cache().await;
+
 		// remove the appservice from the database
 		self.db.id_appserviceregistrations.del(appservice_id);
 
@@ -222,6 +247,15 @@ pub async fn find_from_token(&self, token: &str) -> Result<RegistrationInfo> {
 			.ok_or_else(|| err!(Request(NotFound("Appservice token not found"))))
 	}
 
+	/// Finds the appservice whose namespace includes the given user
+	pub async fn find_from_user(&self, user_id: &UserId) -> Option<RegistrationInfo> {
+		self.read()
+			.await
+			.values()
+			.find(|info| info.is_user_match(user_id))
+			.cloned()
+	}
+
 	/// Checks if a given user id matches any exclusive appservice regex
 	pub async fn is_exclusive_user_id(&self, user_id: &UserId) -> bool {
 		self.read()
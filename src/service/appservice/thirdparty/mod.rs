@@ -0,0 +1,352 @@
+//! Third-party protocol lookups, proxied to the appservices which declare the
+//! protocols in their registrations.
+
+mod tests;
+
+use std::{
+	collections::{BTreeMap, btree_map::Entry},
+	fmt::Debug,
+	time::{Duration, Instant},
+};
+
+use conduwuit::{Err, Result, SyncMutex, debug, debug_warn, implement};
+use futures::future::join_all;
+use lru_cache::LruCache;
+use ruma::{
+	RoomAliasId, UserId,
+	api::{
+		OutgoingRequest,
+		appservice::{
+			Registration,
+			thirdparty::{
+				get_location_for_protocol, get_location_for_room_alias, get_protocol,
+				get_user_for_protocol, get_user_for_user_id,
+			},
+		},
+	},
+	thirdparty::{Location, Protocol, User},
+};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+/// Protocol metadata aggregated from all appservices, keyed by protocol name.
+pub type Protocols = BTreeMap<String, Protocol>;
+
+pub(super) struct ProtocolCache {
+	fetched: Instant,
+	protocols: Protocols,
+}
+
+/// Results of location or user lookups, keyed by the lookup.
+pub(super) type LookupCache<T> = SyncMutex<LruCache<String, (Instant, Vec<T>)>>;
+
+/// Upper bound on the number of cached location and user lookups, each.
+pub(super) const LOOKUP_CACHE_MAX: usize = 256;
+
+/// Returns the metadata of all protocols provided by the appservices. The
+/// instances of a protocol provided by several appservices are merged.
+#[implement(super::Service)]
+pub async fn protocols(&self) -> Protocols {
+	if let Some(protocols) = self.cached_protocols().await {
+		return protocols;
+	}
+
+	// only one request asks the appservices, the others wait for its answer
+	let _fetching = self.protocols_fetch.lock().await;
+	if let Some(protocols) = self.cached_protocols().await {
+		return protocols;
+	}
+
+	let registrations = self.protocol_registrations(None).await;
+	let fetched = join_all(
+		registrations
+			.into_iter()
+			.map(|(name, registration)| async move {
+				let request = get_protocol::v1::Request::new(name.clone());
+				let appservice_id = registration.id.clone();
+				let response = self.thirdparty_request(registration, request).await?;
+
+				into_protocol(&appservice_id, response.protocol).map(|protocol| (name, protocol))
+			}),
+	)
+	.await;
+
+	let mut protocols = Protocols::new();
+	for (name, protocol) in fetched.into_iter().flatten() {
+		match protocols.entry(name) {
+			| Entry::Vacant(entry) => {
+				entry.insert(protocol);
+			},
+			| Entry::Occupied(mut entry) => {
+				entry.get_mut().instances.extend(protocol.instances);
+			},
+		}
+	}
+
+	*self.protocols.write().await = Some(ProtocolCache {
+		fetched: Instant::now(),
+		protocols: protocols.clone(),
+	});
+
+	protocols
+}
+
+#[implement(super::Service)]
+async fn cached_protocols(&self) -> Option<Protocols> {
+	let ttl = self.thirdparty_cache_ttl();
+	self.protocols
+		.read()
+		.await
+		.as_ref()
+		.filter(|cache| cache.fetched.elapsed() < ttl)
+		.map(|cache| cache.protocols.clone())
+}
+
+/// Looks up portal rooms for the protocol from the appservices providing it.
+#[implement(super::Service)]
+pub async fn thirdparty_locations(
+	&self,
+	protocol: &str,
+	fields: &BTreeMap<String, String>,
+) -> Result<Vec<Location>> {
+	let registrations = self.protocol_registrations(Some(protocol)).await;
+	if registrations.is_empty() {
+		return Err!(Request(NotFound("No appservice provides the protocol {protocol}.")));
+	}
+
+	let key = format!("{protocol}\0{fields:?}");
+	if let Some(locations) = cached(&self.location_lookups, &key, self.thirdparty_cache_ttl()) {
+		debug!(?protocol, "Using cached third-party locations");
+		return Ok(locations);
+	}
+
+	let responses = join_all(registrations.into_iter().map(|(_, registration)| {
+		let mut request = get_location_for_protocol::v1::Request::new(protocol.to_owned());
+		request.fields.clone_from(fields);
+		self.thirdparty_request(registration, request)
+	}))
+	.await;
+
+	let answers = responses
+		.into_iter()
+		.map(|response| response.map(|response| response.locations))
+		.collect();
+
+	Ok(merge_lookup(&self.location_lookups, key, answers))
+}
+
+/// Looks up the third-party locations of a portal room alias from the
+/// appservices whose alias namespace includes it.
+#[implement(super::Service)]
+pub async fn thirdparty_locations_by_alias(&self, alias: &RoomAliasId) -> Vec<Location> {
+	let key = format!("\0{alias}");
+	if let Some(locations) = cached(&self.location_lookups, &key, self.thirdparty_cache_ttl()) {
+		debug!(?alias, "Using cached third-party locations");
+		return locations;
+	}
+
+	let registrations: Vec<_> = self
+		.read()
+		.await
+		.values()
+		.filter(|info| info.aliases.is_match(alias.as_str()))
+		.map(|info| info.registration.clone())
+		.collect();
+
+	let responses = join_all(registrations.into_iter().map(|registration| {
+		let request = get_location_for_room_alias::v1::Request::new(alias.to_owned());
+		self.thirdparty_request(registration, request)
+	}))
+	.await;
+
+	let answers = responses
+		.into_iter()
+		.map(|response| response.map(|response| response.locations))
+		.collect();
+
+	merge_lookup(&self.location_lookups, key, answers)
+}
+
+/// Looks up third-party users for the protocol from the appservices
+/// providing it.
+#[implement(super::Service)]
+pub async fn thirdparty_users(
+	&self,
+	protocol: &str,
+	fields: &BTreeMap<String, String>,
+) -> Result<Vec<User>> {
+	let registrations = self.protocol_registrations(Some(protocol)).await;
+	if registrations.is_empty() {
+		return Err!(Request(NotFound("No appservice provides the protocol {protocol}.")));
+	}
+
+	let key = format!("{protocol}\0{fields:?}");
+	if let Some(users) = cached(&self.user_lookups, &key, self.thirdparty_cache_ttl()) {
+		debug!(?protocol, "Using cached third-party users");
+		return Ok(users);
+	}
+
+	let responses = join_all(registrations.into_iter().map(|(_, registration)| {
+		let mut request = get_user_for_protocol::v1::Request::new(protocol.to_owned());
+		request.fields.clone_from(fields);
+		self.thirdparty_request(registration, request)
+	}))
+	.await;
+
+	let answers = responses
+		.into_iter()
+		.map(|response| response.map(|response| response.users))
+		.collect();
+
+	Ok(merge_lookup(&self.user_lookups, key, answers))
+}
+
+/// Looks up the third-party users behind a Matrix user from the appservices
+/// whose user namespace includes it.
+#[implement(super::Service)]
+pub async fn thirdparty_users_by_user_id(&self, user_id: &UserId) -> Vec<User> {
+	let key = format!("\0{user_id}");
+	if let Some(users) = cached(&self.user_lookups, &key, self.thirdparty_cache_ttl()) {
+		debug!(?user_id, "Using cached third-party users");
+		return users;
+	}
+
+	let registrations: Vec<_> = self
+		.read()
+		.await
+		.values()
+		.filter(|info| info.users.is_match(user_id.as_str()))
+		.map(|info| info.registration.clone())
+		.collect();
+
+	let responses = join_all(registrations.into_iter().map(|registration| {
+		let request = get_user_for_user_id::v1::Request::new(user_id.to_owned());
+		self.thirdparty_request(registration, request)
+	}))
+	.await;
+
+	let answers = responses
+		.into_iter()
+		.map(|response| response.map(|response| response.users))
+		.collect();
+
+	merge_lookup(&self.user_lookups, key, answers)
+}
+
+/// Drops the cached protocol metadata and lookups, after the registrations
+/// changed.
+#[implement(super::Service)]
+pub(super) async fn clear_thirdparty_cache(&self) {
+	*self.protocols.write().await = None;
+	self.location_lookups.lock().clear();
+	self.user_lookups.lock().clear();
+}
+
+#[implement(super::Service)]
+fn thirdparty_cache_ttl(&self) -> Duration {
+	Duration::from_secs(self.services.server.config.appservice_thirdparty_cache_ttl)
+}
+
+fn cached<T: Clone>(cache: &LookupCache<T>, key: &str, ttl: Duration) -> Option<Vec<T>> {
+	let mut cache = cache.lock();
+	let (fetched, results) = cache.get_mut(key)?;
+
+	(fetched.elapsed() < ttl).then(|| results.clone())
+}
+
+/// Merges the answers of the appservices to a lookup. The results are only
+/// cached when every appservice answered, so a bridge which is briefly down
+/// is asked again on the next lookup.
+fn merge_lookup<T: Clone>(
+	cache: &LookupCache<T>,
+	key: String,
+	answers: Vec<Option<Vec<T>>>,
+) -> Vec<T> {
+	let complete = answers.iter().all(Option::is_some);
+	let results: Vec<T> = answers.into_iter().flatten().flatten().collect();
+	if complete {
+		cache.lock().insert(key, (Instant::now(), results.clone()));
+	}
+
+	results
+}
+
+/// Returns the protocols declared by each appservice, optionally only the
+/// given protocol.
+#[implement(super::Service)]
+async fn protocol_registrations(&self, protocol: Option<&str>) -> Vec<(String, Registration)> {
+	self.read()
+		.await
+		.values()
+		.flat_map(|info| {
+			info.registration
+				.protocols
+				.iter()
+				.flatten()
+				.filter(|name| protocol.is_none_or(|protocol| *name == protocol))
+				.map(|name| (name.clone(), info.registration.clone()))
+		})
+		.collect()
+}
+
+/// Sends a third-party request to an appservice. Failures are logged and
+/// treated as an empty answer, so one unresponsive bridge does not fail the
+/// whole lookup.
+#[implement(super::Service)]
+async fn thirdparty_request<T>(
+	&self,
+	registration: Registration,
+	request: T,
+) -> Option<T::IncomingResponse>
+where
+	T: OutgoingRequest + Debug + Send,
+{
+	let appservice_id = registration.id.clone();
+	let timeout = Duration::from_secs(self.services.server.config.appservice_thirdparty_timeout);
+	let response = self
+		.services
+		.sending
+		.send_appservice_request(registration, request);
+
+	match tokio::time::timeout(timeout, response).await {
+		| Ok(Ok(response)) => response,
+		| Ok(Err(e)) => {
+			debug_warn!(appservice = %appservice_id, "Third-party lookup failed: {e}");
+			None
+		},
+		| Err(_) => {
+			debug_warn!(appservice = %appservice_id, "Third-party lookup timed out");
+			None
+		},
+	}
+}
+
+/// Converts the protocol metadata of an appservice, which lacks instance IDs,
+/// to the metadata served to clients. The instance IDs name the appservice,
+/// keeping them unique when several appservices provide one protocol.
+fn into_protocol(appservice_id: &str, protocol: impl Serialize) -> Option<Protocol> {
+	let mut protocol = serde_json::to_value(protocol).ok()?;
+	let instances = protocol
+		.get_mut("instances")
+		.and_then(JsonValue::as_array_mut)
+		.into_iter()
+		.flatten();
+
+	for instance in instances {
+		let network_id = instance
+			.get("network_id")
+			.and_then(JsonValue::as_str)
+			.unwrap_or_default();
+
+		let instance_id = format!("{appservice_id}|{network_id}");
+		if let Some(instance) = instance.as_object_mut() {
+			instance.insert("instance_id".to_owned(), instance_id.into());
+		}
+	}
+
+	serde_json::from_value(protocol)
+		.inspect_err(|e| {
+			debug_warn!(appservice = %appservice_id, "Invalid third-party protocol: {e}");
+		})
+		.ok()
+}
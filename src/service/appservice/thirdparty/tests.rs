@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+use serde_json::{Value as JsonValue, json};
+
+use super::into_protocol;
+
+fn protocol(instances: JsonValue) -> JsonValue {
+	json!({
+		"user_fields": ["network", "nickname"],
+		"location_fields": ["network", "channel"],
+		"icon": "mxc://example.com/irc",
+		"field_types": {
+			"network": { "regexp": "([a-z0-9]+\\.)*[a-z0-9]+", "placeholder": "irc.example.org" },
+			"nickname": { "regexp": "[^\\s#]+", "placeholder": "username" },
+			"channel": { "regexp": "#[^\\s]+", "placeholder": "#foobar" },
+		},
+		"instances": instances,
+	})
+}
+
+#[test]
+fn instance_ids_name_the_appservice() {
+	let instances = json!([
+		{ "desc": "Libera", "fields": { "network": "libera.chat" }, "network_id": "libera" },
+		{ "desc": "OFTC", "fields": { "network": "irc.oftc.net" }, "network_id": "oftc" },
+	]);
+
+	let protocol = into_protocol("irc_bridge", protocol(instances)).expect("valid protocol");
+
+	let instance_ids: Vec<_> = protocol
+		.instances
+		.iter()
+		.map(|instance| instance.instance_id.as_str())
+		.collect();
+	assert_eq!(instance_ids, ["irc_bridge|libera", "irc_bridge|oftc"]);
+	assert_eq!(protocol.instances[0].desc, "Libera");
+	assert_eq!(protocol.user_fields, ["network", "nickname"]);
+}
+
+#[test]
+fn protocol_without_instances() {
+	let protocol = into_protocol("irc_bridge", protocol(json!([]))).expect("valid protocol");
+
+	assert!(protocol.instances.is_empty());
+	assert_eq!(protocol.location_fields, ["network", "channel"]);
+}
+
+#[test]
+fn invalid_protocol_is_dropped() {
+	// the instance lacks its network_id
+	let instances = json!([{ "desc": "Libera", "fields": {} }]);
+	assert!(into_protocol("irc_bridge", protocol(instances)).is_none());
+
+	assert!(into_protocol("irc_bridge", json!({ "instances": [] })).is_none());
+	assert!(into_protocol("irc_bridge", json!("irc")).is_none());
+}
@@ -0,0 +1,231 @@
+//! # Audit log
+//!
+//! An append-only record of administrative actions. Every admin command,
+//! whether sent in the admin room, escaped in another room, typed on the
+//! console or run at startup, is recorded along with the administrative
+//! actions taken through the client API, such as suspending users.
+//!
+//! Entries are keyed by a global count so they are stored in the order they
+//! were recorded, and are never modified or removed.
+
+mod tests;
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use conduwuit::{
+	Result,
+	utils::{ReadyExt, stream::TryIgnore, time::now_millis},
+	warn,
+};
+use database::{Ignore, Json, Map};
+use futures::{Stream, StreamExt};
+use ruma::{OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+
+use crate::{Dep, admin::InvocationSource, globals};
+
+pub struct Service {
+	db: Data,
+	services: Services,
+}
+
+struct Data {
+	auditid_entry: Arc<Map>,
+}
+
+struct Services {
+	globals: Dep<globals::Service>,
+}
+
+/// A recorded administrative action.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+	/// When the action was taken, in milliseconds since the unix epoch.
+	pub ts: u64,
+
+	/// Who took the action. Actions from the console and startup commands are
+	/// attributed to the server user.
+	pub actor: OwnedUserId,
+
+	/// Where the action was taken from.
+	pub source: Source,
+
+	/// The action taken: the canonical path of an admin command, e.g. `users
+	/// suspend`, or the equivalent for actions taken through the API.
+	pub action: String,
+
+	/// The arguments given to the action.
+	pub args: Vec<String>,
+
+	/// The result of the action.
+	pub outcome: Outcome,
+}
+
+/// Where an audited action was taken from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+	/// The server's private admin room
+	AdminRoom,
+	/// An escaped `\!admin` command in another room
+	EscapedCommand,
+	/// The server's admin console or startup commands
+	Console,
+	/// Some other trusted internal source
+	Internal,
+	/// An admin endpoint of the client API
+	Api,
+}
+
+/// The result of an audited action.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+	/// The action was carried out.
+	Succeeded,
+	/// The action was attempted but failed.
+	Failed {
+		error: String,
+	},
+	/// The actor was not permitted to take the action.
+	Denied,
+}
+
+/// Criteria for listing audit entries. Unset criteria match any entry.
+#[derive(Debug, Default)]
+pub struct Filter<'a> {
+	/// Only entries by this actor.
+	pub actor: Option<&'a UserId>,
+
+	/// Only entries whose action is this or a subcommand of it, e.g. `users`
+	/// or `media delete`.
+	pub action: Option<&'a str>,
+
+	/// Only entries from this source.
+	pub source: Option<Source>,
+
+	/// Only entries recorded within this long ago.
+	pub since: Option<Duration>,
+
+	/// Only entries recorded at least this long ago, to page back through
+	/// older entries.
+	pub until: Option<Duration>,
+
+	/// Only entries which failed or were denied.
+	pub unsuccessful: bool,
+}
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			db: Data {
+				auditid_entry: args.db["auditid_entry"].clone(),
+			},
+			services: Services {
+				globals: args.depend::<globals::Service>("globals"),
+			},
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl Service {
+	/// Records an administrative action taken by `actor`.
+	pub fn record(
+		&self,
+		actor: &UserId,
+		source: Source,
+		action: &str,
+		args: Vec<String>,
+		outcome: Outcome,
+	) {
+		let entry = Entry {
+			ts: now_millis(),
+			actor: actor.to_owned(),
+			source,
+			action: action.to_owned(),
+			args,
+			outcome,
+		};
+
+		match self.services.globals.next_count() {
+			| Ok(count) => self.db.auditid_entry.put(count, Json(entry)),
+			| Err(e) => warn!(?entry, "Failed to record audit log entry: {e}"),
+		}
+	}
+
+	/// Iterates the entries matching `filter`, most recent first.
+	pub fn entries<'a>(
+		&'a self,
+		filter: &'a Filter<'a>,
+	) -> impl Stream<Item = Entry> + Send + 'a {
+		let ago = |duration: Duration| {
+			let duration = duration.as_millis().try_into().unwrap_or(u64::MAX);
+			now_millis().saturating_sub(duration)
+		};
+
+		let oldest = filter.since.map_or(0, ago);
+		let newest = filter.until.map_or(u64::MAX, ago);
+
+		self.db
+			.auditid_entry
+			.rev_stream()
+			.ignore_err()
+			.map(|(_, entry): (Ignore, Entry)| entry)
+			.ready_skip_while(move |entry| entry.ts > newest)
+			.ready_take_while(move |entry| entry.ts >= oldest)
+			.ready_filter(move |entry| filter.matches(entry))
+	}
+}
+
+impl Filter<'_> {
+	fn matches(&self, entry: &Entry) -> bool {
+		self.actor.is_none_or(|actor| entry.actor == actor)
+			&& self
+				.action
+				.is_none_or(|action| covers(action, &entry.action))
+			&& self.source.is_none_or(|source| entry.source == source)
+			&& (!self.unsuccessful || entry.outcome != Outcome::Succeeded)
+	}
+}
+
+/// Whether the action `prefix` is `action` or one of its ancestors, comparing
+/// whole command names so that `users suspend` does not cover `users
+/// suspend-all`.
+fn covers(prefix: &str, action: &str) -> bool {
+	let mut action = action.split_whitespace();
+	prefix
+		.split_whitespace()
+		.all(|name| action.next() == Some(name))
+}
+
+impl From<InvocationSource> for Source {
+	fn from(source: InvocationSource) -> Self {
+		match source {
+			| InvocationSource::AdminRoom => Self::AdminRoom,
+			| InvocationSource::EscapedCommand => Self::EscapedCommand,
+			| InvocationSource::Console => Self::Console,
+			| InvocationSource::Internal => Self::Internal,
+		}
+	}
+}
+
+impl<T> From<&Result<T>> for Outcome {
+	fn from(result: &Result<T>) -> Self {
+		match result {
+			| Ok(_) => Self::Succeeded,
+			| Err(e) => Self::Failed { error: e.to_string() },
+		}
+	}
+}
+
+impl fmt::Display for Outcome {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			| Self::Succeeded => write!(f, "succeeded"),
+			| Self::Failed { error } => write!(f, "failed: {error}"),
+			| Self::Denied => write!(f, "denied"),
+		}
+	}
+}
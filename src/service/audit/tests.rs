@@ -0,0 +1,94 @@
+#![cfg(test)]
+
+use ruma::{UserId, user_id};
+
+use super::{Entry, Filter, Outcome, Source};
+
+fn entry(actor: &UserId, source: Source, action: &str, outcome: Outcome) -> Entry {
+	Entry {
+		ts: 0,
+		actor: actor.to_owned(),
+		source,
+		action: action.to_owned(),
+		args: vec![],
+		outcome,
+	}
+}
+
+#[test]
+fn empty_filter_matches_everything() {
+	let alice = user_id!("@alice:example.com");
+	let filter = Filter::default();
+
+	assert!(filter.matches(&entry(
+		alice,
+		Source::AdminRoom,
+		"users suspend",
+		Outcome::Succeeded
+	)));
+	assert!(filter.matches(&entry(alice, Source::Api, "users suspend", Outcome::Denied)));
+}
+
+#[test]
+fn action_filter_matches_whole_commands() {
+	let alice = user_id!("@alice:example.com");
+	let suspend = entry(alice, Source::AdminRoom, "users suspend", Outcome::Succeeded);
+	let suspend_all = entry(alice, Source::AdminRoom, "users suspend-all", Outcome::Succeeded);
+	let media = entry(alice, Source::AdminRoom, "media delete", Outcome::Succeeded);
+
+	let users = Filter {
+		action: Some("users"),
+		..Filter::default()
+	};
+	assert!(users.matches(&suspend));
+	assert!(users.matches(&suspend_all));
+	assert!(!users.matches(&media));
+
+	let exact = Filter {
+		action: Some("users suspend"),
+		..Filter::default()
+	};
+	assert!(exact.matches(&suspend));
+	assert!(!exact.matches(&suspend_all));
+
+	let partial = Filter {
+		action: Some("user"),
+		..Filter::default()
+	};
+	assert!(!partial.matches(&suspend));
+
+	let deeper = Filter {
+		action: Some("users suspend now"),
+		..Filter::default()
+	};
+	assert!(!deeper.matches(&suspend));
+}
+
+#[test]
+fn actor_and_source_filters() {
+	let (alice, bob) = (user_id!("@alice:example.com"), user_id!("@bob:example.com"));
+	let by_alice = entry(alice, Source::Api, "users suspend", Outcome::Succeeded);
+	let by_bob = entry(bob, Source::Console, "users suspend", Outcome::Succeeded);
+
+	let filter = Filter { actor: Some(alice), ..Filter::default() };
+	assert!(filter.matches(&by_alice));
+	assert!(!filter.matches(&by_bob));
+
+	let filter = Filter {
+		source: Some(Source::Console),
+		..Filter::default()
+	};
+	assert!(!filter.matches(&by_alice));
+	assert!(filter.matches(&by_bob));
+}
+
+#[test]
+fn unsuccessful_filter() {
+	let alice = user_id!("@alice:example.com");
+	let filter = Filter { unsuccessful: true, ..Filter::default() };
+	let failed = Outcome::Failed { error: "oops".to_owned() };
+
+	assert!(!filter.matches(&entry(alice, Source::Api, "users suspend", Outcome::Succeeded)));
+	assert!(filter.matches(&entry(alice, Source::Api, "users suspend", failed)));
+	assert!(filter.matches(&entry(alice, Source::Api, "users suspend", Outcome::Denied)));
+}
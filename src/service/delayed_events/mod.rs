@@ -0,0 +1,415 @@
+//! # Delayed events service
+//!
+//! Implements the server side of MSC4140. Clients may ask for a message or
+//! state event to be sent after a delay; the event is kept in the database
+//! until its timeout elapses, at which point it is built and appended to the
+//! room timeline on behalf of the sender like any other event. Until then the
+//! sender may restart the timeout, send the event immediately, or cancel it.
+//!
+//! Delayed events are persisted so they survive restarts. Timers are rebuilt
+//! from the database when the service worker starts. A pending delayed state
+//! event is cancelled when any newer state event with the same type and state
+//! key is appended to its room.
+
+mod tests;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, Error, Result, Server, debug, debug_warn, err, implement,
+	matrix::pdu::PduBuilder,
+	utils::{
+		self,
+		stream::{ReadyExt, TryIgnore},
+	},
+	warn,
+};
+use database::{Deserialized, Ignore, Interfix, Json, Map};
+use futures::{Stream, StreamExt, stream::FuturesUnordered};
+use loole::{Receiver, Sender};
+use ruma::{
+	OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId, api::client::error::ErrorKind,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue as RawJsonValue;
+use tokio::time::sleep;
+
+use crate::{Dep, rooms};
+
+pub struct Service {
+	timer_channel: (Sender<TimerType>, Receiver<TimerType>),
+	db: Data,
+	services: Services,
+}
+
+struct Data {
+	roomstatekey_userdelayid: Arc<Map>,
+	userdelayid_delayedevent: Arc<Map>,
+}
+
+struct Services {
+	server: Arc<Server>,
+	state: Dep<rooms::state::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+}
+
+/// An event scheduled to be sent at a later time.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DelayedEvent {
+	/// The opaque identifier handed to the client for managing this event.
+	pub delay_id: String,
+
+	/// The room the event will be sent to.
+	pub room_id: OwnedRoomId,
+
+	/// The event type.
+	#[serde(rename = "type")]
+	pub event_type: String,
+
+	/// The state key, if this is a delayed state event.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub state_key: Option<String>,
+
+	/// The event content, as supplied by the client.
+	pub content: Box<RawJsonValue>,
+
+	/// The delay in milliseconds requested by the client.
+	pub delay: u64,
+
+	/// When the delay last (re)started, in milliseconds since the unix epoch.
+	pub running_since: u64,
+}
+
+/// Action to take on an existing delayed event.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpdateAction {
+	/// Reset the timeout to the full delay, counting from now.
+	Restart,
+	/// Send the event immediately.
+	Send,
+	/// Discard the event without sending it.
+	Cancel,
+}
+
+/// What a fired timer should do with the event it was started for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TimerOutcome {
+	/// The event was sent early or cancelled.
+	Gone,
+	/// The event was restarted; a newer timer is pending for it.
+	Restarted,
+	/// The event is due and should be sent.
+	Due,
+}
+
+type TimerType = (OwnedUserId, String, Duration);
+
+/// Length of generated delay IDs
+const DELAY_ID_LENGTH: usize = 24;
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			timer_channel: loole::unbounded(),
+			db: Data {
+				roomstatekey_userdelayid: args.db["roomstatekey_userdelayid"].clone(),
+				userdelayid_delayedevent: args.db["userdelayid_delayedevent"].clone(),
+			},
+			services: Services {
+				server: args.server.clone(),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+			},
+		}))
+	}
+
+	#[tracing::instrument(skip_all, name = "delayed_events", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		let receiver = self.timer_channel.1.clone();
+
+		// Timers count down from when the event was last (re)started, so an event
+		// which fell due while the server was down fires at once.
+		let now = utils::millis_since_unix_epoch();
+		let mut timers: FuturesUnordered<_> = self
+			.all_delayed_events()
+			.map(|(sender, event)| {
+				// also indexes state events delayed before the index existed
+				self.save(&sender, &event);
+				let timeout = event.remaining_at(now);
+				delay_timer(sender, event.delay_id, timeout)
+			})
+			.collect()
+			.await;
+
+		debug!("Restored {} delayed event timers", timers.len());
+
+		while !receiver.is_closed() {
+			tokio::select! {
+				Some((sender, delay_id)) = timers.next() => {
+					self.handle_timer(&sender, &delay_id).await;
+				},
+				timer = receiver.recv_async() => match timer {
+					| Err(_) => break,
+					| Ok((sender, delay_id, timeout)) => {
+						timers.push(delay_timer(sender, delay_id, timeout));
+					},
+				},
+			}
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) {
+		let (timer_sender, _) = &self.timer_channel;
+		if !timer_sender.is_closed() {
+			timer_sender.close();
+		}
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+impl DelayedEvent {
+	/// Milliseconds since the unix epoch at which this event is due.
+	#[inline]
+	#[must_use]
+	pub fn due(&self) -> u64 { self.running_since.saturating_add(self.delay) }
+
+	/// Time left until this event is due; zero when overdue.
+	#[must_use]
+	pub fn remaining(&self) -> Duration { self.remaining_at(utils::millis_since_unix_epoch()) }
+
+	/// Time left at `now` (milliseconds since the unix epoch) until this event
+	/// is due; zero when overdue.
+	#[inline]
+	#[must_use]
+	pub fn remaining_at(&self, now: u64) -> Duration {
+		Duration::from_millis(self.due().saturating_sub(now))
+	}
+}
+
+/// Schedules a new delayed event for `sender`, returning its delay ID.
+///
+/// The caller is expected to have validated that the sender may send this
+/// event at all; authorization is checked again when the event is sent.
+#[implement(Service)]
+#[tracing::instrument(skip(self, content), level = "debug")]
+pub async fn schedule(
+	&self,
+	sender: &UserId,
+	room_id: &RoomId,
+	event_type: String,
+	state_key: Option<String>,
+	content: Box<RawJsonValue>,
+	delay: u64,
+) -> Result<String> {
+	let config = &self.services.server.config;
+	if config.max_delayed_event_delay == 0 {
+		return Err!(Request(Forbidden("Delayed events are disabled on this server.")));
+	}
+
+	if delay > config.max_delayed_event_delay {
+		return Err!(Request(InvalidParam(
+			"Requested delay of {delay}ms exceeds the maximum of {}ms.",
+			config.max_delayed_event_delay
+		)));
+	}
+
+	let count = self.delayed_events_for_user(sender).count().await;
+	if count >= config.max_delayed_events_per_user {
+		return Err(Error::BadRequest(
+			ErrorKind::LimitExceeded { retry_after: None },
+			"You have too many pending delayed events.",
+		));
+	}
+
+	let event = DelayedEvent {
+		delay_id: utils::random_string(DELAY_ID_LENGTH),
+		room_id: room_id.to_owned(),
+		event_type,
+		state_key,
+		content,
+		delay,
+		running_since: utils::millis_since_unix_epoch(),
+	};
+
+	self.save(sender, &event);
+	self.start_timer(sender, &event)?;
+
+	Ok(event.delay_id)
+}
+
+/// Restarts, sends or cancels one of `sender`'s pending delayed events.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn update(&self, sender: &UserId, delay_id: &str, action: UpdateAction) -> Result {
+	let mut event = self
+		.get_delayed_event(sender, delay_id)
+		.await
+		.map_err(|_| err!(Request(NotFound("No delayed event with this ID."))))?;
+
+	match action {
+		| UpdateAction::Restart => {
+			event.running_since = utils::millis_since_unix_epoch();
+			self.save(sender, &event);
+			self.start_timer(sender, &event)
+		},
+		| UpdateAction::Send => {
+			self.remove(sender, &event);
+			self.send(sender, event).await.map(|_| ())
+		},
+		| UpdateAction::Cancel => {
+			self.remove(sender, &event);
+			Ok(())
+		},
+	}
+}
+
+/// Fetches one of `sender`'s pending delayed events.
+#[implement(Service)]
+pub async fn get_delayed_event(&self, sender: &UserId, delay_id: &str) -> Result<DelayedEvent> {
+	let key = (sender, delay_id);
+	self.db
+		.userdelayid_delayedevent
+		.qry(&key)
+		.await
+		.deserialized()
+}
+
+/// Iterates the pending delayed events scheduled by `sender`.
+#[implement(Service)]
+pub fn delayed_events_for_user<'a>(
+	&'a self,
+	sender: &'a UserId,
+) -> impl Stream<Item = DelayedEvent> + Send + 'a {
+	let prefix = (sender, Interfix);
+	self.db
+		.userdelayid_delayedevent
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.map(|(_, event): (Ignore, DelayedEvent)| event)
+}
+
+/// Iterates every pending delayed event along with its sender.
+#[implement(Service)]
+pub fn all_delayed_events(&self) -> impl Stream<Item = (OwnedUserId, DelayedEvent)> + Send + '_ {
+	self.db
+		.userdelayid_delayedevent
+		.stream()
+		.ignore_err()
+		.map(|((sender, _), event): ((&UserId, Ignore), DelayedEvent)| (sender.to_owned(), event))
+}
+
+/// Cancels every pending delayed state event in `room_id` with the given type
+/// and state key. Called when a state event replacing them is appended.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn cancel_state_events(&self, room_id: &RoomId, event_type: &str, state_key: &str) {
+	let prefix = (room_id, event_type, state_key, Interfix);
+	self.db
+		.roomstatekey_userdelayid
+		.keys_prefix(&prefix)
+		.ignore_err()
+		.ready_for_each(|(_, _, _, sender, delay_id): (Ignore, Ignore, Ignore, &UserId, &str)| {
+			debug!(%sender, %delay_id, "Cancelling delayed state event superseded by new state");
+			self.db.userdelayid_delayedevent.del((sender, delay_id));
+			self.db
+				.roomstatekey_userdelayid
+				.del((room_id, event_type, state_key, sender, delay_id));
+		})
+		.await;
+}
+
+#[implement(Service)]
+async fn handle_timer(&self, sender: &UserId, delay_id: &str) {
+	let event = self.get_delayed_event(sender, delay_id).await.ok();
+	let now = utils::millis_since_unix_epoch();
+	let Some(event) = event.filter(|event| timer_outcome(Some(event), now) == TimerOutcome::Due)
+	else {
+		return;
+	};
+
+	self.remove(sender, &event);
+	if let Err(e) = self.send(sender, event).await {
+		warn!(%sender, %delay_id, "Failed to send delayed event: {e}");
+	}
+}
+
+#[implement(Service)]
+async fn send(&self, sender: &UserId, event: DelayedEvent) -> Result<OwnedEventId> {
+	debug!(%sender, delay_id = %event.delay_id, room_id = %event.room_id, "Sending delayed event");
+
+	let state_lock = self.services.state.mutex.lock(&event.room_id).await;
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder {
+				event_type: event.event_type.into(),
+				content: event.content,
+				state_key: event.state_key.map(Into::into),
+				..Default::default()
+			},
+			sender,
+			Some(&event.room_id),
+			&state_lock,
+		)
+		.await
+}
+
+#[implement(Service)]
+fn start_timer(&self, sender: &UserId, event: &DelayedEvent) -> Result {
+	self.timer_channel
+		.0
+		.send((sender.to_owned(), event.delay_id.clone(), event.remaining()))
+		.map_err(|e| {
+			debug_warn!("Failed to add delayed event timer: {e}");
+			err!("Failed to add delayed event timer")
+		})
+}
+
+#[implement(Service)]
+fn save(&self, sender: &UserId, event: &DelayedEvent) {
+	let delay_id = event.delay_id.as_str();
+	self.db
+		.userdelayid_delayedevent
+		.put((sender, delay_id), Json(event));
+
+	if let Some(state_key) = event.state_key.as_deref() {
+		let key = (&event.room_id, event.event_type.as_str(), state_key, sender, delay_id);
+		self.db.roomstatekey_userdelayid.put_raw(key, []);
+	}
+}
+
+#[implement(Service)]
+fn remove(&self, sender: &UserId, event: &DelayedEvent) {
+	let delay_id = event.delay_id.as_str();
+	self.db.userdelayid_delayedevent.del((sender, delay_id));
+
+	if let Some(state_key) = event.state_key.as_deref() {
+		let key = (&event.room_id, event.event_type.as_str(), state_key, sender, delay_id);
+		self.db.roomstatekey_userdelayid.del(key);
+	}
+}
+
+/// Decides what a timer firing at `now` does with the event it was started
+/// for, as currently stored.
+fn timer_outcome(event: Option<&DelayedEvent>, now: u64) -> TimerOutcome {
+	match event {
+		| None => TimerOutcome::Gone,
+		| Some(event) if !event.remaining_at(now).is_zero() => TimerOutcome::Restarted,
+		| Some(_) => TimerOutcome::Due,
+	}
+}
+
+async fn delay_timer(
+	sender: OwnedUserId,
+	delay_id: String,
+	timeout: Duration,
+) -> (OwnedUserId, String) {
+	sleep(timeout).await;
+
+	(sender, delay_id)
+}
@@ -0,0 +1,85 @@
+#![cfg(test)]
+
+use std::time::Duration;
+
+use database::{Json, serialize_val};
+use ruma::owned_room_id;
+use serde_json::value::to_raw_value;
+
+use super::{DelayedEvent, TimerOutcome, timer_outcome};
+
+const SCHEDULED_AT: u64 = 1_700_000_000_000;
+
+fn delayed_event(delay: u64) -> DelayedEvent {
+	DelayedEvent {
+		delay_id: "delayid".to_owned(),
+		room_id: owned_room_id!("!room:example.com"),
+		event_type: "m.room.message".to_owned(),
+		state_key: None,
+		content: to_raw_value(&serde_json::json!({"body": "hello"})).expect("raw content"),
+		delay,
+		running_since: SCHEDULED_AT,
+	}
+}
+
+/// Round-trips an event through its database encoding, as the worker reads it
+/// back after a restart.
+fn persisted(event: &DelayedEvent) -> DelayedEvent {
+	let val = serialize_val(Json(event)).expect("delayed event serializes");
+	serde_json::from_slice(&val).expect("persisted delayed event deserializes")
+}
+
+#[test]
+fn timer_sends_event_once_due() {
+	let event = delayed_event(5_000);
+
+	assert_eq!(event.remaining_at(SCHEDULED_AT), Duration::from_secs(5));
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 4_999), TimerOutcome::Restarted);
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 5_000), TimerOutcome::Due);
+}
+
+#[test]
+fn timer_ignores_sent_or_cancelled_event() {
+	// sending early or cancelling removes the event before its timer fires
+	assert_eq!(timer_outcome(None, SCHEDULED_AT + 5_000), TimerOutcome::Gone);
+}
+
+#[test]
+fn restart_defers_original_timer() {
+	let mut event = delayed_event(5_000);
+	event.running_since = SCHEDULED_AT + 3_000;
+
+	// the timer started at scheduling fires first and finds the event restarted
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 5_000), TimerOutcome::Restarted);
+	assert_eq!(event.remaining_at(SCHEDULED_AT + 5_000), Duration::from_secs(3));
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 8_000), TimerOutcome::Due);
+}
+
+#[test]
+fn overdue_event_sent_after_reboot() {
+	let event = persisted(&delayed_event(5_000));
+	let now = SCHEDULED_AT + 60_000;
+
+	assert!(event.remaining_at(now).is_zero());
+	assert_eq!(timer_outcome(Some(&event), now), TimerOutcome::Due);
+}
+
+#[test]
+fn pending_event_keeps_remaining_delay_after_reboot() {
+	let event = persisted(&delayed_event(60_000));
+	let now = SCHEDULED_AT + 2_000;
+
+	assert_eq!(event.remaining_at(now), Duration::from_secs(58));
+	assert_eq!(timer_outcome(Some(&event), now), TimerOutcome::Restarted);
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 60_000), TimerOutcome::Due);
+}
+
+#[test]
+fn restart_survives_reboot() {
+	let mut event = delayed_event(5_000);
+	event.running_since = SCHEDULED_AT + 4_000;
+	let event = persisted(&event);
+
+	assert_eq!(event.remaining_at(SCHEDULED_AT + 5_000), Duration::from_secs(4));
+	assert_eq!(timer_outcome(Some(&event), SCHEDULED_AT + 9_000), TimerOutcome::Due);
+}
@@ -0,0 +1,73 @@
+//! Media access tracking
+//!
+//! Every download of a file or thumbnail counts as an access of its MXC,
+//! including downloads of remote media fetched for the client. Uploading or
+//! generating media is not an access.
+//! Accesses are collected in memory and written to the database in batches
+//! by the media worker, so serving media does not cost a write per request.
+
+use std::{collections::HashMap, mem::take};
+
+use conduwuit::{debug, implement, utils::time::now_millis};
+use ruma::{Mxc, OwnedMxcUri};
+use serde::{Deserialize, Serialize};
+
+use super::Service;
+
+/// When an MXC was last accessed and how often.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Access {
+	/// When the MXC was last accessed, in milliseconds since the unix epoch.
+	pub last_access: u64,
+
+	/// How often the MXC was accessed.
+	pub hits: u64,
+}
+
+/// Accesses not yet written to the database.
+pub(super) type Pending = HashMap<OwnedMxcUri, Access>;
+
+/// Records an access of an MXC. The access is written to the database with
+/// the next batch.
+#[implement(Service)]
+pub(super) fn record_access(&self, mxc: &Mxc<'_>) {
+	let mut pending = self.pending_accesses.lock();
+	let access = pending.entry(mxc.to_string().into()).or_default();
+	access.last_access = now_millis();
+	access.hits = access.hits.saturating_add(1);
+}
+
+/// Writes the pending accesses to the database.
+#[implement(Service)]
+pub(super) async fn flush_accesses(&self) {
+	let pending = take(&mut *self.pending_accesses.lock());
+	if pending.is_empty() {
+		return;
+	}
+
+	debug!(count = pending.len(), "Writing media accesses");
+	for (mxc, access) in pending {
+		let stored = self.db.get_access(&mxc).await.unwrap_or_default();
+		self.db.set_access(&mxc, &Access {
+			last_access: access.last_access.max(stored.last_access),
+			hits: stored.hits.saturating_add(access.hits),
+		});
+	}
+}
+
+/// Returns when an MXC was last accessed and how often, including accesses
+/// not yet written to the database. None when no access was ever recorded.
+#[implement(Service)]
+pub async fn get_access(&self, mxc: &Mxc<'_>) -> Option<Access> {
+	let mxc: OwnedMxcUri = mxc.to_string().into();
+	let pending = self.pending_accesses.lock().get(&mxc).copied();
+	let stored = self.db.get_access(&mxc).await;
+
+	match (stored, pending) {
+		| (Some(stored), Some(pending)) => Some(Access {
+			last_access: pending.last_access.max(stored.last_access),
+			hits: stored.hits.saturating_add(pending.hits),
+		}),
+		| (access, None) | (None, access) => access,
+	}
+}
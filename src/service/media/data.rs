@@ -4,13 +4,14 @@
 	Err, Result, debug, debug_info, err,
 	utils::{ReadyExt, str_from_bytes, stream::TryIgnore, string_from_bytes},
 };
-use database::{Database, Interfix, Map};
+use database::{Database, Deserialized, Interfix, Json, Map};
 use futures::StreamExt;
 use ruma::{Mxc, OwnedMxcUri, UserId, http_headers::ContentDisposition};
 
-use super::{preview::UrlPreviewData, thumbnail::Dim};
+use super::{access::Access, preview::UrlPreviewData, thumbnail::Dim};
 
 pub(crate) struct Data {
+	mediaid_access: Arc<Map>,
 	mediaid_file: Arc<Map>,
 	mediaid_user: Arc<Map>,
 	url_previews: Arc<Map>,
@@ -26,6 +27,7 @@ pub(super) struct Metadata {
 impl Data {
 	pub(super) fn new(db: &Arc<Database>) -> Self {
 		Self {
+			mediaid_access: db["mediaid_access"].clone(),
 			mediaid_file: db["mediaid_file"].clone(),
 			mediaid_user: db["mediaid_user"].clone(),
 			url_previews: db["url_previews"].clone(),
@@ -77,6 +79,20 @@ pub(super) async fn delete_file_mxc(&self, mxc: &Mxc<'_>) {
 				self.mediaid_user.remove(key);
 			})
 			.await;
+
+		self.mediaid_access.remove(mxc.to_string().as_str());
+	}
+
+	pub(super) async fn get_access(&self, mxc: &OwnedMxcUri) -> Option<Access> {
+		self.mediaid_access
+			.get(mxc.as_str())
+			.await
+			.deserialized()
+			.ok()
+	}
+
+	pub(super) fn set_access(&self, mxc: &OwnedMxcUri, access: &Access) {
+		self.mediaid_access.raw_put(mxc.as_str(), Json(access));
 	}
 
 	/// Searches for all files with the given MXC
@@ -118,6 +134,32 @@ pub(super) async fn search_file_metadata(
 			.await
 			.ok_or_else(|| err!(Request(NotFound("Media not found"))))?;
 
+		Self::parse_metadata(mxc, key)
+	}
+
+	/// Searches for a thumbnail of the given dimensions stored as
+	/// `content_type`.
+	pub(super) async fn search_thumbnail_metadata(
+		&self,
+		mxc: &Mxc<'_>,
+		dim: &Dim,
+		content_type: &str,
+	) -> Result<Metadata> {
+		let dim: &[u32] = &[dim.width, dim.height];
+		let prefix = (mxc, dim, Interfix);
+
+		self.mediaid_file
+			.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.map(ToOwned::to_owned)
+			.ready_filter_map(|key| Self::parse_metadata(mxc, key).ok())
+			.ready_filter(|metadata| metadata.content_type.as_deref() == Some(content_type))
+			.next()
+			.await
+			.ok_or_else(|| err!(Request(NotFound("Thumbnail not found"))))
+	}
+
+	fn parse_metadata(mxc: &Mxc<'_>, key: Vec<u8>) -> Result<Metadata> {
 		let mut parts = key.rsplit(|&b| b == 0xFF);
 
 		let content_type = parts
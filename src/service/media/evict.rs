@@ -0,0 +1,198 @@
+//! Access-based media eviction
+//!
+//! Media which nobody downloaded for `media_evict_unaccessed_after` seconds is
+//! evicted by the media worker, first an hour after startup and hourly
+//! thereafter. Media without a recorded access, cached before
+//! access tracking, counts as last accessed when its file was created.
+
+use std::{
+	collections::{BTreeSet, HashSet},
+	time::{Duration, UNIX_EPOCH},
+};
+
+use conduwuit::{
+	Result, debug_warn, implement, info,
+	utils::{ReadyExt, time::now_millis},
+};
+use futures::{StreamExt, future::OptionFuture};
+use ruma::{Mxc, OwnedMxcUri, ServerName};
+use serde::Serialize;
+use tokio::fs;
+
+use super::{Service, access::Access};
+
+/// Media which would be evicted.
+#[derive(Debug, Serialize)]
+pub struct EvictionCandidate {
+	pub mxc: OwnedMxcUri,
+
+	/// When the media was last accessed, in milliseconds since the unix epoch.
+	pub last_access: u64,
+
+	/// How often the media was accessed.
+	pub hits: u64,
+}
+
+/// Which media the eviction policy allows evicting.
+pub(super) struct Policy<'a> {
+	/// Media last accessed before this, in milliseconds since the unix epoch,
+	/// is evicted.
+	pub(super) cutoff: u64,
+
+	/// This server's name, to tell local media apart.
+	pub(super) server_name: &'a ServerName,
+
+	/// Whether local media is evicted too.
+	pub(super) evict_local_media: bool,
+
+	/// Media which is never evicted.
+	pub(super) keep: &'a HashSet<OwnedMxcUri>,
+}
+
+/// Returns the media which has not been accessed for `unaccessed_for`, and
+/// which the eviction policy allows evicting, least recently accessed first.
+#[implement(Service)]
+pub async fn eviction_candidates(
+	&self,
+	unaccessed_for: Duration,
+) -> Result<Vec<EvictionCandidate>> {
+	self.flush_accesses().await;
+
+	let config = &self.services.server.config;
+	let unaccessed_for = unaccessed_for.as_millis().try_into().unwrap_or(u64::MAX);
+	let cutoff = now_millis().saturating_sub(unaccessed_for);
+	let keep = if config.media_evict_keep_local_avatars {
+		self.local_avatars().await
+	} else {
+		HashSet::new()
+	};
+
+	// Thumbnails are stored under the MXC of their original
+	let mxcs: BTreeSet<OwnedMxcUri> = self.get_all_mxcs().await?.into_iter().collect();
+
+	let policy = Policy {
+		cutoff,
+		server_name: self.services.globals.server_name(),
+		evict_local_media: config.media_evict_local_media,
+		keep: &keep,
+	};
+
+	let mut candidates = Vec::new();
+	for mxc in mxcs {
+		if !policy.may_evict(&mxc) {
+			continue;
+		}
+
+		let Ok(parsed) = <Mxc<'_>>::try_from(mxc.as_str()) else {
+			continue;
+		};
+
+		let access = self.get_access(&parsed).await;
+		let created_at: OptionFuture<_> = access
+			.is_none()
+			.then(|| self.file_created_at(&parsed))
+			.into();
+
+		if let Some(candidate) = policy.candidate(mxc, access, created_at.await.flatten()) {
+			candidates.push(candidate);
+		}
+	}
+
+	candidates.sort_by_key(|candidate| candidate.last_access);
+
+	Ok(candidates)
+}
+
+impl Policy<'_> {
+	/// Whether the policy allows evicting the media at all, however recently
+	/// it was accessed.
+	pub(super) fn may_evict(&self, mxc: &OwnedMxcUri) -> bool {
+		let is_local = mxc.server_name() == Ok(self.server_name);
+
+		(!is_local || self.evict_local_media) && !self.keep.contains(mxc)
+	}
+
+	/// Makes an eviction candidate of the media if it was last accessed before
+	/// the cutoff. Media never accessed counts as accessed when its file was
+	/// created, and is kept when that is unknown.
+	pub(super) fn candidate(
+		&self,
+		mxc: OwnedMxcUri,
+		access: Option<Access>,
+		created_at: Option<u64>,
+	) -> Option<EvictionCandidate> {
+		let Access { last_access, hits } = access.or_else(|| {
+			created_at.map(|created_at| Access { last_access: created_at, hits: 0 })
+		})?;
+
+		(last_access < self.cutoff).then_some(EvictionCandidate { mxc, last_access, hits })
+	}
+}
+
+/// Evicts the media not accessed within `media_evict_unaccessed_after`.
+#[implement(Service)]
+pub(super) async fn evict_unaccessed(&self) {
+	let unaccessed_after = self.services.server.config.media_evict_unaccessed_after;
+	if unaccessed_after == 0 {
+		return;
+	}
+
+	let candidates = match self
+		.eviction_candidates(Duration::from_secs(unaccessed_after))
+		.await
+	{
+		| Ok(candidates) => candidates,
+		| Err(e) => {
+			debug_warn!("Failed to find media to evict: {e}");
+			return;
+		},
+	};
+
+	let mut evicted: usize = 0;
+	for candidate in candidates {
+		let Ok(mxc) = candidate.mxc.as_str().try_into() else {
+			continue;
+		};
+
+		match self.delete(&mxc).await {
+			| Ok(()) => evicted = evicted.saturating_add(1),
+			| Err(e) => debug_warn!(%mxc, "Failed to evict media: {e}"),
+		}
+	}
+
+	if evicted > 0 {
+		info!("Evicted {evicted} media not accessed for {unaccessed_after} seconds");
+	}
+}
+
+#[implement(Service)]
+async fn local_avatars(&self) -> HashSet<OwnedMxcUri> {
+	self.services
+		.users
+		.stream()
+		.ready_filter(|user_id| self.services.globals.user_is_local(user_id))
+		.filter_map(async |user_id| self.services.users.avatar_url(user_id).await.ok())
+		.collect()
+		.await
+}
+
+#[implement(Service)]
+async fn file_created_at(&self, mxc: &Mxc<'_>) -> Option<u64> {
+	let key = self
+		.db
+		.search_mxc_metadata_prefix(mxc)
+		.await
+		.ok()?
+		.into_iter()
+		.next()?;
+
+	let metadata = fs::metadata(self.get_media_file(&key)).await.ok()?;
+	let created_at = metadata.created().or_else(|_| metadata.modified()).ok()?;
+
+	created_at
+		.duration_since(UNIX_EPOCH)
+		.ok()?
+		.as_millis()
+		.try_into()
+		.ok()
+}
@@ -12,6 +12,7 @@
 	utils::{ReadyExt, stream::TryIgnore},
 	warn,
 };
+use futures::StreamExt;
 
 use crate::Services;
 
@@ -53,6 +54,66 @@ pub(crate) async fn migrate_sha256_media(services: &Services) -> Result<()> {
 	Ok(())
 }
 
+/// Thumbnails used to be stored as PNG under the content type of the original
+/// file. They are now stored under their own content type, so the old entries
+/// would never be found again, or be served under a wrong content type. The
+/// thumbnails of local files are dropped to be generated anew; those of
+/// remote files are kept since they are looked up without a content type.
+pub(crate) async fn drop_legacy_thumbnails(services: &Services) -> Result<()> {
+	let db = &services.db;
+
+	warn!("Dropping thumbnails stored by older versions");
+	let mediaid_file = &db["mediaid_file"];
+
+	let keys: Vec<Vec<u8>> = mediaid_file
+		.raw_keys()
+		.ignore_err()
+		.map(<[u8]>::to_vec)
+		.collect()
+		.await;
+
+	let originals: HashSet<&[u8]> = keys
+		.iter()
+		.filter_map(|key| split_media_key(key))
+		.filter(|(_, dim)| is_original(dim))
+		.map(|(mxc, _)| mxc)
+		.collect();
+
+	let mut dropped: usize = 0;
+	for key in &keys {
+		let Some((mxc, dim)) = split_media_key(key) else {
+			continue;
+		};
+
+		if is_original(dim) || !originals.contains(mxc) {
+			continue;
+		}
+
+		if let Err(e) = services.media.remove_media_file(key).await {
+			debug_warn!(?key, "Failed to remove thumbnail file: {e}");
+		}
+
+		mediaid_file.remove(key);
+		dropped = dropped.saturating_add(1);
+	}
+
+	db["global"].insert(b"drop_legacy_thumbnails", []);
+	info!(?dropped, "Finished dropping legacy thumbnails");
+	Ok(())
+}
+
+/// Splits a `mediaid_file` key into its MXC and its serialized dimensions.
+fn split_media_key(key: &[u8]) -> Option<(&[u8], &[u8])> {
+	let end = key.iter().position(|&b| b == 0xFF)?;
+	let mxc = key.get(..end)?;
+	let dim = key.get(end.checked_add(1)?..end.checked_add(9)?)?;
+
+	Some((mxc, dim))
+}
+
+/// Original files are stored with zero dimensions.
+fn is_original(dim: &[u8]) -> bool { dim.iter().all(|&b| b == 0) }
+
 /// Check is run on startup for prior-migrated media directories. This handles:
 /// - Going back and forth to non-sha256 legacy binaries (e.g. upstream).
 /// - Deletion of artifacts in the media directory which will then fall out of
@@ -1,16 +1,24 @@
+mod access;
 pub mod blurhash;
 mod data;
+mod evict;
 pub(super) mod migrations;
 mod preview;
 mod remote;
+mod strip;
 mod tests;
 mod thumbnail;
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+	path::PathBuf,
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
 
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose};
 use conduwuit::{
-	Err, Result, Server, debug, debug_error, debug_info, debug_warn, err, error, trace,
+	Err, Result, Server, SyncMutex, debug, debug_error, debug_info, debug_warn, err, error,
+	trace,
 	utils::{
 		self, MutexMap,
 		time::{self, TimeDirection},
@@ -21,11 +29,20 @@
 use tokio::{
 	fs,
 	io::{AsyncReadExt, AsyncWriteExt, BufReader},
+	sync::Notify,
+	time::{Instant, MissedTickBehavior, interval, interval_at},
 };
 
-use self::data::{Data, Metadata};
-pub use self::thumbnail::Dim;
-use crate::{Dep, client, globals, moderation, sending};
+pub use self::{
+	access::Access,
+	evict::EvictionCandidate,
+	thumbnail::{Dim, Format},
+};
+use self::{
+	access::Pending,
+	data::{Data, Metadata},
+};
+use crate::{Dep, client, globals, moderation, sending, users};
 
 #[derive(Debug)]
 pub struct FileMeta {
@@ -36,6 +53,8 @@ pub struct FileMeta {
 
 pub struct Service {
 	url_preview_mutex: MutexMap<String, ()>,
+	pending_accesses: SyncMutex<Pending>,
+	interrupt: Notify,
 	pub(super) db: Data,
 	services: Services,
 }
@@ -46,6 +65,7 @@ struct Services {
 	globals: Dep<globals::Service>,
 	sending: Dep<sending::Service>,
 	moderation: Dep<moderation::Service>,
+	users: Dep<users::Service>,
 }
 
 /// generated MXC ID (`media-id`) length
@@ -57,11 +77,19 @@ struct Services {
 /// Default cross-origin resource policy.
 pub const CORP_CROSS_ORIGIN: &str = "cross-origin";
 
+/// How often recorded media accesses are written to the database.
+const ACCESS_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often unaccessed media is evicted.
+const EVICT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			url_preview_mutex: MutexMap::new(),
+			pending_accesses: SyncMutex::default(),
+			interrupt: Notify::new(),
 			db: Data::new(args.db),
 			services: Services {
 				server: args.server.clone(),
@@ -69,6 +97,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				globals: args.depend::<globals::Service>("globals"),
 				sending: args.depend::<sending::Service>("sending"),
 				moderation: args.depend::<moderation::Service>("moderation"),
+				users: args.depend::<users::Service>("users"),
 			},
 		}))
 	}
@@ -76,9 +105,29 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 	async fn worker(self: Arc<Self>) -> Result<()> {
 		self.create_media_dir().await?;
 
+		let mut flush = interval(ACCESS_FLUSH_INTERVAL);
+		flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		// Give accesses recorded since startup a chance to be counted first
+		let first_eviction = Instant::now()
+			.checked_add(EVICT_INTERVAL)
+			.unwrap_or_else(Instant::now);
+		let mut evict = interval_at(first_eviction, EVICT_INTERVAL);
+		evict.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = flush.tick() => self.flush_accesses().await,
+				_ = evict.tick() => self.evict_unaccessed().await,
+			}
+		}
+
+		self.flush_accesses().await;
+
 		Ok(())
 	}
 
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -177,6 +226,8 @@ pub async fn get(&self, mxc: &Mxc<'_>) -> Result<Option<FileMeta>> {
 					.read_to_end(&mut content)
 					.await?;
 
+				self.record_access(mxc);
+
 				Ok(Some(FileMeta {
 					content: Some(content),
 					content_type,
@@ -203,6 +203,7 @@ async fn handle_thumbnail_file(
 		&content.file,
 	)
 	.await
+	.inspect(|()| self.record_access(mxc))
 	.map(|()| FileMeta {
 		content: Some(content.file),
 		content_type: content.content_type.map(Into::into),
@@ -231,6 +232,7 @@ async fn handle_content_file(
 		&content.file,
 	)
 	.await
+	.inspect(|()| self.record_access(mxc))
 	.map(|()| FileMeta {
 		content: Some(content.file),
 		content_type: content.content_type.map(Into::into),
@@ -0,0 +1,207 @@
+//! Metadata stripping
+//!
+//! Removes EXIF, XMP and similar metadata (camera details, GPS location,
+//! editing history) from images uploaded by local users. Images are not
+//! decoded or re-encoded; only the containers' metadata segments are dropped,
+//! so the image data itself is preserved bit for bit.
+
+use std::borrow::Cow;
+
+use conduwuit::{debug, implement};
+
+use super::{Service, thumbnail::essence};
+
+/// JPEG APPn markers carrying EXIF/XMP (APP1) and IPTC (APP13), and comments.
+const JPEG_STRIPPED_MARKERS: [u8; 3] = [0xE1, 0xED, 0xFE];
+
+/// PNG ancillary chunks carrying metadata.
+const PNG_STRIPPED_CHUNKS: [&[u8; 4]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+/// EXIF orientation tag, which is kept so images still display upright.
+const EXIF_ORIENTATION: u16 = 0x0112;
+
+/// Strips metadata from an uploaded file if enabled by the configuration and
+/// the file is a JPEG, PNG or WebP image. Any other file, or one that cannot
+/// be parsed, is returned untouched.
+#[implement(Service)]
+pub fn strip_metadata<'a>(&self, content_type: Option<&str>, file: &'a [u8]) -> Cow<'a, [u8]> {
+	if !self.services.server.config.media_strip_metadata {
+		return Cow::Borrowed(file);
+	}
+
+	match strip(content_type, file) {
+		| Some(stripped) => {
+			debug!(before = file.len(), after = stripped.len(), "Stripped image metadata");
+			Cow::Owned(stripped)
+		},
+		| None => Cow::Borrowed(file),
+	}
+}
+
+/// Returns the file without its metadata, or None when it is not a supported
+/// image or is malformed.
+pub(super) fn strip(content_type: Option<&str>, file: &[u8]) -> Option<Vec<u8>> {
+	match content_type.map(essence) {
+		| Some("image/jpeg" | "image/jpg") => strip_jpeg(file),
+		| Some("image/png") => strip_png(file),
+		| Some("image/webp") => strip_webp(file),
+		| _ => None,
+	}
+}
+
+fn strip_jpeg(file: &[u8]) -> Option<Vec<u8>> {
+	let mut rest = file.strip_prefix(&[0xFF, 0xD8])?;
+	let mut out = Vec::with_capacity(file.len());
+	out.extend_from_slice(&[0xFF, 0xD8]);
+
+	loop {
+		let (&[0xFF, marker], after) = rest.split_first_chunk::<2>()? else {
+			return None;
+		};
+
+		// Start of scan: entropy-coded data follows up to the end of the image.
+		if marker == 0xDA {
+			out.extend_from_slice(rest);
+			return Some(out);
+		}
+
+		let len = usize::from(u16::from_be_bytes(*after.first_chunk::<2>()?));
+		let segment = rest.get(..len.checked_add(2)?)?;
+		let payload = segment.get(4..)?;
+
+		if marker == 0xE1 && payload.starts_with(b"Exif\0\0") {
+			if let Some(orientation) = exif_orientation(&payload[6..]) {
+				out.extend_from_slice(&orientation_segment(orientation));
+			}
+		} else if !JPEG_STRIPPED_MARKERS.contains(&marker) {
+			out.extend_from_slice(segment);
+		}
+
+		rest = &rest[segment.len()..];
+	}
+}
+
+/// Reads the orientation from a TIFF-structured EXIF payload, when it is not
+/// the default.
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+	let big_endian = match tiff.get(..4)? {
+		| b"MM\0\x2A" => true,
+		| b"II\x2A\0" => false,
+		| _ => return None,
+	};
+
+	let u16_at = |offset: usize| -> Option<u16> {
+		let bytes = *tiff.get(offset..)?.first_chunk::<2>()?;
+		Some(if big_endian {
+			u16::from_be_bytes(bytes)
+		} else {
+			u16::from_le_bytes(bytes)
+		})
+	};
+
+	let u32_at = |offset: usize| -> Option<usize> {
+		let bytes = *tiff.get(offset..)?.first_chunk::<4>()?;
+		let value = if big_endian {
+			u32::from_be_bytes(bytes)
+		} else {
+			u32::from_le_bytes(bytes)
+		};
+		usize::try_from(value).ok()
+	};
+
+	let ifd = u32_at(4)?;
+	let entries = usize::from(u16_at(ifd)?);
+	(0..entries)
+		.filter_map(|i| ifd.checked_add(2)?.checked_add(i.checked_mul(12)?))
+		.find(|&entry| u16_at(entry) == Some(EXIF_ORIENTATION))
+		.and_then(|entry| u16_at(entry.checked_add(8)?))
+		.filter(|orientation| (2..=8).contains(orientation))
+}
+
+/// A minimal APP1 segment holding nothing but the orientation tag.
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+	let mut tiff = Vec::with_capacity(26);
+	tiff.extend_from_slice(b"MM\0\x2A");
+	tiff.extend_from_slice(&8_u32.to_be_bytes());
+	tiff.extend_from_slice(&1_u16.to_be_bytes());
+	tiff.extend_from_slice(&EXIF_ORIENTATION.to_be_bytes());
+	tiff.extend_from_slice(&3_u16.to_be_bytes()); // SHORT
+	tiff.extend_from_slice(&1_u32.to_be_bytes());
+	tiff.extend_from_slice(&orientation.to_be_bytes());
+	tiff.extend_from_slice(&[0, 0]);
+	tiff.extend_from_slice(&0_u32.to_be_bytes()); // no further IFD
+
+	// the length covers itself, the EXIF header and the TIFF structure
+	let len = u16::try_from(tiff.len().saturating_add(8)).expect("segment length fits in u16");
+	let mut segment = Vec::with_capacity(tiff.len().saturating_add(10));
+	segment.extend_from_slice(&[0xFF, 0xE1]);
+	segment.extend_from_slice(&len.to_be_bytes());
+	segment.extend_from_slice(b"Exif\0\0");
+	segment.extend_from_slice(&tiff);
+	segment
+}
+
+fn strip_png(file: &[u8]) -> Option<Vec<u8>> {
+	const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+	let mut rest = file.strip_prefix(SIGNATURE)?;
+	let mut out = Vec::with_capacity(file.len());
+	out.extend_from_slice(SIGNATURE);
+
+	while !rest.is_empty() {
+		let len = usize::try_from(u32::from_be_bytes(*rest.first_chunk::<4>()?)).ok()?;
+		let chunk_type = rest.get(4..8)?;
+		// length, type, data and CRC
+		let chunk = rest.get(..len.checked_add(12)?)?;
+
+		if !PNG_STRIPPED_CHUNKS
+			.iter()
+			.any(|stripped| stripped[..] == *chunk_type)
+		{
+			out.extend_from_slice(chunk);
+		}
+
+		rest = &rest[chunk.len()..];
+	}
+
+	Some(out)
+}
+
+fn strip_webp(file: &[u8]) -> Option<Vec<u8>> {
+	const VP8X_EXIF: u8 = 0x08;
+	const VP8X_XMP: u8 = 0x04;
+
+	if file.get(..4)? != b"RIFF" || file.get(8..12)? != b"WEBP" {
+		return None;
+	}
+
+	let mut rest = file.get(12..)?;
+	let mut out = Vec::with_capacity(file.len());
+	out.extend_from_slice(&file[..12]);
+
+	while !rest.is_empty() {
+		let fourcc = rest.get(..4)?;
+		let len =
+			usize::try_from(u32::from_le_bytes(*rest.get(4..)?.first_chunk::<4>()?)).ok()?;
+		// chunks are padded to an even size
+		let padded = len.checked_add(len & 1)?;
+		let chunk = rest.get(..padded.checked_add(8)?)?;
+
+		match fourcc {
+			| b"EXIF" | b"XMP " => {},
+			| b"VP8X" => {
+				let mut chunk = chunk.to_vec();
+				*chunk.get_mut(8)? &= !(VP8X_EXIF | VP8X_XMP);
+				out.extend_from_slice(&chunk);
+			},
+			| _ => out.extend_from_slice(chunk),
+		}
+
+		rest = &rest[chunk.len()..];
+	}
+
+	let riff_len = u32::try_from(out.len().checked_sub(8)?).ok()?;
+	out[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+	Some(out)
+}
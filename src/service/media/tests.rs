@@ -103,3 +103,172 @@ fn get_url_preview(&self, _url: &str) -> Option<UrlPreviewData> { todo!() }
 		r.to_str().unwrap().len()
 	);
 }
+
+#[test]
+fn strip_jpeg_keeps_orientation_only() {
+	use super::strip::strip;
+
+	// SOI, APP1 Exif with orientation 6 and a GPS pointer, COM, SOS
+	let exif: &[u8] = &[
+		b'E', b'x', b'i', b'f', 0, 0, b'I', b'I', 0x2A, 0, 8, 0, 0, 0, 2, 0, 0x12, 0x01, 3, 0, 1,
+		0, 0, 0, 6, 0, 0, 0, 0x25, 0x88, 4, 0, 1, 0, 0, 0, 0x26, 0, 0, 0, 0, 0, 0, 0,
+	];
+	let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+	jpeg.extend_from_slice(
+		&u16::try_from(exif.len().saturating_add(2))
+			.unwrap()
+			.to_be_bytes(),
+	);
+	jpeg.extend_from_slice(exif);
+	jpeg.extend_from_slice(&[0xFF, 0xFE, 0, 6, b'h', b'i', b'!', b'!']);
+	jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 2, 1, 2, 3, 0xFF, 0xD9]);
+
+	let stripped = strip(Some("image/jpeg"), &jpeg).expect("valid JPEG");
+	let mut expected = vec![0xFF, 0xD8, 0xFF, 0xE1, 0, 34];
+	expected.extend_from_slice(b"Exif\0\0MM\0\x2A\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06");
+	expected.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+	expected.extend_from_slice(&[0xFF, 0xDA, 0, 2, 1, 2, 3, 0xFF, 0xD9]);
+	assert_eq!(stripped, expected, "only the orientation should remain");
+}
+
+#[test]
+fn strip_png_text_chunks() {
+	use super::strip::strip;
+
+	let chunk = |kind: &[u8; 4], data: &[u8]| {
+		let mut chunk = u32::try_from(data.len()).unwrap().to_be_bytes().to_vec();
+		chunk.extend_from_slice(kind);
+		chunk.extend_from_slice(data);
+		chunk.extend_from_slice(&[0, 0, 0, 0]);
+		chunk
+	};
+
+	let signature = b"\x89PNG\r\n\x1a\n";
+	let ihdr = chunk(b"IHDR", &[0; 13]);
+	let idat = chunk(b"IDAT", &[1, 2, 3]);
+	let iend = chunk(b"IEND", &[]);
+	let png = [
+		signature.as_slice(),
+		ihdr.as_slice(),
+		chunk(b"tEXt", b"Author\0someone").as_slice(),
+		chunk(b"eXIf", b"MM\0\x2A").as_slice(),
+		idat.as_slice(),
+		iend.as_slice(),
+	]
+	.concat();
+
+	let stripped = strip(Some("image/png"), &png).expect("valid PNG");
+	let expected = [signature.as_slice(), ihdr.as_slice(), idat.as_slice(), iend.as_slice()];
+	assert_eq!(stripped, expected.concat(), "text and EXIF chunks should be removed");
+}
+
+#[test]
+fn strip_ignores_other_files() {
+	use super::strip::strip;
+
+	assert!(strip(Some("image/png"), b"not a png").is_none(), "malformed files are kept");
+	assert!(strip(Some("text/plain"), b"hello").is_none(), "non-images are kept");
+	assert!(strip(None, b"\xFF\xD8").is_none(), "files without a type are kept");
+}
+
+#[test]
+fn thumbnail_format() {
+	use super::thumbnail::Format;
+
+	let webp = Some("image/avif,image/webp,*/*;q=0.8");
+	assert_eq!(Format::choose(Some("image/gif"), true, None), Format::Gif);
+	assert_eq!(Format::choose(Some("image/webp"), true, webp), Format::Gif);
+	assert_eq!(Format::choose(Some("image/gif"), false, None), Format::Png);
+	assert_eq!(Format::choose(Some("image/jpeg"), true, webp), Format::Jpeg);
+	assert_eq!(Format::choose(Some("image/png; charset=binary"), false, webp), Format::WebP);
+	assert_eq!(Format::choose(Some("image/png"), false, Some("image/*")), Format::Png);
+}
+
+#[test]
+fn eviction_policy_skips_kept_and_local_media() {
+	use std::collections::HashSet;
+
+	use ruma::{OwnedMxcUri, server_name};
+
+	use super::evict::Policy;
+
+	let remote: OwnedMxcUri = "mxc://remote.example.com/media".into();
+	let local: OwnedMxcUri = "mxc://example.com/media".into();
+	let avatar: OwnedMxcUri = "mxc://remote.example.com/avatar".into();
+	let keep = HashSet::from([avatar.clone()]);
+
+	let mut policy = Policy {
+		cutoff: 1_000,
+		server_name: server_name!("example.com"),
+		evict_local_media: false,
+		keep: &keep,
+	};
+
+	assert!(policy.may_evict(&remote));
+	assert!(!policy.may_evict(&local), "local media is kept by default");
+	assert!(!policy.may_evict(&avatar), "kept media is never evicted");
+
+	policy.evict_local_media = true;
+	assert!(policy.may_evict(&local));
+	assert!(!policy.may_evict(&avatar));
+}
+
+#[test]
+fn eviction_candidates_by_last_access() {
+	use std::collections::HashSet;
+
+	use ruma::{OwnedMxcUri, server_name};
+
+	use super::{access::Access, evict::Policy};
+
+	let mxc: OwnedMxcUri = "mxc://remote.example.com/media".into();
+	let keep = HashSet::new();
+	let policy = Policy {
+		cutoff: 1_000,
+		server_name: server_name!("example.com"),
+		evict_local_media: false,
+		keep: &keep,
+	};
+
+	let stale = Access { last_access: 999, hits: 3 };
+	let candidate = policy
+		.candidate(mxc.clone(), Some(stale), None)
+		.expect("media accessed before the cutoff is evicted");
+	assert_eq!((candidate.mxc, candidate.last_access, candidate.hits), (mxc.clone(), 999, 3));
+
+	let fresh = Access { last_access: 1_000, hits: 1 };
+	assert!(policy.candidate(mxc.clone(), Some(fresh), None).is_none());
+
+	// a recent access outweighs an old file
+	assert!(
+		policy
+			.candidate(mxc.clone(), Some(fresh), Some(10))
+			.is_none()
+	);
+}
+
+#[test]
+fn eviction_falls_back_to_file_creation() {
+	use std::collections::HashSet;
+
+	use ruma::{OwnedMxcUri, server_name};
+
+	use super::evict::Policy;
+
+	let mxc: OwnedMxcUri = "mxc://remote.example.com/media".into();
+	let keep = HashSet::new();
+	let policy = Policy {
+		cutoff: 1_000,
+		server_name: server_name!("example.com"),
+		evict_local_media: false,
+		keep: &keep,
+	};
+
+	let candidate = policy
+		.candidate(mxc.clone(), None, Some(10))
+		.expect("media never accessed since long ago is evicted");
+	assert_eq!((candidate.last_access, candidate.hits), (10, 0));
+
+	assert!(policy.candidate(mxc.clone(), None, Some(1_000)).is_none());
+	assert!(policy.candidate(mxc, None, None).is_none(), "media of unknown age is kept");
+}
@@ -24,6 +24,27 @@ pub struct Dim {
 	pub method: Method,
 }
 
+/// Image format of a generated thumbnail.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+	Png,
+	Jpeg,
+	WebP,
+	/// Used for animated thumbnails.
+	Gif,
+}
+
+/// Upper bound on the frames of an animated thumbnail; longer animations are
+/// cut short.
+#[cfg(feature = "media_thumbnail")]
+const MAX_ANIMATION_FRAMES: usize = 256;
+
+/// Upper bound on the pixels decoded for an animated thumbnail, i.e. the canvas
+/// area times the number of frames. Animations with a larger canvas are cut
+/// to fewer frames.
+#[cfg(feature = "media_thumbnail")]
+const MAX_ANIMATION_PIXELS: u64 = 64 * 1024 * 1024;
+
 impl super::Service {
 	/// Uploads or replaces a file thumbnail.
 	#[allow(clippy::too_many_arguments)]
@@ -60,17 +81,58 @@ pub async fn upload_thumbnail(
 	///
 	/// For width,height <= 96 the server uses another thumbnailing algorithm
 	/// which crops the image afterwards.
+	///
+	/// The thumbnail's format is chosen from the original's and from what the
+	/// client accepts (see [`Format::choose`]); animated GIF and WebP images
+	/// keep their animation when `animated` is requested.
 	#[tracing::instrument(skip(self), name = "thumbnail", level = "debug")]
-	pub async fn get_thumbnail(&self, mxc: &Mxc<'_>, dim: &Dim) -> Result<Option<FileMeta>> {
+	pub async fn get_thumbnail(
+		&self,
+		mxc: &Mxc<'_>,
+		dim: &Dim,
+		animated: bool,
+		accept: Option<&str>,
+	) -> Result<Option<FileMeta>> {
+		let thumbnail = self.find_thumbnail(mxc, dim, animated, accept).await?;
+		if thumbnail.is_some() {
+			self.record_access(mxc);
+		}
+
+		Ok(thumbnail)
+	}
+
+	async fn find_thumbnail(
+		&self,
+		mxc: &Mxc<'_>,
+		dim: &Dim,
+		animated: bool,
+		accept: Option<&str>,
+	) -> Result<Option<FileMeta>> {
 		// 0, 0 because that's the original file
 		let dim = dim.normalized();
 
-		match self.db.search_file_metadata(mxc, &dim).await {
-			| Ok(metadata) => self.get_thumbnail_saved(metadata).await,
-			| _ => match self.db.search_file_metadata(mxc, &Dim::default()).await {
-				| Ok(metadata) => self.get_thumbnail_generate(mxc, &dim, metadata).await,
+		let Ok(original) = self.db.search_file_metadata(mxc, &Dim::default()).await else {
+			// Only thumbnails fetched from a remote server are known
+			return match self.db.search_file_metadata(mxc, &dim).await {
+				| Ok(metadata) => self.get_thumbnail_saved(metadata).await,
 				| _ => Ok(None),
-			},
+			};
+		};
+
+		if dim.width == 0 || dim.height == 0 {
+			return self.get_thumbnail_saved(original).await;
+		}
+
+		let format = Format::choose(original.content_type.as_deref(), animated, accept);
+		match self
+			.db
+			.search_thumbnail_metadata(mxc, &dim, format.content_type())
+			.await
+		{
+			| Ok(metadata) => self.get_thumbnail_saved(metadata).await,
+			| _ =>
+				self.get_thumbnail_generate(mxc, &dim, format, original)
+					.await,
 		}
 	}
 }
@@ -97,6 +159,7 @@ async fn get_thumbnail_generate(
 	&self,
 	mxc: &Mxc<'_>,
 	dim: &Dim,
+	format: Format,
 	data: Metadata,
 ) -> Result<Option<FileMeta>> {
 	let mut content = Vec::new();
@@ -106,21 +169,16 @@ async fn get_thumbnail_generate(
 		.read_to_end(&mut content)
 		.await?;
 
-	let Ok(image) = image::load_from_memory(&content) else {
-		// Couldn't parse file to generate thumbnail, send original
-		return Ok(Some(into_filemeta(data, content)));
+	let thumbnail_bytes = match format {
+		| Format::Gif => thumbnail_animated(&content, data.content_type.as_deref(), dim)?,
+		| _ => thumbnail_static(&content, dim, format)?,
 	};
 
-	if dim.width > image.width() || dim.height > image.height() {
+	let Some(thumbnail_bytes) = thumbnail_bytes else {
+		// Couldn't parse file to generate thumbnail, or it is smaller than
+		// requested: send original
 		return Ok(Some(into_filemeta(data, content)));
-	}
-
-	let mut thumbnail_bytes = Vec::new();
-	let thumbnail = thumbnail_generate(&image, dim)?;
-	let mut cursor = std::io::Cursor::new(&mut thumbnail_bytes);
-	thumbnail
-		.write_to(&mut cursor, image::ImageFormat::Png)
-		.map_err(|error| err!(error!(%error, "Error writing PNG thumbnail.")))?;
+	};
 
 	// Save thumbnail in database so we don't have to generate it again next time
 	let thumbnail_key = self.db.create_file_metadata(
@@ -128,13 +186,17 @@ async fn get_thumbnail_generate(
 		None,
 		dim,
 		data.content_disposition.as_ref(),
-		data.content_type.as_deref(),
+		Some(format.content_type()),
 	)?;
 
 	let mut f = self.create_media_file(&thumbnail_key).await?;
 	f.write_all(&thumbnail_bytes).await?;
 
-	Ok(Some(into_filemeta(data, thumbnail_bytes)))
+	Ok(Some(FileMeta {
+		content: Some(thumbnail_bytes),
+		content_type: Some(format.content_type().to_owned()),
+		content_disposition: data.content_disposition,
+	}))
 }
 
 #[cfg(not(feature = "media_thumbnail"))]
@@ -144,11 +206,127 @@ async fn get_thumbnail_generate(
 	&self,
 	_mxc: &Mxc<'_>,
 	_dim: &Dim,
+	_format: Format,
 	data: Metadata,
 ) -> Result<Option<FileMeta>> {
 	self.get_thumbnail_saved(data).await
 }
 
+/// Encodes a still thumbnail of the image, or returns None when the image
+/// can't be decoded or is already smaller than requested.
+#[cfg(feature = "media_thumbnail")]
+fn thumbnail_static(content: &[u8], dim: &Dim, format: Format) -> Result<Option<Vec<u8>>> {
+	use image::{DynamicImage, ImageFormat};
+
+	let Ok(image) = image::load_from_memory(content) else {
+		return Ok(None);
+	};
+
+	if dim.width > image.width() || dim.height > image.height() {
+		return Ok(None);
+	}
+
+	let thumbnail = thumbnail_generate(&image, dim)?;
+	let (thumbnail, image_format) = match format {
+		// JPEG has no alpha channel
+		| Format::Jpeg => (DynamicImage::ImageRgb8(thumbnail.to_rgb8()), ImageFormat::Jpeg),
+		// the WebP encoder only accepts 8-bit RGB(A)
+		| Format::WebP => (DynamicImage::ImageRgba8(thumbnail.to_rgba8()), ImageFormat::WebP),
+		| Format::Png | Format::Gif => (thumbnail, ImageFormat::Png),
+	};
+
+	let mut thumbnail_bytes = Vec::new();
+	let mut cursor = std::io::Cursor::new(&mut thumbnail_bytes);
+	thumbnail
+		.write_to(&mut cursor, image_format)
+		.map_err(|error| err!(error!(%error, "Error writing {format:?} thumbnail.")))?;
+
+	Ok(Some(thumbnail_bytes))
+}
+
+/// Encodes an animated GIF thumbnail of an animated GIF or WebP image, or
+/// returns None when the image can't be decoded or is already smaller than
+/// requested.
+#[cfg(feature = "media_thumbnail")]
+fn thumbnail_animated(
+	content: &[u8],
+	content_type: Option<&str>,
+	dim: &Dim,
+) -> Result<Option<Vec<u8>>> {
+	use std::io::Cursor;
+
+	use image::codecs::{gif::GifDecoder, webp::WebPDecoder};
+
+	match content_type.map(essence) {
+		| Some("image/gif") => match GifDecoder::new(Cursor::new(content)) {
+			| Ok(decoder) => thumbnail_frames(decoder, dim),
+			| Err(_) => Ok(None),
+		},
+		| Some("image/webp") => match WebPDecoder::new(Cursor::new(content)) {
+			| Ok(decoder) => thumbnail_frames(decoder, dim),
+			| Err(_) => Ok(None),
+		},
+		| _ => Ok(None),
+	}
+}
+
+/// Resizes the frames of an animation as they are decoded, so that only one
+/// full-canvas frame is held in memory at a time.
+#[cfg(feature = "media_thumbnail")]
+fn thumbnail_frames<'a, D>(decoder: D, dim: &Dim) -> Result<Option<Vec<u8>>>
+where
+	D: image::AnimationDecoder<'a> + image::ImageDecoder,
+{
+	use image::{
+		DynamicImage, Frame,
+		codecs::gif::{GifEncoder, Repeat},
+	};
+
+	let (width, height) = decoder.dimensions();
+	if dim.width > width || dim.height > height {
+		return Ok(None);
+	}
+
+	let area = u64::from(width).saturating_mul(u64::from(height)).max(1);
+	let max_frames = MAX_ANIMATION_PIXELS
+		.checked_div(area)
+		.and_then(|frames| usize::try_from(frames).ok())
+		.unwrap_or(0)
+		.min(MAX_ANIMATION_FRAMES);
+
+	if max_frames == 0 {
+		return Ok(None);
+	}
+
+	// Frames are decoded onto the full canvas, so they can be resized alike.
+	let mut frames = Vec::new();
+	for frame in decoder.into_frames().take(max_frames) {
+		let Ok(frame) = frame else {
+			return Ok(None);
+		};
+
+		let delay = frame.delay();
+		let image = DynamicImage::ImageRgba8(frame.into_buffer());
+		let thumbnail = thumbnail_generate(&image, dim)?;
+		frames.push(Frame::from_parts(thumbnail.to_rgba8(), 0, 0, delay));
+	}
+
+	if frames.is_empty() {
+		return Ok(None);
+	}
+
+	let mut thumbnail_bytes = Vec::new();
+	{
+		let mut encoder = GifEncoder::new(&mut thumbnail_bytes);
+		encoder
+			.set_repeat(Repeat::Infinite)
+			.and_then(|()| encoder.encode_frames(frames))
+			.map_err(|error| err!(error!(%error, "Error writing animated thumbnail.")))?;
+	}
+
+	Ok(Some(thumbnail_bytes))
+}
+
 #[cfg(feature = "media_thumbnail")]
 fn thumbnail_generate(
 	image: &image::DynamicImage,
@@ -263,3 +441,47 @@ fn default() -> Self {
 		}
 	}
 }
+
+impl Format {
+	/// Chooses the thumbnail format for an image of `content_type`.
+	///
+	/// Animated thumbnails of GIF and WebP images are GIFs, since the WebP
+	/// encoder can't write animations. Photos stay JPEG; anything else becomes
+	/// a lossless WebP when the client's `Accept` header allows it, PNG
+	/// otherwise.
+	#[must_use]
+	pub fn choose(content_type: Option<&str>, animated: bool, accept: Option<&str>) -> Self {
+		match content_type.map(essence) {
+			| Some("image/gif" | "image/webp") if animated => Self::Gif,
+			| Some("image/jpeg" | "image/jpg") => Self::Jpeg,
+			| _ if accept.is_some_and(accepts_webp) => Self::WebP,
+			| _ => Self::Png,
+		}
+	}
+
+	#[must_use]
+	pub fn content_type(self) -> &'static str {
+		match self {
+			| Self::Png => "image/png",
+			| Self::Jpeg => "image/jpeg",
+			| Self::WebP => "image/webp",
+			| Self::Gif => "image/gif",
+		}
+	}
+}
+
+/// The MIME type of a content type, without parameters.
+pub(super) fn essence(content_type: &str) -> &str {
+	content_type
+		.split(';')
+		.next()
+		.unwrap_or(content_type)
+		.trim()
+}
+
+fn accepts_webp(accept: &str) -> bool {
+	accept
+		.split(',')
+		.map(essence)
+		.any(|media_range| media_range.eq_ignore_ascii_case("image/webp"))
+}
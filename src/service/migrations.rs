@@ -4,7 +4,7 @@
 	Err, Pdu, Result, debug, debug_info, debug_warn, error, info,
 	result::NotFound,
 	utils::{
-		IterStream, ReadyExt,
+		self, IterStream, ReadyExt,
 		stream::{TryExpect, TryIgnore},
 	},
 	warn,
@@ -65,6 +65,9 @@ async fn fresh(services: &Services) -> Result<()> {
 	db["global"].insert(b"retroactively_fix_bad_data_from_roomuserid_joined", []);
 	db["global"].insert(b"fix_referencedevents_missing_sep", []);
 	db["global"].insert(b"fix_readreceiptid_readreceipt_duplicates", []);
+	db["global"].insert(PRUNED_ROOMSYNCTOKEN_SHORTSTATEHASH_MARKER, []);
+	db["global"].insert(b"drop_legacy_thumbnails", []);
+	db["global"].insert(RESTRICTED_ADMIN_ROOM_POWER_LEVELS_MARKER, []);
 
 	// Create the admin room and server user on first run
 	crate::admin::create_admin_room(services).boxed().await?;
@@ -102,6 +105,14 @@ async fn migrate(services: &Services) -> Result<()> {
 		media::migrations::checkup_sha256_media(services).await?;
 	}
 
+	if db["global"]
+		.get(b"drop_legacy_thumbnails")
+		.await
+		.is_not_found()
+	{
+		media::migrations::drop_legacy_thumbnails(services).await?;
+	}
+
 	if db["global"]
 		.get(b"fix_bad_double_separator_in_state_cache")
 		.await
@@ -162,6 +173,22 @@ async fn migrate(services: &Services) -> Result<()> {
 		populate_userroomid_leftstate_table(services).await?;
 	}
 
+	if db["global"]
+		.get(PRUNED_ROOMSYNCTOKEN_SHORTSTATEHASH_MARKER)
+		.await
+		.is_not_found()
+	{
+		prune_roomsynctoken_shortstatehash(services).await?;
+	}
+
+	if db["global"]
+		.get(RESTRICTED_ADMIN_ROOM_POWER_LEVELS_MARKER)
+		.await
+		.is_not_found()
+	{
+		restrict_admin_room_power_levels(services).await?;
+	}
+
 	assert_eq!(
 		services.globals.db.database_version().await,
 		DATABASE_VERSION,
@@ -721,3 +748,64 @@ async fn populate_userroomid_leftstate_table(services: &Services) -> Result {
 	db.db.sort()?;
 	Ok(())
 }
+
+const PRUNED_ROOMSYNCTOKEN_SHORTSTATEHASH_MARKER: &str = "prune_roomsynctoken_shortstatehash";
+async fn prune_roomsynctoken_shortstatehash(services: &Services) -> Result {
+	// Sync used to record the state of every joined room for every sync token,
+	// even when the state had not changed. Only changes are recorded now and
+	// lookups fall back to the latest earlier token, so every entry repeating
+	// the state of the room's previous entry is redundant.
+
+	warn!("Pruning redundant entries in roomsynctoken_shortstatehash...");
+
+	let db = &services.db;
+	let cork = db.cork_and_sync();
+	let roomsynctoken_shortstatehash = db["roomsynctoken_shortstatehash"].clone();
+
+	let mut last: Option<(u64, ShortStateHash)> = None;
+	let (mut total, mut pruned): (usize, usize) = (0, 0);
+	roomsynctoken_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_for_each(|(key, val)| {
+			total = total.saturating_add(1);
+			let shortroomid = key.get(..8).and_then(|id| utils::u64_from_bytes(id).ok());
+			let (Some(shortroomid), Ok(shortstatehash)) =
+				(shortroomid, utils::u64_from_bytes(val))
+			else {
+				return;
+			};
+
+			let current = (shortroomid, shortstatehash);
+			if last.replace(current) == Some(current) {
+				roomsynctoken_shortstatehash.remove(key);
+				pruned = pruned.saturating_add(1);
+			}
+		})
+		.await;
+
+	drop(cork);
+	info!(?total, ?pruned, "Pruned redundant entries in roomsynctoken_shortstatehash.");
+
+	db["global"].insert(PRUNED_ROOMSYNCTOKEN_SHORTSTATEHASH_MARKER, []);
+	db.db.sort()?;
+	Ok(())
+}
+
+const RESTRICTED_ADMIN_ROOM_POWER_LEVELS_MARKER: &str = "restrict_admin_room_power_levels";
+async fn restrict_admin_room_power_levels(services: &Services) -> Result {
+	// Admin rooms used to be created with the default power levels, which let
+	// users holding a delegated role invite anyone, and whoever joins the admin
+	// room without a role is a full admin.
+
+	warn!("Restricting the power levels of the admin room...");
+	services
+		.admin
+		.restrict_admin_room_power_levels()
+		.boxed()
+		.await?;
+
+	services.db["global"].insert(RESTRICTED_ADMIN_ROOM_POWER_LEVELS_MARKER, []);
+	info!("Restricted the power levels of the admin room.");
+	Ok(())
+}
@@ -14,8 +14,10 @@
 pub mod announcements;
 pub mod antispam;
 pub mod appservice;
+pub mod audit;
 pub mod client;
 pub mod config;
+pub mod delayed_events;
 pub mod emergency;
 pub mod federation;
 pub mod globals;
@@ -25,6 +27,7 @@
 pub mod presence;
 pub mod pusher;
 pub mod registration_tokens;
+pub mod rendezvous;
 pub mod resolver;
 pub mod rooms;
 pub mod sending;
@@ -1,12 +1,24 @@
-use std::sync::Arc;
+mod servers;
 
-use conduwuit::{Result, implement};
+use std::{fmt::Write, sync::Arc};
+
+use async_trait::async_trait;
+use conduwuit::{Result, SyncRwLock, implement};
+use database::Map;
 use ruma::ServerName;
 
+use self::servers::Rules;
+pub use self::servers::{RuleKind, ServerRule};
 use crate::{Dep, config};
 
 pub struct Service {
+	db: Data,
 	services: Services,
+	rules: SyncRwLock<Rules>,
+}
+
+struct Data {
+	servername_rule: Arc<Map>,
 }
 
 struct Services {
@@ -14,16 +26,34 @@ struct Services {
 	pub config: Dep<config::Service>,
 }
 
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
+			db: Data {
+				servername_rule: args.db["servername_rule"].clone(),
+			},
 			services: Services {
 				// server: args.server.clone(),
 				config: args.depend::<config::Service>("config"),
 			},
+			rules: SyncRwLock::new(Rules::default()),
 		}))
 	}
 
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		self.load_server_rules().await;
+
+		Ok(())
+	}
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let rules = self.rules.read().len();
+		writeln!(out, "server_rules: {rules}")?;
+
+		Ok(())
+	}
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -35,10 +65,12 @@ pub fn is_remote_server_ignored(&self, server_name: &ServerName) -> bool {
 		return false;
 	}
 
-	self.services
-		.config
-		.ignore_messages_from_server_names
-		.is_match(server_name.host())
+	self.has_server_rule(RuleKind::Ignore, server_name)
+		|| self
+			.services
+			.config
+			.ignore_messages_from_server_names
+			.is_match(server_name.host())
 }
 
 #[implement(Service)]
@@ -49,6 +81,11 @@ pub fn is_remote_server_forbidden(&self, server_name: &ServerName) -> bool {
 		return false;
 	}
 
+	// Blocks added at runtime take precedence over the static allow list
+	if self.has_server_rule(RuleKind::Block, server_name) {
+		return true;
+	}
+
 	// Check if server is explicitly allowed
 	if self
 		.services
@@ -0,0 +1,239 @@
+//! Runtime server rules
+//!
+//! Admins can block or ignore remote servers at runtime in addition to the
+//! static `forbidden_remote_server_names` and
+//! `ignore_messages_from_server_names` config options. Rules are persisted in
+//! the database and mirrored in memory, so the federation checks made for
+//! every request stay synchronous and take effect as soon as a rule changes.
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use conduwuit::{
+	Err, Result, debug_info, implement,
+	utils::{self, stream::TryIgnore, time::now_millis},
+};
+use database::Json;
+use futures::StreamExt;
+use ruma::{OwnedServerName, OwnedUserId, ServerName};
+use serde::{Deserialize, Serialize};
+
+use super::Service;
+
+/// What a server rule does to the server it names.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RuleKind {
+	/// Federation with the server is forbidden in both directions.
+	Block,
+	/// Messages from the server are hidden from local clients.
+	Ignore,
+}
+
+/// A runtime rule against a remote server.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerRule {
+	/// The admin who added the rule.
+	pub added_by: OwnedUserId,
+
+	/// When the rule was added, in milliseconds since the unix epoch.
+	pub added: u64,
+
+	/// When the rule stops applying, in milliseconds since the unix epoch.
+	pub expires: Option<u64>,
+
+	/// Why the rule was added.
+	pub reason: Option<String>,
+}
+
+/// The rules currently in effect, by kind.
+#[derive(Default)]
+pub(super) struct Rules {
+	blocked: BTreeMap<OwnedServerName, ServerRule>,
+	ignored: BTreeMap<OwnedServerName, ServerRule>,
+}
+
+/// Loads the persisted rules, discarding those which have expired.
+#[implement(Service)]
+pub(super) async fn load_server_rules(&self) {
+	let rules: Vec<((String, OwnedServerName), ServerRule)> = self
+		.db
+		.servername_rule
+		.stream()
+		.ignore_err()
+		.map(|((kind, server_name), rule): ((&str, &ServerName), ServerRule)| {
+			((kind.to_owned(), server_name.to_owned()), rule)
+		})
+		.collect()
+		.await;
+
+	let mut loaded = Rules::default();
+	for ((kind, server_name), rule) in rules {
+		let Some(kind) = RuleKind::parse(&kind) else {
+			continue;
+		};
+
+		if rule.is_expired() {
+			self.db.servername_rule.del((kind.as_str(), &server_name));
+			continue;
+		}
+
+		loaded.get_mut(kind).insert(server_name, rule);
+	}
+
+	*self.rules.write() = loaded;
+}
+
+/// Adds or replaces a rule against a server. The rule applies to every
+/// request made from then on.
+#[implement(Service)]
+pub fn add_server_rule(
+	&self,
+	kind: RuleKind,
+	server_name: &ServerName,
+	rule: ServerRule,
+) -> Result {
+	if server_name == self.services.config.server_name {
+		return Err!("Refusing to {kind} our own server name.");
+	}
+
+	self.db
+		.servername_rule
+		.put((kind.as_str(), server_name), Json(&rule));
+
+	self.rules
+		.write()
+		.get_mut(kind)
+		.insert(server_name.to_owned(), rule);
+
+	debug_info!("Added {kind} rule for {server_name}");
+	Ok(())
+}
+
+/// Removes the rule of the given kind against a server. Returns the removed
+/// rule, if there was one.
+#[implement(Service)]
+pub fn remove_server_rule(&self, kind: RuleKind, server_name: &ServerName) -> Option<ServerRule> {
+	self.db.servername_rule.del((kind.as_str(), server_name));
+
+	let removed = self.rules.write().get_mut(kind).remove(server_name);
+	if removed.is_some() {
+		debug_info!("Removed {kind} rule for {server_name}");
+	}
+
+	removed
+}
+
+/// Returns the rules of the given kind which are in effect, ordered by server
+/// name.
+#[implement(Service)]
+#[must_use]
+pub fn server_rules(&self, kind: RuleKind) -> Vec<(OwnedServerName, ServerRule)> {
+	self.rules
+		.read()
+		.get(kind)
+		.iter()
+		.filter(|(_, rule)| !rule.is_expired())
+		.map(|(server_name, rule)| (server_name.clone(), rule.clone()))
+		.collect()
+}
+
+/// Whether a rule of the given kind is in effect against the server, either
+/// under its full name or, for names with a port, under its host alone.
+#[implement(Service)]
+pub(super) fn has_server_rule(&self, kind: RuleKind, server_name: &ServerName) -> bool {
+	let rules = self.rules.read();
+	let rules = rules.get(kind);
+	if rules.is_empty() {
+		return false;
+	}
+
+	let host = <&ServerName>::try_from(server_name.host()).ok();
+	[Some(server_name), host]
+		.into_iter()
+		.flatten()
+		.filter_map(|name| rules.get(name))
+		.any(|rule| !rule.is_expired())
+}
+
+impl Rules {
+	fn get(&self, kind: RuleKind) -> &BTreeMap<OwnedServerName, ServerRule> {
+		match kind {
+			| RuleKind::Block => &self.blocked,
+			| RuleKind::Ignore => &self.ignored,
+		}
+	}
+
+	fn get_mut(&mut self, kind: RuleKind) -> &mut BTreeMap<OwnedServerName, ServerRule> {
+		match kind {
+			| RuleKind::Block => &mut self.blocked,
+			| RuleKind::Ignore => &mut self.ignored,
+		}
+	}
+
+	pub(super) fn len(&self) -> usize { self.blocked.len().saturating_add(self.ignored.len()) }
+}
+
+impl ServerRule {
+	/// Creates a rule added now by `added_by`, expiring after `expires_in`.
+	#[must_use]
+	pub fn new(
+		added_by: OwnedUserId,
+		expires_in: Option<Duration>,
+		reason: Option<String>,
+	) -> Self {
+		let added = now_millis();
+		let expires = expires_in.map(|expires_in| {
+			let expires_in = expires_in.as_millis().try_into().unwrap_or(u64::MAX);
+			added.saturating_add(expires_in)
+		});
+
+		Self { added_by, added, expires, reason }
+	}
+
+	#[must_use]
+	pub fn is_expired(&self) -> bool {
+		self.expires.is_some_and(|expires| expires <= now_millis())
+	}
+}
+
+impl fmt::Display for ServerRule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let added = Duration::from_millis(now_millis().saturating_sub(self.added));
+		write!(f, "added by {} {} ago", self.added_by, utils::time::pretty(added))?;
+
+		match self.expires {
+			| Some(expires) => {
+				let remaining = Duration::from_millis(expires.saturating_sub(now_millis()));
+				write!(f, ", expires in {}", utils::time::pretty(remaining))?;
+			},
+			| None => write!(f, ", never expires")?,
+		}
+
+		if let Some(reason) = &self.reason {
+			write!(f, ": {reason}")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl RuleKind {
+	#[must_use]
+	pub fn as_str(self) -> &'static str {
+		match self {
+			| Self::Block => "block",
+			| Self::Ignore => "ignore",
+		}
+	}
+
+	fn parse(kind: &str) -> Option<Self> {
+		match kind {
+			| "block" => Some(Self::Block),
+			| "ignore" => Some(Self::Ignore),
+			| _ => None,
+		}
+	}
+}
+
+impl fmt::Display for RuleKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
@@ -0,0 +1,366 @@
+//! # Rendezvous service
+//!
+//! In-memory store backing the MSC4108 rendezvous API. A rendezvous session is
+//! a short-lived mailbox through which a new device and an already signed-in
+//! one (e.g. after scanning a QR code) exchange opaque payloads, ultimately to
+//! hand the new device a login token. Every write replaces the payload and
+//! rotates the session's ETag; readers may long-poll for the next write.
+//!
+//! The signed-in device may write to a session with its access token, which
+//! makes it the session's approver. An approver whose device is verified by
+//! the user's self-signing key may then obtain one login token from
+//! `/login/get_token` without interactive authentication, and pass it to the
+//! new device over the rendezvous channel. Requiring cross-signing stops a
+//! client holding nothing but an access token from approving its own session.
+//!
+//! Sessions are never persisted and are forgotten on restart.
+
+mod tests;
+
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use conduwuit::{Err, Error, Result, Server, SyncMutex, debug, implement, utils};
+use ruma::{
+	CanonicalJsonObject, CanonicalJsonValue, DeviceId, OwnedDeviceId, OwnedUserId, UserId,
+	api::client::error::ErrorKind,
+	encryption::{CrossSigningKey, DeviceKeys},
+	serde::{Base64, Raw},
+	signatures::{PublicKeyMap, PublicKeySet, verify_json},
+};
+use tokio::{sync::watch, time::timeout};
+
+use crate::{Dep, users};
+
+pub struct Service {
+	sessions: SyncMutex<HashMap<String, Session>>,
+	server: Arc<Server>,
+	users: Dep<users::Service>,
+}
+
+struct Session {
+	payload: Payload,
+	updates: watch::Sender<String>,
+	creator: IpAddr,
+	approver: Option<(OwnedUserId, OwnedDeviceId)>,
+	granted: bool,
+}
+
+/// The current contents of a rendezvous session.
+#[derive(Clone, Debug)]
+pub struct Payload {
+	pub data: Bytes,
+	pub content_type: String,
+	pub etag: String,
+	pub last_modified: SystemTime,
+	pub expires: SystemTime,
+}
+
+/// Length of generated session IDs
+const SESSION_ID_LENGTH: usize = 32;
+
+/// Length of generated ETags
+const ETAG_LENGTH: usize = 16;
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			sessions: SyncMutex::new(HashMap::new()),
+			server: args.server.clone(),
+			users: args.depend::<users::Service>("users"),
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Whether the rendezvous API is enabled by the configuration.
+#[implement(Service)]
+#[must_use]
+pub fn enabled(&self) -> bool {
+	let config = &self.server.config;
+	config.login_via_existing_session && config.rendezvous_max_sessions > 0
+}
+
+/// Creates a new session holding `data` on behalf of the client at `creator`,
+/// returning its ID and payload.
+#[implement(Service)]
+pub fn create(
+	&self,
+	creator: IpAddr,
+	content_type: String,
+	data: Bytes,
+) -> Result<(String, Payload)> {
+	self.check(&data)?;
+
+	let now = SystemTime::now();
+	let ttl = Duration::from_secs(self.server.config.rendezvous_session_ttl);
+	let payload = Payload {
+		data,
+		content_type,
+		etag: utils::random_string(ETAG_LENGTH),
+		last_modified: now,
+		expires: now.checked_add(ttl).unwrap_or(now),
+	};
+
+	let mut sessions = self.sessions.lock();
+	sessions.retain(|_, session| session.payload.expires > now);
+	if sessions.len() >= self.server.config.rendezvous_max_sessions {
+		return Err(Error::BadRequest(
+			ErrorKind::LimitExceeded { retry_after: None },
+			"Too many rendezvous sessions are in progress.",
+		));
+	}
+
+	let created = sessions
+		.values()
+		.filter(|session| session.creator == creator)
+		.count();
+
+	if created >= self.server.config.rendezvous_max_sessions_per_ip {
+		return Err(Error::BadRequest(
+			ErrorKind::LimitExceeded { retry_after: None },
+			"Too many rendezvous sessions were created from your address.",
+		));
+	}
+
+	let id = utils::random_string(SESSION_ID_LENGTH);
+	let (updates, _) = watch::channel(payload.etag.clone());
+	sessions.insert(id.clone(), Session {
+		payload: payload.clone(),
+		updates,
+		creator,
+		approver: None,
+		granted: false,
+	});
+
+	debug!(%id, "Created rendezvous session");
+	Ok((id, payload))
+}
+
+/// Replaces the contents of a session if its current ETag is `if_match`.
+///
+/// Returns `None` when the ETag does not match, i.e. the session was written
+/// by someone else since `if_match` was read.
+///
+/// A `writer` authenticated by its access token becomes the session's
+/// approver, unless another device already is.
+#[implement(Service)]
+pub fn update(
+	&self,
+	id: &str,
+	if_match: &str,
+	writer: Option<(OwnedUserId, OwnedDeviceId)>,
+	content_type: String,
+	data: Bytes,
+) -> Result<Option<Payload>> {
+	self.check(&data)?;
+
+	let mut sessions = self.sessions.lock();
+	let Some(session) = live_session(&mut sessions, id) else {
+		return Err!(Request(NotFound("Rendezvous session not found.")));
+	};
+
+	if session.payload.etag != if_match {
+		return Ok(None);
+	}
+
+	if let Some(writer) = writer {
+		match &session.approver {
+			| None => session.approver = Some(writer),
+			| Some(approver) if *approver != writer => {
+				return Err!(Request(Forbidden(
+					"Another device is already signing in through this rendezvous session."
+				)));
+			},
+			| Some(_) => {},
+		}
+	}
+
+	session.payload.data = data;
+	session.payload.content_type = content_type;
+	session.payload.etag = utils::random_string(ETAG_LENGTH);
+	session.payload.last_modified = SystemTime::now();
+	session.updates.send_replace(session.payload.etag.clone());
+
+	Ok(Some(session.payload.clone()))
+}
+
+/// Fetches the contents of a session.
+///
+/// When `if_none_match` is the session's current ETag, waits up to `wait` for
+/// the session to be written before returning whatever it holds then.
+#[implement(Service)]
+pub async fn get(
+	&self,
+	id: &str,
+	if_none_match: Option<&str>,
+	wait: Duration,
+) -> Result<Payload> {
+	let mut updates = {
+		let mut sessions = self.sessions.lock();
+		let Some(session) = live_session(&mut sessions, id) else {
+			return Err!(Request(NotFound("Rendezvous session not found.")));
+		};
+
+		if if_none_match.is_none_or(|etag| session.payload.etag != etag) {
+			return Ok(session.payload.clone());
+		}
+
+		session.updates.subscribe()
+	};
+
+	// Either a write or the session going away ends the wait; both outcomes
+	// are resolved by looking the session up again.
+	timeout(wait, updates.changed()).await.ok();
+
+	let mut sessions = self.sessions.lock();
+	live_session(&mut sessions, id)
+		.map(|session| session.payload.clone())
+		.ok_or_else(|| Error::BadRequest(ErrorKind::NotFound, "Rendezvous session not found."))
+}
+
+/// Consumes the login grant of a live session approved by the device, if any.
+///
+/// Returns whether the device may be issued a login token without
+/// interactive authentication. Each session grants at most one token, and
+/// only to a device verified through cross-signing.
+#[implement(Service)]
+pub async fn take_login_grant(&self, user_id: &UserId, device_id: &DeviceId) -> bool {
+	if !self.is_cross_signed_device(user_id, device_id).await {
+		return false;
+	}
+
+	let now = SystemTime::now();
+	let mut sessions = self.sessions.lock();
+	sessions.retain(|_, session| session.payload.expires > now);
+
+	let grant = sessions.values_mut().find(|session| {
+		!session.granted
+			&& session
+				.approver
+				.as_ref()
+				.is_some_and(|(user, device)| user == user_id && device == device_id)
+	});
+
+	match grant {
+		| Some(session) => {
+			session.granted = true;
+			true
+		},
+		| None => false,
+	}
+}
+
+/// Deletes a session.
+#[implement(Service)]
+pub fn delete(&self, id: &str) -> Result {
+	let mut sessions = self.sessions.lock();
+	if live_session(&mut sessions, id).is_none() {
+		return Err!(Request(NotFound("Rendezvous session not found.")));
+	}
+
+	sessions.remove(id);
+	debug!(%id, "Deleted rendezvous session");
+	Ok(())
+}
+
+/// Whether the user's self-signing key has signed the device's keys.
+#[implement(Service)]
+async fn is_cross_signed_device(&self, user_id: &UserId, device_id: &DeviceId) -> bool {
+	let Ok(device_keys) = self.users.get_device_keys(user_id, device_id).await else {
+		return false;
+	};
+
+	let Ok(self_signing_key) = self
+		.users
+		.get_self_signing_key(None, user_id, &|_| true)
+		.await
+	else {
+		return false;
+	};
+
+	is_cross_signed(user_id, &device_keys, &self_signing_key)
+}
+
+#[implement(Service)]
+fn check(&self, data: &Bytes) -> Result {
+	if !self.enabled() {
+		return Err!(Request(Forbidden("Rendezvous sessions are not enabled on this server.")));
+	}
+
+	if data.len() > self.server.config.rendezvous_max_payload_size {
+		return Err(Error::BadRequest(ErrorKind::TooLarge, "Rendezvous payload is too large."));
+	}
+
+	Ok(())
+}
+
+/// Looks up a session, evicting it if it has expired.
+fn live_session<'a>(
+	sessions: &'a mut HashMap<String, Session>,
+	id: &str,
+) -> Option<&'a mut Session> {
+	let expired = sessions
+		.get(id)
+		.is_some_and(|session| session.payload.expires <= SystemTime::now());
+
+	if expired {
+		sessions.remove(id);
+	}
+
+	sessions.get_mut(id)
+}
+
+/// Checks the signature made on `device_keys` with the user's
+/// `self_signing_key`. Signatures by any other key, including the device's
+/// own, are disregarded.
+fn is_cross_signed(
+	user_id: &UserId,
+	device_keys: &Raw<DeviceKeys>,
+	self_signing_key: &Raw<CrossSigningKey>,
+) -> bool {
+	let Ok(self_signing_key) = self_signing_key.deserialize() else {
+		return false;
+	};
+
+	let Ok(mut device_keys) =
+		serde_json::from_str::<CanonicalJsonObject>(device_keys.json().get())
+	else {
+		return false;
+	};
+
+	let pubkeys: PublicKeySet = self_signing_key
+		.keys
+		.iter()
+		.filter_map(|(key_id, key)| Some((key_id.to_string(), Base64::parse(key).ok()?)))
+		.collect();
+
+	let Some(CanonicalJsonValue::Object(signatures)) = device_keys.get_mut("signatures") else {
+		return false;
+	};
+
+	let Some(CanonicalJsonValue::Object(user_signatures)) = signatures.remove(user_id.as_str())
+	else {
+		return false;
+	};
+
+	let user_signatures: CanonicalJsonObject = user_signatures
+		.into_iter()
+		.filter(|(key_id, _)| pubkeys.contains_key(key_id))
+		.collect();
+
+	if *self_signing_key.user_id != *user_id || user_signatures.is_empty() {
+		return false;
+	}
+
+	*signatures = [(user_id.to_string(), CanonicalJsonValue::Object(user_signatures))].into();
+	let pubkeys: PublicKeyMap = [(user_id.to_string(), pubkeys)].into();
+
+	verify_json(&pubkeys, device_keys).is_ok()
+}
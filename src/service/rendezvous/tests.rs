@@ -0,0 +1,107 @@
+#![cfg(test)]
+
+use ruma::{
+	CanonicalJsonObject, UserId,
+	encryption::{CrossSigningKey, DeviceKeys},
+	serde::{Base64, Raw},
+	signatures::{Ed25519KeyPair, sign_json},
+	user_id,
+};
+use serde_json::{json, value::to_raw_value};
+
+use super::is_cross_signed;
+
+/// Generates a keypair whose key ID is derived from its public key, as for
+/// cross-signing keys.
+fn cross_signing_keypair() -> Ed25519KeyPair {
+	let der = Ed25519KeyPair::generate().expect("keypair generates");
+	let public_key = Ed25519KeyPair::from_der(&der, String::new())
+		.expect("keypair loads")
+		.public_key()
+		.to_vec();
+
+	Ed25519KeyPair::from_der(&der, Base64::new(public_key).encode()).expect("keypair loads")
+}
+
+fn self_signing_key(user_id: &UserId, keypair: &Ed25519KeyPair) -> Raw<CrossSigningKey> {
+	let public_key = Base64::new(keypair.public_key().to_vec()).encode();
+	let key = json!({
+		"user_id": user_id,
+		"usage": ["self_signing"],
+		"keys": { format!("ed25519:{public_key}"): public_key },
+		"signatures": {},
+	});
+
+	Raw::from_json(to_raw_value(&key).expect("self-signing key serializes"))
+}
+
+fn device_keys(user_id: &UserId, signers: &[&Ed25519KeyPair]) -> Raw<DeviceKeys> {
+	let device = Ed25519KeyPair::from_der(
+		&Ed25519KeyPair::generate().expect("keypair generates"),
+		"DEVICE".to_owned(),
+	)
+	.expect("keypair loads");
+
+	let keys = json!({
+		"user_id": user_id,
+		"device_id": "DEVICE",
+		"algorithms": ["m.olm.v1.curve25519-aes-sha2"],
+		"keys": {
+			"ed25519:DEVICE": Base64::new(device.public_key().to_vec()).encode(),
+		},
+	});
+
+	let mut object: CanonicalJsonObject =
+		serde_json::from_value(keys).expect("device keys are canonical JSON");
+
+	sign_json(user_id.as_str(), &device, &mut object).expect("device signs its keys");
+	for signer in signers {
+		sign_json(user_id.as_str(), *signer, &mut object).expect("signer signs device keys");
+	}
+
+	Raw::from_json(to_raw_value(&object).expect("device keys serialize"))
+}
+
+#[test]
+fn cross_signed_device_may_approve() {
+	let user_id = user_id!("@alice:example.com");
+	let ssk = cross_signing_keypair();
+
+	let device_keys = device_keys(user_id, &[&ssk]);
+	assert!(is_cross_signed(user_id, &device_keys, &self_signing_key(user_id, &ssk)));
+}
+
+#[test]
+fn self_approved_session_is_rejected() {
+	let user_id = user_id!("@alice:example.com");
+	let ssk = cross_signing_keypair();
+
+	// signed only by the device itself, as any client with an access token can
+	let device_keys = device_keys(user_id, &[]);
+	assert!(!is_cross_signed(user_id, &device_keys, &self_signing_key(user_id, &ssk)));
+}
+
+#[test]
+fn forged_cross_signature_is_rejected() {
+	let user_id = user_id!("@alice:example.com");
+	let ssk = cross_signing_keypair();
+	let forger = Ed25519KeyPair::from_der(
+		&Ed25519KeyPair::generate().expect("keypair generates"),
+		ssk.version().to_owned(),
+	)
+	.expect("keypair loads");
+
+	// signed under the self-signing key's ID, but not by that key
+	let device_keys = device_keys(user_id, &[&forger]);
+	assert!(!is_cross_signed(user_id, &device_keys, &self_signing_key(user_id, &ssk)));
+}
+
+#[test]
+fn other_users_self_signing_key_is_rejected() {
+	let user_id = user_id!("@alice:example.com");
+	let ssk = cross_signing_keypair();
+
+	let device_keys = device_keys(user_id, &[&ssk]);
+	let other = self_signing_key(user_id!("@mallory:example.com"), &ssk);
+	assert!(!is_cross_signed(user_id, &device_keys, &other));
+}
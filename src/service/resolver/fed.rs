@@ -18,6 +18,10 @@ pub enum FedDest {
 
 const DEFAULT_PORT: &str = ":8448";
 
+/// Identity servers are reached on the standard HTTPS port unless their name
+/// carries one.
+const IDENTITY_SERVER_PORT: u16 = 443;
+
 pub(crate) fn get_ip_with_port(dest_str: &str) -> Option<FedDest> {
 	if let Ok(dest) = dest_str.parse::<SocketAddr>() {
 		Some(FedDest::Literal(dest))
@@ -40,6 +44,29 @@ pub(crate) fn add_port_to_hostname(dest: &str) -> FedDest {
 	)
 }
 
+/// Destination of the identity server named `dest_str`, as given by clients.
+/// Unlike homeservers, identity servers are not discovered through
+/// `.well-known` or SRV records.
+pub(crate) fn identity_server_dest(dest_str: &str) -> FedDest {
+	let ip_addr = dest_str
+		.strip_prefix('[')
+		.and_then(|dest| dest.strip_suffix(']'))
+		.unwrap_or(dest_str)
+		.parse::<IpAddr>();
+
+	if let Ok(dest) = dest_str.parse::<SocketAddr>() {
+		FedDest::Literal(dest)
+	} else if let Ok(ip_addr) = ip_addr {
+		FedDest::Literal(SocketAddr::new(ip_addr, IDENTITY_SERVER_PORT))
+	} else {
+		let (host, port) = dest_str.split_once(':').unwrap_or((dest_str, ""));
+		let port = port.parse::<u16>().unwrap_or(IDENTITY_SERVER_PORT);
+		let port = PortString::from(&format!(":{port}")).expect("port fits in a port string");
+
+		FedDest::Named(host.to_owned(), port)
+	}
+}
+
 impl FedDest {
 	pub(crate) fn https_string(&self) -> String {
 		match self {
@@ -1,4 +1,4 @@
-use super::fed::{FedDest, add_port_to_hostname, get_ip_with_port};
+use super::fed::{FedDest, add_port_to_hostname, get_ip_with_port, identity_server_dest};
 
 #[test]
 fn ips_get_default_ports() {
@@ -39,3 +39,25 @@ fn hostnames_keep_custom_ports() {
 		FedDest::Named(String::from("example.com"), ":1337".try_into().unwrap())
 	);
 }
+
+#[test]
+fn identity_servers_get_https_port() {
+	assert_eq!(
+		identity_server_dest("id.example.com").https_string(),
+		"https://id.example.com:443"
+	);
+	assert_eq!(identity_server_dest("1.1.1.1").https_string(), "https://1.1.1.1:443");
+	assert_eq!(identity_server_dest("[dead::beef]").https_string(), "https://[dead::beef]:443");
+}
+
+#[test]
+fn identity_servers_keep_custom_ports() {
+	assert_eq!(
+		identity_server_dest("id.example.com:8090").https_string(),
+		"https://id.example.com:8090"
+	);
+	assert_eq!(
+		identity_server_dest("[dead::beef]:8090").https_string(),
+		"https://[dead::beef]:8090"
+	);
+}
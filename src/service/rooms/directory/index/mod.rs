@@ -0,0 +1,319 @@
+mod tests;
+
+use std::{cmp::Reverse, collections::HashSet, sync::Arc};
+
+use conduwuit::{
+	Err, Result, debug_warn, err, implement,
+	matrix::pdu::PduEvent,
+	utils::{ReadyExt, TryFutureExtExt, result::FlatOk, stream::TryIgnore},
+};
+use database::{Deserialized, Json};
+use futures::{
+	FutureExt, StreamExt, TryFutureExt,
+	future::{join, join4, join5},
+};
+use ruma::{
+	OwnedRoomId, RoomId, UInt,
+	directory::{Filter, PublicRoomJoinRule, PublicRoomsChunk, RoomTypeFilter},
+	events::{
+		StateEventType, TimelineEventType,
+		room::join_rules::{JoinRule, RoomJoinRulesEventContent},
+	},
+	uint,
+};
+
+use super::{Page, Service};
+
+/// Position of an entry in a listing: its search rank, member count and room
+/// ID, in listing order.
+type SortKey<'a> = (Reverse<u8>, Reverse<UInt>, &'a RoomId);
+
+/// Whether appending `pdu` may change the directory entry of its room.
+#[must_use]
+pub fn affects_listing(pdu: &PduEvent) -> bool {
+	pdu.state_key.is_some()
+		&& matches!(
+			pdu.kind,
+			TimelineEventType::RoomMember
+				| TimelineEventType::RoomName
+				| TimelineEventType::RoomTopic
+				| TimelineEventType::RoomAvatar
+				| TimelineEventType::RoomCanonicalAlias
+				| TimelineEventType::RoomJoinRules
+				| TimelineEventType::RoomGuestAccess
+				| TimelineEventType::RoomHistoryVisibility
+				| TimelineEventType::RoomCreate
+		)
+}
+
+/// Refreshes the directory entry of a published room from its current state.
+/// Does nothing for rooms which are not published.
+#[implement(Service)]
+pub async fn update_room(&self, room_id: &RoomId) {
+	if !self.is_public_room(room_id).await {
+		return;
+	}
+
+	let chunk = self.public_rooms_chunk(room_id.to_owned()).await;
+	self.db.publicroomid_chunk.raw_put(room_id, Json(chunk));
+
+	// The room may have been unpublished while its entry was built. Listings
+	// skip entries of unpublished rooms meanwhile.
+	if !self.is_public_room(room_id).await {
+		self.db.publicroomid_chunk.remove(room_id);
+	}
+
+	self.invalidate();
+}
+
+/// Lists a page of this server's directory.
+///
+/// Rooms matching a search term are ranked by how well their name, alias and
+/// topic match; otherwise, and among equally ranked rooms, rooms with more
+/// members come first. Batch tokens name the room at the edge of the page, so
+/// they remain valid across restarts and while the directory changes.
+#[implement(Service)]
+pub async fn query(&self, filter: &Filter, since: Option<&str>, limit: usize) -> Result<Page> {
+	page(&self.listing().await, filter, since, limit)
+}
+
+/// Selects the page of `listing` matching `filter` after or before the entry
+/// named by the `since` token.
+fn page(
+	listing: &[PublicRoomsChunk],
+	filter: &Filter,
+	since: Option<&str>,
+	limit: usize,
+) -> Result<Page> {
+	let term = filter
+		.generic_search_term
+		.as_deref()
+		.map(str::trim)
+		.filter(|term| !term.is_empty())
+		.map(str::to_lowercase);
+
+	let mut matches: Vec<(u8, &PublicRoomsChunk)> = listing
+		.iter()
+		.filter(|chunk| {
+			filter.room_types.is_empty()
+				|| filter
+					.room_types
+					.contains(&RoomTypeFilter::from(chunk.room_type.clone()))
+		})
+		.filter_map(|chunk| match &term {
+			| Some(term) => rank(chunk, term).map(|rank| (rank, chunk)),
+			| None => Some((0, chunk)),
+		})
+		.collect();
+
+	// The listing is already ordered by member count; the sort is stable.
+	matches.sort_by_key(|&(rank, _)| Reverse(rank));
+
+	let total_room_count_estimate = UInt::try_from(matches.len()).ok();
+	let (start, end) = match since.map(parse_token).transpose()? {
+		| None => (0, limit.min(matches.len())),
+		| Some((backwards, key)) => {
+			let key = (key.0, key.1, key.2.as_ref());
+			if backwards {
+				let end = matches.partition_point(|entry| sort_key(entry) < key);
+				(end.saturating_sub(limit), end)
+			} else {
+				let start = matches.partition_point(|entry| sort_key(entry) <= key);
+				(start, start.saturating_add(limit).min(matches.len()))
+			}
+		},
+	};
+
+	let page = &matches[start..end];
+	let prev_batch = page
+		.first()
+		.filter(|_| start > 0)
+		.map(|entry| make_token('p', entry));
+
+	let next_batch = page
+		.last()
+		.filter(|_| end < matches.len())
+		.map(|entry| make_token('n', entry));
+
+	Ok(Page {
+		chunk: page.iter().map(|(_, chunk)| (*chunk).clone()).collect(),
+		prev_batch,
+		next_batch,
+		total_room_count_estimate,
+	})
+}
+
+/// Returns the directory entry of every published room, ordered by member
+/// count.
+#[implement(Service)]
+async fn listing(&self) -> Arc<Vec<PublicRoomsChunk>> {
+	if let Some(listing) = self.listing.read().clone() {
+		return listing;
+	}
+
+	let public_rooms: HashSet<&RoomId> = self.public_rooms().collect().await;
+	let mut listing: Vec<PublicRoomsChunk> = self
+		.db
+		.publicroomid_chunk
+		.stream()
+		.ignore_err()
+		.ready_filter(|(room_id, _): &(&RoomId, PublicRoomsChunk)| public_rooms.contains(room_id))
+		.map(|(_, chunk)| chunk)
+		.collect()
+		.await;
+
+	listing.sort_by(|a, b| {
+		b.num_joined_members
+			.cmp(&a.num_joined_members)
+			.then_with(|| a.room_id.cmp(&b.room_id))
+	});
+
+	let listing = Arc::new(listing);
+	self.listing.write().replace(listing.clone());
+
+	listing
+}
+
+#[implement(Service)]
+pub(super) fn invalidate(&self) { self.listing.write().take(); }
+
+/// Returns the stored directory entry of a published room.
+#[implement(Service)]
+pub async fn public_room(&self, room_id: &RoomId) -> Result<PublicRoomsChunk> {
+	if !self.is_public_room(room_id).await {
+		return Err!(Request(NotFound("Room is not published.")));
+	}
+
+	self.db.publicroomid_chunk.get(room_id).await.deserialized()
+}
+
+#[implement(Service)]
+async fn public_rooms_chunk(&self, room_id: OwnedRoomId) -> PublicRoomsChunk {
+	let state_accessor = &self.services.state_accessor;
+
+	let name = state_accessor.get_name(&room_id).ok();
+
+	let room_type = state_accessor.get_room_type(&room_id).ok();
+
+	let canonical_alias = state_accessor.get_canonical_alias(&room_id).ok();
+
+	let avatar_url = state_accessor.get_avatar(&room_id);
+
+	let topic = state_accessor.get_room_topic(&room_id).ok();
+
+	let world_readable = state_accessor.is_world_readable(&room_id);
+
+	let join_rule = state_accessor
+		.room_state_get_content(&room_id, &StateEventType::RoomJoinRules, "")
+		.map_ok(|c: RoomJoinRulesEventContent| match c.join_rule {
+			| JoinRule::Public => PublicRoomJoinRule::Public,
+			| JoinRule::Knock => "knock".into(),
+			| JoinRule::KnockRestricted(_) => "knock_restricted".into(),
+			| _ => "invite".into(),
+		});
+
+	let guest_can_join = state_accessor.guest_can_join(&room_id);
+
+	let num_joined_members = self.services.state_cache.room_joined_count(&room_id);
+
+	let (
+		(avatar_url, canonical_alias, guest_can_join, join_rule, name),
+		(num_joined_members, room_type, topic, world_readable),
+	) = join(
+		join5(avatar_url, canonical_alias, guest_can_join, join_rule, name),
+		join4(num_joined_members, room_type, topic, world_readable),
+	)
+	.boxed()
+	.await;
+
+	PublicRoomsChunk {
+		avatar_url: avatar_url.into_option().unwrap_or_default().url,
+		canonical_alias,
+		guest_can_join,
+		join_rule: join_rule.unwrap_or_default(),
+		name,
+		num_joined_members: num_joined_members
+			.map(TryInto::try_into)
+			.map(Result::ok)
+			.flat_ok()
+			.unwrap_or_else(|| uint!(0)),
+		room_id,
+		room_type,
+		topic,
+		world_readable,
+	}
+}
+
+/// Ranks how well a room matches a lowercase search term, from exact matches
+/// of its name or alias down to matches within its topic. None when the room
+/// does not match at all.
+///
+/// Aliases are matched in full, with or without the leading `#`, so that
+/// searching for a server name finds the rooms with aliases on it.
+fn rank(chunk: &PublicRoomsChunk, term: &str) -> Option<u8> {
+	let name = chunk.name.as_deref().map(str::to_lowercase);
+	let name = name.as_deref().unwrap_or_default();
+	let alias = chunk
+		.canonical_alias
+		.as_ref()
+		.map(|alias| alias.as_str().trim_start_matches('#').to_lowercase());
+	let alias = alias.as_deref().unwrap_or_default();
+	let localpart = alias.split(':').next().unwrap_or_default();
+	let alias_term = term.strip_prefix('#').unwrap_or(term);
+	let topic = chunk.topic.as_deref().map(str::to_lowercase);
+	let topic = topic.as_deref().unwrap_or_default();
+
+	[
+		(name == term, 8),
+		(!alias.is_empty() && (alias == alias_term || localpart == alias_term), 7),
+		(name.starts_with(term), 6),
+		(!alias_term.is_empty() && alias.starts_with(alias_term), 5),
+		(name.split_whitespace().any(|word| word.starts_with(term)), 4),
+		(name.contains(term), 3),
+		(!alias_term.is_empty() && alias.contains(alias_term), 2),
+		(topic.contains(term), 1),
+	]
+	.into_iter()
+	.find_map(|(matched, rank)| matched.then_some(rank))
+}
+
+fn sort_key<'a>((rank, chunk): &(u8, &'a PublicRoomsChunk)) -> SortKey<'a> {
+	(Reverse(*rank), Reverse(chunk.num_joined_members), &chunk.room_id)
+}
+
+/// Makes a batch token for the page before (`p`) or after (`n`) an entry.
+fn make_token(direction: char, entry: &(u8, &PublicRoomsChunk)) -> String {
+	let (_, chunk) = entry;
+	format!("{direction}{}_{}_{}", entry.0, chunk.num_joined_members, chunk.room_id)
+}
+
+/// Parses a batch token into its direction, true for backwards, and the sort
+/// key of the entry it was made from.
+fn parse_token(token: &str) -> Result<(bool, (Reverse<u8>, Reverse<UInt>, OwnedRoomId))> {
+	let invalid = || err!(Request(InvalidParam("Invalid `since` token.")));
+
+	let (backwards, key) = if let Some(key) = token.strip_prefix('n') {
+		(false, key)
+	} else if let Some(key) = token.strip_prefix('p') {
+		(true, key)
+	} else {
+		return Err!(Request(InvalidParam("Invalid `since` token.")));
+	};
+
+	let mut parts = key.splitn(3, '_');
+	let rank = parts
+		.next()
+		.and_then(|rank| rank.parse().ok())
+		.ok_or_else(invalid)?;
+	let members = parts
+		.next()
+		.and_then(|members| members.parse().ok())
+		.ok_or_else(invalid)?;
+	let room_id = parts
+		.next()
+		.and_then(|room_id| RoomId::parse(room_id).ok())
+		.ok_or_else(invalid)
+		.inspect_err(|_| debug_warn!(?token, "Rejecting invalid directory token"))?;
+
+	Ok((backwards, (Reverse(rank), Reverse(members), room_id)))
+}
@@ -0,0 +1,165 @@
+#![cfg(test)]
+
+use std::cmp::Reverse;
+
+use ruma::{
+	OwnedRoomId, RoomAliasId, RoomId, UInt,
+	directory::{Filter, PublicRoomJoinRule, PublicRoomsChunk},
+	owned_room_id,
+};
+use serde_json::json;
+
+use super::{make_token, page, parse_token, rank};
+
+fn chunk(room_id: &str, members: u32) -> PublicRoomsChunk {
+	PublicRoomsChunk {
+		avatar_url: None,
+		canonical_alias: None,
+		guest_can_join: false,
+		join_rule: PublicRoomJoinRule::Public,
+		name: None,
+		num_joined_members: UInt::from(members),
+		room_id: RoomId::parse(room_id).expect("valid room id"),
+		room_type: None,
+		topic: None,
+		world_readable: false,
+	}
+}
+
+fn named(room_id: &str, name: &str, alias: &str, topic: &str) -> PublicRoomsChunk {
+	PublicRoomsChunk {
+		name: Some(name.to_owned()),
+		canonical_alias: Some(RoomAliasId::parse(alias).expect("valid alias")),
+		topic: Some(topic.to_owned()),
+		..chunk(room_id, 1)
+	}
+}
+
+fn search(term: &str) -> Filter {
+	serde_json::from_value(json!({ "generic_search_term": term })).expect("valid filter")
+}
+
+fn room_ids(chunks: &[PublicRoomsChunk]) -> Vec<OwnedRoomId> {
+	chunks.iter().map(|chunk| chunk.room_id.clone()).collect()
+}
+
+/// Rooms `!0` to `!9`, with the lower numbered rooms having more members.
+fn listing() -> Vec<PublicRoomsChunk> {
+	(0..10_u32)
+		.map(|i| chunk(&format!("!{i}:example.com"), 100_u32.saturating_sub(i)))
+		.collect()
+}
+
+#[test]
+fn rank_orders_matches() {
+	let room =
+		named("!a:example.com", "Rust Lounge", "#rustaceans:example.org", "talk about ferris");
+
+	assert_eq!(rank(&room, "rust lounge"), Some(8));
+	assert_eq!(rank(&room, "rustaceans"), Some(7));
+	assert_eq!(rank(&room, "#rustaceans:example.org"), Some(7));
+	assert_eq!(rank(&room, "rustaceans:example.org"), Some(7));
+	assert_eq!(rank(&room, "rust"), Some(6));
+	assert_eq!(rank(&room, "#rustace"), Some(5));
+	assert_eq!(rank(&room, "lou"), Some(4));
+	assert_eq!(rank(&room, "t lo"), Some(3));
+	assert_eq!(rank(&room, "taceans"), Some(2));
+	assert_eq!(rank(&room, "ferris"), Some(1));
+	assert_eq!(rank(&room, "python"), None);
+}
+
+#[test]
+fn rank_matches_alias_server_name() {
+	let room = named("!a:example.com", "Lounge", "#lounge:chat.example.org", "");
+
+	assert_eq!(rank(&room, "chat.example.org"), Some(2));
+	assert_eq!(rank(&room, ":chat.example.org"), Some(2));
+	assert_eq!(rank(&chunk("!b:example.com", 1), "chat.example.org"), None);
+}
+
+#[test]
+fn token_round_trips() {
+	let room = chunk("!a:example.com", 42);
+
+	let token = make_token('n', &(3, &room));
+	assert_eq!(token, "n3_42_!a:example.com");
+
+	let (backwards, key) = parse_token(&token).expect("valid token");
+	assert!(!backwards);
+	assert_eq!(key, (Reverse(3), Reverse(UInt::from(42_u32)), owned_room_id!("!a:example.com")));
+
+	let (backwards, _) = parse_token(&make_token('p', &(3, &room))).expect("valid token");
+	assert!(backwards);
+}
+
+#[test]
+fn malformed_tokens_are_rejected() {
+	for token in [
+		"",
+		"x3_42_!a:example.com",
+		"n3_42",
+		"nx_42_!a:example.com",
+		"n3_x_!a:example.com",
+		"n3_42_a",
+	] {
+		assert!(parse_token(token).is_err(), "{token:?} should be rejected");
+	}
+}
+
+#[test]
+fn pages_forwards_and_backwards() {
+	let listing = listing();
+	let filter = Filter::default();
+
+	let first = page(&listing, &filter, None, 4).expect("first page");
+	assert_eq!(room_ids(&first.chunk), room_ids(&listing[0..4]));
+	assert_eq!(first.prev_batch, None);
+	assert_eq!(first.total_room_count_estimate, Some(UInt::from(10_u32)));
+
+	let next = first.next_batch.expect("more rooms");
+	let second = page(&listing, &filter, Some(&next), 4).expect("second page");
+	assert_eq!(room_ids(&second.chunk), room_ids(&listing[4..8]));
+
+	let next = second.next_batch.clone().expect("more rooms");
+	let last = page(&listing, &filter, Some(&next), 4).expect("last page");
+	assert_eq!(room_ids(&last.chunk), room_ids(&listing[8..10]));
+	assert_eq!(last.next_batch, None);
+
+	let prev = second.prev_batch.expect("earlier rooms");
+	let back = page(&listing, &filter, Some(&prev), 4).expect("previous page");
+	assert_eq!(room_ids(&back.chunk), room_ids(&listing[0..4]));
+	assert_eq!(back.prev_batch, None);
+}
+
+#[test]
+fn token_stays_valid_when_room_is_removed() {
+	let mut listing = listing();
+	let filter = Filter::default();
+
+	let first = page(&listing, &filter, None, 4).expect("first page");
+	let next = first.next_batch.expect("more rooms");
+
+	// the room the token was made from is unpublished
+	listing.remove(3);
+	let second = page(&listing, &filter, Some(&next), 4).expect("second page");
+	assert_eq!(room_ids(&second.chunk), room_ids(&listing[3..7]));
+}
+
+#[test]
+fn search_ranks_before_member_count() {
+	let listing = vec![
+		PublicRoomsChunk {
+			num_joined_members: UInt::from(50_u32),
+			..named("!topic:example.com", "Other", "#other:example.com", "all about rust")
+		},
+		named("!name:example.com", "Rust", "#rs:example.com", ""),
+		named("!none:example.com", "Python", "#py:example.com", ""),
+	];
+
+	let found = page(&listing, &search("Rust"), None, 10).expect("search results");
+	assert_eq!(room_ids(&found.chunk), [
+		owned_room_id!("!name:example.com"),
+		owned_room_id!("!topic:example.com")
+	]);
+	assert_eq!(found.total_room_count_estimate, Some(UInt::from(2_u32)));
+}
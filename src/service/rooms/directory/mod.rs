@@ -1,35 +1,142 @@
-use std::sync::Arc;
+//! # Room directory
+//!
+//! Keeps track of which rooms are published to this server's public room
+//! directory, and maintains an index of their directory entries so listing
+//! the directory doesn't require looking up the state of every published room.
+//! Entries are refreshed whenever a state event affecting them is appended,
+//! and rebuilt for every published room on startup.
+//!
+//! Listings of other servers' directories are cached for a few minutes.
 
-use conduwuit::{Result, implement, utils::stream::TryIgnore};
+mod index;
+mod remote;
+
+use std::{
+	fmt::Write,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Result, SyncMutex, SyncRwLock, debug, implement,
+	utils::{ReadyExt, stream::TryIgnore},
+};
 use database::Map;
-use futures::Stream;
-use ruma::{RoomId, api::client::room::Visibility};
+use futures::{Stream, StreamExt};
+use lru_cache::LruCache;
+use ruma::{RoomId, UInt, api::client::room::Visibility, directory::PublicRoomsChunk};
+
+pub use self::index::affects_listing;
+use crate::{Dep, rooms, sending};
 
 pub struct Service {
 	db: Data,
+	services: Services,
+	listing: SyncRwLock<Option<Arc<Vec<PublicRoomsChunk>>>>,
+	remote: SyncMutex<LruCache<String, (Instant, Page)>>,
 }
 
 struct Data {
 	publicroomids: Arc<Map>,
+	publicroomid_chunk: Arc<Map>,
 }
 
+struct Services {
+	sending: Dep<sending::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	state_cache: Dep<rooms::state_cache::Service>,
+}
+
+/// A page of a room directory listing.
+#[derive(Clone, Debug, Default)]
+pub struct Page {
+	pub chunk: Vec<PublicRoomsChunk>,
+	pub prev_batch: Option<String>,
+	pub next_batch: Option<String>,
+	pub total_room_count_estimate: Option<UInt>,
+}
+
+/// How long a page of a remote server's directory is reused.
+const REMOTE_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Upper bound on the number of cached pages of remote directories.
+const REMOTE_CACHE_MAX: usize = 256;
+
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			db: Data {
 				publicroomids: args.db["publicroomids"].clone(),
+				publicroomid_chunk: args.db["publicroomid_chunk"].clone(),
 			},
+			services: Services {
+				sending: args.depend::<sending::Service>("sending"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+			},
+			listing: SyncRwLock::new(None),
+			remote: SyncMutex::new(LruCache::new(REMOTE_CACHE_MAX)),
 		}))
 	}
 
+	#[tracing::instrument(skip_all, name = "directory", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		// Entries may be missing or stale after an upgrade or a missed update.
+		let rooms: Vec<_> = self.public_rooms().map(ToOwned::to_owned).collect().await;
+		for room_id in &rooms {
+			self.update_room(room_id).await;
+		}
+
+		self.db
+			.publicroomid_chunk
+			.keys()
+			.ignore_err()
+			.ready_filter(|room_id: &&RoomId| !rooms.iter().any(|public| **public == **room_id))
+			.ready_for_each(|room_id| self.db.publicroomid_chunk.remove(room_id))
+			.await;
+
+		self.invalidate();
+		debug!("Indexed {} published rooms", rooms.len());
+
+		Ok(())
+	}
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let listing = self
+			.listing
+			.read()
+			.as_ref()
+			.map_or(0, |listing| listing.len());
+		let remote = self.remote.lock().len();
+		writeln!(out, "directory_listing: {listing}")?;
+		writeln!(out, "directory_remote_cache: {remote}")?;
+
+		Ok(())
+	}
+
+	async fn clear_cache(&self) {
+		self.invalidate();
+		self.remote.lock().clear();
+	}
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 #[implement(Service)]
-pub fn set_public(&self, room_id: &RoomId) { self.db.publicroomids.insert(room_id, []); }
+pub async fn set_public(&self, room_id: &RoomId) {
+	self.db.publicroomids.insert(room_id, []);
+	self.update_room(room_id).await;
+}
 
 #[implement(Service)]
-pub fn set_not_public(&self, room_id: &RoomId) { self.db.publicroomids.remove(room_id); }
+pub fn set_not_public(&self, room_id: &RoomId) {
+	self.db.publicroomids.remove(room_id);
+	self.db.publicroomid_chunk.remove(room_id);
+	self.invalidate();
+}
 
 #[implement(Service)]
 pub fn public_rooms(&self) -> impl Stream<Item = &RoomId> + Send {
@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+use conduwuit::{Result, debug, implement};
+use ruma::{
+	ServerName, UInt,
+	api::federation::directory::get_public_rooms_filtered,
+	directory::{Filter, RoomNetwork},
+};
+
+use super::{Page, REMOTE_CACHE_TTL, Service};
+
+/// Lists a page of another server's directory over federation. Pages are
+/// reused for a few minutes, so clients paginating or repeating a search do
+/// not request the same page from the remote server again.
+#[implement(Service)]
+pub async fn remote_rooms(
+	&self,
+	server: &ServerName,
+	limit: Option<UInt>,
+	since: Option<&str>,
+	filter: &Filter,
+) -> Result<Page> {
+	let key = format!(
+		"{server}\0{}\0{}\0{}\0{:?}",
+		since.unwrap_or_default(),
+		limit.map(u64::from).unwrap_or_default(),
+		filter.generic_search_term.as_deref().unwrap_or_default(),
+		filter.room_types,
+	);
+
+	if let Some((fetched, page)) = self.remote.lock().get_mut(&key) {
+		if fetched.elapsed() < REMOTE_CACHE_TTL {
+			debug!(?server, ?since, "Using cached page of remote directory");
+			return Ok(page.clone());
+		}
+	}
+
+	let response = self
+		.services
+		.sending
+		.send_federation_request(server, get_public_rooms_filtered::v1::Request {
+			limit,
+			since: since.map(ToOwned::to_owned),
+			filter: Filter {
+				generic_search_term: filter.generic_search_term.clone(),
+				room_types: filter.room_types.clone(),
+			},
+			room_network: RoomNetwork::Matrix,
+		})
+		.await?;
+
+	let page = Page {
+		chunk: response.chunk,
+		prev_batch: response.prev_batch,
+		next_batch: response.next_batch,
+		total_room_count_estimate: response.total_room_count_estimate,
+	};
+
+	self.remote
+		.lock()
+		.insert(key, (Instant::now(), page.clone()));
+
+	Ok(page)
+}
@@ -6,6 +6,7 @@
 pub mod metadata;
 pub mod outlier;
 pub mod pdu_metadata;
+pub mod peek;
 pub mod read_receipt;
 pub mod search;
 pub mod short;
@@ -14,6 +15,7 @@
 pub mod state_accessor;
 pub mod state_cache;
 pub mod state_compressor;
+pub mod third_party_invite;
 pub mod threads;
 pub mod timeline;
 pub mod typing;
@@ -30,6 +32,7 @@ pub struct Service {
 	pub metadata: Arc<metadata::Service>,
 	pub outlier: Arc<outlier::Service>,
 	pub pdu_metadata: Arc<pdu_metadata::Service>,
+	pub peek: Arc<peek::Service>,
 	pub read_receipt: Arc<read_receipt::Service>,
 	pub search: Arc<search::Service>,
 	pub short: Arc<short::Service>,
@@ -38,6 +41,7 @@ pub struct Service {
 	pub state_accessor: Arc<state_accessor::Service>,
 	pub state_cache: Arc<state_cache::Service>,
 	pub state_compressor: Arc<state_compressor::Service>,
+	pub third_party_invite: Arc<third_party_invite::Service>,
 	pub threads: Arc<threads::Service>,
 	pub timeline: Arc<timeline::Service>,
 	pub typing: Arc<typing::Service>,
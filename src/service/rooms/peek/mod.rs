@@ -0,0 +1,472 @@
+//! # Federation peeking
+//!
+//! Lets local users preview world-readable rooms this server is not in yet.
+//! The room's current state and latest events are learned from a resident
+//! server: a join template (which is never sent) names the forward extremities,
+//! and the state before them is fetched with `/state`. History is then read
+//! from the same server with `/backfill` and `/event`, which it serves for
+//! world-readable rooms to any server.
+//!
+//! Only the servers listed in `trusted_servers` are asked, never a server
+//! named by the room ID, so that local users cannot make this server contact
+//! arbitrary hosts. The returned state must pass the auth rules, back to the
+//! room's create event, before the room is taken to be world-readable.
+//!
+//! Peeked events are verified but never persisted; snapshots of the room are
+//! cached in memory for a short while so paginating clients do not refetch the
+//! state on every request. Failed peeks are remembered for as long, and only
+//! one peek into a room is in flight at a time.
+
+mod tests;
+
+use std::{
+	collections::HashMap,
+	fmt::Write,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use conduwuit::{
+	Err, PduEvent, Result, Server, SyncMutex, debug, debug_warn, err, implement,
+	matrix::{Event, RoomVersion, StateKey, state_res},
+	utils::{IterStream, MutexMap},
+	warn,
+};
+use futures::{StreamExt, future::ready};
+use ruma::{
+	OwnedEventId, OwnedRoomId, OwnedServerName, RoomId, RoomVersionId, UInt, UserId,
+	api::federation::{
+		backfill::get_backfill,
+		event::{get_event, get_room_state},
+		membership::prepare_join_event,
+	},
+	events::{
+		StateEventType, TimelineEventType,
+		room::history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+	},
+};
+use serde::Deserialize;
+use serde_json::value::RawValue as RawJsonValue;
+
+use crate::{Dep, globals, moderation, sending, server_keys};
+
+pub struct Service {
+	peeks: SyncMutex<HashMap<OwnedRoomId, Arc<Peek>>>,
+	failures: SyncMutex<HashMap<OwnedRoomId, Instant>>,
+	fetching: MutexMap<OwnedRoomId, ()>,
+	services: Services,
+}
+
+struct Services {
+	server: Arc<Server>,
+	globals: Dep<globals::Service>,
+	moderation: Dep<moderation::Service>,
+	sending: Dep<sending::Service>,
+	server_keys: Dep<server_keys::Service>,
+}
+
+/// A snapshot of a remote room as seen by a resident server.
+#[derive(Debug)]
+pub struct Peek {
+	pub room_id: OwnedRoomId,
+	pub room_version: RoomVersionId,
+	/// The server answering peek requests for this room.
+	pub server: OwnedServerName,
+	/// The current state of the room.
+	pub state: Vec<PduEvent>,
+	/// The room's forward extremities; history is paginated back from these.
+	pub latest: Vec<OwnedEventId>,
+	fetched: Instant,
+}
+
+#[derive(Deserialize)]
+struct JoinTemplate {
+	prev_events: Vec<OwnedEventId>,
+}
+
+/// How long a peeked snapshot of a room is reused before it is fetched again,
+/// and how long a failed peek is remembered.
+const PEEK_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Upper bound on the number of cached peeked rooms, and of remembered
+/// failures.
+const PEEK_CACHE_MAX: usize = 256;
+
+#[async_trait]
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			peeks: SyncMutex::new(HashMap::new()),
+			failures: SyncMutex::new(HashMap::new()),
+			fetching: MutexMap::new(),
+			services: Services {
+				server: args.server.clone(),
+				globals: args.depend::<globals::Service>("globals"),
+				moderation: args.depend::<moderation::Service>("moderation"),
+				sending: args.depend::<sending::Service>("sending"),
+				server_keys: args.depend::<server_keys::Service>("server_keys"),
+			},
+		}))
+	}
+
+	async fn memory_usage(&self, out: &mut (dyn Write + Send)) -> Result {
+		let peeks = self.peeks.lock().len();
+		writeln!(out, "peeks: {peeks}")?;
+
+		Ok(())
+	}
+
+	async fn clear_cache(&self) {
+		self.peeks.lock().clear();
+		self.failures.lock().clear();
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Peeks into a world-readable room this server is not in, on behalf of
+/// `user_id`.
+///
+/// Fails with `Forbidden` when the room's history is not world-readable.
+#[implement(Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn peek(&self, room_id: &RoomId, user_id: &UserId) -> Result<Arc<Peek>> {
+	if !self.services.server.config.allow_federation {
+		return Err!(Request(Forbidden("Federation is disabled.")));
+	}
+
+	if let Some(peek) = self.cached(room_id)? {
+		return Ok(peek);
+	}
+
+	// Concurrent peeks into the room wait for the first one and share its result
+	let _fetching = self.fetching.lock(room_id).await;
+	if let Some(peek) = self.cached(room_id)? {
+		return Ok(peek);
+	}
+
+	let mut servers = self.candidate_servers();
+	while let Some(server) = servers.next().await {
+		match self.fetch(room_id, user_id, &server).await {
+			| Ok(peek) => {
+				let peek = Arc::new(peek);
+				if !is_world_readable(&peek.state) {
+					self.remember_failure(room_id);
+					return Err!(Request(Forbidden("This room is not world-readable.")));
+				}
+
+				let mut peeks = self.peeks.lock();
+				peeks.retain(|_, peek| peek.fetched.elapsed() < PEEK_CACHE_TTL);
+				if peeks.len() < PEEK_CACHE_MAX {
+					peeks.insert(room_id.to_owned(), peek.clone());
+				}
+
+				return Ok(peek);
+			},
+			| Err(e) => {
+				debug_warn!(%server, "Failed to peek into {room_id}: {e}");
+			},
+		}
+	}
+
+	self.remember_failure(room_id);
+	Err!(Request(NotFound("Room not found or is not accessible.")))
+}
+
+/// Looks up a recent peek into the room, failing if a recent peek failed.
+#[implement(Service)]
+fn cached(&self, room_id: &RoomId) -> Result<Option<Arc<Peek>>> {
+	let failed = self
+		.failures
+		.lock()
+		.get(room_id)
+		.is_some_and(|failed| failed.elapsed() < PEEK_CACHE_TTL);
+
+	if failed {
+		return Err!(Request(NotFound("Room not found or is not accessible.")));
+	}
+
+	Ok(self
+		.peeks
+		.lock()
+		.get(room_id)
+		.filter(|peek| peek.fetched.elapsed() < PEEK_CACHE_TTL)
+		.cloned())
+}
+
+#[implement(Service)]
+fn remember_failure(&self, room_id: &RoomId) {
+	let mut failures = self.failures.lock();
+	failures.retain(|_, failed| failed.elapsed() < PEEK_CACHE_TTL);
+	if failures.len() < PEEK_CACHE_MAX {
+		failures.insert(room_id.to_owned(), Instant::now());
+	}
+}
+
+/// Fetches up to `limit` events preceding and including `from`, newest first.
+#[implement(Service)]
+#[tracing::instrument(skip(self, peek), fields(room_id = %peek.room_id), level = "debug")]
+pub async fn backfill(
+	&self,
+	peek: &Peek,
+	from: Vec<OwnedEventId>,
+	limit: usize,
+) -> Result<Vec<PduEvent>> {
+	let response = self
+		.services
+		.sending
+		.send_federation_request(&peek.server, get_backfill::v1::Request {
+			room_id: peek.room_id.clone(),
+			v: from,
+			limit: UInt::try_from(limit)?,
+		})
+		.await?;
+
+	let mut pdus: Vec<_> = response
+		.pdus
+		.iter()
+		.stream()
+		.filter_map(|pdu| self.verify(peek, pdu))
+		.collect()
+		.await;
+
+	pdus.sort_by(|a, b| {
+		b.depth
+			.cmp(&a.depth)
+			.then_with(|| b.event_id.cmp(&a.event_id))
+	});
+	pdus.dedup_by(|a, b| a.event_id == b.event_id);
+
+	Ok(pdus)
+}
+
+/// Fetches a single event of a peeked room.
+#[implement(Service)]
+#[tracing::instrument(skip(self, peek), fields(room_id = %peek.room_id), level = "debug")]
+pub async fn event(&self, peek: &Peek, event_id: &OwnedEventId) -> Result<PduEvent> {
+	let response = self
+		.services
+		.sending
+		.send_federation_request(&peek.server, get_event::v1::Request {
+			event_id: event_id.clone(),
+			include_unredacted_content: Some(false),
+		})
+		.await?;
+
+	self.verify(peek, &response.pdu)
+		.await
+		.filter(|pdu| pdu.event_id == *event_id)
+		.ok_or_else(|| err!(Request(NotFound("Event not found."))))
+}
+
+#[implement(Service)]
+async fn fetch(
+	&self,
+	room_id: &RoomId,
+	user_id: &UserId,
+	server: &OwnedServerName,
+) -> Result<Peek> {
+	// The join template carries the room version and the forward extremities;
+	// it is only used to learn those and is never signed or sent back.
+	let template = self
+		.services
+		.sending
+		.send_federation_request(server, prepare_join_event::v1::Request {
+			room_id: room_id.to_owned(),
+			user_id: user_id.to_owned(),
+			ver: self.services.server.supported_room_versions().collect(),
+		})
+		.await?;
+
+	let Some(room_version) = template.room_version else {
+		return Err!(BadServerResponse("make_join response is missing the room version."));
+	};
+
+	let JoinTemplate { prev_events: latest } = serde_json::from_str(template.event.get())?;
+
+	let Some(extremity) = latest.first() else {
+		return Err!(BadServerResponse("make_join response has no prev_events."));
+	};
+
+	let mut peek = Peek {
+		room_id: room_id.to_owned(),
+		room_version,
+		server: server.clone(),
+		state: Vec::new(),
+		latest: latest.clone(),
+		fetched: Instant::now(),
+	};
+
+	let state = self
+		.services
+		.sending
+		.send_federation_request(server, get_room_state::v1::Request {
+			room_id: room_id.to_owned(),
+			event_id: extremity.clone(),
+		})
+		.await?;
+
+	let auth_chain: Vec<_> = state
+		.auth_chain
+		.iter()
+		.stream()
+		.filter_map(|pdu| self.verify(&peek, pdu))
+		.collect()
+		.await;
+
+	let mut pdus: Vec<_> = state
+		.pdus
+		.iter()
+		.stream()
+		.filter_map(|pdu| self.verify(&peek, pdu))
+		.collect()
+		.await;
+
+	// `/state` returns the state before the extremity, which may itself be a
+	// state event.
+	if let Ok(pdu) = self.event(&peek, extremity).await {
+		if pdu.state_key.is_some() {
+			pdus.push(pdu);
+		}
+	}
+
+	peek.state = auth_checked_state(&peek.room_version, pdus, auth_chain).await?;
+	debug!(%server, "Peeked into {room_id} with {} state events", peek.state.len());
+
+	Ok(peek)
+}
+
+/// Verifies the signatures of a PDU received for a peeked room.
+#[implement(Service)]
+async fn verify(&self, peek: &Peek, pdu: &RawJsonValue) -> Option<PduEvent> {
+	let (event_id, value) = self
+		.services
+		.server_keys
+		.validate_and_add_event_id(pdu, &peek.room_version)
+		.await
+		.inspect_err(|e| debug_warn!("Dropping peeked PDU failing verification: {e}"))
+		.ok()?;
+
+	let pdu = PduEvent::from_id_val(&event_id, value)
+		.inspect_err(|e| warn!("Dropping invalid peeked PDU {event_id}: {e}"))
+		.ok()?;
+
+	(pdu.room_id_or_hash() == peek.room_id).then_some(pdu)
+}
+
+/// Servers to peek through: the trusted servers federation is allowed with.
+#[implement(Service)]
+fn candidate_servers(&self) -> impl futures::Stream<Item = OwnedServerName> + Send + '_ {
+	self.services
+		.server
+		.config
+		.trusted_servers
+		.iter()
+		.filter(|server| !self.services.globals.server_is_ours(server))
+		.filter(|server| !self.services.moderation.is_remote_server_forbidden(server))
+		.cloned()
+		.stream()
+}
+
+/// Keeps the state events which pass the auth rules against their auth
+/// events, all of which must have passed themselves, back to the room's create
+/// event. Where a state key holds several events, the deepest is kept.
+async fn auth_checked_state(
+	room_version_id: &RoomVersionId,
+	state: Vec<PduEvent>,
+	auth_chain: Vec<PduEvent>,
+) -> Result<Vec<PduEvent>> {
+	let room_version = RoomVersion::new(room_version_id)?;
+	let Some(create_event) = state
+		.iter()
+		.find(|pdu| {
+			pdu.kind == TimelineEventType::RoomCreate && pdu.state_key.as_deref() == Some("")
+		})
+		.cloned()
+	else {
+		return Err!(BadServerResponse("Room state is missing the create event."));
+	};
+
+	let state_ids: Vec<OwnedEventId> = state.iter().map(|pdu| pdu.event_id.clone()).collect();
+	let mut events: Vec<PduEvent> = auth_chain
+		.into_iter()
+		.chain(state)
+		.map(|pdu| (pdu.event_id.clone(), pdu))
+		.collect::<HashMap<_, _>>()
+		.into_values()
+		.collect();
+
+	events.sort_by_key(|pdu| pdu.depth);
+
+	// Auth events precede the events they authorize, so checking in order of
+	// depth finds every auth event already checked.
+	let mut accepted: HashMap<OwnedEventId, PduEvent> = HashMap::new();
+	for pdu in events {
+		let auth_events: Option<HashMap<(StateEventType, StateKey), PduEvent>> = pdu
+			.auth_events()
+			.map(|event_id| {
+				let auth_event = accepted.get(event_id)?;
+				let key = (auth_event.kind.to_string().into(), auth_event.state_key.clone()?);
+				Some((key, auth_event.clone()))
+			})
+			.collect();
+
+		let Some(auth_events) = auth_events else {
+			debug_warn!(
+				event_id = %pdu.event_id,
+				"Dropping peeked event with rejected auth events"
+			);
+			continue;
+		};
+
+		let fetch_state = |ty: &StateEventType, state_key: &str| {
+			ready(auth_events.get(&(ty.clone(), state_key.into())).cloned())
+		};
+
+		let allowed = state_res::event_auth::auth_check(
+			&room_version,
+			&pdu,
+			None,
+			fetch_state,
+			&create_event,
+		)
+		.await
+		.unwrap_or(false);
+
+		if !allowed {
+			debug_warn!(event_id = %pdu.event_id, "Dropping peeked event failing auth");
+			continue;
+		}
+
+		accepted.insert(pdu.event_id.clone(), pdu);
+	}
+
+	if !accepted.contains_key(&create_event.event_id) {
+		return Err!(BadServerResponse("Room create event failed the auth rules."));
+	}
+
+	let mut state: Vec<PduEvent> = state_ids
+		.iter()
+		.filter_map(|event_id| accepted.remove(event_id))
+		.collect();
+
+	state.sort_by_key(|pdu| pdu.depth);
+	let state: HashMap<_, _> = state
+		.into_iter()
+		.filter_map(|pdu| Some(((pdu.kind.to_string(), pdu.state_key.clone()?), pdu)))
+		.collect();
+
+	Ok(state.into_values().collect())
+}
+
+fn is_world_readable(state: &[PduEvent]) -> bool {
+	state
+		.iter()
+		.find(|pdu| {
+			pdu.kind == TimelineEventType::RoomHistoryVisibility
+				&& pdu.state_key.as_deref() == Some("")
+		})
+		.and_then(|pdu| pdu.get_content::<RoomHistoryVisibilityEventContent>().ok())
+		.is_some_and(|content| content.history_visibility == HistoryVisibility::WorldReadable)
+}
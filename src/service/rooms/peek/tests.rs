@@ -0,0 +1,220 @@
+#![cfg(test)]
+
+use conduwuit::{PduEvent, pdu::EventHash};
+use ruma::{
+	EventId, OwnedEventId, RoomVersionId, UInt, UserId, events::TimelineEventType, room_id,
+	user_id,
+};
+use serde_json::{Value as JsonValue, json, value::to_raw_value};
+
+use super::{auth_checked_state, is_world_readable};
+
+const ALICE: &str = "@alice:example.com";
+const MALLORY: &str = "@mallory:evil.example";
+
+fn event_id(id: &str) -> OwnedEventId {
+	EventId::parse(format!("${id}:example.com")).expect("valid event id")
+}
+
+fn pdu(
+	id: &str,
+	depth: u32,
+	sender: &UserId,
+	kind: TimelineEventType,
+	state_key: &str,
+	content: JsonValue,
+	auth_events: &[&str],
+) -> PduEvent {
+	PduEvent {
+		event_id: event_id(id),
+		room_id: Some(room_id!("!room:example.com").to_owned()),
+		sender: sender.to_owned(),
+		origin_server_ts: UInt::from(depth),
+		kind,
+		content: to_raw_value(&content).expect("content"),
+		state_key: Some(state_key.into()),
+		prev_events: (depth > 1)
+			.then(|| event_id(auth_events.last().copied().unwrap_or("create")))
+			.into_iter()
+			.collect(),
+		depth: UInt::from(depth),
+		auth_events: auth_events.iter().copied().map(event_id).collect(),
+		redacts: None,
+		unsigned: None,
+		hashes: EventHash { sha256: String::new() },
+		signatures: None,
+		origin: None,
+	}
+}
+
+fn history_visibility(id: &str, depth: u32, sender: &UserId, visibility: &str) -> PduEvent {
+	pdu(
+		id,
+		depth,
+		sender,
+		TimelineEventType::RoomHistoryVisibility,
+		"",
+		json!({ "history_visibility": visibility }),
+		&["create", "join", "power_levels"],
+	)
+}
+
+/// The create event, the creator's join and the power levels of a room
+/// created by Alice.
+fn room() -> Vec<PduEvent> {
+	let alice = user_id!("@alice:example.com");
+	vec![
+		pdu(
+			"create",
+			1,
+			alice,
+			TimelineEventType::RoomCreate,
+			"",
+			json!({ "creator": ALICE, "room_version": "6" }),
+			&[],
+		),
+		pdu(
+			"join",
+			2,
+			alice,
+			TimelineEventType::RoomMember,
+			ALICE,
+			json!({ "membership": "join" }),
+			&["create"],
+		),
+		pdu(
+			"power_levels",
+			3,
+			alice,
+			TimelineEventType::RoomPowerLevels,
+			"",
+			json!({ "users": { ALICE: 100 } }),
+			&["create", "join"],
+		),
+	]
+}
+
+fn state_ids(state: &[PduEvent]) -> Vec<OwnedEventId> {
+	let mut ids: Vec<_> = state.iter().map(|pdu| pdu.event_id.clone()).collect();
+	ids.sort();
+	ids
+}
+
+#[tokio::test]
+async fn authorized_state_is_kept() {
+	let alice = user_id!("@alice:example.com");
+	let mut state = room();
+	state.push(history_visibility("visibility", 4, alice, "world_readable"));
+
+	let checked = auth_checked_state(&RoomVersionId::V6, state.clone(), vec![])
+		.await
+		.expect("room state passes auth");
+
+	assert_eq!(state_ids(&checked), state_ids(&state));
+	assert!(is_world_readable(&checked));
+}
+
+#[tokio::test]
+async fn state_is_checked_against_auth_chain() {
+	let alice = user_id!("@alice:example.com");
+	let mut auth_chain = room();
+	let mut state = vec![auth_chain.remove(0)];
+	state.push(history_visibility("visibility", 4, alice, "world_readable"));
+
+	let checked = auth_checked_state(&RoomVersionId::V6, state.clone(), auth_chain)
+		.await
+		.expect("room state passes auth");
+
+	// only events in the state itself are returned
+	assert_eq!(state_ids(&checked), state_ids(&state));
+	assert!(is_world_readable(&checked));
+}
+
+#[tokio::test]
+async fn forged_state_from_non_member_is_dropped() {
+	let (alice, mallory) = (user_id!("@alice:example.com"), user_id!("@mallory:evil.example"));
+	let mut state = room();
+	state.push(history_visibility("visibility", 4, alice, "shared"));
+	state.push(history_visibility("forged", 5, mallory, "world_readable"));
+
+	let checked = auth_checked_state(&RoomVersionId::V6, state, vec![])
+		.await
+		.expect("room state passes auth");
+
+	assert!(!checked.iter().any(|pdu| pdu.event_id == event_id("forged")));
+	assert!(
+		checked
+			.iter()
+			.any(|pdu| pdu.event_id == event_id("visibility"))
+	);
+	assert!(!is_world_readable(&checked));
+}
+
+#[tokio::test]
+async fn state_authorized_by_rejected_event_is_dropped() {
+	let mallory = user_id!("@mallory:evil.example");
+	let mut state = room();
+
+	// the room is invite-only by default, so the join is rejected
+	state.push(pdu(
+		"mallory_join",
+		4,
+		mallory,
+		TimelineEventType::RoomMember,
+		MALLORY,
+		json!({ "membership": "join" }),
+		&["create", "power_levels"],
+	));
+	state.push(pdu(
+		"topic",
+		5,
+		mallory,
+		TimelineEventType::RoomTopic,
+		"",
+		json!({ "topic": "hijacked" }),
+		&["create", "power_levels", "mallory_join"],
+	));
+	state.push(pdu(
+		"name",
+		6,
+		mallory,
+		TimelineEventType::RoomName,
+		"",
+		json!({ "name": "hijacked" }),
+		&["create", "power_levels", "unknown"],
+	));
+
+	let checked = auth_checked_state(&RoomVersionId::V6, state, vec![])
+		.await
+		.expect("room state passes auth");
+
+	assert_eq!(state_ids(&checked), state_ids(&room()));
+}
+
+#[tokio::test]
+async fn state_without_create_event_is_rejected() {
+	let alice = user_id!("@alice:example.com");
+	let mut state = room();
+	state.remove(0);
+	state.push(history_visibility("visibility", 4, alice, "world_readable"));
+
+	assert!(
+		auth_checked_state(&RoomVersionId::V6, state, vec![])
+			.await
+			.is_err()
+	);
+}
+
+#[test]
+fn world_readable_needs_history_visibility() {
+	let alice = user_id!("@alice:example.com");
+	let mut state = room();
+	assert!(!is_world_readable(&state));
+
+	state.push(history_visibility("visibility", 4, alice, "joined"));
+	assert!(!is_world_readable(&state));
+
+	state.pop();
+	state.push(history_visibility("visibility", 4, alice, "world_readable"));
+	assert!(is_world_readable(&state));
+}
@@ -4,19 +4,25 @@
 	Result,
 	utils::{ReadyExt, stream::TryIgnore},
 };
-use database::{Deserialized, Json, Map};
+use database::{Deserialized, Ignore, Interfix, Json, Map};
 use futures::{Stream, StreamExt};
 use ruma::{
-	CanonicalJsonObject, OwnedUserId, RoomId, UserId,
-	events::{AnySyncEphemeralRoomEvent, receipt::ReceiptEvent},
+	CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, OwnedUserId, RoomId, UserId,
+	events::{
+		AnySyncEphemeralRoomEvent,
+		receipt::{ReceiptEvent, ReceiptThread},
+	},
 	serde::Raw,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{Dep, globals};
 
 pub(super) struct Data {
 	roomuserid_privateread: Arc<Map>,
 	roomuserid_lastprivatereadupdate: Arc<Map>,
+	roomuserthreadid_privateread: Arc<Map>,
+	roomuserthreadid_readreceiptid: Arc<Map>,
 	services: Services,
 	readreceiptid_readreceipt: Arc<Map>,
 }
@@ -27,12 +33,21 @@ struct Services {
 
 pub(super) type ReceiptItem = (OwnedUserId, u64, Raw<AnySyncEphemeralRoomEvent>);
 
+/// A private read receipt in one thread.
+#[derive(Deserialize, Serialize)]
+pub(super) struct PrivateRead {
+	pub(super) count: u64,
+	pub(super) ts: MilliSecondsSinceUnixEpoch,
+}
+
 impl Data {
 	pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		let db = &args.db;
 		Self {
 			roomuserid_privateread: db["roomuserid_privateread"].clone(),
 			roomuserid_lastprivatereadupdate: db["roomuserid_lastprivatereadupdate"].clone(),
+			roomuserthreadid_privateread: db["roomuserthreadid_privateread"].clone(),
+			roomuserthreadid_readreceiptid: db["roomuserthreadid_readreceiptid"].clone(),
 			readreceiptid_readreceipt: db["readreceiptid_readreceipt"].clone(),
 			services: Services {
 				globals: args.depend::<globals::Service>("globals"),
@@ -46,19 +61,47 @@ pub(super) async fn readreceipt_update(
 		room_id: &RoomId,
 		event: &ReceiptEvent,
 	) {
-		// Remove old entry
-		let last_possible_key = (room_id, u64::MAX);
-		self.readreceiptid_readreceipt
-			.rev_keys_from_raw(&last_possible_key)
-			.ignore_err()
-			.ready_take_while(|key| key.starts_with(room_id.as_bytes()))
-			.ready_filter_map(|key| key.ends_with(user_id.as_bytes()).then_some(key))
-			.ready_for_each(|key| self.readreceiptid_readreceipt.del(key))
-			.await;
+		// Remove the old entry in the same thread
+		let thread = receipt_thread(event);
+		let thread_key = (room_id, user_id, thread.as_str().unwrap_or_default());
+		match self
+			.roomuserthreadid_readreceiptid
+			.qry(&thread_key)
+			.await
+			.deserialized::<u64>()
+		{
+			| Ok(prev) => self.readreceiptid_readreceipt.del((room_id, prev, user_id)),
+			| Err(_) =>
+				self.remove_unindexed_readreceipt(user_id, room_id, &thread)
+					.await,
+		}
 
 		let count = self.services.globals.next_count().unwrap();
 		let latest_id = (room_id, count, user_id);
 		self.readreceiptid_readreceipt.put(latest_id, Json(event));
+		self.roomuserthreadid_readreceiptid.put(thread_key, count);
+	}
+
+	/// Removes the user's receipt in the thread when it predates the index of
+	/// receipts by user and thread, by looking through the room's receipts.
+	async fn remove_unindexed_readreceipt(
+		&self,
+		user_id: &UserId,
+		room_id: &RoomId,
+		thread: &ReceiptThread,
+	) {
+		let last_possible_key = (room_id, u64::MAX);
+		self.readreceiptid_readreceipt
+			.rev_stream_from_raw(&last_possible_key)
+			.ignore_err()
+			.ready_take_while(|(key, _)| key.starts_with(room_id.as_bytes()))
+			.ready_filter(|(key, _)| key.ends_with(user_id.as_bytes()))
+			.ready_filter(|(_, prev)| {
+				serde_json::from_slice::<ReceiptEvent>(prev)
+					.is_ok_and(|prev| receipt_thread(&prev) == *thread)
+			})
+			.ready_for_each(|(key, _)| self.readreceiptid_readreceipt.del(key))
+			.await;
 	}
 
 	pub(super) fn readreceipts_since<'a>(
@@ -86,14 +129,47 @@ pub(super) fn readreceipts_since<'a>(
 			.ignore_err()
 	}
 
-	pub(super) fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, pdu_count: u64) {
+	pub(super) fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		pdu_count: u64,
+		thread: &ReceiptThread,
+	) {
 		let key = (room_id, user_id);
 		let next_count = self.services.globals.next_count().unwrap();
 
-		self.roomuserid_privateread.put(key, pdu_count);
+		if *thread == ReceiptThread::Unthreaded {
+			self.roomuserid_privateread.put(key, pdu_count);
+		}
+
+		let thread_key = (room_id, user_id, thread.as_str().unwrap_or_default());
+		let read = PrivateRead {
+			count: pdu_count,
+			ts: MilliSecondsSinceUnixEpoch::now(),
+		};
+
+		self.roomuserthreadid_privateread
+			.put(thread_key, Json(read));
 		self.roomuserid_lastprivatereadupdate.put(key, next_count);
 	}
 
+	pub(super) fn private_reads<'a>(
+		&'a self,
+		room_id: &'a RoomId,
+		user_id: &'a UserId,
+	) -> impl Stream<Item = (ReceiptThread, PrivateRead)> + Send + 'a {
+		type KeyVal<'a> = ((Ignore, Ignore, &'a str), PrivateRead);
+
+		let prefix = (room_id, user_id, Interfix);
+		self.roomuserthreadid_privateread
+			.stream_prefix(&prefix)
+			.ignore_err()
+			.ready_filter_map(|((_, _, thread), read): KeyVal<'_>| {
+				parse_thread(thread).map(|thread| (thread, read))
+			})
+	}
+
 	pub(super) async fn private_read_get_count(
 		&self,
 		room_id: &RoomId,
@@ -116,3 +192,24 @@ pub(super) async fn last_privateread_update(
 			.unwrap_or(0)
 	}
 }
+
+/// Returns the thread of a receipt event; ours hold a single receipt.
+pub(super) fn receipt_thread(event: &ReceiptEvent) -> ReceiptThread {
+	event
+		.content
+		.0
+		.values()
+		.flat_map(|receipts| receipts.values())
+		.flat_map(|receipts| receipts.values())
+		.map(|receipt| receipt.thread.clone())
+		.next()
+		.unwrap_or(ReceiptThread::Unthreaded)
+}
+
+fn parse_thread(thread: &str) -> Option<ReceiptThread> {
+	match thread {
+		| "" => Some(ReceiptThread::Unthreaded),
+		| "main" => Some(ReceiptThread::Main),
+		| event_id => EventId::parse(event_id).ok().map(ReceiptThread::Thread),
+	}
+}
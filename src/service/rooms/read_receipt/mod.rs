@@ -3,19 +3,21 @@
 use std::{collections::BTreeMap, sync::Arc};
 
 use conduwuit::{
-	Result, debug, err,
+	Err, Result, debug, err,
 	matrix::{
 		Event,
 		pdu::{PduCount, PduId, RawPduId},
 	},
 	warn,
 };
-use futures::{Stream, TryFutureExt, try_join};
+use futures::{Stream, StreamExt};
 use ruma::{
-	OwnedEventId, OwnedUserId, RoomId, UserId,
+	OwnedEventId, RoomId, UserId,
 	events::{
 		AnySyncEphemeralRoomEvent, SyncEphemeralRoomEvent,
-		receipt::{ReceiptEvent, ReceiptEventContent, Receipts},
+		receipt::{
+			Receipt, ReceiptEvent, ReceiptEventContent, ReceiptThread, ReceiptType, Receipts,
+		},
 	},
 	serde::Raw,
 };
@@ -65,36 +67,63 @@ pub async fn readreceipt_update(
 			.expect("room flush failed");
 	}
 
-	/// Gets the latest private read receipt from the user in the room
+	/// Gets the latest private read receipts from the user in the room, one
+	/// per thread.
 	pub async fn private_read_get(
 		&self,
 		room_id: &RoomId,
 		user_id: &UserId,
 	) -> Result<Raw<AnySyncEphemeralRoomEvent>> {
-		let pdu_count = self.private_read_get_count(room_id, user_id).map_err(|e| {
-			err!(Database(warn!("No private read receipt was set in {room_id}: {e}")))
-		});
-		let shortroomid = self.services.short.get_shortroomid(room_id).map_err(|e| {
-			err!(Database(warn!("Short room ID does not exist in database for {room_id}: {e}")))
-		});
-
-		let (pdu_count, shortroomid) = try_join!(pdu_count, shortroomid)?;
-		let shorteventid = PduCount::Normal(pdu_count);
-		let pdu_id: RawPduId = PduId { shortroomid, shorteventid }.into();
-		let pdu = self.services.timeline.get_pdu_from_id(&pdu_id).await?;
-
-		let event_id: OwnedEventId = pdu.event_id().to_owned();
-		let user_id: OwnedUserId = user_id.to_owned();
-		let content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::from_iter([(
-			event_id,
-			BTreeMap::from_iter([(
-				ruma::events::receipt::ReceiptType::ReadPrivate,
-				BTreeMap::from_iter([(user_id, ruma::events::receipt::Receipt {
-					ts: None, // TODO: start storing the timestamp so we can return one
-					thread: ruma::events::receipt::ReceiptThread::Unthreaded,
-				})]),
-			)]),
-		)]);
+		let mut reads: Vec<_> = self
+			.db
+			.private_reads(room_id, user_id)
+			.map(|(thread, read)| (thread, read.count, Some(read.ts)))
+			.collect()
+			.await;
+
+		// receipts set before their timestamps were stored
+		if reads.is_empty() {
+			let count = self
+				.private_read_get_count(room_id, user_id)
+				.await
+				.map_err(|e| {
+					err!(Database(warn!("No private read receipt was set in {room_id}: {e}")))
+				})?;
+
+			reads.push((ReceiptThread::Unthreaded, count, None));
+		}
+
+		let shortroomid = self
+			.services
+			.short
+			.get_shortroomid(room_id)
+			.await
+			.map_err(|e| {
+				err!(Database(warn!(
+					"Short room ID does not exist in database for {room_id}: {e}"
+				)))
+			})?;
+
+		let mut content = BTreeMap::<OwnedEventId, Receipts>::new();
+		for (thread, count, ts) in reads {
+			let shorteventid = PduCount::Normal(count);
+			let pdu_id: RawPduId = PduId { shortroomid, shorteventid }.into();
+			let Ok(pdu) = self.services.timeline.get_pdu_from_id(&pdu_id).await else {
+				continue;
+			};
+
+			content
+				.entry(pdu.event_id().to_owned())
+				.or_default()
+				.entry(ReceiptType::ReadPrivate)
+				.or_default()
+				.insert(user_id.to_owned(), Receipt { ts, thread });
+		}
+
+		if content.is_empty() {
+			return Err!(Database("Private read receipts in {room_id} point to no events."));
+		}
+
 		let receipt_event_content = ReceiptEventContent(content);
 		let receipt_sync_event = SyncEphemeralRoomEvent { content: receipt_event_content };
 
@@ -116,11 +145,17 @@ pub fn readreceipts_since<'a>(
 		self.db.readreceipts_since(room_id, since.unwrap_or(0))
 	}
 
-	/// Sets a private read marker at PDU `count`.
+	/// Sets a private read marker at PDU `count` in the thread.
 	#[inline]
 	#[tracing::instrument(skip(self), level = "debug")]
-	pub fn private_read_set(&self, room_id: &RoomId, user_id: &UserId, count: u64) {
-		self.db.private_read_set(room_id, user_id, count);
+	pub fn private_read_set(
+		&self,
+		room_id: &RoomId,
+		user_id: &UserId,
+		count: u64,
+		thread: &ReceiptThread,
+	) {
+		self.db.private_read_set(room_id, user_id, count, thread);
 	}
 
 	/// Returns the private read marker PDU count.
@@ -153,8 +188,14 @@ pub fn pack_receipts<I>(receipts: I) -> Raw<SyncEphemeralRoomEvent<ReceiptEventC
 		);
 		match receipt {
 			| Ok(value) =>
-				for (event, receipt) in value.content {
-					json.insert(event, receipt);
+				for (event, receipts) in value.content.0 {
+					let event_receipts: &mut Receipts = json.entry(event).or_default();
+					for (receipt_type, user_receipts) in receipts {
+						event_receipts
+							.entry(receipt_type)
+							.or_default()
+							.extend(user_receipts);
+					}
 				},
 			| _ => {
 				debug!("failed to parse receipt: {:?}", receipt);
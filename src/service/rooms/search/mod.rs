@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use conduwuit::{
-	PduCount, PduEvent, Result,
+	PduCount, Result,
 	arrayvec::ArrayVec,
 	debug_warn, implement,
 	matrix::event::{Event, Matches},
@@ -18,7 +18,7 @@
 	Dep, rooms,
 	rooms::{
 		short::ShortRoomId,
-		timeline::{PduId, RawPduId},
+		timeline::{PduId, PdusIterItem, RawPduId},
 	},
 };
 
@@ -44,7 +44,9 @@ pub struct RoomQuery<'a> {
 	pub user_id: Option<&'a UserId>,
 	pub criteria: &'a Criteria,
 	pub limit: usize,
-	pub skip: usize,
+	/// Only return results older than this position, i.e. continue after the
+	/// last result of a previous batch.
+	pub before: Option<PduCount>,
 }
 
 type TokenId = ArrayVec<u8, TOKEN_ID_MAX_LEN>;
@@ -107,7 +109,7 @@ pub async fn search_pdus<'a>(
 	&'a self,
 	query: &'a RoomQuery<'a>,
 	sender_user: &'a UserId,
-) -> Result<(usize, impl Stream<Item = PduEvent> + Send + 'a)> {
+) -> Result<(usize, impl Stream<Item = PdusIterItem> + Send + 'a)> {
 	let pdu_ids: Vec<_> = self.search_pdu_ids(query).await?.collect().await;
 
 	let filter = &query.criteria.filter;
@@ -115,30 +117,35 @@ pub async fn search_pdus<'a>(
 	let pdus = pdu_ids
 		.into_iter()
 		.stream()
+		.ready_filter(move |pdu_id| {
+			query
+				.before
+				.is_none_or(|before| pdu_id.pdu_count() < before)
+		})
 		.wide_filter_map(move |result_pdu_id: RawPduId| async move {
 			self.services
 				.timeline
 				.get_pdu_from_id(&result_pdu_id)
 				.await
+				.map(|pdu| (result_pdu_id.pdu_count(), pdu))
 				.ok()
 		})
-		.ready_filter(|pdu| !pdu.is_redacted())
-		.ready_filter(move |pdu| filter.matches(pdu))
-		.wide_filter_map(move |pdu| async move {
+		.ready_filter(|(_, pdu)| !pdu.is_redacted())
+		.ready_filter(move |(_, pdu)| filter.matches(pdu))
+		.wide_filter_map(move |(count, pdu)| async move {
 			self.services
 				.state_accessor
 				.user_can_see_event(query.user_id?, pdu.room_id().unwrap(), pdu.event_id())
 				.await
-				.then_some(pdu)
+				.then_some((count, pdu))
 		})
-		.skip(query.skip)
 		.take(query.limit)
-		.map(move |mut pdu| {
+		.map(move |(count, mut pdu)| {
 			pdu.set_unsigned(query.user_id);
 
-			pdu
+			(count, pdu)
 		})
-		.then(async move |mut pdu| {
+		.then(async move |(count, mut pdu)| {
 			if let Err(e) = self
 				.services
 				.pdu_metadata
@@ -147,7 +154,7 @@ pub async fn search_pdus<'a>(
 			{
 				debug_warn!("Failed to add bundled aggregations: {e}");
 			}
-			pdu
+			(count, pdu)
 		});
 
 	Ok((count, pdus))
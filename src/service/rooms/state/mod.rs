@@ -40,6 +40,7 @@ pub struct Service {
 }
 
 struct Services {
+	directory: Dep<rooms::directory::Service>,
 	globals: Dep<globals::Service>,
 	short: Dep<rooms::short::Service>,
 	spaces: Dep<rooms::spaces::Service>,
@@ -64,6 +65,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			mutex: RoomMutexMap::new(),
 			services: Services {
+				directory: args.depend::<rooms::directory::Service>("rooms::directory"),
 				globals: args.depend::<globals::Service>("globals"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				spaces: args.depend::<rooms::spaces::Service>("rooms::spaces"),
@@ -146,6 +148,7 @@ pub async fn force_state(
 		self.services.state_cache.update_joined_count(room_id).await;
 
 		self.set_room_state(room_id, shortstatehash, state_lock);
+		self.services.directory.update_room(room_id).await;
 
 		Ok(())
 	}
@@ -2,12 +2,15 @@
 
 use conduwuit::{
 	Result, err, implement,
-	matrix::{Event, StateKey},
+	matrix::{Event, StateKey, pdu::PduCount},
+	utils::stream::TryIgnore,
 };
 use futures::{Stream, StreamExt, TryFutureExt};
 use ruma::{EventId, RoomId, events::StateEventType};
 use serde::Deserialize;
 
+use crate::rooms::short::ShortStateHash;
+
 /// Returns a single PDU from `room_id` with key (`event_type`,`state_key`).
 #[implement(super::Service)]
 pub async fn room_state_get_content<T>(
@@ -110,3 +113,27 @@ pub async fn room_state_keys(
 
 	Ok(state_keys)
 }
+
+/// Returns the state of the room as of a position in its timeline, such as a
+/// sync or pagination token: the state before the first event following
+/// `count`, or the current state when no event has followed it yet.
+#[implement(super::Service)]
+#[tracing::instrument(skip(self), level = "debug")]
+pub async fn room_state_at_count(
+	&self,
+	room_id: &RoomId,
+	count: PduCount,
+) -> Result<ShortStateHash> {
+	let next = self
+		.services
+		.timeline
+		.pdus(room_id, Some(count))
+		.ignore_err()
+		.next()
+		.await;
+
+	match next {
+		| Some((_, pdu)) => self.pdu_shortstatehash(&pdu.event_id).await,
+		| None => self.services.state.get_room_shortstatehash(room_id).await,
+	}
+}
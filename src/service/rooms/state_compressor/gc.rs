@@ -0,0 +1,437 @@
+//! Garbage collection of state snapshots.
+//!
+//! A state snapshot (shortstatehash) is referenced when it is the current state
+//! of a room, the state at an event, or the state at a sync token. Diffs of
+//! unreferenced snapshots are deleted; referenced diffs which were layered on
+//! top of a deleted one are re-parented onto their nearest surviving ancestor.
+//! An unreferenced diff which several surviving diffs still depend on is kept,
+//! since folding it into each of them would cost more space than it frees.
+
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+	mem::size_of,
+	sync::Arc,
+};
+
+use conduwuit::{
+	Err, Result, debug, err, implement, info,
+	utils::{self, ReadyExt, bytes::pretty, stream::TryIgnore},
+	warn,
+};
+
+use super::{CompressedState, StateDiff};
+use crate::rooms::short::ShortStateHash;
+
+/// Outcome of a state garbage collection run.
+#[derive(Debug, Default)]
+pub struct GcReport {
+	/// Whether changes were only computed and not written.
+	pub dry_run: bool,
+
+	/// Number of state diffs in the database.
+	pub total: usize,
+
+	/// Number of state diffs referenced by a room, event or sync token.
+	pub referenced: usize,
+
+	/// Unreferenced diffs kept because several diffs depend on them.
+	pub retained: usize,
+
+	/// Diffs rewritten on top of a different parent.
+	pub reparented: usize,
+
+	/// Diffs deleted.
+	pub deleted: usize,
+
+	/// Bytes freed by deleting diffs and their state hash entries.
+	pub bytes_deleted: usize,
+
+	/// Bytes added by rewriting re-parented diffs; may be negative.
+	pub bytes_rewritten: isize,
+}
+
+struct DiffInfo {
+	parent: Option<ShortStateHash>,
+	size: usize,
+}
+
+type Diffs = HashMap<ShortStateHash, DiffInfo>;
+
+impl GcReport {
+	/// Net number of bytes reclaimed.
+	#[must_use]
+	pub fn bytes_reclaimed(&self) -> isize {
+		isize::try_from(self.bytes_deleted)
+			.unwrap_or(isize::MAX)
+			.saturating_sub(self.bytes_rewritten)
+	}
+}
+
+impl fmt::Display for GcReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let reclaimed = self.bytes_reclaimed();
+		let sign = if reclaimed < 0 { "-" } else { "" };
+		let reclaimed = pretty(reclaimed.unsigned_abs());
+
+		writeln!(f, "State diffs: {}", self.total)?;
+		writeln!(f, "Referenced: {}", self.referenced)?;
+		writeln!(f, "Retained (shared by several diffs): {}", self.retained)?;
+		writeln!(f, "Re-parented: {}", self.reparented)?;
+		writeln!(f, "Deleted: {}", self.deleted)?;
+		write!(f, "Bytes reclaimed: {sign}{reclaimed}")?;
+		if self.dry_run {
+			write!(f, " (dry run, nothing was changed)")?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Finds state snapshots no longer referenced by any room, event or sync
+/// token and deletes their diffs, re-parenting any diffs that depend on them.
+/// With `dry_run` nothing is written and the report describes what would
+/// happen.
+#[implement(super::Service)]
+#[tracing::instrument(name = "gc", level = "info", skip(self))]
+pub async fn collect_garbage(&self, dry_run: bool) -> Result<GcReport> {
+	// Snapshots created after this point are never collected by this run.
+	let horizon = self.services.globals.current_count()?;
+
+	let diffs = self.load_diffs().await;
+	let referenced = self.referenced_states(&diffs, |_| true).await;
+	debug!(diffs = diffs.len(), referenced = referenced.len(), "Loaded state graph");
+
+	let mut report = GcReport {
+		dry_run,
+		total: diffs.len(),
+		referenced: referenced.len(),
+		..Default::default()
+	};
+
+	let needed = plan(&diffs, &referenced, horizon);
+	let doomed: HashSet<ShortStateHash> = diffs
+		.keys()
+		.copied()
+		.filter(|shortstatehash| !needed.contains(shortstatehash))
+		.collect();
+
+	report.retained = needed.len().saturating_sub(referenced.len());
+	report.deleted = doomed.len();
+	report.bytes_deleted = doomed
+		.iter()
+		.filter_map(|shortstatehash| diffs.get(shortstatehash))
+		.map(|diff| diff.size.saturating_add(size_of::<ShortStateHash>()))
+		.fold(0_usize, usize::saturating_add);
+
+	if doomed.is_empty() {
+		return Ok(report);
+	}
+
+	// Compute every rewrite before touching the database, since building the
+	// full states reads through the diffs about to be deleted.
+	let mut rewrites = Vec::new();
+	for (&shortstatehash, diff) in &diffs {
+		let Some(parent) = diff.parent.filter(|parent| doomed.contains(parent)) else {
+			continue;
+		};
+
+		if !needed.contains(&shortstatehash) {
+			continue;
+		}
+
+		let new_parent = nearest_needed_ancestor(&diffs, &needed, parent);
+		let rewrite = self.rebase(shortstatehash, new_parent).await?;
+		let new_size = encoded_size(&rewrite);
+		report.bytes_rewritten = report
+			.bytes_rewritten
+			.saturating_add_unsigned(new_size)
+			.saturating_sub_unsigned(diff.size);
+
+		rewrites.push((shortstatehash, rewrite));
+	}
+
+	report.reparented = rewrites.len();
+
+	let statehashes = self.find_statehashes(&doomed).await;
+	report.bytes_deleted = statehashes
+		.iter()
+		.map(|hash| hash.len().saturating_add(size_of::<ShortStateHash>()))
+		.fold(report.bytes_deleted, usize::saturating_add);
+
+	if dry_run {
+		return Ok(report);
+	}
+
+	// Unlink the doomed snapshots from their state hashes first, so identical
+	// state saved from here on gets a fresh snapshot instead of reusing one of
+	// them, then make sure nothing started referencing them in the meantime.
+	for (hash, shortstatehash) in &statehashes {
+		self.db.statehash_shortstatehash.remove(hash);
+		debug!(?shortstatehash, "Unlinked state hash");
+	}
+
+	let raced = self
+		.referenced_states(&diffs, |shortstatehash| doomed.contains(&shortstatehash))
+		.await;
+
+	let adopted = self
+		.db
+		.shortstatehash_statediff
+		.raw_stream()
+		.ignore_err()
+		.ready_any(|(key, val)| {
+			utils::u64_from_bytes(key).is_ok_and(|shortstatehash| shortstatehash > horizon)
+				&& parse_parent(val).is_some_and(|parent| doomed.contains(&parent))
+		})
+		.await;
+
+	if !raced.is_empty() || adopted {
+		const BUFSIZE: usize = size_of::<ShortStateHash>();
+
+		for (hash, shortstatehash) in &statehashes {
+			self.db
+				.statehash_shortstatehash
+				.raw_aput::<BUFSIZE, _, _>(hash, *shortstatehash);
+		}
+
+		warn!("State changed while collecting garbage; nothing was deleted");
+		return Err!("Room state changed during garbage collection, please try again.");
+	}
+
+	for (shortstatehash, rewrite) in &rewrites {
+		self.save_statediff(*shortstatehash, rewrite);
+	}
+
+	for shortstatehash in &doomed {
+		self.db
+			.shortstatehash_statediff
+			.remove(&shortstatehash.to_be_bytes());
+	}
+
+	self.stateinfo_cache.lock().clear();
+
+	info!(
+		deleted = report.deleted,
+		reparented = report.reparented,
+		reclaimed = report.bytes_reclaimed(),
+		"Collected unreferenced state"
+	);
+
+	Ok(report)
+}
+
+/// Loads the parent and encoded size of every state diff.
+#[implement(super::Service)]
+async fn load_diffs(&self) -> Diffs {
+	let mut diffs = Diffs::new();
+	self.db
+		.shortstatehash_statediff
+		.raw_stream()
+		.ignore_err()
+		.ready_for_each(|(key, val)| {
+			let Ok(shortstatehash) = utils::u64_from_bytes(key) else {
+				return;
+			};
+
+			diffs.insert(shortstatehash, DiffInfo {
+				parent: parse_parent(val),
+				size: val.len(),
+			});
+		})
+		.await;
+
+	diffs
+}
+
+/// Collects the snapshots referenced by rooms, events and sync tokens which
+/// pass `filter` and have a diff.
+#[implement(super::Service)]
+async fn referenced_states<F>(&self, diffs: &Diffs, filter: F) -> HashSet<ShortStateHash>
+where
+	F: Fn(ShortStateHash) -> bool + Send + Sync,
+{
+	let mut referenced = HashSet::new();
+	for map in [
+		&self.db.roomid_shortstatehash,
+		&self.db.shorteventid_shortstatehash,
+		&self.db.roomsynctoken_shortstatehash,
+	] {
+		map.raw_stream()
+			.ignore_err()
+			.ready_filter_map(|(_, val)| utils::u64_from_bytes(val).ok())
+			.ready_filter(|shortstatehash| diffs.contains_key(shortstatehash))
+			.ready_filter(|shortstatehash| filter(*shortstatehash))
+			.ready_for_each(|shortstatehash| {
+				referenced.insert(shortstatehash);
+			})
+			.await;
+	}
+
+	referenced
+}
+
+/// Finds the state hash entries pointing at the given snapshots.
+#[implement(super::Service)]
+async fn find_statehashes(
+	&self,
+	shortstatehashes: &HashSet<ShortStateHash>,
+) -> Vec<(Vec<u8>, ShortStateHash)> {
+	self.db
+		.statehash_shortstatehash
+		.raw_stream()
+		.ignore_err()
+		.ready_filter_map(|(key, val)| {
+			let shortstatehash = utils::u64_from_bytes(val).ok()?;
+			shortstatehashes
+				.contains(&shortstatehash)
+				.then(|| (key.to_vec(), shortstatehash))
+		})
+		.collect()
+		.await
+}
+
+/// Computes the diff of a snapshot against another snapshot, or against the
+/// empty state when `parent` is `None`.
+#[implement(super::Service)]
+async fn rebase(
+	&self,
+	shortstatehash: ShortStateHash,
+	parent: Option<ShortStateHash>,
+) -> Result<StateDiff> {
+	let full_state = self.full_state(shortstatehash).await?;
+	let Some(parent) = parent else {
+		return Ok(StateDiff {
+			parent: None,
+			added: full_state,
+			removed: Arc::new(CompressedState::new()),
+		});
+	};
+
+	let parent_state = self.full_state(parent).await?;
+	Ok(StateDiff {
+		parent: Some(parent),
+		added: Arc::new(full_state.difference(&parent_state).copied().collect()),
+		removed: Arc::new(parent_state.difference(&full_state).copied().collect()),
+	})
+}
+
+#[implement(super::Service)]
+async fn full_state(&self, shortstatehash: ShortStateHash) -> Result<Arc<CompressedState>> {
+	self.load_shortstatehash_info(shortstatehash)
+		.await?
+		.pop()
+		.map(|info| info.full_state)
+		.ok_or_else(|| err!(Database("Empty state stack for {shortstatehash}")))
+}
+
+/// Decides which diffs must survive: referenced ones, ones newer than the
+/// horizon, and unreferenced ones that at least two surviving diffs depend on.
+///
+/// A parent is always older than its children, so visiting snapshots from the
+/// newest down sees every child before its parent.
+fn plan(
+	diffs: &Diffs,
+	referenced: &HashSet<ShortStateHash>,
+	horizon: u64,
+) -> HashSet<ShortStateHash> {
+	let mut order: Vec<_> = diffs.keys().copied().collect();
+	order.sort_unstable_by(|a, b| b.cmp(a));
+
+	let mut dependents = HashMap::<ShortStateHash, usize>::new();
+	let mut needed = HashSet::new();
+	for shortstatehash in order {
+		let count = dependents.get(&shortstatehash).copied().unwrap_or(0);
+		let keep = referenced.contains(&shortstatehash) || shortstatehash > horizon || count >= 2;
+		if keep {
+			needed.insert(shortstatehash);
+		}
+
+		// A removed diff with a single surviving dependent passes it up.
+		let passes_up = keep || count == 1;
+		if let Some(parent) = diffs[&shortstatehash].parent.filter(|_| passes_up) {
+			*dependents.entry(parent).or_default() += 1;
+		}
+	}
+
+	needed
+}
+
+fn nearest_needed_ancestor(
+	diffs: &Diffs,
+	needed: &HashSet<ShortStateHash>,
+	mut shortstatehash: ShortStateHash,
+) -> Option<ShortStateHash> {
+	loop {
+		if needed.contains(&shortstatehash) {
+			return Some(shortstatehash);
+		}
+
+		shortstatehash = diffs.get(&shortstatehash)?.parent?;
+	}
+}
+
+fn parse_parent(val: &[u8]) -> Option<ShortStateHash> {
+	val.get(0..size_of::<ShortStateHash>())
+		.and_then(|parent| utils::u64_from_bytes(parent).ok())
+		.filter(|parent| *parent != 0)
+}
+
+/// Size of a diff as encoded by `save_statediff`.
+fn encoded_size(diff: &StateDiff) -> usize {
+	let entries = diff.added.len().saturating_add(diff.removed.len());
+	let separator = usize::from(!diff.removed.is_empty());
+
+	size_of::<ShortStateHash>()
+		.saturating_mul(1_usize.saturating_add(separator))
+		.saturating_add(entries.saturating_mul(size_of::<super::CompressedStateEvent>()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn diffs(edges: &[(u64, Option<u64>)]) -> Diffs {
+		edges
+			.iter()
+			.map(|&(shortstatehash, parent)| (shortstatehash, DiffInfo { parent, size: 0 }))
+			.collect()
+	}
+
+	#[test]
+	fn plan_removes_unreferenced_leaves() {
+		let diffs = diffs(&[(1, None), (2, Some(1)), (3, Some(2))]);
+		let referenced = HashSet::from([1, 2]);
+
+		let needed = plan(&diffs, &referenced, u64::MAX - 1);
+		assert_eq!(needed, HashSet::from([1, 2]));
+	}
+
+	#[test]
+	fn plan_collapses_single_dependent_chains() {
+		let diffs = diffs(&[(1, None), (2, Some(1)), (3, Some(2)), (4, Some(3))]);
+		let referenced = HashSet::from([1, 4]);
+
+		let needed = plan(&diffs, &referenced, u64::MAX - 1);
+		assert_eq!(needed, HashSet::from([1, 4]));
+		assert_eq!(nearest_needed_ancestor(&diffs, &needed, 3), Some(1));
+	}
+
+	#[test]
+	fn plan_keeps_shared_parents() {
+		let diffs = diffs(&[(1, None), (2, Some(1)), (3, Some(2)), (4, Some(2))]);
+		let referenced = HashSet::from([3, 4]);
+
+		let needed = plan(&diffs, &referenced, u64::MAX - 1);
+		assert_eq!(needed, HashSet::from([2, 3, 4]));
+		assert_eq!(nearest_needed_ancestor(&diffs, &needed, 1), None);
+	}
+
+	#[test]
+	fn plan_keeps_states_newer_than_horizon() {
+		let diffs = diffs(&[(1, None), (2, Some(1))]);
+
+		let needed = plan(&diffs, &HashSet::new(), 1);
+		assert_eq!(needed, HashSet::from([2]));
+	}
+}
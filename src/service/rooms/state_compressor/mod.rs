@@ -1,3 +1,5 @@
+mod gc;
+
 use std::{
 	collections::{BTreeSet, HashMap},
 	fmt::{Debug, Write},
@@ -17,8 +19,9 @@
 use lru_cache::LruCache;
 use ruma::{EventId, RoomId};
 
+pub use self::gc::GcReport;
 use crate::{
-	Dep, rooms,
+	Dep, globals, rooms,
 	rooms::short::{ShortEventId, ShortId, ShortStateHash, ShortStateKey},
 };
 
@@ -29,12 +32,17 @@ pub struct Service {
 }
 
 struct Services {
+	globals: Dep<globals::Service>,
 	short: Dep<rooms::short::Service>,
 	state: Dep<rooms::state::Service>,
 }
 
 struct Data {
+	roomid_shortstatehash: Arc<Map>,
+	roomsynctoken_shortstatehash: Arc<Map>,
+	shorteventid_shortstatehash: Arc<Map>,
 	shortstatehash_statediff: Arc<Map>,
+	statehash_shortstatehash: Arc<Map>,
 }
 
 #[derive(Clone)]
@@ -75,9 +83,14 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
 			stateinfo_cache: LruCache::new(usize_from_f64(cache_capacity)?).into(),
 			db: Data {
+				roomid_shortstatehash: args.db["roomid_shortstatehash"].clone(),
+				roomsynctoken_shortstatehash: args.db["roomsynctoken_shortstatehash"].clone(),
+				shorteventid_shortstatehash: args.db["shorteventid_shortstatehash"].clone(),
 				shortstatehash_statediff: args.db["shortstatehash_statediff"].clone(),
+				statehash_shortstatehash: args.db["statehash_shortstatehash"].clone(),
 			},
 			services: Services {
+				globals: args.depend::<globals::Service>("globals"),
 				short: args.depend::<rooms::short::Service>("rooms::short"),
 				state: args.depend::<rooms::state::Service>("rooms::state"),
 			},
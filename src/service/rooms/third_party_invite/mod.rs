@@ -0,0 +1,322 @@
+//! # Third-party invites
+//!
+//! Invites addressed to a third-party identifier such as an email address
+//! rather than to a Matrix user. The inviting server asks the identity server
+//! chosen by the client to store the invite, then records it in the room as an
+//! `m.room.third_party_invite` state event keyed by the token the identity
+//! server issued.
+//!
+//! When someone later binds the address to their Matrix ID, the identity
+//! server notifies that user's homeserver, which exchanges the invite signed
+//! by the identity server for a regular `m.room.member` invite with the
+//! inviting server. The signature is verified against the public keys in the
+//! state event by the room's auth rules.
+
+mod tests;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use conduwuit::{Err, Result, debug, err, implement, matrix::pdu::PduBuilder, trace, warn};
+use ipaddress::IPAddress;
+use reqwest::{Client, Method, Response, header::CONTENT_TYPE};
+use ruma::{
+	OwnedUserId, RoomId, ServerName, UserId,
+	api::client::membership::Invite3pid,
+	events::room::third_party_invite::{PublicKey, RoomThirdPartyInviteEventContent},
+	serde::Base64,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+
+use crate::{Dep, client, resolver::fed::identity_server_dest, rooms, users};
+
+pub struct Service {
+	services: Services,
+}
+
+struct Services {
+	client: Dep<client::Service>,
+	state: Dep<rooms::state::Service>,
+	state_accessor: Dep<rooms::state_accessor::Service>,
+	timeline: Dep<rooms::timeline::Service>,
+	users: Dep<users::Service>,
+}
+
+#[derive(Deserialize)]
+struct HashDetails {
+	algorithms: Vec<String>,
+	lookup_pepper: String,
+}
+
+#[derive(Serialize)]
+struct LookupRequest<'a> {
+	addresses: [&'a str; 1],
+	algorithm: &'a str,
+	pepper: &'a str,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+	mappings: BTreeMap<String, OwnedUserId>,
+}
+
+#[derive(Serialize)]
+struct StoreInviteRequest<'a> {
+	medium: &'a str,
+	address: &'a str,
+	room_id: &'a RoomId,
+	sender: &'a UserId,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	room_alias: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	room_avatar_url: Option<String>,
+	room_join_rules: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	room_name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	room_type: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sender_display_name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sender_avatar_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StoreInviteResponse {
+	token: String,
+	display_name: String,
+	public_keys: Vec<StoredPublicKey>,
+}
+
+#[derive(Deserialize)]
+struct StoredPublicKey {
+	public_key: Base64,
+	key_validity_url: String,
+}
+
+impl crate::Service for Service {
+	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
+		Ok(Arc::new(Self {
+			services: Services {
+				client: args.depend::<client::Service>("client"),
+				state: args.depend::<rooms::state::Service>("rooms::state"),
+				state_accessor: args
+					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
+				timeline: args.depend::<rooms::timeline::Service>("rooms::timeline"),
+				users: args.depend::<users::Service>("users"),
+			},
+		}))
+	}
+
+	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
+}
+
+/// Looks up the Matrix ID bound to the invited address, if any.
+///
+/// When the address is bound, the caller should invite that user directly
+/// instead of storing a third-party invite.
+#[implement(Service)]
+#[tracing::instrument(skip(self, invite), fields(id_server = %invite.id_server), level = "debug")]
+pub async fn lookup(&self, invite: &Invite3pid) -> Result<Option<OwnedUserId>> {
+	let details: HashDetails = self
+		.request(invite, Method::GET, "/_matrix/identity/v2/hash_details", None::<()>)
+		.await?;
+
+	let medium = invite.medium.as_str();
+	let (algorithm, address) = if details.algorithms.iter().any(|a| a == "sha256") {
+		let input = format!("{} {medium} {}", invite.address, details.lookup_pepper);
+		("sha256", URL_SAFE_NO_PAD.encode(Sha256::digest(input)))
+	} else if details.algorithms.iter().any(|a| a == "none") {
+		("none", format!("{} {medium}", invite.address))
+	} else {
+		return Err!(BadServerResponse(
+			"Identity server {} supports no known lookup algorithm.",
+			invite.id_server
+		));
+	};
+
+	let body = LookupRequest {
+		addresses: [&address],
+		algorithm,
+		pepper: &details.lookup_pepper,
+	};
+
+	let mut response: LookupResponse = self
+		.request(invite, Method::POST, "/_matrix/identity/v2/lookup", Some(body))
+		.await?;
+
+	Ok(response.mappings.remove(&address))
+}
+
+/// Stores an invite for `invite.address` with the identity server and records
+/// it in the room as an `m.room.third_party_invite` state event sent by
+/// `sender`.
+#[implement(Service)]
+#[tracing::instrument(skip(self, invite), fields(id_server = %invite.id_server), level = "debug")]
+pub async fn invite(&self, sender: &UserId, room_id: &RoomId, invite: &Invite3pid) -> Result {
+	let state_accessor = &self.services.state_accessor;
+	let body = StoreInviteRequest {
+		medium: invite.medium.as_str(),
+		address: &invite.address,
+		room_id,
+		sender,
+		room_alias: state_accessor
+			.get_canonical_alias(room_id)
+			.await
+			.ok()
+			.map(|alias| alias.to_string()),
+		room_avatar_url: state_accessor
+			.get_avatar(room_id)
+			.await
+			.into_option()
+			.and_then(|avatar| avatar.url)
+			.map(|url| url.to_string()),
+		room_join_rules: state_accessor
+			.get_join_rules(room_id)
+			.await
+			.as_str()
+			.to_owned(),
+		room_name: state_accessor.get_name(room_id).await.ok(),
+		room_type: state_accessor
+			.get_room_type(room_id)
+			.await
+			.ok()
+			.map(|room_type| room_type.to_string()),
+		sender_display_name: self.services.users.displayname(sender).await.ok(),
+		sender_avatar_url: self
+			.services
+			.users
+			.avatar_url(sender)
+			.await
+			.ok()
+			.map(|url| url.to_string()),
+	};
+
+	let stored: StoreInviteResponse = self
+		.request(invite, Method::POST, "/_matrix/identity/v2/store-invite", Some(body))
+		.await?;
+
+	let content = invite_content(&stored).map_err(|e| {
+		err!(BadServerResponse(
+			"Identity server {} stored a bad invite: {e}",
+			invite.id_server
+		))
+	})?;
+
+	let state_lock = self.services.state.mutex.lock(room_id).await;
+	self.services
+		.timeline
+		.build_and_append_pdu(
+			PduBuilder::state(stored.token, &content),
+			sender,
+			Some(room_id),
+			&state_lock,
+		)
+		.await?;
+
+	debug!(%sender, %room_id, "Stored third-party invite for {}", stored.display_name);
+	Ok(())
+}
+
+/// Sends a request to the identity server named by `invite`, authenticated
+/// with the client's identity server access token.
+#[implement(Service)]
+async fn request<T, B>(
+	&self,
+	invite: &Invite3pid,
+	method: Method,
+	path: &str,
+	body: Option<B>,
+) -> Result<T>
+where
+	T: DeserializeOwned,
+	B: Serialize,
+{
+	let id_server = <&ServerName>::try_from(invite.id_server.as_str())
+		.map_err(|e| err!(Request(InvalidParam("Invalid identity server name: {e}"))))?;
+
+	if let Ok(ip) = IPAddress::parse(id_server.host()) {
+		if !self.services.client.valid_cidr_range(&ip) {
+			return Err!(Request(InvalidParam("Identity server is a forbidden remote address.")));
+		}
+	}
+
+	let url = format!("{}{path}", identity_server_dest(id_server.as_str()).https_string());
+	let client = &self.services.client.default;
+	let response = send(client, method, &url, &invite.id_access_token, body).await?;
+	if let Some(remote_addr) = response.remote_addr() {
+		if let Ok(ip) = IPAddress::parse(remote_addr.ip().to_string()) {
+			if !self.services.client.valid_cidr_range(&ip) {
+				return Err!(BadServerResponse("Not allowed to send requests to this IP"));
+			}
+		}
+	}
+
+	read_response(&url, response).await
+}
+
+async fn send<B>(
+	client: &Client,
+	method: Method,
+	url: &str,
+	access_token: &str,
+	body: Option<B>,
+) -> Result<Response>
+where
+	B: Serialize,
+{
+	trace!(%url, "Sending identity server request");
+
+	let mut request = client.request(method, url).bearer_auth(access_token);
+	if let Some(body) = body {
+		request = request
+			.header(CONTENT_TYPE, "application/json")
+			.body(serde_json::to_vec(&body)?);
+	}
+
+	Ok(request.send().await?)
+}
+
+async fn read_response<T>(url: &str, response: Response) -> Result<T>
+where
+	T: DeserializeOwned,
+{
+	let status = response.status();
+	let body = response.bytes().await?;
+	if !status.is_success() {
+		return Err!(BadServerResponse(warn!(
+			%url, %status,
+			"Identity server request failed: {}",
+			String::from_utf8_lossy(&body)
+		)));
+	}
+
+	serde_json::from_slice(&body).map_err(|e| {
+		err!(BadServerResponse(warn!(%url, "Invalid identity server response: {e}")))
+	})
+}
+
+/// The content of the `m.room.third_party_invite` event recording an invite
+/// stored with an identity server.
+fn invite_content(stored: &StoreInviteResponse) -> Result<RoomThirdPartyInviteEventContent> {
+	let Some(first_key) = stored.public_keys.first() else {
+		return Err!("no public keys were returned for the invite");
+	};
+
+	Ok(RoomThirdPartyInviteEventContent {
+		display_name: stored.display_name.clone(),
+		key_validity_url: first_key.key_validity_url.clone(),
+		public_key: first_key.public_key.clone(),
+		public_keys: Some(
+			stored
+				.public_keys
+				.iter()
+				.map(|key| PublicKey {
+					key_validity_url: Some(key.key_validity_url.clone()),
+					public_key: key.public_key.clone(),
+				})
+				.collect(),
+		),
+	})
+}
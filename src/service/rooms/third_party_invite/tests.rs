@@ -0,0 +1,160 @@
+#![cfg(test)]
+
+use reqwest::{Client, Method};
+use ruma::{room_id, serde::Base64, user_id};
+use serde_json::{Value as JsonValue, json};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::{TcpListener, TcpStream},
+	task::JoinHandle,
+};
+
+use super::{StoreInviteRequest, StoreInviteResponse, invite_content, read_response, send};
+
+const STORE_INVITE: &str = "/_matrix/identity/v2/store-invite";
+
+/// Starts an identity server answering a single request with `status` and the
+/// JSON `response`. Resolves to the request it received.
+async fn mock_identity_server(
+	status: &str,
+	response: &JsonValue,
+) -> (String, JoinHandle<String>) {
+	let listener = TcpListener::bind("127.0.0.1:0")
+		.await
+		.expect("mock identity server binds");
+
+	let base_url = format!("http://{}", listener.local_addr().expect("bound address"));
+	let body = response.to_string();
+	let reply = format!(
+		"HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: \
+		 {}\r\nconnection: close\r\n\r\n{body}",
+		body.len()
+	);
+
+	let server = tokio::spawn(async move {
+		let (mut stream, _) = listener.accept().await.expect("client connects");
+		let request = read_request(&mut stream).await;
+		stream
+			.write_all(reply.as_bytes())
+			.await
+			.expect("response is written");
+
+		request
+	});
+
+	(base_url, server)
+}
+
+async fn read_request(stream: &mut TcpStream) -> String {
+	let mut request = Vec::new();
+	let mut buf = [0_u8; 4096];
+	loop {
+		let read = stream.read(&mut buf).await.expect("request is read");
+		request.extend_from_slice(&buf[..read]);
+
+		let text = String::from_utf8_lossy(&request).into_owned();
+		let Some((head, body)) = text.split_once("\r\n\r\n") else {
+			assert_ne!(read, 0, "connection closed before the request was complete");
+			continue;
+		};
+
+		let length: usize = head
+			.lines()
+			.find_map(|line| {
+				let (name, value) = line.split_once(':')?;
+				name.eq_ignore_ascii_case("content-length")
+					.then(|| value.trim().parse().expect("valid content-length"))
+			})
+			.unwrap_or(0);
+
+		if body.len() >= length || read == 0 {
+			return text;
+		}
+	}
+}
+
+fn store_invite_request() -> StoreInviteRequest<'static> {
+	StoreInviteRequest {
+		medium: "email",
+		address: "carol@example.com",
+		room_id: room_id!("!room:example.com"),
+		sender: user_id!("@alice:example.com"),
+		room_alias: None,
+		room_avatar_url: None,
+		room_join_rules: "invite".to_owned(),
+		room_name: Some("Test room".to_owned()),
+		room_type: None,
+		sender_display_name: Some("Alice".to_owned()),
+		sender_avatar_url: None,
+	}
+}
+
+#[tokio::test]
+async fn stores_invite_with_identity_server() {
+	let public_key = Base64::new(vec![7_u8; 32]).encode();
+	let key_validity_url = "https://id.example.com/_matrix/identity/v2/pubkey/isvalid";
+	let (base_url, server) = mock_identity_server(
+		"200 OK",
+		&json!({
+			"token": "invitetoken",
+			"display_name": "c...@e...",
+			"public_keys": [{ "public_key": public_key, "key_validity_url": key_validity_url }],
+		}),
+	)
+	.await;
+
+	let url = format!("{base_url}{STORE_INVITE}");
+	let response =
+		send(&Client::new(), Method::POST, &url, "idtoken", Some(store_invite_request()))
+			.await
+			.expect("identity server answers");
+
+	let stored: StoreInviteResponse = read_response(&url, response)
+		.await
+		.expect("valid store-invite response");
+
+	let request = server.await.expect("mock identity server finishes");
+	let request_lowercase = request.to_lowercase();
+	assert!(request.starts_with(&format!("POST {STORE_INVITE} ")));
+	assert!(request_lowercase.contains("authorization: bearer idtoken"));
+	assert!(request.contains(r#""address":"carol@example.com""#));
+	assert!(request.contains(r#""room_id":"!room:example.com""#));
+
+	let content = invite_content(&stored).expect("invite has a public key");
+	assert_eq!(stored.token, "invitetoken");
+	assert_eq!(content.display_name, "c...@e...");
+	assert_eq!(content.key_validity_url, key_validity_url);
+	assert_eq!(content.public_key.encode(), public_key);
+	assert_eq!(content.public_keys.map(|keys| keys.len()), Some(1));
+}
+
+#[tokio::test]
+async fn identity_server_refusal_is_an_error() {
+	let (base_url, server) = mock_identity_server(
+		"403 Forbidden",
+		&json!({ "errcode": "M_TERMS_NOT_SIGNED", "error": "Terms not signed" }),
+	)
+	.await;
+
+	let url = format!("{base_url}{STORE_INVITE}");
+	let response =
+		send(&Client::new(), Method::POST, &url, "idtoken", Some(store_invite_request()))
+			.await
+			.expect("identity server answers");
+
+	let stored = read_response::<StoreInviteResponse>(&url, response).await;
+	server.await.expect("mock identity server finishes");
+	assert!(stored.is_err());
+}
+
+#[test]
+fn invite_without_public_keys_is_rejected() {
+	let stored: StoreInviteResponse = serde_json::from_value(json!({
+		"token": "invitetoken",
+		"display_name": "c...@e...",
+		"public_keys": [],
+	}))
+	.expect("valid store-invite response");
+
+	assert!(invite_content(&stored).is_err());
+}
@@ -18,6 +18,7 @@
 	events::{
 		GlobalAccountDataEventType, StateEventType, TimelineEventType,
 		push_rules::PushRulesEvent,
+		receipt::ReceiptThread,
 		room::{
 			encrypted::Relation, power_levels::RoomPowerLevelsEventContent,
 			redaction::RoomRedactionEventContent,
@@ -167,13 +168,17 @@ pub async fn append_pdu<'a, Leaves>(
 
 	// Mark as read first so the sending client doesn't get a notification even if
 	// appending fails
-	self.services
-		.read_receipt
-		.private_read_set(room_id, pdu.sender(), count1);
+	self.services.read_receipt.private_read_set(
+		room_id,
+		pdu.sender(),
+		count1,
+		&ReceiptThread::Unthreaded,
+	);
 
 	self.services
 		.user
-		.reset_notification_counts(pdu.sender(), room_id);
+		.reset_notification_counts(pdu.sender(), room_id)
+		.await;
 
 	let count2 = PduCount::Normal(self.services.globals.next_count().unwrap());
 	let pdu_id: RawPduId = PduId { shortroomid, shorteventid: count2 }.into();
@@ -270,8 +275,24 @@ pub async fn append_pdu<'a, Leaves>(
 			.await;
 	}
 
+	// Events in a thread also count towards the thread's own unread counts
+	let thread_root =
+		pdu.get_content::<ExtractRelatesTo>()
+			.ok()
+			.and_then(|content| match content.relates_to {
+				| Relation::Thread(thread) => Some(thread.event_id),
+				| _ => None,
+			});
+
 	self.db
-		.increment_notification_counts(room_id, notifies, highlights);
+		.increment_notification_counts(room_id, thread_root.as_deref(), notifies, highlights);
+
+	if let Some(state_key) = pdu.state_key() {
+		self.services
+			.delayed_events
+			.cancel_state_events(room_id, &pdu.kind().to_string(), state_key)
+			.await;
+	}
 
 	match *pdu.kind() {
 		| TimelineEventType::RoomRedaction => {
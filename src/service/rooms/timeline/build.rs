@@ -19,6 +19,7 @@
 };
 
 use super::RoomMutexGuard;
+use crate::rooms::directory;
 
 /// Creates a new persisted data unit and adds it to a room. This function
 /// takes a roomid_mutex_state, meaning that only this function is able to
@@ -133,6 +134,10 @@ pub async fn build_and_append_pdu(
 		.state
 		.set_room_state(&room_id, statehashid, state_lock);
 
+	if directory::affects_listing(&pdu) {
+		self.services.directory.update_room(&room_id).await;
+	}
+
 	let mut servers: HashSet<OwnedServerName> = self
 		.services
 		.state_cache
@@ -239,7 +239,7 @@ fn from_evt(
 	let auth_check = state_res::auth_check(
 		&room_version,
 		&pdu,
-		None, // TODO: third_party_invite
+		None, // third_party_invite is looked up among the auth events
 		auth_fetch,
 		create_event,
 	)
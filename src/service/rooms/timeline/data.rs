@@ -18,6 +18,8 @@ pub(super) struct Data {
 	pduid_pdu: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
 	userroomid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
 	pub(super) db: Arc<Database>,
 	services: Services,
 }
@@ -37,6 +39,8 @@ pub(super) fn new(args: &crate::Args<'_>) -> Self {
 			pduid_pdu: db["pduid_pdu"].clone(),
 			userroomid_highlightcount: db["userroomid_highlightcount"].clone(),
 			userroomid_notificationcount: db["userroomid_notificationcount"].clone(),
+			userroomthreadid_highlightcount: db["userroomthreadid_highlightcount"].clone(),
+			userroomthreadid_notificationcount: db["userroomthreadid_notificationcount"].clone(),
 			db: args.db.clone(),
 			services: Services {
 				short: args.depend::<rooms::short::Service>("rooms::short"),
@@ -254,6 +258,7 @@ fn from_json_slice((pdu_id, pdu): KeyVal<'_>) -> Result<PdusIterItem> {
 	pub(super) fn increment_notification_counts(
 		&self,
 		room_id: &RoomId,
+		thread_root: Option<&EventId>,
 		notifies: Vec<OwnedUserId>,
 		highlights: Vec<OwnedUserId>,
 	) {
@@ -264,6 +269,12 @@ pub(super) fn increment_notification_counts(
 			userroom_id.push(0xFF);
 			userroom_id.extend_from_slice(room_id.as_bytes());
 			increment(&self.userroomid_notificationcount, &userroom_id);
+
+			if let Some(thread_root) = thread_root {
+				userroom_id.push(0xFF);
+				userroom_id.extend_from_slice(thread_root.as_bytes());
+				increment(&self.userroomthreadid_notificationcount, &userroom_id);
+			}
 		}
 
 		for user in highlights {
@@ -271,6 +282,12 @@ pub(super) fn increment_notification_counts(
 			userroom_id.push(0xFF);
 			userroom_id.extend_from_slice(room_id.as_bytes());
 			increment(&self.userroomid_highlightcount, &userroom_id);
+
+			if let Some(thread_root) = thread_root {
+				userroom_id.push(0xFF);
+				userroom_id.extend_from_slice(thread_root.as_bytes());
+				increment(&self.userroomthreadid_highlightcount, &userroom_id);
+			}
 		}
 	}
 
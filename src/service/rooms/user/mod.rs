@@ -1,10 +1,19 @@
-use std::sync::Arc;
+mod tests;
 
-use conduwuit::{Result, implement};
-use database::{Database, Deserialized, Map};
-use ruma::{RoomId, UserId};
+use std::{collections::BTreeMap, sync::Arc};
 
-use crate::{Dep, globals, rooms, rooms::short::ShortStateHash};
+use conduwuit::{
+	Result, err, implement,
+	utils::{self, ReadyExt, stream::TryIgnore},
+};
+use database::{Database, Deserialized, Ignore, Interfix, Map};
+use futures::StreamExt;
+use ruma::{EventId, OwnedEventId, RoomId, UserId, events::receipt::ReceiptThread};
+
+use crate::{
+	Dep, globals, rooms,
+	rooms::short::{ShortRoomId, ShortStateHash},
+};
 
 pub struct Service {
 	db: Data,
@@ -15,6 +24,8 @@ struct Data {
 	db: Arc<Database>,
 	userroomid_notificationcount: Arc<Map>,
 	userroomid_highlightcount: Arc<Map>,
+	userroomthreadid_notificationcount: Arc<Map>,
+	userroomthreadid_highlightcount: Arc<Map>,
 	roomuserid_lastnotificationread: Arc<Map>,
 	roomsynctoken_shortstatehash: Arc<Map>,
 }
@@ -31,6 +42,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				db: args.db.clone(),
 				userroomid_notificationcount: args.db["userroomid_notificationcount"].clone(),
 				userroomid_highlightcount: args.db["userroomid_highlightcount"].clone(),
+				userroomthreadid_notificationcount: args.db["userroomthreadid_notificationcount"]
+					.clone(),
+				userroomthreadid_highlightcount: args.db["userroomthreadid_highlightcount"]
+					.clone(),
 				roomuserid_lastnotificationread: args.db["userroomid_highlightcount"].clone(),
 				roomsynctoken_shortstatehash: args.db["roomsynctoken_shortstatehash"].clone(),
 			},
@@ -45,12 +60,108 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
+/// Unread notification and highlight counts of each thread in a room, keyed
+/// by the thread root.
+pub type ThreadCounts = BTreeMap<OwnedEventId, (u64, u64)>;
+
 #[implement(Service)]
-pub fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
+pub async fn reset_notification_counts(&self, user_id: &UserId, room_id: &RoomId) {
 	let userroom_id = (user_id, room_id);
 	self.db.userroomid_highlightcount.put(userroom_id, 0_u64);
 	self.db.userroomid_notificationcount.put(userroom_id, 0_u64);
 
+	let prefix = (user_id, room_id, Interfix);
+	for map in [
+		&self.db.userroomthreadid_notificationcount,
+		&self.db.userroomthreadid_highlightcount,
+	] {
+		map.keys_prefix_raw(&prefix)
+			.ignore_err()
+			.ready_for_each(|key| map.remove(key))
+			.await;
+	}
+
+	self.mark_notifications_read(user_id, room_id);
+}
+
+/// Resets the unread counts a read receipt covers: an unthreaded receipt
+/// covers the whole room, a threaded one only its thread (MSC3771).
+#[implement(Service)]
+pub async fn reset_receipt_notification_counts(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+	thread: &ReceiptThread,
+) {
+	if !matches!(thread, ReceiptThread::Thread(_) | ReceiptThread::Main) {
+		return self.reset_notification_counts(user_id, room_id).await;
+	}
+
+	let mut counts = UnreadCounts {
+		room: (
+			self.notification_count(user_id, room_id).await,
+			self.highlight_count(user_id, room_id).await,
+		),
+		threads: self.thread_notification_counts(user_id, room_id).await,
+	};
+
+	counts.read(thread);
+
+	if let ReceiptThread::Thread(thread_root) = thread {
+		let key = (user_id, room_id, thread_root);
+		self.db.userroomthreadid_notificationcount.del(key);
+		self.db.userroomthreadid_highlightcount.del(key);
+	}
+
+	let (notifications, highlights) = counts.room;
+	let userroom_id = (user_id, room_id);
+	self.db
+		.userroomid_notificationcount
+		.put(userroom_id, notifications);
+	self.db
+		.userroomid_highlightcount
+		.put(userroom_id, highlights);
+
+	self.mark_notifications_read(user_id, room_id);
+}
+
+/// Unread notification and highlight counts of a room, in total and of each
+/// of its threads.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct UnreadCounts {
+	room: (u64, u64),
+	threads: ThreadCounts,
+}
+
+impl UnreadCounts {
+	/// Resets the counts covered by a read receipt in the thread.
+	fn read(&mut self, thread: &ReceiptThread) {
+		match thread {
+			| ReceiptThread::Thread(thread_root) => {
+				let (notifications, highlights) =
+					self.threads.remove(thread_root).unwrap_or_default();
+
+				self.room = (
+					self.room.0.saturating_sub(notifications),
+					self.room.1.saturating_sub(highlights),
+				);
+			},
+			| ReceiptThread::Main => {
+				// what remains unread is in the threads
+				self.room =
+					self.threads
+						.values()
+						.fold((0_u64, 0_u64), |(n, h), (thread_n, thread_h)| {
+							(n.saturating_add(*thread_n), h.saturating_add(*thread_h))
+						});
+			},
+			| _ => *self = Self::default(),
+		}
+	}
+}
+
+#[implement(Service)]
+fn mark_notifications_read(&self, user_id: &UserId, room_id: &RoomId) {
 	let roomuser_id = (room_id, user_id);
 	let count = self.services.globals.next_count().unwrap();
 	self.db
@@ -80,6 +191,38 @@ pub async fn highlight_count(&self, user_id: &UserId, room_id: &RoomId) -> u64 {
 		.unwrap_or(0)
 }
 
+/// Returns the unread counts of the threads in the room with unread
+/// notifications (MSC3773).
+#[implement(Service)]
+pub async fn thread_notification_counts(
+	&self,
+	user_id: &UserId,
+	room_id: &RoomId,
+) -> ThreadCounts {
+	type KeyVal<'a> = ((Ignore, Ignore, &'a EventId), u64);
+
+	let prefix = (user_id, room_id, Interfix);
+	let mut counts: ThreadCounts = self
+		.db
+		.userroomthreadid_notificationcount
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.map(|((_, _, thread_root), count): KeyVal<'_>| (thread_root.to_owned(), (count, 0)))
+		.collect()
+		.await;
+
+	self.db
+		.userroomthreadid_highlightcount
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.ready_for_each(|((_, _, thread_root), count): KeyVal<'_>| {
+			counts.entry(thread_root.to_owned()).or_default().1 = count;
+		})
+		.await;
+
+	counts
+}
+
 #[implement(Service)]
 pub async fn last_notification_read(&self, user_id: &UserId, room_id: &RoomId) -> u64 {
 	let key = (room_id, user_id);
@@ -91,6 +234,9 @@ pub async fn last_notification_read(&self, user_id: &UserId, room_id: &RoomId) -
 		.unwrap_or(0)
 }
 
+/// Records the room's state as of a sync token. Only changes are recorded: when
+/// the state is the same as recorded at the latest earlier token, nothing is
+/// written, since lookups fall back to that earlier record.
 #[implement(Service)]
 pub async fn associate_token_shortstatehash(
 	&self,
@@ -105,6 +251,14 @@ pub async fn associate_token_shortstatehash(
 		.await
 		.expect("room exists");
 
+	if self
+		.token_shortstatehash(shortroomid, token)
+		.await
+		.is_some_and(|recorded| recorded == shortstatehash)
+	{
+		return;
+	}
+
 	let _cork = self.db.db.cork();
 	let key: &[u64] = &[shortroomid, token];
 	self.db
@@ -112,6 +266,8 @@ pub async fn associate_token_shortstatehash(
 		.put(key, shortstatehash);
 }
 
+/// Returns the room's state as of a sync token: the state recorded at the
+/// token, or at the latest earlier token if it was unchanged since.
 #[implement(Service)]
 pub async fn get_token_shortstatehash(
 	&self,
@@ -120,10 +276,25 @@ pub async fn get_token_shortstatehash(
 ) -> Result<ShortStateHash> {
 	let shortroomid = self.services.short.get_shortroomid(room_id).await?;
 
+	self.token_shortstatehash(shortroomid, token)
+		.await
+		.ok_or_else(|| err!(Request(NotFound("No state recorded for the room at this token"))))
+}
+
+#[implement(Service)]
+async fn token_shortstatehash(
+	&self,
+	shortroomid: ShortRoomId,
+	token: u64,
+) -> Option<ShortStateHash> {
+	let prefix = shortroomid.to_be_bytes();
 	let key: &[u64] = &[shortroomid, token];
 	self.db
 		.roomsynctoken_shortstatehash
-		.qry(key)
+		.rev_stream_from_raw(key)
+		.ignore_err()
+		.ready_take_while(|(key, _)| key.starts_with(&prefix))
+		.ready_filter_map(|(_, val)| utils::u64_from_bytes(val).ok())
+		.next()
 		.await
-		.deserialized()
 }
@@ -0,0 +1,54 @@
+#![cfg(test)]
+
+use ruma::{events::receipt::ReceiptThread, owned_event_id};
+
+use super::{ThreadCounts, UnreadCounts};
+
+/// A room with 10 unread notifications, 3 of them highlights: 4 in thread A
+/// with 2 highlights, 3 in thread B with 1 highlight, and 3 in the main
+/// timeline.
+fn counts() -> UnreadCounts {
+	UnreadCounts {
+		room: (10, 3),
+		threads: ThreadCounts::from([
+			(owned_event_id!("$a:example.com"), (4, 2)),
+			(owned_event_id!("$b:example.com"), (3, 1)),
+		]),
+	}
+}
+
+#[test]
+fn thread_receipt_resets_only_its_thread() {
+	let mut counts = counts();
+	counts.read(&ReceiptThread::Thread(owned_event_id!("$a:example.com")));
+
+	assert_eq!(counts, UnreadCounts {
+		room: (6, 1),
+		threads: ThreadCounts::from([(owned_event_id!("$b:example.com"), (3, 1))]),
+	});
+}
+
+#[test]
+fn receipt_in_thread_without_unread_notifications() {
+	let mut counts = counts();
+	counts.read(&ReceiptThread::Thread(owned_event_id!("$c:example.com")));
+
+	assert_eq!(counts, self::counts());
+}
+
+#[test]
+fn main_receipt_resets_main_timeline() {
+	let mut counts = counts();
+	counts.read(&ReceiptThread::Main);
+
+	// only the threads remain unread
+	assert_eq!(counts, UnreadCounts { room: (7, 3), ..self::counts() });
+}
+
+#[test]
+fn unthreaded_receipt_resets_everything() {
+	let mut counts = counts();
+	counts.read(&ReceiptThread::Unthreaded);
+
+	assert_eq!(counts, UnreadCounts::default());
+}
@@ -4,11 +4,11 @@
 	Error, Result, at, utils,
 	utils::{ReadyExt, stream::TryIgnore},
 };
-use database::{Database, Deserialized, Map};
+use database::{Database, Deserialized, Interfix, Map};
 use futures::{Stream, StreamExt};
-use ruma::{OwnedServerName, ServerName, UserId};
+use ruma::{OwnedServerName, OwnedUserId, ServerName, UserId};
 
-use super::{Destination, SendingEvent};
+use super::{Destination, DeviceChange, SendingEvent};
 use crate::{Dep, globals};
 
 pub(super) type OutgoingItem = (Key, SendingEvent, Destination);
@@ -17,6 +17,7 @@
 pub(super) type Key = Vec<u8>;
 
 pub struct Data {
+	appserviceid_devicechange: Arc<Map>,
 	servercurrentevent_data: Arc<Map>,
 	servernameevent_data: Arc<Map>,
 	servername_educount: Arc<Map>,
@@ -32,6 +33,7 @@ impl Data {
 	pub(super) fn new(args: &crate::Args<'_>) -> Self {
 		let db = &args.db;
 		Self {
+			appserviceid_devicechange: db["appserviceid_devicechange"].clone(),
 			servercurrentevent_data: db["servercurrentevent_data"].clone(),
 			servernameevent_data: db["servernameevent_data"].clone(),
 			servername_educount: db["servername_educount"].clone(),
@@ -180,6 +182,51 @@ pub async fn get_latest_educount(&self, server_name: &ServerName) -> u64 {
 			.deserialized()
 			.unwrap_or(0)
 	}
+
+	/// Notes a change to a user's device data which an appservice has to be
+	/// told about in its next transaction.
+	///
+	/// Each change is queued under a new count, so that a change noted again
+	/// while a transaction carrying it is in flight is not dropped once that
+	/// transaction succeeds.
+	pub(super) fn queue_appservice_device_change(
+		&self,
+		appservice_id: &str,
+		change: DeviceChange,
+		user_id: &UserId,
+	) {
+		let count = self.services.globals.next_count().unwrap();
+		let key = (appservice_id, count, change.as_str(), user_id);
+		self.appserviceid_devicechange.put_raw(key, []);
+	}
+
+	/// Iterates the device data changes pending for an appservice, oldest
+	/// first, along with the count each was queued under.
+	pub(super) fn appservice_device_changes<'a>(
+		&'a self,
+		appservice_id: &'a str,
+	) -> impl Stream<Item = (u64, DeviceChange, OwnedUserId)> + Send + 'a {
+		let prefix = (appservice_id, Interfix);
+		self.appserviceid_devicechange
+			.keys_prefix(&prefix)
+			.ignore_err()
+			.ready_filter_map(|(_, count, change, user_id): (&str, u64, &str, &UserId)| {
+				Some((count, DeviceChange::parse(change)?, user_id.to_owned()))
+			})
+	}
+
+	/// Drops a device data change once the appservice received it. Changes
+	/// queued again since are kept under their own counts.
+	pub(super) fn remove_appservice_device_change(
+		&self,
+		appservice_id: &str,
+		count: u64,
+		change: DeviceChange,
+		user_id: &UserId,
+	) {
+		let key = (appservice_id, count, change.as_str(), user_id);
+		self.appserviceid_devicechange.del(key);
+	}
 }
 
 fn parse_servercurrentevent(key: &[u8], value: &[u8]) -> Result<(Destination, SendingEvent)> {
@@ -62,6 +62,15 @@ struct Msg {
 	queue_id: Vec<u8>,
 }
 
+/// MSC3202 device data of a user which an appservice is told about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceChange {
+	/// The user's device list changed.
+	DeviceList,
+	/// The one-time key counts of the user's devices changed.
+	OneTimeKeys,
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SendingEvent {
@@ -175,6 +184,43 @@ pub fn send_pdu_appservice(&self, appservice_id: String, pdu_id: RawPduId) -> Re
 		})
 	}
 
+	#[tracing::instrument(skip(self, serialized), level = "debug")]
+	pub fn send_edu_appservice(&self, appservice_id: String, serialized: EduBuf) -> Result {
+		let dest = Destination::Appservice(appservice_id);
+		let event = SendingEvent::Edu(serialized);
+		let _cork = self.db.db.cork();
+		let keys = self.db.queue_requests(once((&event, &dest)));
+		self.dispatch(Msg {
+			dest,
+			event,
+			queue_id: keys.into_iter().next().expect("request queue key"),
+		})
+	}
+
+	/// Notes a change to a user's device data for an appservice and wakes its
+	/// sender, so that the change is pushed promptly.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub fn send_device_change_appservice(
+		&self,
+		appservice_id: String,
+		change: DeviceChange,
+		user_id: &UserId,
+	) -> Result {
+		self.db
+			.queue_appservice_device_change(&appservice_id, change, user_id);
+		self.flush_appservice(appservice_id)
+	}
+
+	/// Wakes the appservice's sender without queueing anything.
+	#[tracing::instrument(skip(self), level = "debug")]
+	pub fn flush_appservice(&self, appservice_id: String) -> Result {
+		self.dispatch(Msg {
+			dest: Destination::Appservice(appservice_id),
+			event: SendingEvent::Flush,
+			queue_id: Vec::<u8>::new(),
+		})
+	}
+
 	#[tracing::instrument(skip(self, room_id, pdu_id), level = "debug")]
 	pub async fn send_pdu_room(&self, room_id: &RoomId, pdu_id: &RawPduId) -> Result {
 		let servers = self
@@ -381,6 +427,24 @@ pub(super) fn shard_id(&self, dest: &Destination) -> usize {
 	}
 }
 
+impl DeviceChange {
+	#[must_use]
+	pub fn as_str(self) -> &'static str {
+		match self {
+			| Self::DeviceList => "device_list",
+			| Self::OneTimeKeys => "one_time_keys",
+		}
+	}
+
+	fn parse(change: &str) -> Option<Self> {
+		match change {
+			| "device_list" => Some(Self::DeviceList),
+			| "one_time_keys" => Some(Self::OneTimeKeys),
+			| _ => None,
+		}
+	}
+}
+
 fn num_senders(args: &crate::Args<'_>) -> usize {
 	const MIN_SENDERS: usize = 1;
 	// Limit the number of senders to the number of workers threads or number of
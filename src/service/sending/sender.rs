@@ -27,10 +27,10 @@
 	stream::FuturesUnordered,
 };
 use ruma::{
-	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedServerName, OwnedUserId,
-	RoomId, RoomVersionId, ServerName, UInt,
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OwnedDeviceId,
+	OwnedRoomId, OwnedServerName, OwnedUserId, RoomId, RoomVersionId, ServerName, UInt,
 	api::{
-		appservice::event::push_events::v1::EphemeralData,
+		appservice::event::push_events::v1::{DeviceLists, EphemeralData},
 		federation::transactions::{
 			edu::{
 				DeviceListUpdateContent, Edu, PresenceContent, PresenceUpdate, ReceiptContent,
@@ -41,8 +41,8 @@
 	},
 	device_id,
 	events::{
-		AnySyncEphemeralRoomEvent, GlobalAccountDataEventType, push_rules::PushRulesEvent,
-		receipt::ReceiptType,
+		AnySyncEphemeralRoomEvent, AnyToDeviceEvent, GlobalAccountDataEventType,
+		push_rules::PushRulesEvent, receipt::ReceiptType,
 	},
 	push,
 	serde::Raw,
@@ -50,7 +50,9 @@
 };
 use serde_json::value::{RawValue as RawJsonValue, to_raw_value};
 
-use super::{Destination, EduBuf, EduVec, Msg, SendingEvent, Service, data::QueueItem};
+use super::{
+	Destination, DeviceChange, EduBuf, EduVec, Msg, SendingEvent, Service, data::QueueItem,
+};
 
 #[derive(Debug)]
 enum TransactionStatus {
@@ -64,6 +66,16 @@ enum TransactionStatus {
 type SendingFuture<'a> = BoxFuture<'a, SendingResult>;
 type SendingFutures<'a> = FuturesUnordered<SendingFuture<'a>>;
 type CurTransactionStatus = HashMap<Destination, TransactionStatus>;
+type OneTimeKeyCounts =
+	BTreeMap<OwnedUserId, BTreeMap<OwnedDeviceId, BTreeMap<OneTimeKeyAlgorithm, UInt>>>;
+
+/// MSC3202 extensions to an appservice transaction.
+#[derive(Default)]
+struct AppserviceDeviceData {
+	changed: Vec<OwnedUserId>,
+	one_time_keys_count: OneTimeKeyCounts,
+	pending: Vec<(u64, DeviceChange, OwnedUserId)>,
+}
 
 const SELECT_PRESENCE_LIMIT: usize = 256;
 const SELECT_RECEIPT_LIMIT: usize = 256;
@@ -73,6 +85,10 @@ enum TransactionStatus {
 pub const PDU_LIMIT: usize = 50;
 pub const EDU_LIMIT: usize = 100;
 
+impl AppserviceDeviceData {
+	fn is_empty(&self) -> bool { self.changed.is_empty() && self.one_time_keys_count.is_empty() }
+}
+
 impl Service {
 	#[tracing::instrument(skip(self), level = "debug")]
 	pub(super) async fn sender(self: Arc<Self>, id: usize) -> Result {
@@ -431,10 +447,18 @@ async fn select_edus_device_changes(
 				}
 
 				max_edu_count.fetch_max(count, Ordering::Relaxed);
-				if !device_list_changes.insert(user_id.into()) {
+				if device_list_changes.contains(user_id) {
 					continue;
 				}
 
+				// The server doesn't need updates through a room the user has since
+				// left; it still gets them through any room they share.
+				if !self.services.state_cache.is_joined(user_id, room_id).await {
+					continue;
+				}
+
+				device_list_changes.insert(user_id.into());
+
 				// Empty prev id forces synapse to resync; because synapse resyncs,
 				// we can just insert placeholder data
 				let edu = Edu::DeviceListUpdate(DeviceListUpdateContent {
@@ -527,6 +551,12 @@ async fn select_edus_receipts_room(
 				break;
 			}
 
+			// An EDU holds one receipt per user, so another of the user's threaded
+			// receipts goes out in a later transaction.
+			if read.contains_key(&user_id) {
+				break;
+			}
+
 			max_edu_count.fetch_max(count, Ordering::Relaxed);
 			if !self.services.globals.user_is_local(&user_id) {
 				continue;
@@ -691,6 +721,7 @@ async fn send_events_dest_appservice(
 				.filter(|event| matches!(event, SendingEvent::Edu(_)))
 				.count(),
 		);
+		let mut to_device_jsons: Vec<Raw<AnyToDeviceEvent>> = Vec::new();
 		for event in &events {
 			match event {
 				| SendingEvent::Pdu(pdu_id) => {
@@ -700,19 +731,59 @@ async fn send_events_dest_appservice(
 				},
 				| SendingEvent::Edu(edu) =>
 					if appservice.receive_ephemeral {
-						if let Ok(edu) = serde_json::from_slice(edu) {
-							edu_jsons.push(edu);
+						// to-device events are queued with their recipient attached
+						match serde_json::from_slice::<Raw<AnyToDeviceEvent>>(edu) {
+							| Ok(event)
+								if matches!(
+									event.get_field::<OwnedDeviceId>("to_device_id"),
+									Ok(Some(_))
+								) =>
+								to_device_jsons.push(event),
+							| _ =>
+								if let Ok(edu) = serde_json::from_slice(edu) {
+									edu_jsons.push(edu);
+								},
 						}
 					},
 				| SendingEvent::Flush => {}, // flush only; no new content
 			}
 		}
 
-		let txn_hash = calculate_hash(events.iter().filter_map(|e| match e {
-			| SendingEvent::Edu(b) => Some(&**b),
-			| SendingEvent::Pdu(b) => Some(b.as_ref()),
-			| SendingEvent::Flush => None,
-		}));
+		// MSC3202 device list changes and one-time key counts noted for this
+		// appservice since its last successful transaction.
+		let device_data = if appservice.receive_ephemeral {
+			self.select_appservice_device_data(&id).await
+		} else {
+			AppserviceDeviceData::default()
+		};
+
+		if pdu_jsons.is_empty()
+			&& edu_jsons.is_empty()
+			&& to_device_jsons.is_empty()
+			&& device_data.is_empty()
+		{
+			// nothing to send, e.g. a flush with no device changes; changes which
+			// turned out empty, e.g. of users without devices, are dropped
+			for (count, change, user_id) in &device_data.pending {
+				self.db
+					.remove_appservice_device_change(&id, *count, *change, user_id);
+			}
+
+			return Ok(Destination::Appservice(id));
+		}
+
+		let txn_hash = calculate_hash(
+			events
+				.iter()
+				.filter_map(|e| match e {
+					| SendingEvent::Edu(b) => Some(&**b),
+					| SendingEvent::Pdu(b) => Some(b.as_ref()),
+					| SendingEvent::Flush => None,
+				})
+				.chain(device_data.pending.iter().flat_map(|(_, change, user_id)| {
+					[change.as_str().as_bytes(), user_id.as_bytes()]
+				})),
+		);
 
 		let txn_id = &*URL_SAFE_NO_PAD.encode(txn_hash);
 
@@ -726,16 +797,72 @@ async fn send_events_dest_appservice(
 					events: pdu_jsons,
 					txn_id: txn_id.into(),
 					ephemeral: edu_jsons,
-					to_device: Vec::new(), // TODO
+					to_device: to_device_jsons,
+					device_lists: DeviceLists {
+						changed: device_data.changed,
+						left: Vec::new(),
+					},
+					device_one_time_keys_count: device_data.one_time_keys_count,
+					device_unused_fallback_key_types: BTreeMap::new(),
 				},
 			)
 			.await
 		{
-			| Ok(_) => Ok(Destination::Appservice(id)),
+			| Ok(_) => {
+				for (count, change, user_id) in &device_data.pending {
+					self.db
+						.remove_appservice_device_change(&id, *count, *change, user_id);
+				}
+
+				Ok(Destination::Appservice(id))
+			},
 			| Err(e) => Err((Destination::Appservice(id), e)),
 		}
 	}
 
+	/// Collects the MSC3202 device data noted for an appservice: the users
+	/// sharing an encrypted room with its users whose device lists changed,
+	/// and the one-time key counts of its users' devices whose keys changed.
+	#[tracing::instrument(name = "device_data", level = "trace", skip(self))]
+	async fn select_appservice_device_data(&self, id: &str) -> AppserviceDeviceData {
+		let pending: Vec<_> = self.db.appservice_device_changes(id).collect().await;
+
+		let mut data = AppserviceDeviceData::default();
+		for (_, change, user_id) in &pending {
+			match change {
+				| DeviceChange::DeviceList if data.changed.contains(user_id) => {},
+				| DeviceChange::DeviceList => data.changed.push(user_id.clone()),
+				| DeviceChange::OneTimeKeys if data.one_time_keys_count.contains_key(user_id) => {
+				},
+				| DeviceChange::OneTimeKeys => {
+					let devices: Vec<OwnedDeviceId> = self
+						.services
+						.users
+						.all_device_ids(user_id)
+						.map(ToOwned::to_owned)
+						.collect()
+						.await;
+
+					for device_id in devices {
+						let counts = self
+							.services
+							.users
+							.count_one_time_keys(user_id, &device_id)
+							.await;
+
+						data.one_time_keys_count
+							.entry(user_id.clone())
+							.or_default()
+							.insert(device_id, counts);
+					}
+				},
+			}
+		}
+
+		data.pending = pending;
+		data
+	}
+
 	#[tracing::instrument(
 		name = "push",
 		level = "info",
@@ -1,6 +1,7 @@
 mod acquire;
 mod get;
 mod keypair;
+mod notary;
 mod request;
 mod sign;
 mod verify;
@@ -40,6 +41,7 @@ struct Services {
 
 struct Data {
 	server_signingkeys: Arc<Map>,
+	servername_signedkeys: Arc<Map>,
 }
 
 pub type VerifyKeys = BTreeMap<OwnedServerSigningKeyId, VerifyKey>;
@@ -64,6 +66,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			},
 			db: Data {
 				server_signingkeys: args.db["server_signingkeys"].clone(),
+				servername_signedkeys: args.db["servername_signedkeys"].clone(),
 			},
 		}))
 	}
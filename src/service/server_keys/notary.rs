@@ -0,0 +1,127 @@
+//! Notary key server
+//!
+//! Keys of other servers are served as last fetched from their origin, with
+//! the origin's signature intact and countersigned with our key. The signed
+//! responses are stored apart from `server_signingkeys`, which merges keys
+//! from every source and so no longer carries verifiable signatures.
+
+use std::time::Duration;
+
+use conduwuit::{Err, Result, debug, debug_warn, err, implement, utils::timepoint_from_now};
+use database::{Deserialized, Json};
+use ruma::{
+	CanonicalJsonObject, MilliSecondsSinceUnixEpoch, ServerName,
+	api::federation::discovery::{ServerSigningKeys, get_server_keys},
+	serde::Raw,
+	signatures::verify_json,
+};
+use serde_json::value::to_raw_value;
+
+use super::{PubKeyMap, PubKeys};
+
+/// Cached keys remaining valid for this long are served whatever the requested
+/// `minimum_valid_until_ts`, so that queries cannot force a fetch from the
+/// origin every time.
+const MAX_REQUIRED_VALIDITY: Duration = Duration::from_secs(60 * 60);
+
+/// Returns the signing keys of a remote server, countersigned with our key.
+/// Cached keys are served while they remain valid until at least
+/// `minimum_valid_until_ts`, capped to [`MAX_REQUIRED_VALIDITY`] from now;
+/// otherwise the keys are fetched from the origin, falling back to the cached
+/// keys when the origin cannot be reached.
+#[implement(super::Service)]
+pub async fn notary_keys(
+	&self,
+	origin: &ServerName,
+	minimum_valid_until_ts: MilliSecondsSinceUnixEpoch,
+) -> Result<Raw<ServerSigningKeys>> {
+	let cached: Option<Raw<ServerSigningKeys>> = self
+		.db
+		.servername_signedkeys
+		.get(origin)
+		.await
+		.deserialized()
+		.ok();
+
+	let minimum_valid_until_ts = timepoint_from_now(MAX_REQUIRED_VALIDITY)
+		.ok()
+		.and_then(MilliSecondsSinceUnixEpoch::from_system_time)
+		.map_or(minimum_valid_until_ts, |max| minimum_valid_until_ts.min(max));
+
+	let valid_long_enough = cached
+		.as_ref()
+		.and_then(|keys| {
+			keys.get_field::<MilliSecondsSinceUnixEpoch>("valid_until_ts")
+				.ok()
+				.flatten()
+		})
+		.is_some_and(|valid_until_ts| valid_until_ts >= minimum_valid_until_ts);
+
+	let keys = match cached {
+		| Some(cached) if valid_long_enough => {
+			debug!(%origin, "Serving cached keys");
+			cached
+		},
+		| cached => match self.fetch_signed_keys(origin).await {
+			| Ok(keys) => keys,
+			| Err(e) => {
+				let Some(cached) = cached else {
+					return Err(e);
+				};
+
+				debug_warn!(%origin, "Serving cached keys, failed to fetch fresh keys: {e}");
+				cached
+			},
+		},
+	};
+
+	self.countersign(&keys)
+}
+
+/// Fetches the keys of a server from the server itself, checks they are
+/// signed by it and stores them.
+#[implement(super::Service)]
+async fn fetch_signed_keys(&self, origin: &ServerName) -> Result<Raw<ServerSigningKeys>> {
+	use get_server_keys::v2::Request;
+
+	let raw = self
+		.services
+		.sending
+		.send_federation_request(origin, Request::new())
+		.await?
+		.server_key;
+
+	let server_keys: ServerSigningKeys = raw.deserialize()?;
+	if server_keys.server_name != origin {
+		return Err!(BadServerResponse(debug_warn!(
+			requested = ?origin,
+			response = ?server_keys.server_name,
+			"Server responded with bogus server_name"
+		)));
+	}
+
+	let pubkeys: PubKeys = server_keys
+		.verify_keys
+		.iter()
+		.map(|(key_id, key)| (key_id.to_string(), key.key.clone()))
+		.collect();
+
+	let pubkeys: PubKeyMap = [(origin.to_string(), pubkeys)].into();
+	let object: CanonicalJsonObject = serde_json::from_str(raw.json().get())?;
+	verify_json(&pubkeys, object)
+		.map_err(|e| err!(BadServerResponse("Keys of {origin} are not signed by it: {e}")))?;
+
+	self.db.servername_signedkeys.raw_put(origin, Json(&raw));
+
+	self.add_signing_keys(server_keys).await;
+
+	Ok(raw)
+}
+
+#[implement(super::Service)]
+fn countersign(&self, keys: &Raw<ServerSigningKeys>) -> Result<Raw<ServerSigningKeys>> {
+	let mut object: CanonicalJsonObject = serde_json::from_str(keys.json().get())?;
+	self.sign_json(&mut object)?;
+
+	Ok(Raw::from_json(to_raw_value(&object)?))
+}
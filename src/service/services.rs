@@ -8,11 +8,11 @@
 use tokio::sync::Mutex;
 
 use crate::{
-	account_data, admin, announcements, antispam, appservice, client, config, emergency,
-	federation, globals, key_backups,
+	account_data, admin, announcements, antispam, appservice, audit, client, config,
+	delayed_events, emergency, federation, globals, key_backups,
 	manager::Manager,
-	media, moderation, presence, pusher, registration_tokens, resolver, rooms, sending,
-	server_keys,
+	media, moderation, presence, pusher, registration_tokens, rendezvous, resolver, rooms,
+	sending, server_keys,
 	service::{self, Args, Map, Service},
 	sync, transaction_ids, uiaa, users,
 };
@@ -21,8 +21,10 @@ pub struct Services {
 	pub account_data: Arc<account_data::Service>,
 	pub admin: Arc<admin::Service>,
 	pub appservice: Arc<appservice::Service>,
+	pub audit: Arc<audit::Service>,
 	pub config: Arc<config::Service>,
 	pub client: Arc<client::Service>,
+	pub delayed_events: Arc<delayed_events::Service>,
 	pub emergency: Arc<emergency::Service>,
 	pub globals: Arc<globals::Service>,
 	pub key_backups: Arc<key_backups::Service>,
@@ -30,6 +32,7 @@ pub struct Services {
 	pub presence: Arc<presence::Service>,
 	pub pusher: Arc<pusher::Service>,
 	pub registration_tokens: Arc<registration_tokens::Service>,
+	pub rendezvous: Arc<rendezvous::Service>,
 	pub resolver: Arc<resolver::Service>,
 	pub rooms: rooms::Service,
 	pub federation: Arc<federation::Service>,
@@ -70,9 +73,11 @@ macro_rules! build {
 			account_data: build!(account_data::Service),
 			admin: build!(admin::Service),
 			appservice: build!(appservice::Service),
+			audit: build!(audit::Service),
 			resolver: build!(resolver::Service),
 			client: build!(client::Service),
 			config: build!(config::Service),
+			delayed_events: build!(delayed_events::Service),
 			emergency: build!(emergency::Service),
 			globals: build!(globals::Service),
 			key_backups: build!(key_backups::Service),
@@ -80,6 +85,7 @@ macro_rules! build {
 			presence: build!(presence::Service),
 			pusher: build!(pusher::Service),
 			registration_tokens: build!(registration_tokens::Service),
+			rendezvous: build!(rendezvous::Service),
 			rooms: rooms::Service {
 				alias: build!(rooms::alias::Service),
 				auth_chain: build!(rooms::auth_chain::Service),
@@ -89,6 +95,7 @@ macro_rules! build {
 				metadata: build!(rooms::metadata::Service),
 				outlier: build!(rooms::outlier::Service),
 				pdu_metadata: build!(rooms::pdu_metadata::Service),
+				peek: build!(rooms::peek::Service),
 				read_receipt: build!(rooms::read_receipt::Service),
 				search: build!(rooms::search::Service),
 				short: build!(rooms::short::Service),
@@ -97,6 +104,7 @@ macro_rules! build {
 				state_accessor: build!(rooms::state_accessor::Service),
 				state_cache: build!(rooms::state_cache::Service),
 				state_compressor: build!(rooms::state_compressor::Service),
+				third_party_invite: build!(rooms::third_party_invite::Service),
 				threads: build!(rooms::threads::Service),
 				timeline: build!(rooms::timeline::Service),
 				typing: build!(rooms::typing::Service),
@@ -1,20 +1,40 @@
+//! # Sync service
+//!
+//! Sticky parameters and known rooms of sliding sync connections are kept in
+//! memory while connections are in use, and persisted to the database after
+//! every request so clients can resume their connections after a restart.
+//! Persisted connections are restored when a client returns with a known
+//! connection and position, and are swept once unused for longer than
+//! `sliding_sync_connection_ttl`.
+
 mod watch;
 
 use std::{
 	collections::{BTreeMap, BTreeSet},
 	sync::Arc,
+	time::Duration,
 };
 
-use conduwuit::{Result, Server, SyncMutex};
-use database::Map;
+use async_trait::async_trait;
+use conduwuit::{
+	Result, Server, SyncMutex, debug,
+	utils::{ReadyExt, stream::TryIgnore, time::now_millis},
+};
+use database::{Deserialized, Json, Map};
+use futures::StreamExt;
 use ruma::{
-	OwnedDeviceId, OwnedRoomId, OwnedUserId,
+	DeviceId, OwnedDeviceId, OwnedRoomId, OwnedUserId, UserId,
 	api::client::sync::sync_events::{
 		self,
 		v4::{ExtensionsConfig, SyncRequestList},
 		v5,
 	},
 };
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
 
 use crate::{Dep, rooms};
 
@@ -23,6 +43,7 @@ pub struct Service {
 	services: Services,
 	connections: DbConnections<DbConnectionsKey, DbConnectionsVal>,
 	snake_connections: DbConnections<SnakeConnectionsKey, SnakeConnectionsVal>,
+	interrupt: Notify,
 }
 
 pub struct Data {
@@ -35,8 +56,10 @@ pub struct Data {
 	pduid_pdu: Arc<Map>,
 	keychangeid_userid: Arc<Map>,
 	roomusertype_roomuserdataid: Arc<Map>,
+	roomuserdataid_deletedat: Arc<Map>,
 	readreceiptid_readreceipt: Arc<Map>,
 	userid_lastonetimekeyupdate: Arc<Map>,
+	userdeviceconnid_snakesync: Arc<Map>,
 }
 
 struct Services {
@@ -54,12 +77,26 @@ struct SlidingSyncCache {
 	extensions: ExtensionsConfig,
 }
 
-#[derive(Default)]
+#[derive(Default, Deserialize, Serialize)]
 struct SnakeSyncCache {
 	lists: BTreeMap<String, v5::request::List>,
 	subscriptions: BTreeMap<OwnedRoomId, v5::request::RoomSubscription>,
 	known_rooms: BTreeMap<String, BTreeMap<OwnedRoomId, u64>>,
 	extensions: v5::request::Extensions,
+	/// When the connection was last used, in milliseconds since the unix epoch.
+	#[serde(default)]
+	last_used: u64,
+}
+
+impl SnakeSyncCache {
+	/// A new connection counts as used now, so that a sweep before it is first
+	/// saved does not drop it.
+	fn new_shared() -> Arc<SyncMutex<Self>> {
+		Arc::new(SyncMutex::new(Self {
+			last_used: now_millis(),
+			..Default::default()
+		}))
+	}
 }
 
 type DbConnections<K, V> = SyncMutex<BTreeMap<K, V>>;
@@ -68,6 +105,10 @@ struct SnakeSyncCache {
 type SnakeConnectionsKey = (OwnedUserId, OwnedDeviceId, Option<String>);
 type SnakeConnectionsVal = Arc<SyncMutex<SnakeSyncCache>>;
 
+/// How often persisted connections are checked for expiry.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -81,8 +122,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				pduid_pdu: args.db["pduid_pdu"].clone(),
 				keychangeid_userid: args.db["keychangeid_userid"].clone(),
 				roomusertype_roomuserdataid: args.db["roomusertype_roomuserdataid"].clone(),
+				roomuserdataid_deletedat: args.db["roomuserdataid_deletedat"].clone(),
 				readreceiptid_readreceipt: args.db["readreceiptid_readreceipt"].clone(),
 				userid_lastonetimekeyupdate: args.db["userid_lastonetimekeyupdate"].clone(),
+				userdeviceconnid_snakesync: args.db["userdeviceconnid_snakesync"].clone(),
 			},
 			services: Services {
 				server: args.server.clone(),
@@ -92,19 +135,119 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 			},
 			connections: SyncMutex::new(BTreeMap::new()),
 			snake_connections: SyncMutex::new(BTreeMap::new()),
+			interrupt: Notify::new(),
 		}))
 	}
 
+	#[tracing::instrument(skip_all, name = "sync", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		let mut i = interval(SWEEP_INTERVAL);
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.sweep_snake_connections().await;
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
 impl Service {
-	pub fn snake_connection_cached(&self, key: &SnakeConnectionsKey) -> bool {
-		self.snake_connections.lock().contains_key(key)
+	/// Whether the connection is known, either in memory or persisted. A
+	/// persisted connection is restored into memory.
+	pub async fn snake_connection_cached(&self, key: &SnakeConnectionsKey) -> bool {
+		if self.snake_connections.lock().contains_key(key) {
+			return true;
+		}
+
+		let Ok(mut cached) = self
+			.db
+			.userdeviceconnid_snakesync
+			.qry(&snake_db_key(key))
+			.await
+			.deserialized::<Json<SnakeSyncCache>>()
+			.map(|Json(cached)| cached)
+		else {
+			return false;
+		};
+
+		if self.is_expired(&cached) {
+			self.db.userdeviceconnid_snakesync.del(snake_db_key(key));
+			return false;
+		}
+
+		debug!(?key, "Restored persisted sliding sync connection");
+		cached.last_used = now_millis();
+		self.snake_connections
+			.lock()
+			.entry(key.clone())
+			.or_insert_with(|| Arc::new(SyncMutex::new(cached)));
+
+		true
 	}
 
 	pub fn forget_snake_sync_connection(&self, key: &SnakeConnectionsKey) {
 		self.snake_connections.lock().remove(key);
+		self.db.userdeviceconnid_snakesync.del(snake_db_key(key));
+	}
+
+	/// Persists the connection's current state, so it can be resumed after a
+	/// restart.
+	pub fn save_snake_connection(&self, key: &SnakeConnectionsKey) {
+		let Some(cached) = self.snake_connections.lock().get(key).cloned() else {
+			return;
+		};
+
+		let cached = &mut cached.lock();
+		cached.last_used = now_millis();
+		self.db
+			.userdeviceconnid_snakesync
+			.put(snake_db_key(key), Json(&**cached));
+	}
+
+	/// Removes connections which have not been used within the configured TTL,
+	/// from memory and from the database.
+	async fn sweep_snake_connections(&self) {
+		self.snake_connections
+			.lock()
+			.retain(|_, cached| !self.is_expired(&cached.lock()));
+
+		let mut swept: usize = 0;
+		self.db
+			.userdeviceconnid_snakesync
+			.stream()
+			.ignore_err()
+			.ready_filter(|(_, Json(cached)): &(SnakeDbKey<'_>, Json<SnakeSyncCache>)| {
+				self.is_expired(cached)
+			})
+			.ready_for_each(|(key, _)| {
+				self.db.userdeviceconnid_snakesync.del(key);
+				swept = swept.saturating_add(1);
+			})
+			.await;
+
+		if swept > 0 {
+			debug!("Swept {swept} expired sliding sync connections");
+		}
+	}
+
+	fn is_expired(&self, cached: &SnakeSyncCache) -> bool {
+		let ttl = self
+			.services
+			.server
+			.config
+			.sliding_sync_connection_ttl
+			.saturating_mul(1000);
+
+		now_millis().saturating_sub(cached.last_used) > ttl
 	}
 
 	pub fn remembered(&self, key: &DbConnectionsKey) -> bool {
@@ -124,7 +267,7 @@ pub fn update_snake_sync_request_with_cache(
 		let cached = Arc::clone(
 			cache
 				.entry(snake_key.clone())
-				.or_insert_with(|| Arc::new(SyncMutex::new(SnakeSyncCache::default()))),
+				.or_insert_with(SnakeSyncCache::new_shared),
 		);
 		let cached = &mut cached.lock();
 		drop(cache);
@@ -387,7 +530,7 @@ pub fn update_snake_sync_known_rooms(
 		let cached = Arc::clone(
 			cache
 				.entry(key.clone())
-				.or_insert_with(|| Arc::new(SyncMutex::new(SnakeSyncCache::default()))),
+				.or_insert_with(SnakeSyncCache::new_shared),
 		);
 		let cached = &mut cached.lock();
 		drop(cache);
@@ -417,7 +560,7 @@ pub fn update_snake_sync_subscriptions(
 		let cached = Arc::clone(
 			cache
 				.entry(key.clone())
-				.or_insert_with(|| Arc::new(SyncMutex::new(SnakeSyncCache::default()))),
+				.or_insert_with(SnakeSyncCache::new_shared),
 		);
 		let cached = &mut cached.lock();
 		drop(cache);
@@ -436,6 +579,15 @@ pub fn into_snake_key<U, D, C>(user_id: U, device_id: D, conn_id: C) -> SnakeCon
 	(user_id.into(), device_id.into(), conn_id.into())
 }
 
+type SnakeDbKey<'a> = (&'a UserId, &'a DeviceId, &'a str);
+
+/// The database key of a connection. Connections without an ID are stored
+/// under an empty one.
+fn snake_db_key(key: &SnakeConnectionsKey) -> SnakeDbKey<'_> {
+	let (user_id, device_id, conn_id) = key;
+	(user_id, device_id, conn_id.as_deref().unwrap_or_default())
+}
+
 #[inline]
 pub fn into_db_key<U, D, C>(user_id: U, device_id: D, conn_id: C) -> DbConnectionsKey
 where
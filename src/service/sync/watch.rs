@@ -58,6 +58,11 @@ pub async fn watch(&self, user_id: &UserId, device_id: &DeviceId) -> Result {
 				.roomusertype_roomuserdataid
 				.watch_prefix(&roomuser_prefix),
 		);
+		futures.push(
+			self.db
+				.roomuserdataid_deletedat
+				.watch_prefix(&roomuser_prefix),
+		);
 
 		// PDUs
 		let short_roomid = short_roomid.to_be_bytes().to_vec();
@@ -85,6 +90,11 @@ pub async fn watch(&self, user_id: &UserId, device_id: &DeviceId) -> Result {
 			.roomusertype_roomuserdataid
 			.watch_prefix(&globaluserdata_prefix),
 	);
+	futures.push(
+		self.db
+			.roomuserdataid_deletedat
+			.watch_prefix(&globaluserdata_prefix),
+	);
 
 	// More key changes (used when user is not joined to any rooms)
 	futures.push(self.db.keychangeid_userid.watch_prefix(&userid_prefix));
@@ -0,0 +1,143 @@
+//! Connection history
+//!
+//! Every IP address and device a local user connects from is recorded with the
+//! user agent and when it was first and last seen, for abuse investigation.
+//! Connections unseen for longer than `connection_history_retention` are
+//! swept.
+
+use std::net::IpAddr;
+
+use conduwuit::{
+	implement,
+	utils::{ReadyExt, stream::TryIgnore, time::now_millis},
+};
+use database::{Deserialized, Ignore, Interfix, Json};
+use futures::{Stream, StreamExt};
+use ruma::{DeviceId, OwnedDeviceId, OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+
+use super::Service;
+
+/// A user's connections from an IP address with a device.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Connection {
+	pub device_id: OwnedDeviceId,
+	pub ip: IpAddr,
+
+	/// The user agent of the most recent request.
+	pub user_agent: Option<String>,
+
+	/// When the connection was first seen, in milliseconds since the unix
+	/// epoch.
+	pub first_seen: u64,
+
+	/// When the connection was last seen, in milliseconds since the unix epoch.
+	pub last_seen: u64,
+}
+
+type ConnectionKey<'a> = (&'a UserId, &'a DeviceId, &'a str);
+type IpUserKey<'a> = (&'a str, &'a UserId);
+
+/// How often connections are updated, in milliseconds.
+const UPDATE_INTERVAL: u64 = 10_000;
+
+/// Records a request of a user from an IP address with a device.
+#[implement(Service)]
+pub(super) async fn record_connection(
+	&self,
+	user_id: &UserId,
+	device_id: &DeviceId,
+	ip: IpAddr,
+	user_agent: Option<&str>,
+) {
+	if self.services.server.config.connection_history_retention == 0 {
+		return;
+	}
+
+	let now = now_millis();
+	let key = (user_id, device_id, ip.to_string());
+	let connection = match self
+		.db
+		.userdeviceip_connection
+		.qry(&key)
+		.await
+		.deserialized::<Connection>()
+	{
+		| Ok(connection)
+			if now.saturating_sub(connection.last_seen) < UPDATE_INTERVAL
+				&& connection.user_agent.as_deref() == user_agent =>
+			return,
+		| Ok(connection) => Connection {
+			user_agent: user_agent.map(ToOwned::to_owned),
+			last_seen: now,
+			..connection
+		},
+		| Err(_) => Connection {
+			device_id: device_id.to_owned(),
+			ip,
+			user_agent: user_agent.map(ToOwned::to_owned),
+			first_seen: now,
+			last_seen: now,
+		},
+	};
+
+	self.db.userdeviceip_connection.put(key, Json(&connection));
+
+	self.db
+		.ipuserid_lastseen
+		.put((ip.to_string(), user_id), now);
+}
+
+/// Returns the connections of a user, ordered by device and IP address.
+#[implement(Service)]
+pub fn connections<'a>(
+	&'a self,
+	user_id: &'a UserId,
+) -> impl Stream<Item = Connection> + Send + 'a {
+	let prefix = (user_id, Interfix);
+	self.db
+		.userdeviceip_connection
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.map(|(_, connection): (Ignore, Connection)| connection)
+}
+
+/// Returns the users who connected from an IP address, with when they last
+/// did.
+#[implement(Service)]
+pub fn users_by_ip(&self, ip: IpAddr) -> impl Stream<Item = (OwnedUserId, u64)> + Send + '_ {
+	let prefix = (ip.to_string(), Interfix);
+	self.db
+		.ipuserid_lastseen
+		.stream_prefix(&prefix)
+		.ignore_err()
+		.map(|((_, user_id), last_seen): (IpUserKey<'_>, u64)| (user_id.to_owned(), last_seen))
+}
+
+/// Removes the connections last seen longer ago than the retention period.
+#[implement(Service)]
+pub(super) async fn sweep_connections(&self) {
+	let retention = self.services.server.config.connection_history_retention;
+	if retention == 0 {
+		return;
+	}
+
+	let cutoff = now_millis().saturating_sub(retention.saturating_mul(1000));
+	self.db
+		.userdeviceip_connection
+		.stream()
+		.ignore_err()
+		.ready_filter(|(_, connection): &(ConnectionKey<'_>, Connection)| {
+			connection.last_seen < cutoff
+		})
+		.ready_for_each(|(key, _)| self.db.userdeviceip_connection.del(key))
+		.await;
+
+	self.db
+		.ipuserid_lastseen
+		.stream()
+		.ignore_err()
+		.ready_filter(|(_, last_seen): &(IpUserKey<'_>, u64)| *last_seen < cutoff)
+		.ready_for_each(|(key, _)| self.db.ipuserid_lastseen.del(key))
+		.await;
+}
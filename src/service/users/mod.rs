@@ -1,11 +1,14 @@
+mod connections;
+
 #[cfg(feature = "ldap")]
 use std::collections::HashMap;
-use std::{collections::BTreeMap, mem, net::IpAddr, sync::Arc};
+use std::{collections::BTreeMap, mem, net::IpAddr, sync::Arc, time::Duration};
 
-#[cfg(feature = "ldap")]
-use conduwuit::result::LogErr;
+use async_trait::async_trait;
 use conduwuit::{
-	Err, Error, Result, Server, at, debug_warn, err, is_equal_to, trace,
+	Err, Error, Result, Server, at, debug_warn, err, is_equal_to,
+	result::LogErr,
+	trace,
 	utils::{self, ReadyExt, stream::TryIgnore, string::Unquoted},
 };
 #[cfg(feature = "ldap")]
@@ -16,7 +19,8 @@
 use ldap3::{LdapConnAsync, Scope, SearchEntry};
 use ruma::{
 	DeviceId, KeyId, MilliSecondsSinceUnixEpoch, OneTimeKeyAlgorithm, OneTimeKeyId,
-	OneTimeKeyName, OwnedDeviceId, OwnedKeyId, OwnedMxcUri, OwnedUserId, RoomId, UInt, UserId,
+	OneTimeKeyName, OwnedDeviceId, OwnedKeyId, OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomId,
+	UInt, UserId,
 	api::client::{device::Device, error::ErrorKind, filter::FilterDefinition},
 	encryption::{CrossSigningKey, DeviceKeys, OneTimeKey},
 	events::{
@@ -29,8 +33,16 @@
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio::{
+	sync::Notify,
+	time::{MissedTickBehavior, interval},
+};
 
-use crate::{Dep, account_data, admin, appservice, globals, rooms};
+pub use self::connections::Connection;
+use crate::{
+	Dep, account_data, admin, appservice, globals, rooms,
+	sending::{self, DeviceChange, EduBuf},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSuspension {
@@ -45,6 +57,7 @@ pub struct UserSuspension {
 pub struct Service {
 	services: Services,
 	db: Data,
+	interrupt: Notify,
 }
 
 struct Services {
@@ -55,9 +68,11 @@ struct Services {
 	globals: Dep<globals::Service>,
 	state_accessor: Dep<rooms::state_accessor::Service>,
 	state_cache: Dep<rooms::state_cache::Service>,
+	sending: Dep<sending::Service>,
 }
 
 struct Data {
+	ipuserid_lastseen: Arc<Map>,
 	keychangeid_userid: Arc<Map>,
 	keyid_key: Arc<Map>,
 	onetimekeyid_onetimekeys: Arc<Map>,
@@ -66,6 +81,7 @@ struct Data {
 	todeviceid_events: Arc<Map>,
 	token_userdeviceid: Arc<Map>,
 	userdeviceid_metadata: Arc<Map>,
+	userdeviceip_connection: Arc<Map>,
 	userdeviceid_token: Arc<Map>,
 	userfilterid_filter: Arc<Map>,
 	userid_avatarurl: Arc<Map>,
@@ -84,6 +100,10 @@ struct Data {
 	useridprofilekey_value: Arc<Map>,
 }
 
+/// How often expired connections are swept.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[async_trait]
 impl crate::Service for Service {
 	fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 		Ok(Arc::new(Self {
@@ -96,8 +116,10 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				state_accessor: args
 					.depend::<rooms::state_accessor::Service>("rooms::state_accessor"),
 				state_cache: args.depend::<rooms::state_cache::Service>("rooms::state_cache"),
+				sending: args.depend::<sending::Service>("sending"),
 			},
 			db: Data {
+				ipuserid_lastseen: args.db["ipuserid_lastseen"].clone(),
 				keychangeid_userid: args.db["keychangeid_userid"].clone(),
 				keyid_key: args.db["keyid_key"].clone(),
 				onetimekeyid_onetimekeys: args.db["onetimekeyid_onetimekeys"].clone(),
@@ -106,6 +128,7 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				todeviceid_events: args.db["todeviceid_events"].clone(),
 				token_userdeviceid: args.db["token_userdeviceid"].clone(),
 				userdeviceid_metadata: args.db["userdeviceid_metadata"].clone(),
+				userdeviceip_connection: args.db["userdeviceip_connection"].clone(),
 				userdeviceid_token: args.db["userdeviceid_token"].clone(),
 				userfilterid_filter: args.db["userfilterid_filter"].clone(),
 				userid_avatarurl: args.db["userid_avatarurl"].clone(),
@@ -123,9 +146,28 @@ fn build(args: crate::Args<'_>) -> Result<Arc<Self>> {
 				userid_usersigningkeyid: args.db["userid_usersigningkeyid"].clone(),
 				useridprofilekey_value: args.db["useridprofilekey_value"].clone(),
 			},
+			interrupt: Notify::new(),
 		}))
 	}
 
+	#[tracing::instrument(skip_all, name = "users", level = "debug")]
+	async fn worker(self: Arc<Self>) -> Result<()> {
+		let mut i = interval(SWEEP_INTERVAL);
+		i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			tokio::select! {
+				() = self.interrupt.notified() => break,
+				_ = i.tick() => (),
+			}
+
+			self.sweep_connections().await;
+		}
+
+		Ok(())
+	}
+
+	fn interrupt(&self) { self.interrupt.notify_waiters(); }
+
 	fn name(&self) -> &str { crate::service::make_name(std::module_path!()) }
 }
 
@@ -616,6 +658,8 @@ pub async fn add_one_time_key(
 		let count = self.services.globals.next_count().unwrap();
 		self.db.userid_lastonetimekeyupdate.raw_put(user_id, count);
 
+		self.push_one_time_keys_appservice(user_id).await;
+
 		Ok(())
 	}
 
@@ -672,6 +716,9 @@ pub async fn take_one_time_key(
 			.next()
 			.await;
 
+		// Let the owning appservice know the key count changed
+		self.push_one_time_keys_appservice(user_id).await;
+
 		one_time_key.ok_or_else(|| err!(Request(NotFound("No one-time-key found"))))
 	}
 
@@ -884,19 +931,25 @@ fn keys_changed_user_or_room<'a>(
 	pub async fn mark_device_key_update(&self, user_id: &UserId) {
 		let count = self.services.globals.next_count().unwrap();
 
-		self.services
+		let rooms: Vec<OwnedRoomId> = self
+			.services
 			.state_cache
 			.rooms_joined(user_id)
 			// Don't send key updates to unencrypted rooms
 			.filter(|room_id| self.services.state_accessor.is_encrypted_room(room_id))
-			.ready_for_each(|room_id| {
-				let key = (room_id, count);
-				self.db.keychangeid_userid.put_raw(key, user_id);
-			})
+			.map(ToOwned::to_owned)
+			.collect()
 			.await;
 
+		for room_id in &rooms {
+			let key = (room_id, count);
+			self.db.keychangeid_userid.put_raw(key, user_id);
+		}
+
 		let key = (user_id, count);
 		self.db.keychangeid_userid.put_raw(key, user_id);
+
+		self.push_device_list_appservices(user_id, &rooms).await;
 	}
 
 	pub async fn get_device_keys<'a>(
@@ -972,17 +1025,115 @@ pub async fn add_to_device_event(
 		event_type: &str,
 		content: serde_json::Value,
 	) {
-		let count = self.services.globals.next_count().unwrap();
+		let event = json!({
+			"type": event_type,
+			"sender": sender,
+			"content": content,
+		});
+
+		// Devices of appservice users are only reached through the appservice
+		if self
+			.push_to_device_appservice(target_user_id, target_device_id, &event)
+			.await
+		{
+			return;
+		}
 
+		let count = self.services.globals.next_count().unwrap();
 		let key = (target_user_id, target_device_id, count);
-		self.db.todeviceid_events.put(
-			key,
-			Json(json!({
-				"type": event_type,
-				"sender": sender,
-				"content": content,
-			})),
-		);
+		self.db.todeviceid_events.put(key, Json(event));
+	}
+
+	/// Forwards a to-device event for a user in an appservice's namespace to
+	/// that appservice (MSC2409). Returns whether the event was queued for the
+	/// appservice, in which case it is not stored for the device.
+	async fn push_to_device_appservice(
+		&self,
+		target_user_id: &UserId,
+		target_device_id: &DeviceId,
+		event: &serde_json::Value,
+	) -> bool {
+		let Some(appservice_id) = self.ephemeral_appservice(target_user_id).await else {
+			return false;
+		};
+
+		let mut event = event.clone();
+		event["to_user_id"] = json!(target_user_id);
+		event["to_device_id"] = json!(target_device_id);
+
+		let mut buf = EduBuf::new();
+		serde_json::to_writer(&mut buf, &event).expect("failed to serialize to-device event");
+
+		self.services
+			.sending
+			.send_edu_appservice(appservice_id, buf)
+			.log_err()
+			.is_ok()
+	}
+
+	/// Tells the appservices receiving ephemeral data whose users share one of
+	/// the rooms with the user, or which own the user, that its device list
+	/// changed (MSC3202).
+	async fn push_device_list_appservices(&self, user_id: &UserId, rooms: &[OwnedRoomId]) {
+		let appservices: Vec<_> = self
+			.services
+			.appservice
+			.read()
+			.await
+			.values()
+			.filter(|info| info.registration.receive_ephemeral)
+			.cloned()
+			.collect();
+
+		for info in appservices {
+			let mut interested = info.is_user_match(user_id);
+			for room_id in rooms {
+				if interested {
+					break;
+				}
+
+				interested = self
+					.services
+					.state_cache
+					.appservice_in_room(room_id, &info)
+					.await;
+			}
+
+			if interested {
+				self.services
+					.sending
+					.send_device_change_appservice(
+						info.registration.id,
+						DeviceChange::DeviceList,
+						user_id,
+					)
+					.log_err()
+					.ok();
+			}
+		}
+	}
+
+	/// Tells the appservice owning the user, if it receives ephemeral data,
+	/// that the user's one-time key counts changed (MSC3202).
+	async fn push_one_time_keys_appservice(&self, user_id: &UserId) {
+		if let Some(appservice_id) = self.ephemeral_appservice(user_id).await {
+			self.services
+				.sending
+				.send_device_change_appservice(appservice_id, DeviceChange::OneTimeKeys, user_id)
+				.log_err()
+				.ok();
+		}
+	}
+
+	/// Returns the ID of the appservice owning this user if it receives
+	/// ephemeral data, which includes the MSC2409 and MSC3202 extensions.
+	async fn ephemeral_appservice(&self, user_id: &UserId) -> Option<String> {
+		self.services
+			.appservice
+			.find_from_user(user_id)
+			.await
+			.filter(|info| info.registration.receive_ephemeral)
+			.map(|info| info.registration.id)
 	}
 
 	pub fn get_to_device_events<'a>(
@@ -1061,14 +1212,20 @@ pub async fn update_device_metadata_no_increment(
 		Ok(())
 	}
 
+	/// Updates the last seen time and IP address of a device, and records the
+	/// connection in the user's connection history.
 	pub async fn update_device_last_seen(
 		&self,
 		user_id: &UserId,
 		device_id: Option<&DeviceId>,
 		ip: IpAddr,
+		user_agent: Option<&str>,
 	) {
 		let now = MilliSecondsSinceUnixEpoch::now();
 		if let Some(device_id) = device_id {
+			self.record_connection(user_id, device_id, ip, user_agent)
+				.await;
+
 			if let Ok(mut device) = self.get_device_metadata(user_id, device_id).await {
 				device.last_seen_ip = Some(ip.to_string());
 				// If the last update was less than 10 seconds ago, don't update the timestamp